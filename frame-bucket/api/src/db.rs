@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use frame_bucket_common::config::DatabaseMode;
+use rusqlite::Connection;
+use tracing::{debug, warn};
+
+/// Attempts made by `open_robot_db` before giving up. Kept small: this runs on the request path.
+const DB_OPEN_ATTEMPTS: u32 = 3;
+/// Backoff before the first retry; doubles on each subsequent attempt.
+const DB_OPEN_BACKOFF: Duration = Duration::from_millis(20);
+/// Idle connections kept per robot. Extra connections are closed when returned.
+const MAX_IDLE_PER_ROBOT: usize = 4;
+
+/// Open a robot's DB, retrying with exponential backoff on transient failures
+/// (file locked by the consumer, momentary FS hiccups). Must be called from a
+/// blocking context (`spawn_blocking`) since it sleeps between attempts.
+pub fn open_robot_db(db_dir: &Path, mode: DatabaseMode, robot_id: &str) -> rusqlite::Result<Connection> {
+    let path = mode.db_file(db_dir, robot_id);
+    let mut backoff = DB_OPEN_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match try_open_db(&path) {
+            Ok(conn) => return Ok(conn),
+            Err(e) if attempt < DB_OPEN_ATTEMPTS && is_transient_db_error(&e) => {
+                warn!(error = %e, robot_id, attempt, "transient DB open failure, retrying in {:?}", backoff);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn try_open_db(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;")?;
+    Ok(conn)
+}
+
+/// Errors worth retrying: the DB is busy/locked or the file could not be opened right now.
+fn is_transient_db_error(e: &rusqlite::Error) -> bool {
+    use rusqlite::ErrorCode;
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(err, _)
+            if matches!(
+                err.code,
+                ErrorCode::DatabaseBusy
+                    | ErrorCode::DatabaseLocked
+                    | ErrorCode::CannotOpen
+                    | ErrorCode::SystemIoFailure
+            )
+    )
+}
+
+/// Identity of the DB file a pooled connection was opened against. If the file
+/// is deleted or replaced underneath us, pooled connections for it are stale.
+#[cfg(unix)]
+fn file_identity(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.ino())
+}
+
+#[cfg(not(unix))]
+fn file_identity(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|_| 0)
+}
+
+struct RobotPool {
+    file_id: Option<u64>,
+    idle: Vec<Connection>,
+}
+
+/// Per-robot pool of SQLite connections, created lazily on first access.
+///
+/// Connections are checked out for the duration of one `spawn_blocking` task
+/// and returned on drop, so a `Connection` is never shared between threads.
+/// If a robot's DB file disappears or is replaced, its idle connections are
+/// evicted and a fresh one is opened.
+pub struct DbPool {
+    db_dir: PathBuf,
+    mode: DatabaseMode,
+    pools: Mutex<HashMap<String, RobotPool>>,
+}
+
+impl DbPool {
+    pub fn new(db_dir: PathBuf, mode: DatabaseMode) -> Self {
+        Self {
+            db_dir,
+            mode,
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn db_path(&self, robot_id: &str) -> PathBuf {
+        self.mode.db_file(&self.db_dir, robot_id)
+    }
+
+    /// All robots with recorded data: one per `.db` file, or the distinct
+    /// `robot_id`s of the shared database. Sorted. Blocking.
+    pub fn robot_ids(&self) -> rusqlite::Result<Vec<String>> {
+        let mut robots = Vec::new();
+        match self.mode {
+            DatabaseMode::PerRobot => {
+                let Ok(entries) = std::fs::read_dir(&self.db_dir) else {
+                    return Ok(robots);
+                };
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if path.extension().and_then(|e| e.to_str()) == Some("db") {
+                        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                            robots.push(stem.to_string());
+                        }
+                    }
+                }
+                robots.sort();
+            }
+            DatabaseMode::Shared => {
+                let path = self.db_dir.join(DatabaseMode::SHARED_FILE);
+                if !path.exists() {
+                    return Ok(robots);
+                }
+                let conn = try_open_db(&path)?;
+                let mut stmt = conn.prepare(
+                    "SELECT robot_id FROM segments
+                     UNION SELECT robot_id FROM collections
+                     ORDER BY robot_id",
+                )?;
+                let rows = stmt.query_map([], |row| row.get(0))?;
+                robots = rows.collect::<rusqlite::Result<_>>()?;
+            }
+        }
+        Ok(robots)
+    }
+
+    /// Whether `robot_id` has any recorded data. Blocking.
+    pub fn has_robot(&self, robot_id: &str) -> rusqlite::Result<bool> {
+        match self.mode {
+            DatabaseMode::PerRobot => Ok(self.db_path(robot_id).exists()),
+            DatabaseMode::Shared => Ok(self.robot_ids()?.iter().any(|r| r == robot_id)),
+        }
+    }
+
+    /// Check out a connection for `robot_id`, reusing an idle one when possible.
+    /// Blocking: call from within `spawn_blocking`.
+    pub fn get(self: &Arc<Self>, robot_id: &str) -> rusqlite::Result<PooledConn> {
+        let current_id = file_identity(&self.db_path(robot_id));
+
+        {
+            let mut pools = self.pools.lock().unwrap();
+            if let Some(pool) = pools.get_mut(robot_id) {
+                if current_id.is_some() && pool.file_id == current_id {
+                    if let Some(conn) = pool.idle.pop() {
+                        return Ok(self.wrap(robot_id, conn));
+                    }
+                } else {
+                    debug!(robot_id, "DB file changed or removed, evicting pooled connections");
+                    pools.remove(robot_id);
+                }
+            }
+        }
+
+        let conn = open_robot_db(&self.db_dir, self.mode, robot_id)?;
+        Ok(self.wrap(robot_id, conn))
+    }
+
+    fn wrap(self: &Arc<Self>, robot_id: &str, conn: Connection) -> PooledConn {
+        PooledConn {
+            conn: Some(conn),
+            robot_id: robot_id.to_string(),
+            pool: Arc::clone(self),
+        }
+    }
+
+    fn put_back(&self, robot_id: String, conn: Connection) {
+        let file_id = file_identity(&self.db_path(&robot_id));
+        if file_id.is_none() {
+            // File is gone; don't keep a connection to an unlinked DB.
+            return;
+        }
+        let mut pools = self.pools.lock().unwrap();
+        let pool = pools.entry(robot_id).or_insert_with(|| RobotPool {
+            file_id,
+            idle: Vec::new(),
+        });
+        if pool.file_id != file_id {
+            pool.file_id = file_id;
+            pool.idle.clear();
+        }
+        if pool.idle.len() < MAX_IDLE_PER_ROBOT {
+            pool.idle.push(conn);
+        }
+    }
+}
+
+/// A connection checked out of a `DbPool`. Returned to the pool on drop.
+pub struct PooledConn {
+    conn: Option<Connection>,
+    robot_id: String,
+    pool: Arc<DbPool>,
+}
+
+impl Deref for PooledConn {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection present until drop")
+    }
+}
+
+impl DerefMut for PooledConn {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection present until drop")
+    }
+}
+
+impl Drop for PooledConn {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            // Never return a connection mid-transaction.
+            if conn.is_autocommit() {
+                self.pool.put_back(std::mem::take(&mut self.robot_id), conn);
+            }
+        }
+    }
+}
+
+/// An in-memory database with the real schema (`frame_bucket_common::schema`),
+/// for tests.
+#[cfg(test)]
+pub fn test_conn() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    frame_bucket_common::schema::init_schema(&conn).unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("frame-bucket-api-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn pooled_connection_is_reused() {
+        let dir = temp_db_dir("reuse");
+        let pool = Arc::new(DbPool::new(dir.clone(), DatabaseMode::PerRobot));
+
+        let conn = pool.get("robot").unwrap();
+        conn.execute_batch("CREATE TEMP TABLE marker (x INTEGER);").unwrap();
+        drop(conn);
+
+        // Temp tables are per-connection, so seeing it proves the same connection came back.
+        let conn = pool.get("robot").unwrap();
+        assert!(conn.execute_batch("SELECT x FROM temp.marker;").is_ok());
+        drop(conn);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shared_mode_lists_robots_from_rows() {
+        let dir = temp_db_dir("shared");
+        let pool = Arc::new(DbPool::new(dir.clone(), DatabaseMode::Shared));
+        assert!(pool.robot_ids().unwrap().is_empty());
+
+        let conn = pool.get("robot-b").unwrap();
+        frame_bucket_common::schema::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key)
+                 VALUES ('robot-b', 'idle', 0, 1, 'a'), ('robot-a', 'idle', 0, 1, 'b'), ('robot-b', 'idle', 0, 1, 'c');
+             INSERT INTO collections (robot_id, name, created_at, updated_at) VALUES ('robot-c', 'grasps', 0, 0);",
+        )
+        .unwrap();
+        drop(conn);
+
+        assert_eq!(pool.robot_ids().unwrap(), ["robot-a", "robot-b", "robot-c"]);
+        assert!(pool.has_robot("robot-a").unwrap());
+        assert!(!pool.has_robot("robot-z").unwrap());
+        assert!(!dir.join("robot-b.db").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn deleted_db_file_evicts_pooled_connections() {
+        let dir = temp_db_dir("evict");
+        let pool = Arc::new(DbPool::new(dir.clone(), DatabaseMode::PerRobot));
+
+        let conn = pool.get("robot").unwrap();
+        conn.execute_batch("CREATE TEMP TABLE marker (x INTEGER);").unwrap();
+        drop(conn);
+
+        std::fs::remove_file(dir.join("robot.db")).unwrap();
+        let _ = std::fs::remove_file(dir.join("robot.db-wal"));
+        let _ = std::fs::remove_file(dir.join("robot.db-shm"));
+
+        let conn = pool.get("robot").unwrap();
+        assert!(conn.execute_batch("SELECT x FROM temp.marker;").is_err());
+        drop(conn);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,743 @@
+//! Export of a collection as a LeRobot (v2.0 layout) dataset.
+//!
+//! Each clip becomes one episode. The clip's active segment MP4s are joined
+//! with ffmpeg's concat demuxer and trimmed to the clip bounds (stream copy, no
+//! re-encode), and the per-frame index columns LeRobot expects are written as
+//! one parquet file per episode. The dataset is uploaded as:
+//!
+//! ```text
+//! {prefix}meta/info.json
+//! {prefix}meta/episodes.jsonl
+//! {prefix}meta/tasks.jsonl
+//! {prefix}data/chunk-000/episode_000000.parquet
+//! {prefix}videos/chunk-000/observation.images.camera/episode_000000.mp4
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use aws_sdk_s3::primitives::ByteStream;
+use frame_bucket_common::api_types::LeRobotExportResponse;
+use parquet::data_type::{FloatType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+pub const CODEBASE_VERSION: &str = "v2.0";
+/// Episodes per `chunk-NNN` directory.
+pub const CHUNKS_SIZE: usize = 1000;
+/// Feature key of the camera stream.
+pub const VIDEO_KEY: &str = "observation.images.camera";
+
+const DATA_PATH: &str = "data/chunk-{episode_chunk:03d}/episode_{episode_index:06d}.parquet";
+const VIDEO_PATH: &str = "videos/chunk-{episode_chunk:03d}/{video_key}/episode_{episode_index:06d}.mp4";
+
+const EPISODE_SCHEMA: &str = "message episode {
+    REQUIRED FLOAT timestamp;
+    REQUIRED INT64 frame_index;
+    REQUIRED INT64 episode_index;
+    REQUIRED INT64 index;
+    REQUIRED INT64 task_index;
+}";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("failed to download {key}: {message}")]
+    Download { key: String, message: String },
+    #[error("failed to upload {key}: {message}")]
+    Upload { key: String, message: String },
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+    #[error("ffprobe failed: {0}")]
+    Probe(String),
+    #[error("failed to write parquet: {0}")]
+    Parquet(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// An active segment's video, as stored in RustFS.
+#[derive(Debug, Clone)]
+pub struct SourceSegment {
+    pub start_ms: i64,
+    pub s3_key: String,
+}
+
+/// A clip to export, with its active segments sorted by `start_ms`.
+#[derive(Debug, Clone)]
+pub struct ClipSource {
+    pub clip_id: i64,
+    pub clip_start_ms: i64,
+    pub clip_end_ms: i64,
+    pub task: String,
+    pub segments: Vec<SourceSegment>,
+}
+
+/// Where and how to write the dataset.
+pub struct ExportTarget<'a> {
+    pub s3: &'a aws_sdk_s3::Client,
+    pub source_bucket: &'a str,
+    pub dest_bucket: &'a str,
+    /// Dataset root key prefix, ending in `/`.
+    pub prefix: &'a str,
+    pub robot_type: &'a str,
+    pub fps: f64,
+}
+
+/// A clip listed in a dataset manifest, with every segment it references.
+#[derive(Debug, Clone)]
+pub struct ManifestClip {
+    pub clip_id: i64,
+    pub clip_start_ms: i64,
+    pub clip_end_ms: i64,
+    pub modality: String,
+    pub task: String,
+    pub segments: Vec<ManifestSegment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ManifestSegment {
+    pub segment_id: i64,
+    pub segment_type: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub s3_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoProbe {
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub frames: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Episode {
+    pub episode_index: usize,
+    pub task_index: usize,
+    pub length: u64,
+}
+
+/// Export `clips` as a LeRobot dataset. Clips without any active segment are
+/// skipped and reported in the response.
+pub async fn export(
+    target: &ExportTarget<'_>,
+    clips: Vec<ClipSource>,
+) -> Result<LeRobotExportResponse, ExportError> {
+    let work_dir = std::env::temp_dir().join(format!(
+        "lerobot-export-{}-{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis()
+    ));
+    tokio::fs::create_dir_all(&work_dir).await?;
+
+    let result = export_in(target, clips, &work_dir).await;
+
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        warn!(path = work_dir.display().to_string(), error = %e, "failed to remove export work dir");
+    }
+    result
+}
+
+async fn export_in(
+    target: &ExportTarget<'_>,
+    clips: Vec<ClipSource>,
+    work_dir: &Path,
+) -> Result<LeRobotExportResponse, ExportError> {
+    let mut tasks: Vec<String> = Vec::new();
+    let mut episodes: Vec<Episode> = Vec::new();
+    let mut skipped_clip_ids = Vec::new();
+    let mut first_probe: Option<VideoProbe> = None;
+    let mut total_frames: u64 = 0;
+
+    for clip in clips {
+        if clip.segments.is_empty() {
+            skipped_clip_ids.push(clip.clip_id);
+            continue;
+        }
+        let episode_index = episodes.len();
+
+        // Fetch the clip's segment videos.
+        let mut inputs = Vec::with_capacity(clip.segments.len());
+        for (i, seg) in clip.segments.iter().enumerate() {
+            let path = work_dir.join(format!("ep{episode_index}_seg{i}.mp4"));
+            download(target, &seg.s3_key, &path).await?;
+            inputs.push(path);
+        }
+
+        let (offset_ms, duration_ms) =
+            trim_window(clip.clip_start_ms, clip.clip_end_ms, clip.segments[0].start_ms);
+        let video = work_dir.join(format!("episode_{episode_index:06}.mp4"));
+        concat_and_trim(&inputs, offset_ms, duration_ms, &video).await?;
+        for input in &inputs {
+            let _ = tokio::fs::remove_file(input).await;
+        }
+
+        let probe = probe_video(&video).await?;
+        let task_index = task_index(&mut tasks, &clip.task);
+        let episode = Episode {
+            episode_index,
+            task_index,
+            length: probe.frames,
+        };
+
+        let video_key = format!("{}{}", target.prefix, video_path(episode_index));
+        upload_file(target, &video_key, &video, "video/mp4").await?;
+        let _ = tokio::fs::remove_file(&video).await;
+
+        let parquet = episode_parquet(&episode, total_frames, target.fps)?;
+        let data_key = format!("{}{}", target.prefix, data_path(episode_index));
+        upload(target, &data_key, parquet, "application/octet-stream").await?;
+
+        debug!(clip_id = clip.clip_id, episode_index, frames = probe.frames, "exported episode");
+        total_frames += probe.frames;
+        first_probe.get_or_insert(probe);
+        episodes.push(episode);
+    }
+
+    let info = info_json(target.robot_type, target.fps, first_probe.as_ref(), &episodes, tasks.len());
+    let meta = [
+        ("meta/info.json", serde_json::to_vec_pretty(&info).unwrap()),
+        ("meta/episodes.jsonl", episodes_jsonl(&episodes, &tasks).into_bytes()),
+        ("meta/tasks.jsonl", tasks_jsonl(&tasks).into_bytes()),
+    ];
+    for (name, body) in meta {
+        let key = format!("{}{name}", target.prefix);
+        let content_type = if name.ends_with(".json") { "application/json" } else { "application/x-ndjson" };
+        upload(target, &key, body, content_type).await?;
+    }
+
+    info!(
+        prefix = target.prefix,
+        episodes = episodes.len(),
+        total_frames,
+        skipped = skipped_clip_ids.len(),
+        "LeRobot export complete"
+    );
+
+    Ok(LeRobotExportResponse {
+        bucket: target.dest_bucket.to_string(),
+        prefix: target.prefix.to_string(),
+        episodes: episodes.len(),
+        total_frames,
+        skipped_clip_ids,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Layout
+// ---------------------------------------------------------------------------
+
+fn episode_chunk(episode_index: usize) -> usize {
+    episode_index / CHUNKS_SIZE
+}
+
+pub fn data_path(episode_index: usize) -> String {
+    format!(
+        "data/chunk-{:03}/episode_{episode_index:06}.parquet",
+        episode_chunk(episode_index)
+    )
+}
+
+pub fn video_path(episode_index: usize) -> String {
+    format!(
+        "videos/chunk-{:03}/{VIDEO_KEY}/episode_{episode_index:06}.mp4",
+        episode_chunk(episode_index)
+    )
+}
+
+/// Index of `task` in `tasks`, appending it if new.
+fn task_index(tasks: &mut Vec<String>, task: &str) -> usize {
+    match tasks.iter().position(|t| t == task) {
+        Some(i) => i,
+        None => {
+            tasks.push(task.to_string());
+            tasks.len() - 1
+        }
+    }
+}
+
+/// Task description for a clip: its segments' labels, or `fallback` (the
+/// collection name) when none are labelled.
+pub fn task_for(labels: &[String], fallback: &str) -> String {
+    let mut unique: Vec<&str> = Vec::new();
+    for label in labels {
+        if !unique.contains(&label.as_str()) {
+            unique.push(label);
+        }
+    }
+    if unique.is_empty() {
+        fallback.to_string()
+    } else {
+        unique.join(", ")
+    }
+}
+
+/// Seek offset into the first segment and duration of the clip, in ms.
+/// Segments are concatenated back to back, so a clip starting before its
+/// first segment is shortened to start at the segment.
+fn trim_window(clip_start_ms: i64, clip_end_ms: i64, first_segment_start_ms: i64) -> (i64, i64) {
+    let offset_ms = (clip_start_ms - first_segment_start_ms).max(0);
+    let duration_ms = (clip_end_ms - first_segment_start_ms.max(clip_start_ms)).max(0);
+    (offset_ms, duration_ms)
+}
+
+pub fn info_json(
+    robot_type: &str,
+    fps: f64,
+    video: Option<&VideoProbe>,
+    episodes: &[Episode],
+    total_tasks: usize,
+) -> serde_json::Value {
+    let fps = fps.round() as u32;
+    let total_frames: u64 = episodes.iter().map(|e| e.length).sum();
+    let total_chunks = episodes.len().div_ceil(CHUNKS_SIZE);
+    let (codec, height, width) = video
+        .map(|v| (v.codec.as_str(), v.height, v.width))
+        .unwrap_or(("h264", 0, 0));
+    let scalar = |dtype: &str| serde_json::json!({ "dtype": dtype, "shape": [1], "names": null });
+
+    serde_json::json!({
+        "codebase_version": CODEBASE_VERSION,
+        "robot_type": robot_type,
+        "total_episodes": episodes.len(),
+        "total_frames": total_frames,
+        "total_tasks": total_tasks,
+        "total_videos": episodes.len(),
+        "total_chunks": total_chunks,
+        "chunks_size": CHUNKS_SIZE,
+        "fps": fps,
+        "splits": { "train": format!("0:{}", episodes.len()) },
+        "data_path": DATA_PATH,
+        "video_path": VIDEO_PATH,
+        "features": {
+            VIDEO_KEY: {
+                "dtype": "video",
+                "shape": [height, width, 3],
+                "names": ["height", "width", "channels"],
+                "info": {
+                    "video.fps": fps,
+                    "video.height": height,
+                    "video.width": width,
+                    "video.channels": 3,
+                    "video.codec": codec,
+                    "video.pix_fmt": "yuv420p",
+                    "video.is_depth_map": false,
+                    "has_audio": false
+                }
+            },
+            "timestamp": scalar("float32"),
+            "frame_index": scalar("int64"),
+            "episode_index": scalar("int64"),
+            "index": scalar("int64"),
+            "task_index": scalar("int64"),
+        }
+    })
+}
+
+pub fn episodes_jsonl(episodes: &[Episode], tasks: &[String]) -> String {
+    episodes
+        .iter()
+        .map(|e| {
+            serde_json::json!({
+                "episode_index": e.episode_index,
+                "tasks": [tasks[e.task_index]],
+                "length": e.length,
+            })
+            .to_string()
+                + "\n"
+        })
+        .collect()
+}
+
+pub fn tasks_jsonl(tasks: &[String]) -> String {
+    tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| serde_json::json!({ "task_index": i, "task": t }).to_string() + "\n")
+        .collect()
+}
+
+/// A `meta/info.json` for `clips` as episodes, without rendering anything:
+/// `tasks` and `episodes` are added alongside the usual fields, and each
+/// episode lists the source segments (in `source_bucket`) it would be cut
+/// from. Segment timestamps are seconds into the episode, clamped to the clip.
+/// Frame counts are nominal (clip duration at `fps`) since no video is probed.
+pub fn dataset_manifest(
+    robot_type: &str,
+    fps: f64,
+    source_bucket: &str,
+    clips: &[ManifestClip],
+) -> serde_json::Value {
+    let mut tasks = Vec::new();
+    let episodes: Vec<Episode> = clips
+        .iter()
+        .enumerate()
+        .map(|(i, clip)| Episode {
+            episode_index: i,
+            task_index: task_index(&mut tasks, &clip.task),
+            length: nominal_frames(clip.clip_end_ms - clip.clip_start_ms, fps),
+        })
+        .collect();
+
+    let entries: Vec<serde_json::Value> = clips
+        .iter()
+        .zip(&episodes)
+        .map(|(clip, episode)| {
+            let timestamp = |ms: i64| {
+                (ms.clamp(clip.clip_start_ms, clip.clip_end_ms) - clip.clip_start_ms) as f64 / 1000.0
+            };
+            let segments: Vec<serde_json::Value> = clip
+                .segments
+                .iter()
+                .map(|s| {
+                    serde_json::json!({
+                        "segment_id": s.segment_id,
+                        "type": s.segment_type,
+                        "modality": clip.modality,
+                        "source_bucket": source_bucket,
+                        "s3_key": s.s3_key,
+                        "start_ms": s.start_ms,
+                        "end_ms": s.end_ms,
+                        "from_timestamp": timestamp(s.start_ms),
+                        "to_timestamp": timestamp(s.end_ms),
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "episode_index": episode.episode_index,
+                "clip_id": clip.clip_id,
+                "tasks": [tasks[episode.task_index]],
+                "task_index": episode.task_index,
+                "length": episode.length,
+                "modality": clip.modality,
+                "clip_start_ms": clip.clip_start_ms,
+                "clip_end_ms": clip.clip_end_ms,
+                "data_path": data_path(episode.episode_index),
+                "video_path": video_path(episode.episode_index),
+                "segments": segments,
+            })
+        })
+        .collect();
+
+    let mut manifest = info_json(robot_type, fps, None, &episodes, tasks.len());
+    manifest["tasks"] = tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| serde_json::json!({ "task_index": i, "task": t }))
+        .collect();
+    manifest["episodes"] = entries.into();
+    manifest
+}
+
+/// Frames in `duration_ms` of video at `fps`.
+fn nominal_frames(duration_ms: i64, fps: f64) -> u64 {
+    (duration_ms.max(0) as f64 * fps / 1000.0).round() as u64
+}
+
+/// Per-frame index data for one episode. `first_index` is the dataset-wide
+/// index of the episode's first frame.
+pub fn episode_parquet(episode: &Episode, first_index: u64, fps: f64) -> Result<Vec<u8>, ExportError> {
+    let err = |e: parquet::errors::ParquetError| ExportError::Parquet(e.to_string());
+
+    let n = episode.length as i64;
+    let first = first_index as i64;
+    let timestamps: Vec<f32> = (0..n).map(|i| (i as f64 / fps) as f32).collect();
+    let frame_index: Vec<i64> = (0..n).collect();
+    let episode_index = vec![episode.episode_index as i64; n as usize];
+    let index: Vec<i64> = (first..first + n).collect();
+    let task_index = vec![episode.task_index as i64; n as usize];
+
+    let schema = Arc::new(parse_message_type(EPISODE_SCHEMA).map_err(err)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut buf = Vec::new();
+    let mut writer = SerializedFileWriter::new(&mut buf, schema, props).map_err(err)?;
+    let mut row_group = writer.next_row_group().map_err(err)?;
+
+    let mut col = row_group.next_column().map_err(err)?.expect("timestamp column");
+    col.typed::<FloatType>().write_batch(&timestamps, None, None).map_err(err)?;
+    col.close().map_err(err)?;
+    for values in [&frame_index, &episode_index, &index, &task_index] {
+        let mut col = row_group.next_column().map_err(err)?.expect("int64 column");
+        col.typed::<Int64Type>().write_batch(values, None, None).map_err(err)?;
+        col.close().map_err(err)?;
+    }
+
+    row_group.close().map_err(err)?;
+    writer.close().map_err(err)?;
+    Ok(buf)
+}
+
+// ---------------------------------------------------------------------------
+// ffmpeg
+// ---------------------------------------------------------------------------
+
+/// Join `inputs` back to back and cut `[offset_ms, offset_ms + duration_ms)`
+/// into `output`. Stream copy: cuts land on keyframes (every 1s for our segments).
+async fn concat_and_trim(
+    inputs: &[PathBuf],
+    offset_ms: i64,
+    duration_ms: i64,
+    output: &Path,
+) -> Result<(), ExportError> {
+    let offset = format!("{:.3}", offset_ms as f64 / 1000.0);
+    let duration = format!("{:.3}", duration_ms as f64 / 1000.0);
+    run_concat(inputs, &["-ss", &offset], &["-t", &duration], output).await
+}
+
+/// Join `inputs` back to back into `output`, untrimmed (stream copy).
+pub(crate) async fn concat(inputs: &[PathBuf], output: &Path) -> Result<(), ExportError> {
+    run_concat(inputs, &[], &[], output).await
+}
+
+/// Run ffmpeg's concat demuxer over `inputs`, with extra options before the
+/// input (`input_args`) and before the output (`output_args`).
+async fn run_concat(
+    inputs: &[PathBuf],
+    input_args: &[&str],
+    output_args: &[&str],
+    output: &Path,
+) -> Result<(), ExportError> {
+    let list_path = output.with_extension("txt");
+    let list: String = inputs
+        .iter()
+        .map(|p| format!("file '{}'\n", p.display()))
+        .collect();
+    tokio::fs::write(&list_path, list).await?;
+
+    let out = Command::new("ffmpeg")
+        .args(["-v", "error", "-f", "concat", "-safe", "0"])
+        .args(input_args)
+        .arg("-i")
+        .arg(&list_path)
+        .args(output_args)
+        .args(["-c", "copy", "-y"])
+        .args(if output.extension().is_some_and(|ext| ext == "mp4") { &["-movflags", "+faststart"][..] } else { &[] })
+        .arg(output)
+        .output()
+        .await
+        .map_err(|e| ExportError::Ffmpeg(e.to_string()))?;
+    let _ = tokio::fs::remove_file(&list_path).await;
+
+    if !out.status.success() {
+        return Err(ExportError::Ffmpeg(String::from_utf8_lossy(&out.stderr).into_owned()));
+    }
+    Ok(())
+}
+
+async fn probe_video(path: &Path) -> Result<VideoProbe, ExportError> {
+    let out = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "v:0",
+            "-count_packets",
+            "-show_entries", "stream=codec_name,width,height,nb_read_packets",
+            "-of", "json",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| ExportError::Probe(e.to_string()))?;
+    if !out.status.success() {
+        return Err(ExportError::Probe(String::from_utf8_lossy(&out.stderr).into_owned()));
+    }
+    parse_probe(&out.stdout)
+        .ok_or_else(|| ExportError::Probe(format!("no video stream in {}", path.display())))
+}
+
+/// Parse `ffprobe -of json` output for the first video stream.
+fn parse_probe(json: &[u8]) -> Option<VideoProbe> {
+    let value: serde_json::Value = serde_json::from_slice(json).ok()?;
+    let stream = value.get("streams")?.get(0)?;
+    Some(VideoProbe {
+        codec: stream.get("codec_name")?.as_str()?.to_string(),
+        width: stream.get("width")?.as_u64()? as u32,
+        height: stream.get("height")?.as_u64()? as u32,
+        // ffprobe reports counts as strings.
+        frames: stream.get("nb_read_packets")?.as_str()?.parse().ok()?,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// S3
+// ---------------------------------------------------------------------------
+
+async fn download(target: &ExportTarget<'_>, key: &str, path: &Path) -> Result<(), ExportError> {
+    let err = |message: String| ExportError::Download { key: key.to_string(), message };
+    let resp = target
+        .s3
+        .get_object()
+        .bucket(target.source_bucket)
+        .key(key.trim_start_matches('/'))
+        .send()
+        .await
+        .map_err(|e| err(e.to_string()))?;
+    let data = resp.body.collect().await.map_err(|e| err(e.to_string()))?;
+    tokio::fs::write(path, data.into_bytes()).await?;
+    Ok(())
+}
+
+async fn upload(
+    target: &ExportTarget<'_>,
+    key: &str,
+    body: Vec<u8>,
+    content_type: &str,
+) -> Result<(), ExportError> {
+    target
+        .s3
+        .put_object()
+        .bucket(target.dest_bucket)
+        .key(key)
+        .content_type(content_type)
+        .body(ByteStream::from(body))
+        .send()
+        .await
+        .map_err(|e| ExportError::Upload { key: key.to_string(), message: e.to_string() })?;
+    Ok(())
+}
+
+async fn upload_file(
+    target: &ExportTarget<'_>,
+    key: &str,
+    path: &Path,
+    content_type: &str,
+) -> Result<(), ExportError> {
+    let body = tokio::fs::read(path).await?;
+    upload(target, key, body, content_type).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    #[test]
+    fn paths_follow_lerobot_chunking() {
+        assert_eq!(data_path(0), "data/chunk-000/episode_000000.parquet");
+        assert_eq!(
+            video_path(1234),
+            "videos/chunk-001/observation.images.camera/episode_001234.mp4"
+        );
+    }
+
+    #[test]
+    fn trim_window_clamps_to_first_segment() {
+        // Clip starts 2.5s into the first segment.
+        assert_eq!(trim_window(12_500, 20_000, 10_000), (2_500, 7_500));
+        // Clip starts before the first segment: no seek, shortened.
+        assert_eq!(trim_window(8_000, 20_000, 10_000), (0, 10_000));
+    }
+
+    #[test]
+    fn task_for_joins_unique_labels_or_falls_back() {
+        let labels = vec!["grasp".to_string(), "cup".to_string(), "grasp".to_string()];
+        assert_eq!(task_for(&labels, "pick-cup"), "grasp, cup");
+        assert_eq!(task_for(&[], "pick-cup"), "pick-cup");
+    }
+
+    #[test]
+    fn meta_files_reference_tasks_by_index() {
+        let mut tasks = Vec::new();
+        let episodes = vec![
+            Episode { episode_index: 0, task_index: task_index(&mut tasks, "a"), length: 10 },
+            Episode { episode_index: 1, task_index: task_index(&mut tasks, "b"), length: 20 },
+            Episode { episode_index: 2, task_index: task_index(&mut tasks, "a"), length: 5 },
+        ];
+        assert_eq!(tasks, vec!["a", "b"]);
+        assert_eq!(
+            tasks_jsonl(&tasks),
+            "{\"task\":\"a\",\"task_index\":0}\n{\"task\":\"b\",\"task_index\":1}\n"
+        );
+        let lines: Vec<serde_json::Value> = episodes_jsonl(&episodes, &tasks)
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines[2]["tasks"], serde_json::json!(["a"]));
+        assert_eq!(lines[1]["length"], 20);
+
+        let info = info_json("reachy-001", 30.0, None, &episodes, tasks.len());
+        assert_eq!(info["total_frames"], 35);
+        assert_eq!(info["total_chunks"], 1);
+        assert_eq!(info["splits"]["train"], "0:3");
+        assert_eq!(info["features"][VIDEO_KEY]["dtype"], "video");
+    }
+
+    #[test]
+    fn dataset_manifest_lists_clips_as_episodes() {
+        let segment = |segment_id: i64, start_ms: i64, end_ms: i64| ManifestSegment {
+            segment_id,
+            segment_type: "active".into(),
+            start_ms,
+            end_ms,
+            s3_key: format!("reachy-001/camera/{segment_id}.mp4"),
+        };
+        let clip = |clip_id: i64, task: &str, segments: Vec<ManifestSegment>| ManifestClip {
+            clip_id,
+            clip_start_ms: 10_000,
+            clip_end_ms: 12_000,
+            modality: "camera".into(),
+            task: task.into(),
+            segments,
+        };
+        let clips = [
+            clip(7, "grasp", vec![segment(1, 9_000, 11_000), segment(2, 11_000, 13_000)]),
+            clip(8, "grasp", vec![]),
+        ];
+
+        let manifest = dataset_manifest("reachy-001", 30.0, "camera-frames", &clips);
+        assert_eq!(manifest["codebase_version"], CODEBASE_VERSION);
+        assert_eq!(manifest["total_episodes"], 2);
+        assert_eq!(manifest["total_frames"], 120);
+        assert_eq!(manifest["tasks"], serde_json::json!([{ "task_index": 0, "task": "grasp" }]));
+
+        let episode = &manifest["episodes"][0];
+        assert_eq!(episode["clip_id"], 7);
+        assert_eq!(episode["length"], 60);
+        assert_eq!(episode["video_path"], video_path(0));
+        let segments = episode["segments"].as_array().unwrap();
+        assert_eq!(segments[0]["s3_key"], "reachy-001/camera/1.mp4");
+        assert_eq!((segments[0]["from_timestamp"].as_f64(), segments[0]["to_timestamp"].as_f64()), (Some(0.0), Some(1.0)));
+        assert_eq!((segments[1]["from_timestamp"].as_f64(), segments[1]["to_timestamp"].as_f64()), (Some(1.0), Some(2.0)));
+        assert_eq!(manifest["episodes"][1]["episode_index"], 1);
+    }
+
+    #[test]
+    fn episode_parquet_has_one_row_per_frame() {
+        let episode = Episode { episode_index: 3, task_index: 1, length: 4 };
+        let bytes = episode_parquet(&episode, 100, 10.0).unwrap();
+
+        let reader = SerializedFileReader::new(bytes::Bytes::from(bytes)).unwrap();
+        let meta = reader.metadata();
+        assert_eq!(meta.file_metadata().num_rows(), 4);
+        let fields: Vec<&str> = meta
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|c| c.name())
+            .collect();
+        assert_eq!(fields, ["timestamp", "frame_index", "episode_index", "index", "task_index"]);
+
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|r| r.unwrap()).collect();
+        let last = &rows[3];
+        assert!((last.get_float(0).unwrap() - 0.3).abs() < 1e-6);
+        assert_eq!(last.get_long(1).unwrap(), 3);
+        assert_eq!(last.get_long(2).unwrap(), 3);
+        assert_eq!(last.get_long(3).unwrap(), 103);
+        assert_eq!(last.get_long(4).unwrap(), 1);
+    }
+
+    #[test]
+    fn parse_probe_reads_first_video_stream() {
+        let json = br#"{"streams":[{"codec_name":"h264","width":640,"height":480,"nb_read_packets":"300"}]}"#;
+        assert_eq!(
+            parse_probe(json),
+            Some(VideoProbe { codec: "h264".into(), width: 640, height: 480, frames: 300 })
+        );
+        assert_eq!(parse_probe(br#"{"streams":[]}"#), None);
+    }
+}
@@ -1,154 +1,152 @@
-use std::path::{Path, PathBuf};
+mod db;
+mod lerobot;
+mod merge;
+mod openapi;
+mod restore;
+mod thumbnail;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 use aws_credential_types::Credentials;
 use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
 use aws_types::region::Region;
-use axum::extract::{Path as AxumPath, Query, State};
-use axum::http::StatusCode;
-use axum::response::{IntoResponse, Redirect};
-use axum::routing::{delete, get};
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path as AxumPath, Query, Request, State};
+use axum::http::{header, HeaderName, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{delete, get, patch, post};
 use axum::{Json, Router};
-use frame_bucket_common::config::Config;
-use rusqlite::{params, Connection};
-use serde::{Deserialize, Serialize};
+// Accepts repeated keys (`?label=a&label=b`) as a Vec.
+use axum_extra::extract::Query as MultiQuery;
+use frame_bucket_common::api_types::{
+    CLIP_MODALITIES, BatchClipResult, BulkDeleteQuery, BulkDeleteResponse, BulkLabelMode,
+    BulkLabels, BulkLabelsResponse, ClipQuery, ClipResponse, CollectionExportQuery,
+    CollectionResponse, CollectionSearchQuery, CreateClip, CreateCollection, DeleteSegmentQuery,
+    DensityBucket, DensityQuery, DownloadInfo, ExportFormat, FleetTimelineQuery, IdlePruneQuery,
+    IdlePruneResponse, LabelCount, LabelMatch, LabelQuery, LeRobotExportResponse, ListQuery,
+    MergeSegments, MoveClip, NeighborQuery, OverlapQuery, OverlapSide, PatchLabels, PatchMetadata,
+    PresignedUrl, RecordingDate, RobotStats, Segment, SegmentOverlap, SegmentQuery, SegmentSort,
+    SegmentTier, TimeBounds, TimelineResponse, UpdateCollection, WsAction, WsClientMessage,
+    WsServerMessage,
+};
+use frame_bucket_common::config::{Config, thumbnail_key};
+use frame_bucket_common::db_types::{
+    decode_segment_ids, encode_segment_ids, ClipRow, CollectionRow,
+};
+use futures_util::StreamExt;
+use db::DbPool;
+use lerobot::{ClipSource, ExportTarget, ManifestClip, ManifestSegment, SourceSegment};
+use rusqlite::{params, OptionalExtension};
+use sha2::{Digest, Sha256};
 use tower_http::cors::{Any, CorsLayer};
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 // ---------------------------------------------------------------------------
 // App state
 // ---------------------------------------------------------------------------
 
 struct AppState {
-    db_dir: PathBuf,
+    /// Per-robot SQLite connections reused across requests.
+    db_pool: Arc<DbPool>,
     #[allow(dead_code)]
     rustfs_public_url: String,
     rustfs_bucket: String,
+    /// `rustfs.prefix`, under which thumbnails are cached.
+    rustfs_prefix: String,
     s3_client: aws_sdk_s3::Client,
     labelled_data_bucket: String,
+    /// AWS client for `aws_s3.bucket`, where eviction archives segments.
+    archive_client: aws_sdk_s3::Client,
+    archive_bucket: String,
+    /// Recording frame rate, used as the dataset fps for exports.
+    recording_fps: f64,
     health_file_path: PathBuf,
+    /// `GET /health` reports 503 once the health file is older than this.
+    health_stale_after: std::time::Duration,
+    db_dir: PathBuf,
+    /// Lifetime of presigned segment URLs (`api.presign_expiry_secs`).
+    presign_expiry: std::time::Duration,
+    /// One permit per open WebSocket (`/ws` and live feeds), `api.max_websockets` in all.
+    websockets: Arc<tokio::sync::Semaphore>,
+    /// Recent `GET /robots/:robot_id/labels` results; each answer scans
+    /// every segment's labels.
+    label_cache: std::sync::Mutex<LabelCache>,
+    /// Cancelled on SIGTERM / Ctrl-C; WebSocket sessions close when it is.
+    shutdown: CancellationToken,
 }
 
-// ---------------------------------------------------------------------------
-// Types — Segments
-// ---------------------------------------------------------------------------
-
-#[derive(Debug, Serialize)]
-struct Segment {
-    id: i64,
-    robot_id: String,
-    #[serde(rename = "type")]
-    segment_type: String,
-    start_ms: i64,
-    end_ms: i64,
-    s3_key: String,
-    size_bytes: Option<i64>,
-    frame_count: Option<i64>,
-    labels: Vec<String>,
-}
+/// Label lists by `(robot_id, prefix, limit)`, with when they were queried.
+type LabelCache = HashMap<(String, String, usize), (Instant, Vec<LabelCount>)>;
 
-#[derive(Debug, Deserialize)]
-struct SegmentQuery {
-    start_ms: Option<i64>,
-    end_ms: Option<i64>,
-    #[serde(rename = "type")]
-    segment_type: Option<String>,
-    limit: Option<i64>,
-}
+/// Max serialized size of a segment's metadata object.
+const MAX_METADATA_BYTES: usize = 16 * 1024;
 
-#[derive(Debug, Deserialize)]
-struct PatchLabels {
-    labels: Vec<String>,
-}
+/// Max `label` filters per segment query; each adds a `json_each` scan per row.
+const MAX_LABEL_FILTERS: usize = 10;
 
-// ---------------------------------------------------------------------------
-// Types — Collections
-// ---------------------------------------------------------------------------
+/// Browser/CDN caching for segment thumbnails. A segment's video doesn't
+/// change, but its id can go away (delete, merge).
+const THUMBNAIL_CACHE_CONTROL: &str = "public, max-age=86400";
 
-#[derive(Debug, Serialize)]
-struct CollectionResponse {
-    id: i64,
-    robot_id: String,
-    name: String,
-    description: String,
-    created_at: i64,
-    updated_at: i64,
-    clip_count: Option<i64>,
-}
+/// RustFS object deletes in flight at once during bulk segment deletes.
+const OBJECT_DELETE_CONCURRENCY: usize = 16;
 
-#[derive(Debug, Deserialize)]
-struct CreateCollection {
-    name: String,
-    description: Option<String>,
-}
+/// Max buckets in one `GET /robots/:robot_id/density` response.
+const MAX_DENSITY_BUCKETS: i64 = 2000;
 
-// ---------------------------------------------------------------------------
-// Types — Clips
-// ---------------------------------------------------------------------------
+/// Max robots in one `GET /timeline` request; each is a query on its own DB.
+const MAX_FLEET_ROBOTS: usize = 20;
 
-#[derive(Debug, Serialize)]
-struct ClipResponse {
-    id: i64,
-    collection_id: i64,
-    robot_id: String,
-    modality: String,
-    clip_start_ms: i64,
-    clip_end_ms: i64,
-    segment_ids: Vec<i64>,
-    manifest_s3_key: Option<String>,
-    created_at: i64,
-}
+/// Max clips in one `POST .../clips/batch` request.
+const MAX_BATCH_CLIPS: usize = 500;
 
-#[derive(Debug, Deserialize)]
-struct CreateClip {
-    clip_start_ms: i64,
-    clip_end_ms: i64,
-    segment_ids: Vec<i64>,
-    labels: Option<Vec<String>>,
-}
+/// Manifest uploads in flight at once while saving a clip batch.
+const MANIFEST_UPLOAD_CONCURRENCY: usize = 8;
 
-// ---------------------------------------------------------------------------
-// Types — Timeline
-// ---------------------------------------------------------------------------
+/// Labels returned by `GET /robots/:robot_id/labels` without / at most with `limit`.
+const DEFAULT_LABEL_LIMIT: usize = 20;
+const MAX_LABEL_LIMIT: usize = 200;
 
-#[derive(Debug, Serialize)]
-struct TimelineResponse {
-    segments: Vec<Segment>,
-    time_bounds: TimeBounds,
-}
+/// How long a label list is served from `AppState.label_cache`.
+const LABEL_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5);
 
-#[derive(Debug, Serialize)]
-struct TimeBounds {
-    earliest_ms: Option<i64>,
-    latest_ms: Option<i64>,
-}
+/// Response header carrying the total number of results of a list request
+/// made with `?with_count=true`.
+const TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
 
-// ---------------------------------------------------------------------------
-// Types — Download info
-// ---------------------------------------------------------------------------
+/// Results returned by collection search without / at most with `limit`.
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
 
-#[derive(Debug, Serialize)]
-struct DownloadInfo {
-    total_bytes: i64,
-    clip_count: i64,
+/// Per-robot subscription state held by one `/ws` socket.
+struct WsSubscription {
+    last_seen_id: i64,
+    since_ms: Option<i64>,
 }
 
 // ---------------------------------------------------------------------------
 // DB helpers (sync, wrapped in spawn_blocking)
 // ---------------------------------------------------------------------------
 
-fn open_robot_db(db_dir: &Path, robot_id: &str) -> rusqlite::Result<Connection> {
-    let path = db_dir.join(format!("{robot_id}.db"));
-    let conn = Connection::open(path)?;
-    conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;")?;
-    Ok(conn)
-}
+/// Columns mapped by [`row_to_segment`], in order.
+const SEGMENT_COLUMNS: &str = "id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, labels, frame_count, metadata,
+     archived_at, archive_key, region, created_at, motion_score";
 
 fn row_to_segment(row: &rusqlite::Row<'_>) -> rusqlite::Result<Segment> {
     let labels_raw: String = row.get(7)?;
     let labels: Vec<String> =
         serde_json::from_str(&labels_raw).unwrap_or_default();
+    let metadata_raw: String = row.get(9)?;
+    let metadata = serde_json::from_str(&metadata_raw)
+        .unwrap_or_else(|_| serde_json::Value::Object(Default::default()));
     Ok(Segment {
         id: row.get(0)?,
         robot_id: row.get(1)?,
@@ -159,6 +157,11 @@ fn row_to_segment(row: &rusqlite::Row<'_>) -> rusqlite::Result<Segment> {
         size_bytes: row.get(6)?,
         frame_count: row.get(8).ok(),
         labels,
+        metadata,
+        tier: SegmentTier::from_archive_columns(row.get(10)?, row.get::<_, Option<String>>(11)?.as_deref()),
+        region: row.get(12)?,
+        created_at: row.get(13)?,
+        motion_score: row.get(14)?,
     })
 }
 
@@ -166,29 +169,26 @@ fn row_to_segment(row: &rusqlite::Row<'_>) -> rusqlite::Result<Segment> {
 // Handlers — Segments (existing)
 // ---------------------------------------------------------------------------
 
-/// GET /robots — list all robots that have a .db file in db_dir
+/// GET /robots — list all robots with recorded data
+#[utoipa::path(
+    get,
+    path = "/robots",
+    tag = "robots",
+    responses(
+        (status = 200, description = "Robots with recorded data", body = Vec<String>),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
 async fn list_robots(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || {
-        let mut robots = Vec::new();
-        let Ok(entries) = std::fs::read_dir(&db_dir) else {
-            return robots;
-        };
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().and_then(|e| e.to_str()) == Some("db") {
-                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-                    robots.push(stem.to_string());
-                }
-            }
-        }
-        robots.sort();
-        robots
-    })
-    .await;
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || db_pool.robot_ids()).await;
 
     match result {
-        Ok(robots) => Json(robots).into_response(),
+        Ok(Ok(robots)) => Json(robots).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
@@ -196,51 +196,77 @@ async fn list_robots(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     }
 }
 
-/// GET /robots/:robot_id/segments?start_ms=&end_ms=&type=&limit=
+/// GET /robots/:robot_id/segments?start_ms=&end_ms=&type=&region=&label=&match=all|any&min_frame_count=&min_size_bytes=&min_motion=&sort=start_ms|created_at&limit=&with_count=
+/// With `with_count=true`, `X-Total-Count` is the number of segments matching
+/// the filters, not capped by `limit`.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), SegmentQuery),
+    responses(
+        (status = 200, description = "Matching segments", body = Vec<Segment>,
+            headers(("x-total-count" = i64, description = "Number of matching segments, ignoring `limit` (with `with_count=true`)"))),
+        (status = 400, description = "Too many label filters"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
 async fn list_segments(
     State(state): State<Arc<AppState>>,
     AxumPath(robot_id): AxumPath<String>,
-    Query(q): Query<SegmentQuery>,
+    MultiQuery(q): MultiQuery<SegmentQuery>,
 ) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<Segment>> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
+    if q.labels.len() > MAX_LABEL_FILTERS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_LABEL_FILTERS} label filters are allowed"),
+        )
+            .into_response();
+    }
 
-        let mut wheres: Vec<String> = vec!["robot_id = ?".into()];
-        let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(robot_id)];
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<(Vec<Segment>, Option<i64>)> {
+        let conn = db_pool.get(&robot_id)?;
 
-        if let Some(start_ms) = q.start_ms {
-            param_values.push(Box::new(start_ms));
-            wheres.push(format!("end_ms >= ?{}", param_values.len()));
-        }
-        if let Some(end_ms) = q.end_ms {
-            param_values.push(Box::new(end_ms));
-            wheres.push(format!("start_ms <= ?{}", param_values.len()));
-        }
-        if let Some(ref seg_type) = q.segment_type {
-            param_values.push(Box::new(seg_type.clone()));
-            wheres.push(format!("type = ?{}", param_values.len()));
-        }
+        let (mut where_clause, mut param_values) =
+            segment_filter(robot_id, q.start_ms, q.end_ms, q.segment_type.clone(), q.region.clone());
+        push_label_filter(&mut where_clause, &mut param_values, &q.labels, q.label_match);
+        push_minimum_filter(&mut where_clause, &mut param_values, "frame_count", q.min_frame_count);
+        push_minimum_filter(&mut where_clause, &mut param_values, "size_bytes", q.min_size_bytes);
+        push_minimum_filter(&mut where_clause, &mut param_values, "motion_score", q.min_motion);
         let limit_clause = format!("LIMIT {}", q.limit.unwrap_or(100).min(1000));
+        let order_clause = match q.sort {
+            SegmentSort::StartMs => "start_ms ASC",
+            SegmentSort::CreatedAt => "created_at DESC, id DESC",
+        };
         let sql = format!(
-            "SELECT id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, labels, frame_count
+            "SELECT {SEGMENT_COLUMNS}
              FROM segments
              WHERE {}
-             ORDER BY start_ms ASC
+             ORDER BY {}
              {}",
-            wheres.join(" AND "),
+            where_clause,
+            order_clause,
             limit_clause
         );
 
         let params: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
         let mut stmt = conn.prepare(&sql)?;
-        let rows = stmt.query_map(params.as_slice(), row_to_segment)?;
-        rows.collect()
+        let segments = stmt
+            .query_map(params.as_slice(), row_to_segment)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let total = if q.with_count {
+            let sql = format!("SELECT COUNT(*) FROM segments WHERE {where_clause}");
+            Some(conn.query_row(&sql, params.as_slice(), |row| row.get(0))?)
+        } else {
+            None
+        };
+        Ok((segments, total))
     })
     .await;
 
     match result {
-        Ok(Ok(segments)) => Json(segments).into_response(),
+        Ok(Ok((segments, total))) => with_total_count(Json(segments), total),
         Ok(Err(e)) => {
             error!(error = %e, "SQLite query failed");
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
@@ -252,191 +278,394 @@ async fn list_segments(
     }
 }
 
-/// GET /robots/:robot_id/segments/:id
-async fn get_segment(
+/// WHERE clause and parameters selecting a robot's segments intersecting
+/// `[start_ms, end_ms]`, optionally of one type and motion region. Shared by
+/// list and bulk delete.
+fn segment_filter(
+    robot_id: String,
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    segment_type: Option<String>,
+    region: Option<String>,
+) -> (String, Vec<Box<dyn rusqlite::types::ToSql>>) {
+    let mut wheres: Vec<String> = vec!["robot_id = ?1".into()];
+    let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(robot_id)];
+
+    if let Some(start_ms) = start_ms {
+        param_values.push(Box::new(start_ms));
+        wheres.push(format!("end_ms >= ?{}", param_values.len()));
+    }
+    if let Some(end_ms) = end_ms {
+        param_values.push(Box::new(end_ms));
+        wheres.push(format!("start_ms <= ?{}", param_values.len()));
+    }
+    if let Some(seg_type) = segment_type {
+        param_values.push(Box::new(seg_type));
+        wheres.push(format!("type = ?{}", param_values.len()));
+    }
+    if let Some(region) = region {
+        param_values.push(Box::new(region));
+        wheres.push(format!("region = ?{}", param_values.len()));
+    }
+    (wheres.join(" AND "), param_values)
+}
+
+/// Narrow a [`segment_filter`] clause to segments whose `labels` JSON array
+/// contains all (or any) of `labels`. Uses SQLite's `json_each`, which can't
+/// use an index: every row left by the other conditions has its labels
+/// parsed, so it's cheap within a time range but scans the robot's whole
+/// history without one.
+fn push_label_filter(
+    where_clause: &mut String,
+    param_values: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
+    labels: &[String],
+    label_match: LabelMatch,
+) {
+    if labels.is_empty() {
+        return;
+    }
+    let mut placeholders = Vec::with_capacity(labels.len());
+    for label in labels {
+        param_values.push(Box::new(label.clone()));
+        placeholders.push(format!("?{}", param_values.len()));
+    }
+    let contains = |values: &str| {
+        format!("EXISTS (SELECT 1 FROM json_each(segments.labels) WHERE json_each.value IN ({values}))")
+    };
+    let clause = match label_match {
+        LabelMatch::Any => contains(&placeholders.join(", ")),
+        LabelMatch::All => placeholders
+            .iter()
+            .map(|p| contains(p))
+            .collect::<Vec<_>>()
+            .join(" AND "),
+    };
+    where_clause.push_str(" AND ");
+    where_clause.push_str(&clause);
+}
+
+/// Narrow a [`segment_filter`] clause to rows with `column >= min`. Rows where
+/// `column` is NULL (e.g. `frame_count` of idle segments) never match.
+fn push_minimum_filter(
+    where_clause: &mut String,
+    param_values: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
+    column: &str,
+    min: Option<impl rusqlite::types::ToSql + 'static>,
+) {
+    if let Some(min) = min {
+        param_values.push(Box::new(min));
+        where_clause.push_str(&format!(" AND {column} >= ?{}", param_values.len()));
+    }
+}
+
+/// A list response, with its [`TOTAL_COUNT`] header when the count was asked for.
+fn with_total_count(body: impl IntoResponse, total: Option<i64>) -> Response {
+    match total {
+        Some(total) => ([(TOTAL_COUNT, total.to_string())], body).into_response(),
+        None => body.into_response(),
+    }
+}
+
+/// DELETE /robots/:robot_id/segments?start_ms=&end_ms=&type=&confirm=true
+/// Delete every matching segment row and, best-effort, its RustFS object.
+/// Segments referenced by saved clips are skipped and reported.
+#[utoipa::path(
+    delete,
+    path = "/robots/{robot_id}/segments",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), BulkDeleteQuery),
+    responses(
+        (status = 200, description = "Segments deleted", body = BulkDeleteResponse),
+        (status = 400, description = "Missing time bounds or `confirm=true`"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn delete_segments(
     State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<BulkDeleteQuery>,
 ) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<Segment>> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        let mut stmt = conn.prepare(
-            "SELECT id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, labels, frame_count
-             FROM segments WHERE id = ?1 AND robot_id = ?2",
-        )?;
-        let mut rows = stmt.query_map(params![id, robot_id], row_to_segment)?;
-        Ok(rows.next().transpose()?)
+    if !q.confirm {
+        return (StatusCode::BAD_REQUEST, "Bulk delete requires confirm=true").into_response();
+    }
+    let (Some(start_ms), Some(end_ms)) = (q.start_ms, q.end_ms) else {
+        return (StatusCode::BAD_REQUEST, "Bulk delete requires both start_ms and end_ms").into_response();
+    };
+    if start_ms > end_ms {
+        return (StatusCode::BAD_REQUEST, "start_ms must not be after end_ms").into_response();
+    }
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<SegmentDeletion> {
+        let mut conn = db_pool.get(&rid)?;
+        delete_segments_in_range(&mut conn, &rid, start_ms, end_ms, q.segment_type)
     })
     .await;
 
-    match result {
-        Ok(Ok(Some(seg))) => Json(seg).into_response(),
-        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+    let deletion = match result {
+        Ok(Ok(d)) => d,
         Ok(Err(e)) => {
-            error!(error = %e, "SQLite query failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            error!(error = %e, "SQLite bulk delete failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
+    };
+
+    if !deletion.skipped_in_clips.is_empty() {
+        warn!(
+            robot_id,
+            skipped = ?deletion.skipped_in_clips,
+            "bulk delete skipped segments referenced by clips"
+        );
     }
-}
 
-/// GET /robots/:robot_id/segments/:id/video — 302 redirect to RustFS object URL
-async fn video_redirect(
-    State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<String>> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        let mut stmt =
-            conn.prepare("SELECT s3_key FROM segments WHERE id = ?1 AND robot_id = ?2")?;
-        let mut rows = stmt.query_map(params![id, robot_id], |row| row.get::<_, String>(0))?;
-        Ok(rows.next().transpose()?)
+    // Rows are gone; object cleanup is best-effort.
+    let object_delete_failures = delete_hot_objects(&state, &deletion.hot_keys).await;
+    delete_thumbnails(&state, &robot_id, &deletion.active_ids).await;
+
+    info!(
+        robot_id,
+        start_ms,
+        end_ms,
+        deleted = deletion.deleted,
+        skipped = deletion.skipped_in_clips.len(),
+        object_delete_failures,
+        "bulk deleted segments"
+    );
+    Json(BulkDeleteResponse {
+        deleted: deletion.deleted,
+        skipped_in_clips: deletion.skipped_in_clips,
+        object_delete_failures,
     })
-    .await;
+    .into_response()
+}
 
-    match result {
-        Ok(Ok(Some(s3_key))) => {
-            // Generate a presigned URL so the browser can fetch directly from RustFS
-            let presign_config = match PresigningConfig::expires_in(std::time::Duration::from_secs(3600)) {
-                Ok(c) => c,
-                Err(e) => {
-                    error!(error = %e, "failed to create presigning config");
-                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-                }
-            };
-            let presigned = state
+/// Best-effort delete of segment objects from RustFS, a few at a time.
+/// Returns how many could not be deleted.
+async fn delete_hot_objects(state: &AppState, keys: &[String]) -> usize {
+    futures_util::stream::iter(keys.to_vec())
+        .map(|key: String| async move {
+            let resp = state
                 .s3_client
-                .get_object()
+                .delete_object()
                 .bucket(&state.rustfs_bucket)
-                .key(s3_key.trim_start_matches('/'))
-                .presigned(presign_config)
+                .key(key.trim_start_matches('/'))
+                .send()
                 .await;
-            match presigned {
-                Ok(req) => {
-                    let url = req.uri().to_string();
-                    info!(url, "redirecting to presigned RustFS URL");
-                    Redirect::temporary(&url).into_response()
-                }
-                Err(e) => {
-                    error!(error = %e, "failed to generate presigned URL");
-                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
-                }
+            if let Err(e) = &resp {
+                warn!(error = %e, key, "failed to delete object from RustFS");
             }
-        }
-        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
-        Ok(Err(e)) => {
-            error!(error = %e, "SQLite query failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-        }
-        Err(e) => {
-            error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
-    }
+            resp.is_err()
+        })
+        .buffer_unordered(OBJECT_DELETE_CONCURRENCY)
+        .filter(|failed| std::future::ready(*failed))
+        .count()
+        .await
 }
 
-/// PATCH /robots/:robot_id/segments/:id — update labels
-async fn patch_labels(
+/// Best-effort delete of the cached thumbnails of deleted active segments.
+/// Deleting one that was never rendered is a no-op.
+async fn delete_thumbnails(state: &AppState, robot_id: &str, segment_ids: &[i64]) {
+    let keys: Vec<String> = segment_ids
+        .iter()
+        .map(|&id| thumbnail_key(&state.rustfs_prefix, robot_id, id))
+        .collect();
+    delete_hot_objects(state, &keys).await;
+}
+
+/// DELETE /robots/:robot_id/segments/idle?before_ms= — prune old idle snapshots
+#[utoipa::path(
+    delete,
+    path = "/robots/{robot_id}/segments/idle",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), IdlePruneQuery),
+    responses(
+        (status = 200, description = "Idle segments deleted", body = IdlePruneResponse),
+        (status = 400, description = "Missing `before_ms`"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn prune_idle_segments(
     State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
-    Json(body): Json<PatchLabels>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<IdlePruneQuery>,
 ) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let labels_json = match serde_json::to_string(&body.labels) {
-        Ok(j) => j,
-        Err(e) => {
-            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
-        }
+    let Some(before_ms) = q.before_ms else {
+        return (StatusCode::BAD_REQUEST, "Pruning idle segments requires before_ms").into_response();
     };
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        conn.execute(
-            "UPDATE segments SET labels = ?1 WHERE id = ?2 AND robot_id = ?3",
-            params![labels_json, id, robot_id],
-        )
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<IdlePrune> {
+        let mut conn = db_pool.get(&rid)?;
+        delete_idle_before(&mut conn, &rid, before_ms)
     })
     .await;
 
-    match result {
-        Ok(Ok(0)) => StatusCode::NOT_FOUND.into_response(),
-        Ok(Ok(_)) => StatusCode::NO_CONTENT.into_response(),
+    let prune = match result {
+        Ok(Ok(p)) => p,
         Ok(Err(e)) => {
-            error!(error = %e, "SQLite update failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            error!(error = %e, "SQLite idle prune failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
-    }
+    };
+
+    // Rows are gone; object cleanup is best-effort. Idle segments serve their
+    // own JPEG as thumbnail, so there are no cached ones to delete.
+    let object_delete_failures = delete_hot_objects(&state, &prune.hot_keys).await;
+
+    info!(
+        robot_id,
+        before_ms,
+        deleted = prune.deleted,
+        bytes_freed = prune.bytes_freed,
+        skipped = prune.skipped_in_clips,
+        object_delete_failures,
+        "pruned idle segments"
+    );
+    Json(IdlePruneResponse {
+        deleted: prune.deleted,
+        bytes_freed: prune.bytes_freed,
+        skipped_in_clips: prune.skipped_in_clips,
+        object_delete_failures,
+    })
+    .into_response()
 }
 
-// ---------------------------------------------------------------------------
-// Handlers — Timeline
-// ---------------------------------------------------------------------------
+/// `(clip_id, segment_id)` for every segment saved in the clips of robot `?1`
+/// (a malformed `segment_ids` list counts as empty). The one definition of
+/// "referenced by a clip" that the segment deletes check against.
+const CLIP_SEGMENTS: &str = "SELECT collection_clips.id AS clip_id, json_each.value AS segment_id
+     FROM collection_clips,
+          json_each(CASE WHEN json_valid(collection_clips.segment_ids)
+                         THEN collection_clips.segment_ids ELSE '[]' END)
+     WHERE collection_clips.robot_id = ?1";
 
-/// GET /robots/:robot_id/timeline?start_ms=&end_ms=
-async fn get_timeline(
-    State(state): State<Arc<AppState>>,
-    AxumPath(robot_id): AxumPath<String>,
-    Query(q): Query<SegmentQuery>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<TimelineResponse> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
+/// Delete a robot's idle segments ending before `before_ms`, except those
+/// referenced by clips, in one statement.
+fn delete_idle_before(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    before_ms: i64,
+) -> rusqlite::Result<IdlePrune> {
+    let tx = conn.transaction()?;
+    let skipped_in_clips: usize = tx.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM segments
+             WHERE robot_id = ?1 AND type = 'idle' AND end_ms < ?2
+               AND id IN (SELECT segment_id FROM ({CLIP_SEGMENTS}))"
+        ),
+        params![robot_id, before_ms],
+        |row| row.get(0),
+    )?;
 
-        // Get time bounds
-        let mut bounds_stmt = conn.prepare(
-            "SELECT MIN(start_ms), MAX(end_ms) FROM segments WHERE robot_id = ?1",
-        )?;
-        let time_bounds = bounds_stmt.query_row(params![robot_id], |row| {
-            Ok(TimeBounds {
-                earliest_ms: row.get(0)?,
-                latest_ms: row.get(1)?,
-            })
-        })?;
+    let mut prune = IdlePrune {
+        skipped_in_clips,
+        ..IdlePrune::default()
+    };
+    {
+        let mut stmt = tx.prepare(&format!(
+            "DELETE FROM segments
+             WHERE robot_id = ?1 AND type = 'idle' AND end_ms < ?2
+               AND id NOT IN (SELECT segment_id FROM ({CLIP_SEGMENTS}))
+             RETURNING s3_key, size_bytes, archived_at"
+        ))?;
+        let mut rows = stmt.query(params![robot_id, before_ms])?;
+        while let Some(row) = rows.next()? {
+            prune.deleted += 1;
+            // Evicted objects are no longer in RustFS and free nothing there.
+            if row.get::<_, Option<i64>>(2)?.is_none() {
+                prune.hot_keys.push(row.get(0)?);
+                prune.bytes_freed += row.get::<_, Option<i64>>(1)?.unwrap_or(0);
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(prune)
+}
 
-        // Get segments in range
-        let mut wheres: Vec<String> = vec!["robot_id = ?".into()];
-        let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(robot_id)];
+/// Delete a robot's segments intersecting `[start_ms, end_ms]`, except those
+/// referenced by clips, in one transaction.
+fn delete_segments_in_range(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    start_ms: i64,
+    end_ms: i64,
+    segment_type: Option<String>,
+) -> rusqlite::Result<SegmentDeletion> {
+    let tx = conn.transaction()?;
+
+    let in_clips: std::collections::HashSet<i64> = {
+        let mut stmt = tx.prepare(&format!("SELECT DISTINCT segment_id FROM ({CLIP_SEGMENTS})"))?;
+        let rows = stmt.query_map(params![robot_id], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let (where_clause, param_values) =
+        segment_filter(robot_id.to_string(), Some(start_ms), Some(end_ms), segment_type, None);
+    let params: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+    let matches: Vec<(i64, String, String, Option<i64>)> = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT id, type, s3_key, archived_at FROM segments WHERE {where_clause} ORDER BY start_ms ASC"
+        ))?;
+        let rows = stmt.query_map(params.as_slice(), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
 
-        if let Some(start_ms) = q.start_ms {
-            param_values.push(Box::new(start_ms));
-            wheres.push(format!("end_ms >= ?{}", param_values.len()));
+    let mut deletion = SegmentDeletion::default();
+    for (id, segment_type, s3_key, archived_at) in matches {
+        if in_clips.contains(&id) {
+            deletion.skipped_in_clips.push(id);
+            continue;
         }
-        if let Some(end_ms) = q.end_ms {
-            param_values.push(Box::new(end_ms));
-            wheres.push(format!("start_ms <= ?{}", param_values.len()));
+        tx.execute("DELETE FROM segments WHERE id = ?1", params![id])?;
+        deletion.deleted += 1;
+        if segment_type == "active" {
+            deletion.active_ids.push(id);
         }
-        let limit_clause = format!("LIMIT {}", q.limit.unwrap_or(500).min(1000));
-        let sql = format!(
-            "SELECT id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, labels, frame_count
-             FROM segments
-             WHERE {}
-             ORDER BY start_ms ASC
-             {}",
-            wheres.join(" AND "),
-            limit_clause
-        );
-
-        let params: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
-        let mut stmt = conn.prepare(&sql)?;
-        let rows = stmt.query_map(params.as_slice(), row_to_segment)?;
-        let segments: rusqlite::Result<Vec<Segment>> = rows.collect();
+        // Evicted objects are no longer in RustFS.
+        if archived_at.is_none() {
+            deletion.hot_keys.push(s3_key);
+        }
+    }
+    tx.commit()?;
+    Ok(deletion)
+}
 
-        Ok(TimelineResponse {
-            segments: segments?,
-            time_bounds,
-        })
+/// GET /robots/:robot_id/segments/overlaps?start_ms=&end_ms=&limit=
+/// Diagnostic: pairs of segments whose time ranges overlap (a recorder restart bug).
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/overlaps",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), OverlapQuery),
+    responses(
+        (status = 200, description = "Overlapping segment pairs", body = Vec<SegmentOverlap>),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn list_overlaps(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<OverlapQuery>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<SegmentOverlap>> {
+        let conn = db_pool.get(&robot_id)?;
+        query_overlaps(&conn, &robot_id, &q)
     })
     .await;
 
     match result {
-        Ok(Ok(timeline)) => Json(timeline).into_response(),
+        Ok(Ok(overlaps)) => Json(overlaps).into_response(),
         Ok(Err(e)) => {
             error!(error = %e, "SQLite query failed");
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
@@ -448,42 +677,95 @@ async fn get_timeline(
     }
 }
 
-// ---------------------------------------------------------------------------
-// Handlers — Collections
-// ---------------------------------------------------------------------------
+fn query_overlaps(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    q: &OverlapQuery,
+) -> rusqlite::Result<Vec<SegmentOverlap>> {
+    let limit = q.limit.unwrap_or(100).min(1000);
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.type, a.start_ms, a.end_ms,
+                b.id, b.type, b.start_ms, b.end_ms,
+                MIN(a.end_ms, b.end_ms) - MAX(a.start_ms, b.start_ms) AS overlap_ms
+         FROM segments a
+         JOIN segments b
+           ON b.robot_id = a.robot_id
+          AND a.start_ms < b.end_ms AND b.start_ms < a.end_ms
+          AND a.id < b.id
+         WHERE a.robot_id = ?1
+           AND (?2 IS NULL OR (a.end_ms >= ?2 AND b.end_ms >= ?2))
+           AND (?3 IS NULL OR (a.start_ms <= ?3 AND b.start_ms <= ?3))
+         ORDER BY a.start_ms ASC, b.start_ms ASC
+         LIMIT ?4",
+    )?;
+    let rows = stmt.query_map(params![robot_id, q.start_ms, q.end_ms, limit], |row| {
+        Ok(SegmentOverlap {
+            a: OverlapSide {
+                id: row.get(0)?,
+                segment_type: row.get(1)?,
+                start_ms: row.get(2)?,
+                end_ms: row.get(3)?,
+            },
+            b: OverlapSide {
+                id: row.get(4)?,
+                segment_type: row.get(5)?,
+                start_ms: row.get(6)?,
+                end_ms: row.get(7)?,
+            },
+            overlap_ms: row.get(8)?,
+        })
+    })?;
+    rows.collect()
+}
 
-/// GET /robots/:robot_id/collections
-async fn list_collections(
+/// GET /robots/:robot_id/segments/:id
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}",
+    tag = "segments",
+    params(
+        ("robot_id" = String, Path, description = "Robot id"),
+        ("id" = i64, Path, description = "Segment id"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "The segment, with an `ETag` header", body = Segment),
+        (status = 304, description = "The cached copy is current"),
+        (status = 404, description = "No such segment"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn get_segment(
     State(state): State<Arc<AppState>>,
-    AxumPath(robot_id): AxumPath<String>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    headers: axum::http::HeaderMap,
 ) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<CollectionResponse>> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        let mut stmt = conn.prepare(
-            "SELECT c.id, c.robot_id, c.name, c.description, c.created_at, c.updated_at,
-                    (SELECT COUNT(*) FROM collection_clips cc WHERE cc.collection_id = c.id)
-             FROM collections c
-             WHERE c.robot_id = ?1
-             ORDER BY c.updated_at DESC",
-        )?;
-        let rows = stmt.query_map(params![robot_id], |row| {
-            Ok(CollectionResponse {
-                id: row.get(0)?,
-                robot_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                clip_count: row.get(6)?,
-            })
-        })?;
-        rows.collect()
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<Segment>> {
+        let conn = db_pool.get(&robot_id)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SEGMENT_COLUMNS}
+             FROM segments WHERE id = ?1 AND robot_id = ?2",
+        ))?;
+        let mut rows = stmt.query_map(params![id, robot_id], row_to_segment)?;
+        rows.next().transpose()
     })
     .await;
 
     match result {
-        Ok(Ok(collections)) => Json(collections).into_response(),
+        Ok(Ok(Some(seg))) => {
+            let body = serde_json::to_vec(&seg).unwrap_or_default();
+            let etag = segment_etag(&body);
+            if etag_matches(headers.get(header::IF_NONE_MATCH), &etag) {
+                return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+            }
+            (
+                [(header::ETAG, etag), (header::CONTENT_TYPE, "application/json".to_string())],
+                body,
+            )
+                .into_response()
+        }
+        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
         Ok(Err(e)) => {
             error!(error = %e, "SQLite query failed");
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
@@ -495,86 +777,63 @@ async fn list_collections(
     }
 }
 
-/// POST /robots/:robot_id/collections
-async fn create_collection(
-    State(state): State<Arc<AppState>>,
-    AxumPath(robot_id): AxumPath<String>,
-    Json(body): Json<CreateCollection>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<CollectionResponse> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        let now = chrono::Utc::now().timestamp_millis();
-        let desc = body.description.as_deref().unwrap_or("");
-        conn.execute(
-            "INSERT INTO collections (robot_id, name, description, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![robot_id, body.name, desc, now, now],
-        )?;
-        let id = conn.last_insert_rowid();
-        Ok(CollectionResponse {
-            id,
-            robot_id,
-            name: body.name,
-            description: desc.to_string(),
-            created_at: now,
-            updated_at: now,
-            clip_count: Some(0),
-        })
-    })
-    .await;
+/// GET /robots/:robot_id/segments/:id/next?type=
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}/next",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id"), NeighborQuery),
+    responses(
+        (status = 200, description = "The next segment by start time", body = Segment),
+        (status = 404, description = "No such segment, or none after it"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn next_segment(
+    state: State<Arc<AppState>>,
+    path: AxumPath<(String, i64)>,
+    query: Query<NeighborQuery>,
+) -> Response {
+    neighbor_segment(state, path, query, Neighbor::Next).await
+}
 
-    match result {
-        Ok(Ok(collection)) => (StatusCode::CREATED, Json(collection)).into_response(),
-        Ok(Err(e)) => {
-            // Check for UNIQUE constraint violation
-            let msg = e.to_string();
-            if msg.contains("UNIQUE") {
-                (StatusCode::CONFLICT, "Collection with that name already exists").into_response()
-            } else {
-                error!(error = %e, "SQLite insert failed");
-                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
-            }
-        }
-        Err(e) => {
-            error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
-    }
+/// GET /robots/:robot_id/segments/:id/prev?type=
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}/prev",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id"), NeighborQuery),
+    responses(
+        (status = 200, description = "The previous segment by start time", body = Segment),
+        (status = 404, description = "No such segment, or none before it"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn prev_segment(
+    state: State<Arc<AppState>>,
+    path: AxumPath<(String, i64)>,
+    query: Query<NeighborQuery>,
+) -> Response {
+    neighbor_segment(state, path, query, Neighbor::Prev).await
 }
 
-/// GET /robots/:robot_id/collections/:id
-async fn get_collection(
+async fn neighbor_segment(
     State(state): State<Arc<AppState>>,
     AxumPath((robot_id, id)): AxumPath<(String, i64)>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<CollectionResponse>> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        let mut stmt = conn.prepare(
-            "SELECT c.id, c.robot_id, c.name, c.description, c.created_at, c.updated_at,
-                    (SELECT COUNT(*) FROM collection_clips cc WHERE cc.collection_id = c.id)
-             FROM collections c
-             WHERE c.id = ?1 AND c.robot_id = ?2",
-        )?;
-        let mut rows = stmt.query_map(params![id, robot_id], |row| {
-            Ok(CollectionResponse {
-                id: row.get(0)?,
-                robot_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
-                clip_count: row.get(6)?,
-            })
-        })?;
-        Ok(rows.next().transpose()?)
+    Query(q): Query<NeighborQuery>,
+    direction: Neighbor,
+) -> Response {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<Option<Segment>>> {
+        let conn = db_pool.get(&robot_id)?;
+        query_neighbor(&conn, &robot_id, id, direction, q.segment_type.as_deref())
     })
     .await;
 
     match result {
-        Ok(Ok(Some(c))) => Json(c).into_response(),
-        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(Some(Some(seg)))) => Json(seg).into_response(),
+        Ok(Ok(Some(None))) => (StatusCode::NOT_FOUND, "No neighboring segment").into_response(),
+        Ok(Ok(None)) => (StatusCode::NOT_FOUND, "Segment not found").into_response(),
         Ok(Err(e)) => {
             error!(error = %e, "SQLite query failed");
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
@@ -586,144 +845,206 @@ async fn get_collection(
     }
 }
 
-/// DELETE /robots/:robot_id/collections/:id
-async fn delete_collection(
-    State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        conn.execute(
-            "DELETE FROM collections WHERE id = ?1 AND robot_id = ?2",
+/// The segment right after (or before) segment `id` in `(start_ms, id)`
+/// order, optionally only among segments of `segment_type`. `None` if `id`
+/// doesn't exist, `Some(None)` if it has no such neighbor.
+fn query_neighbor(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    id: i64,
+    direction: Neighbor,
+    segment_type: Option<&str>,
+) -> rusqlite::Result<Option<Option<Segment>>> {
+    let start_ms: Option<i64> = conn
+        .query_row(
+            "SELECT start_ms FROM segments WHERE id = ?1 AND robot_id = ?2",
             params![id, robot_id],
+            |row| row.get(0),
         )
+        .optional()?;
+    let Some(start_ms) = start_ms else {
+        return Ok(None);
+    };
+
+    let (cmp, order) = match direction {
+        Neighbor::Next => (">", "ASC"),
+        Neighbor::Prev => ("<", "DESC"),
+    };
+    let sql = format!(
+        "SELECT {SEGMENT_COLUMNS}
+         FROM segments
+         WHERE robot_id = ?1 AND (start_ms, id) {cmp} (?2, ?3) AND (?4 IS NULL OR type = ?4)
+         ORDER BY start_ms {order}, id {order}
+         LIMIT 1"
+    );
+    conn.query_row(&sql, params![robot_id, start_ms, id, segment_type], row_to_segment)
+        .optional()
+        .map(Some)
+}
+
+/// POST /robots/:robot_id/segments/merge
+/// Join contiguous active segments into one new segment (see `merge`). The
+/// originals' rows and objects are deleted; clips follow the new segment.
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/segments/merge",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id")),
+    request_body = MergeSegments,
+    responses(
+        (status = 201, description = "The merged segment", body = Segment),
+        (status = 400, description = "Segments can't be merged"),
+        (status = 404, description = "A segment doesn't exist"),
+        (status = 409, description = "Segments changed during the merge"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn merge_segments(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Json(body): Json<MergeSegments>,
+) -> impl IntoResponse {
+    let mut ids = body.segment_ids;
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.len() < 2 {
+        return (StatusCode::BAD_REQUEST, "Merging needs at least two distinct segment_ids").into_response();
+    }
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Result<Vec<Segment>, i64>> {
+        let conn = db_pool.get(&rid)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SEGMENT_COLUMNS}
+             FROM segments WHERE id = ?1 AND robot_id = ?2",
+        ))?;
+        let mut segments = Vec::with_capacity(ids.len());
+        for id in ids {
+            match stmt.query_row(params![id, rid], row_to_segment).optional()? {
+                Some(seg) => segments.push(seg),
+                None => return Ok(Err(id)),
+            }
+        }
+        Ok(Ok(segments))
     })
     .await;
 
-    match result {
-        Ok(Ok(0)) => StatusCode::NOT_FOUND.into_response(),
-        Ok(Ok(_)) => StatusCode::NO_CONTENT.into_response(),
+    let mut segments = match result {
+        Ok(Ok(Ok(segments))) => segments,
+        Ok(Ok(Err(missing))) => {
+            return (StatusCode::NOT_FOUND, format!("Segment {missing} not found")).into_response();
+        }
         Ok(Err(e)) => {
-            error!(error = %e, "SQLite delete failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
+    };
+    if let Err(msg) = merge::check_mergeable(&mut segments) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
     }
-}
 
-// ---------------------------------------------------------------------------
-// Handlers — Clips
-// ---------------------------------------------------------------------------
+    let end_ms = segments.last().map_or(0, |s| s.end_ms);
+    let merged_key = merge::merged_key(&segments[0], end_ms);
+    let size_bytes = match merge::concat_objects(&state.s3_client, &state.rustfs_bucket, &segments, &merged_key).await {
+        Ok(size) => size,
+        Err(e) => {
+            error!(error = %e, robot_id, "segment merge failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
 
-/// GET /robots/:robot_id/collections/:collection_id/clips
-async fn list_clips(
-    State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<ClipResponse>> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        let mut stmt = conn.prepare(
-            "SELECT id, collection_id, robot_id, modality, clip_start_ms, clip_end_ms,
-                    segment_ids, manifest_s3_key, created_at
-             FROM collection_clips
-             WHERE collection_id = ?1 AND robot_id = ?2
-             ORDER BY clip_start_ms ASC",
-        )?;
-        let rows = stmt.query_map(params![collection_id, robot_id], |row| {
-            let seg_ids_raw: String = row.get(6)?;
-            let segment_ids: Vec<i64> = serde_json::from_str(&seg_ids_raw).unwrap_or_default();
-            Ok(ClipResponse {
-                id: row.get(0)?,
-                collection_id: row.get(1)?,
-                robot_id: row.get(2)?,
-                modality: row.get(3)?,
-                clip_start_ms: row.get(4)?,
-                clip_end_ms: row.get(5)?,
-                segment_ids,
-                manifest_s3_key: row.get(7)?,
-                created_at: row.get(8)?,
-            })
-        })?;
-        rows.collect()
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let key = merged_key.clone();
+    let originals = segments.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<Segment>> {
+        let mut conn = db_pool.get(&rid)?;
+        merge::replace_with_merged(&mut conn, &rid, &originals, &key, size_bytes)
     })
     .await;
 
-    match result {
-        Ok(Ok(clips)) => Json(clips).into_response(),
+    // On success the originals' objects are stale; otherwise the rows were
+    // kept and the merged object is.
+    let (response, stale_keys) = match result {
+        Ok(Ok(Some(merged))) => {
+            info!(robot_id, id = merged.id, merged = segments.len(), "merged segments");
+            let thumbnails = segments.iter().map(|s| thumbnail_key(&state.rustfs_prefix, &robot_id, s.id));
+            let originals = segments.iter().map(|s| s.s3_key.clone()).chain(thumbnails).collect();
+            ((StatusCode::CREATED, Json(merged)).into_response(), originals)
+        }
+        Ok(Ok(None)) => (
+            (StatusCode::CONFLICT, "Segments changed during the merge; nothing was merged").into_response(),
+            vec![merged_key],
+        ),
         Ok(Err(e)) => {
-            error!(error = %e, "SQLite query failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            error!(error = %e, "SQLite merge failed");
+            ((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(), vec![merged_key])
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            (StatusCode::INTERNAL_SERVER_ERROR.into_response(), vec![merged_key])
+        }
+    };
+
+    for key in &stale_keys {
+        let resp = state
+            .s3_client
+            .delete_object()
+            .bucket(&state.rustfs_bucket)
+            .key(key.trim_start_matches('/'))
+            .send()
+            .await;
+        if let Err(e) = resp {
+            warn!(error = %e, key, "failed to delete object from RustFS after merge");
         }
     }
+    response
 }
 
-/// POST /robots/:robot_id/collections/:collection_id/clips
-/// Saves a clip: builds manifest JSON and writes to labelled-data S3 bucket.
-async fn create_clip(
+/// POST /robots/:robot_id/segments/:id/restore
+/// Copy an evicted segment back from the AWS archive to its RustFS key and
+/// mark it hot again. See `restore`.
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/segments/{id}/restore",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id")),
+    responses(
+        (status = 200, description = "The restored segment", body = Segment),
+        (status = 404, description = "No such segment"),
+        (status = 409, description = "Segment is already in RustFS, has no archive copy, or changed during the restore"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn restore_segment(
     State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
-    Json(body): Json<CreateClip>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
 ) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
+    let db_pool = Arc::clone(&state.db_pool);
     let rid = robot_id.clone();
-    let seg_ids = body.segment_ids.clone();
-
-    // Step 1: Look up collection name and segment metadata from DB
-    let db_result = tokio::task::spawn_blocking(move || -> rusqlite::Result<(String, Vec<SegmentInfo>)> {
-        let conn = open_robot_db(&db_dir, &rid)?;
-
-        // Get collection name
-        let collection_name: String = conn.query_row(
-            "SELECT name FROM collections WHERE id = ?1 AND robot_id = ?2",
-            params![collection_id, rid],
-            |row| row.get(0),
-        )?;
-
-        // Get segment metadata for all referenced segments
-        let mut segments = Vec::new();
-        for seg_id in &seg_ids {
-            let result = conn.query_row(
-                "SELECT id, type, start_ms, end_ms, s3_key, size_bytes
-                 FROM segments WHERE id = ?1 AND robot_id = ?2",
-                params![seg_id, rid],
-                |row| {
-                    Ok(SegmentInfo {
-                        segment_id: row.get(0)?,
-                        segment_type: row.get(1)?,
-                        start_ms: row.get(2)?,
-                        end_ms: row.get(3)?,
-                        source_key: row.get(4)?,
-                        size_bytes: row.get::<_, Option<i64>>(5)?,
-                    })
-                },
-            );
-            if let Ok(seg) = result {
-                segments.push(seg);
-            }
-        }
-
-        Ok((collection_name, segments))
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<restore::Restorable> {
+        let conn = db_pool.get(&rid)?;
+        restore::lookup(&conn, &rid, id)
     })
     .await;
 
-    let (collection_name, segment_infos) = match db_result {
-        Ok(Ok(data)) => data,
+    let (s3_key, archive_key) = match result {
+        Ok(Ok(restore::Restorable::Archived { s3_key, archive_key })) => (s3_key, archive_key),
+        Ok(Ok(restore::Restorable::NotFound)) => return StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(restore::Restorable::Hot)) => {
+            return (StatusCode::CONFLICT, "Segment is already in RustFS").into_response();
+        }
+        Ok(Ok(restore::Restorable::NoArchiveCopy)) => {
+            return (StatusCode::CONFLICT, "Segment was evicted without an archive copy").into_response();
+        }
         Ok(Err(e)) => {
-            let msg = e.to_string();
-            if msg.contains("no rows") {
-                return (StatusCode::NOT_FOUND, "Collection not found").into_response();
-            }
             error!(error = %e, "SQLite query failed");
-            return (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response();
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
@@ -731,98 +1052,41 @@ async fn create_clip(
         }
     };
 
-    if segment_infos.is_empty() {
-        return (StatusCode::BAD_REQUEST, "No valid segments found for given segment_ids").into_response();
-    }
-
-    // Step 2: Build manifest JSON
-    let manifest_segments: Vec<serde_json::Value> = segment_infos
-        .iter()
-        .map(|s| {
-            serde_json::json!({
-                "segment_id": s.segment_id,
-                "source_bucket": state.rustfs_bucket,
-                "source_key": s.source_key,
-                "start_ms": s.start_ms,
-                "end_ms": s.end_ms,
-                "type": s.segment_type,
-                "size_bytes": s.size_bytes,
-                "modality": "camera"
-            })
-        })
-        .collect();
-
-    let manifest = serde_json::json!({
-        "robot_id": robot_id,
-        "collection_id": collection_id,
-        "collection_name": collection_name,
-        "clip_start_ms": body.clip_start_ms,
-        "clip_end_ms": body.clip_end_ms,
-        "labels": body.labels.as_deref().unwrap_or(&[]),
-        "segments": manifest_segments,
-        "created_at": chrono::Utc::now().to_rfc3339(),
-    });
-
-    let manifest_bytes = serde_json::to_vec_pretty(&manifest).unwrap();
-    let safe_name = collection_name.replace(' ', "_").replace('/', "-");
-    let manifest_key = format!(
-        "{}/{}/{}_{}.json",
-        robot_id, safe_name, body.clip_start_ms, body.clip_end_ms
-    );
-
-    // Step 3: Write manifest to labelled-data bucket
-    if let Err(e) = state
-        .s3_client
-        .put_object()
-        .bucket(&state.labelled_data_bucket)
-        .key(&manifest_key)
-        .content_type("application/json")
-        .body(ByteStream::from(manifest_bytes))
-        .send()
-        .await
+    let size_bytes = match restore::copy_from_archive(
+        &state.archive_client,
+        &state.archive_bucket,
+        &archive_key,
+        &state.s3_client,
+        &state.rustfs_bucket,
+        &s3_key,
+    )
+    .await
     {
-        warn!(error = %e, "Failed to write manifest to S3 (continuing anyway)");
-    }
-
-    // Step 4: Insert clip into DB
-    let db_dir2 = PathBuf::from(&state.db_dir);
-    let rid2 = robot_id.clone();
-    let seg_ids_json = serde_json::to_string(&body.segment_ids).unwrap();
-    let manifest_key2 = manifest_key.clone();
-    let clip_start = body.clip_start_ms;
-    let clip_end = body.clip_end_ms;
+        Ok(size) => size,
+        Err(e) => {
+            error!(error = %e, robot_id, id, "segment restore failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
 
-    let insert_result = tokio::task::spawn_blocking(move || -> rusqlite::Result<i64> {
-        let conn = open_robot_db(&db_dir2, &rid2)?;
-        let now = chrono::Utc::now().timestamp_millis();
-        conn.execute(
-            "INSERT INTO collection_clips
-             (collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, manifest_s3_key, created_at)
-             VALUES (?1, ?2, 'camera', ?3, ?4, ?5, ?6, ?7)",
-            params![collection_id, rid2, clip_start, clip_end, seg_ids_json, manifest_key2, now],
-        )?;
-        let id = conn.last_insert_rowid();
-        // Touch collection updated_at
-        conn.execute(
-            "UPDATE collections SET updated_at = ?1 WHERE id = ?2",
-            params![now, collection_id],
-        )?;
-        Ok(id)
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<Segment>> {
+        let conn = db_pool.get(&rid)?;
+        restore::mark_restored(&conn, &rid, id)
     })
     .await;
 
-    match insert_result {
-        Ok(Ok(clip_id)) => {
-            let segment_ids: Vec<i64> = body.segment_ids;
-            (StatusCode::CREATED, Json(serde_json::json!({
-                "id": clip_id,
-                "collection_id": collection_id,
-                "manifest_s3_key": manifest_key,
-                "segment_ids": segment_ids,
-            }))).into_response()
+    // The object is back either way; if the row went away meanwhile it is
+    // orphaned like any deleted segment's, and eviction will reclaim it.
+    match result {
+        Ok(Ok(Some(segment))) => {
+            info!(robot_id, id, size_bytes, archive_key, "restored segment from archive");
+            Json(segment).into_response()
         }
+        Ok(Ok(None)) => (StatusCode::CONFLICT, "Segment changed during the restore").into_response(),
         Ok(Err(e)) => {
-            error!(error = %e, "SQLite insert failed");
+            error!(error = %e, "SQLite update failed");
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
         }
         Err(e) => {
@@ -832,94 +1096,3147 @@ async fn create_clip(
     }
 }
 
-/// DELETE /robots/:robot_id/collections/:collection_id/clips/:clip_id
-async fn delete_clip(
+/// DELETE /robots/:robot_id/segments/:id?force=
+/// Delete one segment row and, best-effort, its RustFS object. Refused with
+/// 409 while saved clips reference it, unless `force=true`.
+#[utoipa::path(
+    delete,
+    path = "/robots/{robot_id}/segments/{id}",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id"), DeleteSegmentQuery),
+    responses(
+        (status = 204, description = "Segment deleted"),
+        (status = 404, description = "No such segment"),
+        (status = 409, description = "Saved clips reference the segment"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn delete_segment(
     State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, _collection_id, clip_id)): AxumPath<(String, i64, i64)>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    Query(q): Query<DeleteSegmentQuery>,
 ) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
-        conn.execute(
-            "DELETE FROM collection_clips WHERE id = ?1 AND robot_id = ?2",
-            params![clip_id, robot_id],
-        )
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<SegmentDeleteOutcome> {
+        let mut conn = db_pool.get(&rid)?;
+        delete_one_segment(&mut conn, &rid, id, q.force)
     })
     .await;
 
-    match result {
-        Ok(Ok(0)) => StatusCode::NOT_FOUND.into_response(),
-        Ok(Ok(_)) => StatusCode::NO_CONTENT.into_response(),
+    let hot_key = match result {
+        Ok(Ok(SegmentDeleteOutcome::Deleted { hot_key, clip_ids })) => {
+            if !clip_ids.is_empty() {
+                warn!(robot_id, id, ?clip_ids, "force-deleted segment still referenced by clips");
+            }
+            hot_key
+        }
+        Ok(Ok(SegmentDeleteOutcome::NotFound)) => return StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(SegmentDeleteOutcome::InClips(clip_ids))) => {
+            return (
+                StatusCode::CONFLICT,
+                format!("Segment is referenced by clips {clip_ids:?}; pass force=true to delete anyway"),
+            )
+                .into_response();
+        }
         Ok(Err(e)) => {
             error!(error = %e, "SQLite delete failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // The row is gone; object cleanup is best-effort.
+    if let Some(key) = hot_key {
+        let resp = state
+            .s3_client
+            .delete_object()
+            .bucket(&state.rustfs_bucket)
+            .key(key.trim_start_matches('/'))
+            .send()
+            .await;
+        if let Err(e) = resp {
+            warn!(error = %e, key, "failed to delete segment object from RustFS");
         }
     }
+    delete_thumbnails(&state, &robot_id, &[id]).await;
+    info!(robot_id, id, "deleted segment");
+    StatusCode::NO_CONTENT.into_response()
 }
 
-/// GET /robots/:robot_id/collections/:collection_id/download-info
-async fn download_info(
-    State(state): State<Arc<AppState>>,
-    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
-) -> impl IntoResponse {
-    let db_dir = state.db_dir.clone();
-    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<DownloadInfo> {
-        let conn = open_robot_db(&db_dir, &robot_id)?;
+/// Delete segment `id` of `robot_id` unless clips reference it (or `force`).
+fn delete_one_segment(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    id: i64,
+    force: bool,
+) -> rusqlite::Result<SegmentDeleteOutcome> {
+    let tx = conn.transaction()?;
+    let row: Option<(String, Option<i64>)> = tx
+        .query_row(
+            "SELECT s3_key, archived_at FROM segments WHERE id = ?1 AND robot_id = ?2",
+            params![id, robot_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let Some((s3_key, archived_at)) = row else {
+        return Ok(SegmentDeleteOutcome::NotFound);
+    };
 
-        // Get all segment_ids referenced by clips in this collection
-        let mut stmt = conn.prepare(
-            "SELECT segment_ids FROM collection_clips
-             WHERE collection_id = ?1 AND robot_id = ?2",
+    let clip_ids: Vec<i64> = {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT DISTINCT clip_id FROM ({CLIP_SEGMENTS}) WHERE segment_id = ?2 ORDER BY clip_id"
+        ))?;
+        let rows = stmt.query_map(params![robot_id, id], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    if !clip_ids.is_empty() && !force {
+        return Ok(SegmentDeleteOutcome::InClips(clip_ids));
+    }
+
+    tx.execute("DELETE FROM segments WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(SegmentDeleteOutcome::Deleted {
+        // Evicted objects are no longer in RustFS.
+        hot_key: archived_at.is_none().then_some(s3_key),
+        clip_ids,
+    })
+}
+
+/// GET /robots/:robot_id/segments/:id/video — 302 redirect to RustFS object URL
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}/video",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id")),
+    responses(
+        (status = 302, description = "Redirect to a presigned URL for the segment's object"),
+        (status = 404, description = "No such segment, or it was evicted from RustFS"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn video_redirect(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<SegmentObject> {
+        let conn = db_pool.get(&robot_id)?;
+        segment_object(&conn, &robot_id, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(SegmentObject::Hot(s3_key))) => {
+            // Generate a presigned URL so the browser can fetch directly from RustFS
+            match presign_segment_get(&state, &s3_key).await {
+                Some(url) => {
+                    info!(url, "redirecting to presigned RustFS URL");
+                    Redirect::temporary(&url).into_response()
+                }
+                None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+        Ok(Ok(SegmentObject::NotFound)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(SegmentObject::Evicted)) => (StatusCode::NOT_FOUND, EVICTED_SEGMENT).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /robots/:robot_id/segments/:id/stream — proxy the segment's object
+/// through the API, forwarding `Range` to RustFS so players can seek even
+/// when the object store or CORS setup gets in the way of the redirect.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}/stream",
+    tag = "segments",
+    params(
+        ("robot_id" = String, Path, description = "Robot id"),
+        ("id" = i64, Path, description = "Segment id"),
+        ("Range" = Option<String>, Header, description = "Single `bytes=` range"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "The whole object, with RustFS's `ETag`", body = [u8], content_type = "video/mp4"),
+        (status = 206, description = "The requested range", body = [u8], content_type = "video/mp4"),
+        (status = 304, description = "The cached copy is current"),
+        (status = 404, description = "No such segment or object, or the segment was evicted"),
+        (status = 416, description = "Range not satisfiable"),
+        (status = 502, description = "RustFS error"),
+    )
+)]
+async fn segment_stream(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    headers: axum::http::HeaderMap,
+) -> Response {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<SegmentObject> {
+        let conn = db_pool.get(&robot_id)?;
+        segment_object(&conn, &robot_id, id)
+    })
+    .await;
+
+    let s3_key = match result {
+        Ok(Ok(SegmentObject::Hot(s3_key))) => s3_key,
+        Ok(Ok(SegmentObject::NotFound)) => return StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(SegmentObject::Evicted)) => return (StatusCode::NOT_FOUND, EVICTED_SEGMENT).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let range = byte_range(headers.get(header::RANGE));
+    let object = match state
+        .s3_client
+        .get_object()
+        .bucket(&state.rustfs_bucket)
+        .key(s3_key.trim_start_matches('/'))
+        .set_range(range)
+        .set_if_none_match(
+            headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from),
+        )
+        .send()
+        .await
+    {
+        Ok(object) => object,
+        Err(e) => {
+            let status = e.raw_response().map(|r| r.status().as_u16());
+            return match status {
+                Some(304) => {
+                    let etag = e.raw_response().and_then(|r| r.headers().get("etag")).map(String::from);
+                    let mut response = StatusCode::NOT_MODIFIED.into_response();
+                    if let Some(etag) = etag.and_then(|e| axum::http::HeaderValue::from_str(&e).ok()) {
+                        response.headers_mut().insert(header::ETAG, etag);
+                    }
+                    response
+                }
+                Some(404) => (StatusCode::NOT_FOUND, "Segment object is not in RustFS").into_response(),
+                Some(416) => StatusCode::RANGE_NOT_SATISFIABLE.into_response(),
+                _ => {
+                    error!(error = %e, s3_key, "failed to fetch segment object from RustFS");
+                    StatusCode::BAD_GATEWAY.into_response()
+                }
+            };
+        }
+    };
+
+    let status = if object.content_range().is_some() {
+        StatusCode::PARTIAL_CONTENT
+    } else {
+        StatusCode::OK
+    };
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_TYPE, object.content_type().unwrap_or("video/mp4"));
+    if let Some(len) = object.content_length() {
+        response = response.header(header::CONTENT_LENGTH, len);
+    }
+    if let Some(content_range) = object.content_range() {
+        response = response.header(header::CONTENT_RANGE, content_range);
+    }
+    if let Some(etag) = object.e_tag() {
+        response = response.header(header::ETAG, etag);
+    }
+    let body = tokio_util::io::ReaderStream::new(object.body.into_async_read());
+    response
+        .body(axum::body::Body::from_stream(body))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// GET /robots/:robot_id/segments/:id/thumbnail — JPEG poster image. Idle
+/// segments are already JPEGs and are proxied; active segments get a frame
+/// from their midpoint, rendered once and cached in RustFS.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}/thumbnail",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id")),
+    responses(
+        (status = 200, description = "The thumbnail", body = [u8], content_type = "image/jpeg"),
+        (status = 404, description = "No such segment, or its object is not in RustFS"),
+        (status = 500, description = "Database error"),
+        (status = 502, description = "RustFS or ffmpeg error"),
+    )
+)]
+async fn segment_thumbnail(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+) -> Response {
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<Segment>> {
+        let conn = db_pool.get(&rid)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SEGMENT_COLUMNS}
+             FROM segments WHERE id = ?1 AND robot_id = ?2",
+        ))?;
+        let mut rows = stmt.query_map(params![id, rid], row_to_segment)?;
+        rows.next().transpose()
+    })
+    .await;
+
+    let segment = match result {
+        Ok(Ok(Some(segment))) => segment,
+        Ok(Ok(None)) => return StatusCode::NOT_FOUND.into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if segment.tier != SegmentTier::Hot {
+        return (StatusCode::NOT_FOUND, "Segment object is not in RustFS").into_response();
+    }
+
+    let jpeg_key = if segment.segment_type == "idle" {
+        segment.s3_key.trim_start_matches('/').to_string()
+    } else {
+        thumbnail_key(&state.rustfs_prefix, &robot_id, id)
+    };
+    let cached = state
+        .s3_client
+        .get_object()
+        .bucket(&state.rustfs_bucket)
+        .key(&jpeg_key)
+        .send()
+        .await;
+    let jpeg = match cached {
+        Ok(object) => match object.body.collect().await {
+            Ok(data) => data.into_bytes().to_vec(),
+            Err(e) => {
+                error!(error = %e, key = jpeg_key, "failed to read thumbnail from RustFS");
+                return StatusCode::BAD_GATEWAY.into_response();
+            }
+        },
+        Err(e) if segment.segment_type == "idle" => {
+            return match e.raw_response().map(|r| r.status().as_u16()) {
+                Some(404) => (StatusCode::NOT_FOUND, "Segment object is not in RustFS").into_response(),
+                _ => {
+                    error!(error = %e, key = jpeg_key, "failed to fetch idle snapshot from RustFS");
+                    StatusCode::BAD_GATEWAY.into_response()
+                }
+            };
+        }
+        Err(e) if e.raw_response().map(|r| r.status().as_u16()) != Some(404) => {
+            error!(error = %e, key = jpeg_key, "failed to fetch thumbnail from RustFS");
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+        // Not rendered yet.
+        Err(_) => {
+            let offset_ms = thumbnail::poster_offset_ms(segment.start_ms, segment.end_ms);
+            let jpeg = match thumbnail::render(&state.s3_client, &state.rustfs_bucket, &segment.s3_key, offset_ms).await {
+                Ok(jpeg) => jpeg,
+                Err(e) => {
+                    error!(error = %e, robot_id, id, "failed to render thumbnail");
+                    return (StatusCode::BAD_GATEWAY, e.to_string()).into_response();
+                }
+            };
+            if let Err(e) = state
+                .s3_client
+                .put_object()
+                .bucket(&state.rustfs_bucket)
+                .key(&jpeg_key)
+                .content_type("image/jpeg")
+                .body(ByteStream::from(jpeg.clone()))
+                .send()
+                .await
+            {
+                warn!(error = %e, key = jpeg_key, "failed to cache thumbnail in RustFS");
+            }
+            jpeg
+        }
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/jpeg"),
+            (header::CACHE_CONTROL, THUMBNAIL_CACHE_CONTROL),
+        ],
+        jpeg,
+    )
+        .into_response()
+}
+
+/// The `Range` header to forward to RustFS: a single `bytes=` range. Anything
+/// else (other units, multiple ranges, which S3 doesn't serve) is ignored, so
+/// the whole object is returned with 200.
+fn byte_range(range: Option<&axum::http::HeaderValue>) -> Option<String> {
+    let range = range?.to_str().ok()?.trim();
+    let spec = range.strip_prefix("bytes=")?;
+    (!spec.is_empty() && !spec.contains(',')).then(|| range.to_string())
+}
+
+/// Strong ETag for a serialized segment: changes whenever anything in the
+/// response (labels, metadata, tier, ...) does.
+fn segment_etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("\"{hex}\"")
+}
+
+/// Whether an `If-None-Match` header matches `etag`, i.e. the client's copy is
+/// current. Uses the weak comparison RFC 9110 prescribes for `If-None-Match`,
+/// so `W/"x"` matches `"x"`.
+fn etag_matches(if_none_match: Option<&axum::http::HeaderValue>, etag: &str) -> bool {
+    let Some(value) = if_none_match.and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let etag = etag.trim_start_matches("W/");
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// GET /robots/:robot_id/segments/:id/presigned — presigned RustFS URL as JSON,
+/// for clients that can't follow the redirect or need to know its expiry.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/{id}/presigned",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id")),
+    responses(
+        (status = 200, description = "Presigned URL for the segment's object", body = PresignedUrl),
+        (status = 404, description = "No such segment, or it was evicted from RustFS"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn segment_presigned(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<SegmentObject> {
+        let conn = db_pool.get(&robot_id)?;
+        segment_object(&conn, &robot_id, id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(SegmentObject::Hot(s3_key))) => {
+            let expires_at = chrono::Utc::now().timestamp_millis() + state.presign_expiry.as_millis() as i64;
+            match presign_segment_get(&state, &s3_key).await {
+                Some(url) => Json(PresignedUrl { url, expires_at }).into_response(),
+                None => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+        Ok(Ok(SegmentObject::NotFound)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(SegmentObject::Evicted)) => (StatusCode::NOT_FOUND, EVICTED_SEGMENT).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Body of the 404 the playback routes answer for evicted segments.
+const EVICTED_SEGMENT: &str =
+    "Segment was evicted from RustFS; if it was archived, POST /robots/:robot_id/segments/:id/restore brings it back";
+
+/// Where a segment's object is, for the routes that serve it from RustFS.
+#[derive(Debug, PartialEq, Eq)]
+enum SegmentObject {
+    NotFound,
+    /// The row is kept but eviction removed the object (`archived_at` set).
+    Evicted,
+    Hot(String),
+}
+
+fn segment_object(conn: &rusqlite::Connection, robot_id: &str, id: i64) -> rusqlite::Result<SegmentObject> {
+    let row: Option<(String, Option<i64>)> = conn
+        .query_row(
+            "SELECT s3_key, archived_at FROM segments WHERE id = ?1 AND robot_id = ?2",
+            params![id, robot_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    Ok(match row {
+        None => SegmentObject::NotFound,
+        Some((_, Some(_))) => SegmentObject::Evicted,
+        Some((s3_key, None)) => SegmentObject::Hot(s3_key),
+    })
+}
+
+/// Presigned GET URL for a RustFS object, valid for `api.presign_expiry_secs`.
+/// Errors are logged; `None` means the caller should answer 500.
+async fn presign_segment_get(state: &AppState, s3_key: &str) -> Option<String> {
+    let presign_config = match PresigningConfig::expires_in(state.presign_expiry) {
+        Ok(c) => c,
+        Err(e) => {
+            error!(error = %e, "failed to create presigning config");
+            return None;
+        }
+    };
+    let presigned = state
+        .s3_client
+        .get_object()
+        .bucket(&state.rustfs_bucket)
+        .key(s3_key.trim_start_matches('/'))
+        .presigned(presign_config)
+        .await;
+    match presigned {
+        Ok(req) => Some(req.uri().to_string()),
+        Err(e) => {
+            error!(error = %e, "failed to generate presigned URL");
+            None
+        }
+    }
+}
+
+/// PATCH /robots/:robot_id/segments/:id — update labels
+#[utoipa::path(
+    patch,
+    path = "/robots/{robot_id}/segments/{id}",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id")),
+    request_body = PatchLabels,
+    responses(
+        (status = 204, description = "Labels replaced"),
+        (status = 400, description = "Invalid labels"),
+        (status = 404, description = "No such segment"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn patch_labels(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    Json(body): Json<PatchLabels>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let labels_json = match serde_json::to_string(&body.labels) {
+        Ok(j) => j,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+        }
+    };
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
+        let conn = db_pool.get(&robot_id)?;
+        conn.execute(
+            "UPDATE segments SET labels = ?1 WHERE id = ?2 AND robot_id = ?3",
+            params![labels_json, id, robot_id],
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(0)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(_)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite update failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// PATCH /robots/:robot_id/segments/labels — replace, add or remove labels on
+/// many segments at once, in one transaction.
+#[utoipa::path(
+    patch,
+    path = "/robots/{robot_id}/segments/labels",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id")),
+    request_body = BulkLabels,
+    responses(
+        (status = 200, description = "Segments updated", body = BulkLabelsResponse),
+        (status = 400, description = "Empty `segment_ids` or invalid labels"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn bulk_patch_labels(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Json(body): Json<BulkLabels>,
+) -> impl IntoResponse {
+    if body.segment_ids.is_empty() {
+        return (StatusCode::BAD_REQUEST, "segment_ids must not be empty").into_response();
+    }
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
+        let mut conn = db_pool.get(&robot_id)?;
+        apply_bulk_labels(&mut conn, &robot_id, &body.segment_ids, &body.labels, body.mode)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(updated)) => Json(BulkLabelsResponse { updated }).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite update failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Apply a bulk label change to the robot's segments among `ids`. Returns how
+/// many segments were updated.
+fn apply_bulk_labels(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    ids: &[i64],
+    labels: &[String],
+    mode: BulkLabelMode,
+) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let mut updated = 0;
+    for &id in ids {
+        let current: Option<String> = tx
+            .query_row(
+                "SELECT labels FROM segments WHERE id = ?1 AND robot_id = ?2",
+                params![id, robot_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(current) = current else {
+            continue;
+        };
+        let mut next: Vec<String> = match mode {
+            BulkLabelMode::Replace => Vec::new(),
+            _ => serde_json::from_str(&current).unwrap_or_default(),
+        };
+        match mode {
+            BulkLabelMode::Replace | BulkLabelMode::Add => {
+                for label in labels {
+                    if !next.contains(label) {
+                        next.push(label.clone());
+                    }
+                }
+            }
+            BulkLabelMode::Remove => next.retain(|l| !labels.contains(l)),
+        }
+        let next_json = serde_json::to_string(&next).expect("serializing strings cannot fail");
+        updated += tx.execute(
+            "UPDATE segments SET labels = ?1 WHERE id = ?2",
+            params![next_json, id],
         )?;
-        let rows = stmt.query_map(params![collection_id, robot_id], |row| {
-            row.get::<_, String>(0)
-        })?;
+    }
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// PATCH /robots/:robot_id/segments/:id/metadata — merge (or replace) structured metadata.
+///
+/// Merging follows JSON Merge Patch (RFC 7396): keys set to `null` are removed,
+/// nested objects are merged recursively. Returns the resulting metadata object.
+#[utoipa::path(
+    patch,
+    path = "/robots/{robot_id}/segments/{id}/metadata",
+    tag = "segments",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Segment id")),
+    request_body = PatchMetadata,
+    responses(
+        (status = 200, description = "The stored metadata object", body = Object),
+        (status = 400, description = "Metadata is not a JSON object"),
+        (status = 404, description = "No such segment"),
+        (status = 413, description = "Metadata too large"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn patch_metadata(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    Json(body): Json<PatchMetadata>,
+) -> impl IntoResponse {
+    if !body.metadata.is_object() {
+        return (StatusCode::BAD_REQUEST, "metadata must be a JSON object").into_response();
+    }
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<MetadataPatchOutcome> {
+        let mut conn = db_pool.get(&robot_id)?;
+        let tx = conn.transaction()?;
+        let current: Option<String> = tx
+            .query_row(
+                "SELECT metadata FROM segments WHERE id = ?1 AND robot_id = ?2",
+                params![id, robot_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(current) = current else {
+            return Ok(MetadataPatchOutcome::NotFound);
+        };
+
+        let merged = if body.replace {
+            let mut fresh = serde_json::Value::Object(Default::default());
+            merge_patch(&mut fresh, &body.metadata);
+            fresh
+        } else {
+            let mut existing: serde_json::Value = serde_json::from_str(&current)
+                .unwrap_or_else(|_| serde_json::Value::Object(Default::default()));
+            merge_patch(&mut existing, &body.metadata);
+            existing
+        };
+
+        let serialized = merged.to_string();
+        if serialized.len() > MAX_METADATA_BYTES {
+            return Ok(MetadataPatchOutcome::TooLarge(serialized.len()));
+        }
+        tx.execute(
+            "UPDATE segments SET metadata = ?1 WHERE id = ?2 AND robot_id = ?3",
+            params![serialized, id, robot_id],
+        )?;
+        tx.commit()?;
+        Ok(MetadataPatchOutcome::Updated(merged))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(MetadataPatchOutcome::Updated(metadata))) => Json(metadata).into_response(),
+        Ok(Ok(MetadataPatchOutcome::NotFound)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(MetadataPatchOutcome::TooLarge(len))) => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("metadata is {len} bytes, max is {MAX_METADATA_BYTES}"),
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite update failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Apply a JSON Merge Patch (RFC 7396) to `target` in place.
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch_map) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(Default::default());
+    }
+    let target_map = target.as_object_mut().expect("just ensured object");
+    for (key, value) in patch_map {
+        if value.is_null() {
+            target_map.remove(key);
+        } else {
+            merge_patch(target_map.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Handlers — Timeline
+// ---------------------------------------------------------------------------
+
+/// GET /robots/:robot_id/timeline?start_ms=&end_ms=
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/timeline",
+    tag = "timeline",
+    params(("robot_id" = String, Path, description = "Robot id"), SegmentQuery),
+    responses(
+        (status = 200, description = "Segments in range and the robot's time bounds", body = TimelineResponse),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn get_timeline(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    MultiQuery(q): MultiQuery<SegmentQuery>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<TimelineResponse> {
+        let conn = db_pool.get(&robot_id)?;
+        query_timeline(&conn, &robot_id, q.start_ms, q.end_ms, timeline_limit(q.limit))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(timeline)) => Json(timeline).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Segments per robot in a timeline response.
+fn timeline_limit(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(500).min(1000)
+}
+
+/// A robot's overall time bounds and up to `limit` segments intersecting
+/// `[start_ms, end_ms]`, by start time.
+fn query_timeline(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    limit: i64,
+) -> rusqlite::Result<TimelineResponse> {
+    // Get time bounds
+    let mut bounds_stmt = conn.prepare(
+        "SELECT MIN(start_ms), MAX(end_ms) FROM segments WHERE robot_id = ?1",
+    )?;
+    let time_bounds = bounds_stmt.query_row(params![robot_id], |row| {
+        Ok(TimeBounds {
+            earliest_ms: row.get(0)?,
+            latest_ms: row.get(1)?,
+        })
+    })?;
+
+    // Get segments in range
+    let (where_clause, param_values) = segment_filter(robot_id.to_string(), start_ms, end_ms, None, None);
+    let sql = format!(
+        "SELECT {SEGMENT_COLUMNS}
+         FROM segments
+         WHERE {where_clause}
+         ORDER BY start_ms ASC
+         LIMIT {limit}"
+    );
+
+    let params: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params.as_slice(), row_to_segment)?;
+    let segments: rusqlite::Result<Vec<Segment>> = rows.collect();
+
+    Ok(TimelineResponse {
+        segments: segments?,
+        time_bounds,
+    })
+}
+
+/// GET /timeline?robot_ids=a,b,c&start_ms=&end_ms=&limit=
+/// One timeline across several robots: each robot's segments (up to `limit`
+/// each) merged by start time, with bounds covering all of them.
+#[utoipa::path(
+    get,
+    path = "/timeline",
+    tag = "timeline",
+    params(FleetTimelineQuery),
+    responses(
+        (status = 200, description = "Segments of all robots merged by start time", body = TimelineResponse),
+        (status = 400, description = "No or too many robot ids"),
+        (status = 404, description = "Unknown robot"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn get_fleet_timeline(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<FleetTimelineQuery>,
+) -> impl IntoResponse {
+    let mut robot_ids: Vec<String> = Vec::new();
+    for id in q.robot_ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        if !robot_ids.iter().any(|r| r == id) {
+            robot_ids.push(id.to_string());
+        }
+    }
+    if robot_ids.is_empty() {
+        return (StatusCode::BAD_REQUEST, "robot_ids must name at least one robot").into_response();
+    }
+    if robot_ids.len() > MAX_FLEET_ROBOTS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_FLEET_ROBOTS} robots per timeline"),
+        )
+            .into_response();
+    }
+
+    let limit = timeline_limit(q.limit);
+    let mut queries = tokio::task::JoinSet::new();
+    for robot_id in robot_ids {
+        let db_pool = Arc::clone(&state.db_pool);
+        queries.spawn_blocking(move || -> rusqlite::Result<Result<TimelineResponse, String>> {
+            if !db_pool.has_robot(&robot_id)? {
+                return Ok(Err(robot_id));
+            }
+            let conn = db_pool.get(&robot_id)?;
+            query_timeline(&conn, &robot_id, q.start_ms, q.end_ms, limit).map(Ok)
+        });
+    }
+
+    let mut timelines = Vec::with_capacity(queries.len());
+    while let Some(result) = queries.join_next().await {
+        match result {
+            Ok(Ok(Ok(timeline))) => timelines.push(timeline),
+            Ok(Ok(Err(unknown))) => {
+                return (StatusCode::NOT_FOUND, format!("Unknown robot: {unknown}")).into_response();
+            }
+            Ok(Err(e)) => {
+                error!(error = %e, "SQLite query failed");
+                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+            }
+            Err(e) => {
+                error!(error = %e, "spawn_blocking failed");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+    Json(merge_timelines(timelines)).into_response()
+}
+
+/// Combine per-robot timelines: segments ordered by start time (then robot
+/// and id, so the order is stable) and the widest bounds.
+fn merge_timelines(timelines: Vec<TimelineResponse>) -> TimelineResponse {
+    let mut merged = TimelineResponse {
+        segments: Vec::new(),
+        time_bounds: TimeBounds { earliest_ms: None, latest_ms: None },
+    };
+    for timeline in timelines {
+        let bounds = timeline.time_bounds;
+        merged.time_bounds.earliest_ms = match (merged.time_bounds.earliest_ms, bounds.earliest_ms) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        merged.time_bounds.latest_ms = merged.time_bounds.latest_ms.max(bounds.latest_ms);
+        merged.segments.extend(timeline.segments);
+    }
+    merged
+        .segments
+        .sort_by(|a, b| (a.start_ms, &a.robot_id, a.id).cmp(&(b.start_ms, &b.robot_id, b.id)));
+    merged
+}
+
+/// GET /robots/:robot_id/density?start_ms=&end_ms=&bucket_ms= — segment
+/// counts and bytes per time bucket, for a zoomed-out activity histogram
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/density",
+    tag = "timeline",
+    params(("robot_id" = String, Path, description = "Robot id"), DensityQuery),
+    responses(
+        (status = 200, description = "Every bucket in the range, oldest first", body = Vec<DensityBucket>),
+        (status = 400, description = "Missing or invalid range or bucket size, or too many buckets"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn get_density(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<DensityQuery>,
+) -> impl IntoResponse {
+    let (Some(start_ms), Some(end_ms), Some(bucket_ms)) = (q.start_ms, q.end_ms, q.bucket_ms) else {
+        return (StatusCode::BAD_REQUEST, "start_ms, end_ms and bucket_ms are required").into_response();
+    };
+    if bucket_ms <= 0 || end_ms <= start_ms {
+        return (StatusCode::BAD_REQUEST, "bucket_ms must be positive and end_ms after start_ms").into_response();
+    }
+    let buckets = density_bucket_count(start_ms, end_ms, bucket_ms);
+    if buckets > MAX_DENSITY_BUCKETS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("range spans {buckets} buckets, max is {MAX_DENSITY_BUCKETS}; use a larger bucket_ms"),
+        )
+            .into_response();
+    }
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<DensityBucket>> {
+        let conn = db_pool.get(&robot_id)?;
+        query_density(&conn, &robot_id, start_ms, end_ms, bucket_ms)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(buckets)) => Json(buckets).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Buckets of `bucket_ms` (aligned to multiples of it) covering `[start_ms, end_ms)`.
+fn density_bucket_count(start_ms: i64, end_ms: i64, bucket_ms: i64) -> i64 {
+    (end_ms - 1).div_euclid(bucket_ms) - start_ms.div_euclid(bucket_ms) + 1
+}
+
+/// Per-bucket counts of `robot_id`'s segments starting in `[start_ms, end_ms)`,
+/// by the bucket their start falls in. Buckets are aligned to multiples of
+/// `bucket_ms`, so they stay put as the range pans; empty ones are included
+/// with zeros.
+fn query_density(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    start_ms: i64,
+    end_ms: i64,
+    bucket_ms: i64,
+) -> rusqlite::Result<Vec<DensityBucket>> {
+    let mut stmt = conn.prepare(
+        "SELECT (start_ms / ?4) * ?4 AS bucket,
+                SUM(type = 'active'),
+                SUM(type = 'idle'),
+                COALESCE(SUM(size_bytes), 0)
+         FROM segments
+         WHERE robot_id = ?1 AND start_ms >= ?2 AND start_ms < ?3
+         GROUP BY bucket
+         ORDER BY bucket ASC",
+    )?;
+    let mut filled = stmt
+        .query_map(params![robot_id, start_ms, end_ms, bucket_ms], |row| {
+            Ok(DensityBucket {
+                bucket_start_ms: row.get(0)?,
+                active_count: row.get(1)?,
+                idle_count: row.get(2)?,
+                total_bytes: row.get(3)?,
+            })
+        })?
+        .peekable();
+
+    let first = start_ms.div_euclid(bucket_ms) * bucket_ms;
+    let count = density_bucket_count(start_ms, end_ms, bucket_ms);
+    let mut buckets = Vec::with_capacity(count as usize);
+    for bucket_start_ms in (0..count).map(|i| first + i * bucket_ms) {
+        match filled.next_if(|b| b.as_ref().map_or(true, |b| b.bucket_start_ms == bucket_start_ms)) {
+            Some(bucket) => buckets.push(bucket?),
+            None => buckets.push(DensityBucket {
+                bucket_start_ms,
+                active_count: 0,
+                idle_count: 0,
+                total_bytes: 0,
+            }),
+        }
+    }
+    Ok(buckets)
+}
+
+/// GET /robots/:robot_id/dates — days with footage, for date navigation
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/dates",
+    tag = "timeline",
+    params(("robot_id" = String, Path, description = "Robot id")),
+    responses(
+        (status = 200, description = "Days with footage, oldest first", body = Vec<RecordingDate>),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn list_dates(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<RecordingDate>> {
+        let conn = db_pool.get(&robot_id)?;
+        query_recording_dates(&conn, &robot_id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(dates)) => Json(dates).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Distinct UTC dates (by `start_ms`) with segments, oldest first, with
+/// active/idle counts.
+fn query_recording_dates(conn: &rusqlite::Connection, robot_id: &str) -> rusqlite::Result<Vec<RecordingDate>> {
+    let mut stmt = conn.prepare(
+        "SELECT date(start_ms / 1000, 'unixepoch') AS day,
+                COUNT(*),
+                SUM(type = 'active'),
+                SUM(type = 'idle')
+         FROM segments
+         WHERE robot_id = ?1
+         GROUP BY day
+         ORDER BY day ASC",
+    )?;
+    let rows = stmt.query_map(params![robot_id], |row| {
+        Ok(RecordingDate {
+            date: row.get(0)?,
+            segments: row.get(1)?,
+            active: row.get(2)?,
+            idle: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// GET /robots/:robot_id/labels?prefix=&limit= — label suggestions, most used first
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/labels",
+    tag = "timeline",
+    params(("robot_id" = String, Path, description = "Robot id"), LabelQuery),
+    responses(
+        (status = 200, description = "Labels in use, most used first", body = Vec<LabelCount>),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn list_labels(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<LabelQuery>,
+) -> impl IntoResponse {
+    let prefix = q.prefix.unwrap_or_default();
+    let limit = q.limit.unwrap_or(DEFAULT_LABEL_LIMIT).clamp(1, MAX_LABEL_LIMIT);
+    let key = (robot_id.clone(), prefix.clone(), limit);
+    {
+        let cache = state.label_cache.lock().unwrap();
+        if let Some((at, labels)) = cache.get(&key) {
+            if at.elapsed() < LABEL_CACHE_TTL {
+                return Json(labels.clone()).into_response();
+            }
+        }
+    }
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<LabelCount>> {
+        let conn = db_pool.get(&robot_id)?;
+        query_label_counts(&conn, &robot_id, &prefix, limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(labels)) => {
+            let mut cache = state.label_cache.lock().unwrap();
+            cache.retain(|_, (at, _)| at.elapsed() < LABEL_CACHE_TTL);
+            cache.insert(key, (Instant::now(), labels.clone()));
+            Json(labels).into_response()
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Distinct labels on `robot_id`'s segments starting with `prefix`
+/// (case-sensitive), with the number of segments carrying each; most used
+/// first, ties by label.
+fn query_label_counts(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    prefix: &str,
+    limit: usize,
+) -> rusqlite::Result<Vec<LabelCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT json_each.value AS label, COUNT(DISTINCT segments.id) AS uses
+         FROM segments, json_each(segments.labels)
+         WHERE segments.robot_id = ?1
+           AND json_each.type = 'text'
+           AND substr(json_each.value, 1, length(?2)) = ?2
+         GROUP BY label
+         ORDER BY uses DESC, label ASC
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![robot_id, prefix, limit as i64], |row| {
+        Ok(LabelCount {
+            label: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// GET /robots/:robot_id/stats
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/stats",
+    tag = "timeline",
+    params(("robot_id" = String, Path, description = "Robot id")),
+    responses(
+        (status = 200, description = "Dashboard totals", body = RobotStats),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn get_stats(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<RobotStats> {
+        let conn = db_pool.get(&robot_id)?;
+        query_robot_stats(&conn, &robot_id)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(stats)) => Json(stats).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Segment, collection and clip totals for `robot_id`.
+fn query_robot_stats(conn: &rusqlite::Connection, robot_id: &str) -> rusqlite::Result<RobotStats> {
+    let (total_segments, active_count, idle_count, total_bytes, total_frames, earliest_ms, latest_ms) = conn
+        .query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(type = 'active'), 0),
+                    COALESCE(SUM(type = 'idle'), 0),
+                    COALESCE(SUM(size_bytes), 0),
+                    COALESCE(SUM(frame_count), 0),
+                    MIN(start_ms),
+                    MAX(end_ms)
+             FROM segments
+             WHERE robot_id = ?1",
+            params![robot_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ))
+            },
+        )?;
+    let collection_count = conn.query_row(
+        "SELECT COUNT(*) FROM collections WHERE robot_id = ?1",
+        params![robot_id],
+        |row| row.get(0),
+    )?;
+    let clip_count = conn.query_row(
+        "SELECT COUNT(*) FROM collection_clips WHERE robot_id = ?1",
+        params![robot_id],
+        |row| row.get(0),
+    )?;
+    Ok(RobotStats {
+        total_segments,
+        active_count,
+        idle_count,
+        total_bytes,
+        total_frames,
+        earliest_ms,
+        latest_ms,
+        collection_count,
+        clip_count,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Handlers — Collections
+// ---------------------------------------------------------------------------
+
+/// GET /robots/:robot_id/collections?with_count=
+/// The list isn't paginated, so `X-Total-Count` is just its length.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ListQuery),
+    responses(
+        (status = 200, description = "The robot's collections", body = Vec<CollectionResponse>,
+            headers(("x-total-count" = i64, description = "Number of collections (with `with_count=true`)"))),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn list_collections(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<ListQuery>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<CollectionResponse>> {
+        let conn = db_pool.get(&robot_id)?;
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.robot_id, c.name, c.description, c.created_at, c.updated_at,
+                    (SELECT COUNT(*) FROM collection_clips cc WHERE cc.collection_id = c.id)
+             FROM collections c
+             WHERE c.robot_id = ?1
+             ORDER BY c.updated_at DESC",
+        )?;
+        let rows = stmt.query_map(params![robot_id], |row| {
+            Ok(CollectionRow::from_row(row)?.into_response(row.get(6)?))
+        })?;
+        rows.collect()
+    })
+    .await;
+
+    match result {
+        Ok(Ok(collections)) => {
+            let total = q.with_count.then_some(collections.len() as i64);
+            with_total_count(Json(collections), total)
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /robots/:robot_id/collections/search?q=&limit=
+/// Collections whose name or description match `q`, best match first.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections/search",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), CollectionSearchQuery),
+    responses(
+        (status = 200, description = "Matching collections, best first", body = Vec<CollectionResponse>),
+        (status = 400, description = "`q` has no words"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn search_collections(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Query(q): Query<CollectionSearchQuery>,
+) -> impl IntoResponse {
+    let Some(fts_query) = fts_match_query(&q.q) else {
+        return (StatusCode::BAD_REQUEST, "q must contain at least one word").into_response();
+    };
+    let limit = q.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).min(MAX_SEARCH_LIMIT);
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<CollectionResponse>> {
+        let conn = db_pool.get(&robot_id)?;
+        query_collection_search(&conn, &robot_id, &fts_query, limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(collections)) => Json(collections).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// FTS5 query for user text: each whitespace-separated word becomes a quoted
+/// prefix term, so FTS operators and punctuation in the input are matched
+/// literally instead of being parsed. `None` if there are no words.
+fn fts_match_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Run `fts_query` against `collections_fts`, ranked by bm25.
+fn query_collection_search(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    fts_query: &str,
+    limit: usize,
+) -> rusqlite::Result<Vec<CollectionResponse>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.robot_id, c.name, c.description, c.created_at, c.updated_at,
+                (SELECT COUNT(*) FROM collection_clips cc WHERE cc.collection_id = c.id)
+         FROM collections_fts f
+         JOIN collections c ON c.id = f.rowid
+         WHERE collections_fts MATCH ?1 AND c.robot_id = ?2
+         ORDER BY bm25(collections_fts), c.id
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![fts_query, robot_id, limit as i64], |row| {
+        Ok(CollectionRow::from_row(row)?.into_response(row.get(6)?))
+    })?;
+    rows.collect()
+}
+
+/// POST /robots/:robot_id/collections
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/collections",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id")),
+    request_body = CreateCollection,
+    responses(
+        (status = 201, description = "Collection created", body = CollectionResponse),
+        (status = 409, description = "A collection with that name exists"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn create_collection(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    Json(body): Json<CreateCollection>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<CollectionResponse> {
+        let conn = db_pool.get(&robot_id)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let desc = body.description.as_deref().unwrap_or("");
+        conn.execute(
+            "INSERT INTO collections (robot_id, name, description, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![robot_id, body.name, desc, now, now],
+        )?;
+        let id = conn.last_insert_rowid();
+        let row = CollectionRow {
+            id,
+            robot_id,
+            name: body.name,
+            description: desc.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        Ok(row.into_response(Some(0)))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(collection)) => (StatusCode::CREATED, Json(collection)).into_response(),
+        Ok(Err(e)) => {
+            // Check for UNIQUE constraint violation
+            let msg = e.to_string();
+            if msg.contains("UNIQUE") {
+                (StatusCode::CONFLICT, "Collection with that name already exists").into_response()
+            } else {
+                error!(error = %e, "SQLite insert failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// GET /robots/:robot_id/collections/:id
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections/{id}",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "The collection", body = CollectionResponse),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn get_collection(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<CollectionResponse>> {
+        let conn = db_pool.get(&robot_id)?;
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.robot_id, c.name, c.description, c.created_at, c.updated_at,
+                    (SELECT COUNT(*) FROM collection_clips cc WHERE cc.collection_id = c.id)
+             FROM collections c
+             WHERE c.id = ?1 AND c.robot_id = ?2",
+        )?;
+        let mut rows = stmt.query_map(params![id, robot_id], |row| {
+            Ok(CollectionRow::from_row(row)?.into_response(row.get(6)?))
+        })?;
+        rows.next().transpose()
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(c))) => Json(c).into_response(),
+        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// PATCH /robots/:robot_id/collections/:id
+/// Rename and/or re-describe a collection; omitted fields are unchanged.
+#[utoipa::path(
+    patch,
+    path = "/robots/{robot_id}/collections/{id}",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Collection id")),
+    request_body = UpdateCollection,
+    responses(
+        (status = 200, description = "The updated collection", body = CollectionResponse),
+        (status = 404, description = "No such collection"),
+        (status = 409, description = "A collection with that name exists"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn update_collection(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+    Json(body): Json<UpdateCollection>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<CollectionResponse>> {
+        let conn = db_pool.get(&robot_id)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let Some(row) = CollectionRow::update(
+            &conn,
+            &robot_id,
+            id,
+            body.name.as_deref(),
+            body.description.as_deref(),
+            now,
+        )?
+        else {
+            return Ok(None);
+        };
+        let clip_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM collection_clips WHERE collection_id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(Some(row.into_response(Some(clip_count))))
+    })
+    .await;
+
+    match result {
+        Ok(Ok(Some(c))) => Json(c).into_response(),
+        Ok(Ok(None)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Err(e)) => {
+            let msg = e.to_string();
+            if msg.contains("UNIQUE") {
+                (StatusCode::CONFLICT, "Collection with that name already exists").into_response()
+            } else {
+                error!(error = %e, "SQLite update failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// DELETE /robots/:robot_id/collections/:id
+#[utoipa::path(
+    delete,
+    path = "/robots/{robot_id}/collections/{id}",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("id" = i64, Path, description = "Collection id")),
+    responses(
+        (status = 204, description = "Collection and its clips deleted"),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn delete_collection(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
+        let conn = db_pool.get(&robot_id)?;
+        conn.execute(
+            "DELETE FROM collections WHERE id = ?1 AND robot_id = ?2",
+            params![id, robot_id],
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(0)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(_)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite delete failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Handlers — Clips
+// ---------------------------------------------------------------------------
+
+/// GET /robots/:robot_id/collections/:collection_id/clips?modality=&with_count=
+/// The list isn't paginated, so `X-Total-Count` is just its length.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections/{collection_id}/clips",
+    tag = "clips",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id"), ClipQuery),
+    responses(
+        (status = 200, description = "The collection's clips", body = Vec<ClipResponse>,
+            headers(("x-total-count" = i64, description = "Number of clips (with `with_count=true`)"))),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn list_clips(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+    Query(q): Query<ClipQuery>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let modality = q.modality.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<ClipResponse>> {
+        let conn = db_pool.get(&robot_id)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM collection_clips
+             WHERE collection_id = ?1 AND robot_id = ?2 AND (?3 IS NULL OR modality = ?3)
+             ORDER BY clip_start_ms ASC",
+            ClipRow::COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![collection_id, robot_id, modality], |row| {
+            Ok(ClipResponse::from(ClipRow::from_row(row)?))
+        })?;
+        rows.collect()
+    })
+    .await;
+
+    match result {
+        Ok(Ok(clips)) => {
+            let total = q.with_count.then_some(clips.len() as i64);
+            with_total_count(Json(clips), total)
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /robots/:robot_id/collections/:collection_id/clips
+/// Saves a clip: builds manifest JSON and writes to labelled-data S3 bucket.
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/collections/{collection_id}/clips",
+    tag = "clips",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id")),
+    request_body = CreateClip,
+    responses(
+        (status = 201, description = "Clip saved: `{id, collection_id, manifest_s3_key, segment_ids}`", body = Object),
+        (status = 400, description = "Unknown modality, bounds out of order, or segment ids not found (listed)"),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn create_clip(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+    Json(body): Json<CreateClip>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let seg_ids = body.segment_ids.clone();
+
+    // Step 1: Look up collection name and segment metadata from DB
+    let db_result = tokio::task::spawn_blocking(move || -> rusqlite::Result<(String, Vec<SegmentInfo>)> {
+        let conn = db_pool.get(&rid)?;
+        let collection_name = collection_name(&conn, &rid, collection_id)?;
+        let segments = clip_segment_infos(&conn, &rid, &seg_ids)?;
+        Ok((collection_name, segments))
+    })
+    .await;
+
+    let (collection_name, segment_infos) = match db_result {
+        Ok(Ok(data)) => data,
+        Ok(Err(e)) => {
+            let msg = e.to_string();
+            if msg.contains("no rows") {
+                return (StatusCode::NOT_FOUND, "Collection not found").into_response();
+            }
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if let Err(msg) = validate_clip(&body, &segment_infos) {
+        return (StatusCode::BAD_REQUEST, msg).into_response();
+    }
+
+    // Step 2: Build manifest JSON
+    let (manifest_key, manifest_bytes) = clip_manifest(
+        &robot_id,
+        collection_id,
+        &collection_name,
+        &state.rustfs_bucket,
+        &body,
+        &segment_infos,
+    );
+
+    // Step 3: Write manifest to labelled-data bucket
+    put_manifest(&state, &manifest_key, manifest_bytes).await;
+
+    // Step 4: Insert clip into DB
+    let db_pool2 = Arc::clone(&state.db_pool);
+    let rid2 = robot_id.clone();
+    let manifest_key2 = manifest_key.clone();
+    let clip = body.clone();
+
+    let insert_result = tokio::task::spawn_blocking(move || -> rusqlite::Result<i64> {
+        let conn = db_pool2.get(&rid2)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let id = insert_clip(&conn, &rid2, collection_id, &clip, &manifest_key2, now)?;
+        // Touch collection updated_at
+        conn.execute(
+            "UPDATE collections SET updated_at = ?1 WHERE id = ?2",
+            params![now, collection_id],
+        )?;
+        Ok(id)
+    })
+    .await;
+
+    match insert_result {
+        Ok(Ok(clip_id)) => {
+            let segment_ids: Vec<i64> = body.segment_ids;
+            (StatusCode::CREATED, Json(serde_json::json!({
+                "id": clip_id,
+                "collection_id": collection_id,
+                "manifest_s3_key": manifest_key,
+                "segment_ids": segment_ids,
+            }))).into_response()
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite insert failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /robots/:robot_id/collections/:collection_id/clips/batch
+/// Saves many clips at once: the clips are inserted in one transaction, then
+/// the manifests of those saved are uploaded concurrently. Responds 200 with
+/// one result per input clip, in order; a clip without valid segments, or
+/// repeating an earlier clip of the batch, fails on its own.
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/collections/{collection_id}/clips/batch",
+    tag = "clips",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id")),
+    request_body = Vec<CreateClip>,
+    responses(
+        (status = 200, description = "One result per clip, in request order; invalid clips get an `error`", body = Vec<BatchClipResult>),
+        (status = 400, description = "No clips, or too many"),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn create_clips_batch(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+    Json(clips): Json<Vec<CreateClip>>,
+) -> impl IntoResponse {
+    if clips.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No clips given").into_response();
+    }
+    if clips.len() > MAX_BATCH_CLIPS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_BATCH_CLIPS} clips per batch"),
+        )
+            .into_response();
+    }
+
+    // Step 1: Look up the collection and every clip's segments in one go
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let seg_ids: Vec<Vec<i64>> = clips.iter().map(|c| c.segment_ids.clone()).collect();
+    let db_result = tokio::task::spawn_blocking(move || -> rusqlite::Result<(String, Vec<Vec<SegmentInfo>>)> {
+        let conn = db_pool.get(&rid)?;
+        let collection_name = collection_name(&conn, &rid, collection_id)?;
+        let segments = seg_ids
+            .iter()
+            .map(|ids| clip_segment_infos(&conn, &rid, ids))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok((collection_name, segments))
+    })
+    .await;
+
+    let (collection_name, segment_infos) = match db_result {
+        Ok(Ok(data)) => data,
+        Ok(Err(rusqlite::Error::QueryReturnedNoRows)) => {
+            return (StatusCode::NOT_FOUND, "Collection not found").into_response();
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // Step 2: Build manifests for the valid clips
+    let mut results: Vec<BatchClipResult> = clips
+        .iter()
+        .enumerate()
+        .map(|(index, clip)| BatchClipResult {
+            index,
+            id: None,
+            manifest_s3_key: None,
+            segment_ids: clip.segment_ids.clone(),
+            error: None,
+        })
+        .collect();
+    let mut manifests = HashMap::new();
+    let duplicates = batch_duplicates(&clips);
+    for (((clip, infos), result), duplicate_of) in clips.iter().zip(&segment_infos).zip(&mut results).zip(duplicates) {
+        if let Err(msg) = validate_clip(clip, infos) {
+            result.error = Some(msg);
+            continue;
+        }
+        if let Some(first) = duplicate_of {
+            result.error = Some(format!("Same bounds and modality as clip {first} of this batch"));
+            continue;
+        }
+        let (key, bytes) = clip_manifest(
+            &robot_id,
+            collection_id,
+            &collection_name,
+            &state.rustfs_bucket,
+            clip,
+            infos,
+        );
+        result.manifest_s3_key = Some(key);
+        manifests.insert(result.index, bytes);
+    }
+
+    // Step 3: Insert the clips in one transaction
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let insert_result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<BatchClipResult>> {
+        let mut conn = db_pool.get(&rid)?;
+        insert_clip_batch(&mut conn, &rid, collection_id, &clips, results)
+    })
+    .await;
+
+    match insert_result {
+        Ok(Ok(results)) => {
+            // Step 4: Write the saved clips' manifests to the labelled-data
+            // bucket, a few at a time
+            let uploads: Vec<(String, Vec<u8>)> = results
+                .iter()
+                .filter(|r| r.id.is_some())
+                .filter_map(|r| Some((r.manifest_s3_key.clone()?, manifests.remove(&r.index)?)))
+                .collect();
+            futures_util::stream::iter(uploads)
+                .map(|(key, bytes)| {
+                    let state = &state;
+                    async move { put_manifest(state, &key, bytes).await }
+                })
+                .buffer_unordered(MANIFEST_UPLOAD_CONCURRENCY)
+                .collect::<()>()
+                .await;
+            Json(results).into_response()
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite insert failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// For each clip of a batch, the index of an earlier one with the same
+/// modality and bounds, which would collide with it in the collection.
+fn batch_duplicates(clips: &[CreateClip]) -> Vec<Option<usize>> {
+    let mut first = HashMap::new();
+    clips
+        .iter()
+        .enumerate()
+        .map(|(index, clip)| {
+            let bounds = (clip.modality(), clip.clip_start_ms, clip.clip_end_ms);
+            match first.entry(bounds) {
+                std::collections::hash_map::Entry::Occupied(e) => Some(*e.get()),
+                std::collections::hash_map::Entry::Vacant(e) => {
+                    e.insert(index);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Insert every clip of a batch whose result has a manifest key (i.e. that
+/// hasn't already failed) in one transaction, filling in their ids, and
+/// touch the collection once. A clip whose bounds are already taken in the
+/// collection fails on its own and loses its manifest key.
+fn insert_clip_batch(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    collection_id: i64,
+    clips: &[CreateClip],
+    mut results: Vec<BatchClipResult>,
+) -> rusqlite::Result<Vec<BatchClipResult>> {
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    for (clip, result) in clips.iter().zip(&mut results) {
+        let Some(key) = &result.manifest_s3_key else {
+            continue;
+        };
+        match insert_clip(&tx, robot_id, collection_id, clip, key, now) {
+            Ok(id) => result.id = Some(id),
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+                result.error = Some("A clip with these bounds and modality already exists in the collection".to_string());
+                result.manifest_s3_key = None;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if results.iter().any(|r| r.id.is_some()) {
+        tx.execute(
+            "UPDATE collections SET updated_at = ?1 WHERE id = ?2",
+            params![now, collection_id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(results)
+}
+
+/// Name of one of `robot_id`'s collections; `QueryReturnedNoRows` if it
+/// doesn't exist.
+fn collection_name(conn: &rusqlite::Connection, robot_id: &str, collection_id: i64) -> rusqlite::Result<String> {
+    conn.query_row(
+        "SELECT name FROM collections WHERE id = ?1 AND robot_id = ?2",
+        params![collection_id, robot_id],
+        |row| row.get(0),
+    )
+}
+
+/// Metadata for the segments a clip references. Ids that don't name one of
+/// `robot_id`'s segments are skipped.
+fn clip_segment_infos(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    segment_ids: &[i64],
+) -> rusqlite::Result<Vec<SegmentInfo>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, type, start_ms, end_ms, s3_key, size_bytes
+         FROM segments WHERE id = ?1 AND robot_id = ?2",
+    )?;
+    let mut segments = Vec::new();
+    for seg_id in segment_ids {
+        let segment = stmt
+            .query_row(params![seg_id, robot_id], |row| {
+                Ok(SegmentInfo {
+                    segment_id: row.get(0)?,
+                    segment_type: row.get(1)?,
+                    start_ms: row.get(2)?,
+                    end_ms: row.get(3)?,
+                    source_key: row.get(4)?,
+                    size_bytes: row.get::<_, Option<i64>>(5)?,
+                })
+            })
+            .optional()?;
+        segments.extend(segment);
+    }
+    Ok(segments)
+}
+
+/// Check a clip before saving it: its modality is known, its bounds are in
+/// order and every one of its `segment_ids` was found (as one of the robot's
+/// segments) in `segments`. The error names the ids that weren't, so a
+/// manifest is never silently missing segments.
+fn validate_clip(clip: &CreateClip, segments: &[SegmentInfo]) -> Result<(), String> {
+    if !CLIP_MODALITIES.contains(&clip.modality()) {
+        return Err(format!("modality must be one of {}", CLIP_MODALITIES.join(", ")));
+    }
+    if clip.clip_start_ms > clip.clip_end_ms {
+        return Err("clip_start_ms must not be after clip_end_ms".to_string());
+    }
+    if clip.segment_ids.is_empty() {
+        return Err("segment_ids must not be empty".to_string());
+    }
+    let missing: Vec<i64> = clip
+        .segment_ids
+        .iter()
+        .copied()
+        .filter(|id| !segments.iter().any(|s| s.segment_id == *id))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("segment_ids not found for this robot: {missing:?}"));
+    }
+    Ok(())
+}
+
+/// A clip's manifest key in the labelled-data bucket and its JSON body.
+fn clip_manifest(
+    robot_id: &str,
+    collection_id: i64,
+    collection_name: &str,
+    source_bucket: &str,
+    clip: &CreateClip,
+    segments: &[SegmentInfo],
+) -> (String, Vec<u8>) {
+    let manifest_segments: Vec<serde_json::Value> = segments
+        .iter()
+        .map(|s| manifest_segment(source_bucket, clip.modality(), s))
+        .collect();
+
+    let manifest = serde_json::json!({
+        "robot_id": robot_id,
+        "collection_id": collection_id,
+        "collection_name": collection_name,
+        "clip_start_ms": clip.clip_start_ms,
+        "clip_end_ms": clip.clip_end_ms,
+        "labels": clip.labels.as_deref().unwrap_or(&[]),
+        "segments": manifest_segments,
+        "created_at": chrono::Utc::now().to_rfc3339(),
+    });
+
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest).unwrap();
+    let safe_name = collection_name.replace(' ', "_").replace('/', "-");
+    let manifest_key = format!(
+        "{}/{}/{}_{}_{}.json",
+        robot_id,
+        safe_name,
+        clip.clip_start_ms,
+        clip.clip_end_ms,
+        clip.modality()
+    );
+    (manifest_key, manifest_bytes)
+}
+
+/// A segment's entry in a clip manifest.
+fn manifest_segment(source_bucket: &str, modality: &str, s: &SegmentInfo) -> serde_json::Value {
+    serde_json::json!({
+        "segment_id": s.segment_id,
+        "source_bucket": source_bucket,
+        "source_key": s.source_key,
+        "start_ms": s.start_ms,
+        "end_ms": s.end_ms,
+        "type": s.segment_type,
+        "size_bytes": s.size_bytes,
+        "modality": modality
+    })
+}
+
+/// Write a clip manifest to the labelled-data bucket. Failures are logged
+/// only: the clip is still saved.
+async fn put_manifest(state: &AppState, key: &str, bytes: Vec<u8>) {
+    if let Err(e) = state
+        .s3_client
+        .put_object()
+        .bucket(&state.labelled_data_bucket)
+        .key(key)
+        .content_type("application/json")
+        .body(ByteStream::from(bytes))
+        .send()
+        .await
+    {
+        warn!(error = %e, key, "Failed to write manifest to S3 (continuing anyway)");
+    }
+}
+
+/// Insert one clip row, returning its id.
+fn insert_clip(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    collection_id: i64,
+    clip: &CreateClip,
+    manifest_key: &str,
+    now: i64,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO collection_clips
+         (collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, manifest_s3_key, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            collection_id,
+            robot_id,
+            clip.modality(),
+            clip.clip_start_ms,
+            clip.clip_end_ms,
+            encode_segment_ids(&clip.segment_ids),
+            manifest_key,
+            now
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// DELETE /robots/:robot_id/collections/:collection_id/clips/:clip_id
+#[utoipa::path(
+    delete,
+    path = "/robots/{robot_id}/collections/{collection_id}/clips/{clip_id}",
+    tag = "clips",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id"), ("clip_id" = i64, Path, description = "Clip id")),
+    responses(
+        (status = 204, description = "Clip deleted"),
+        (status = 404, description = "No such clip"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn delete_clip(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, _collection_id, clip_id)): AxumPath<(String, i64, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<usize> {
+        let conn = db_pool.get(&robot_id)?;
+        conn.execute(
+            "DELETE FROM collection_clips WHERE id = ?1 AND robot_id = ?2",
+            params![clip_id, robot_id],
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(0)) => StatusCode::NOT_FOUND.into_response(),
+        Ok(Ok(_)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite delete failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// POST /robots/:robot_id/collections/:collection_id/clips/:clip_id/move
+/// Re-file a clip under another collection of the same robot. The manifest
+/// stays where it was written.
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/collections/{collection_id}/clips/{clip_id}/move",
+    tag = "clips",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id"), ("clip_id" = i64, Path, description = "Clip id")),
+    request_body = MoveClip,
+    responses(
+        (status = 200, description = "The moved clip", body = ClipResponse),
+        (status = 404, description = "No such clip or target collection"),
+        (status = 409, description = "The target already has a clip with these bounds"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn move_clip(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id, clip_id)): AxumPath<(String, i64, i64)>,
+    Json(body): Json<MoveClip>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<ClipMoveOutcome> {
+        let mut conn = db_pool.get(&robot_id)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        move_clip_between_collections(
+            &mut conn,
+            &robot_id,
+            collection_id,
+            clip_id,
+            body.target_collection_id,
+            now,
+        )
+    })
+    .await;
+
+    match result {
+        Ok(Ok(ClipMoveOutcome::Moved(clip))) => Json(ClipResponse::from(clip)).into_response(),
+        Ok(Ok(ClipMoveOutcome::NotFound(what))) => (StatusCode::NOT_FOUND, what).into_response(),
+        Ok(Err(e)) => {
+            let msg = e.to_string();
+            if msg.contains("UNIQUE") {
+                (
+                    StatusCode::CONFLICT,
+                    "Target collection already has a clip with the same bounds and modality",
+                )
+                    .into_response()
+            } else {
+                error!(error = %e, "SQLite update failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
+            }
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Move clip `clip_id` from `collection_id` to `target_collection_id` in one
+/// transaction, touching both collections' `updated_at`. Both collections
+/// must belong to `robot_id`. A clip with the same bounds and modality
+/// already in the target fails with the `collection_clips` UNIQUE constraint error.
+fn move_clip_between_collections(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    collection_id: i64,
+    clip_id: i64,
+    target_collection_id: i64,
+    now_ms: i64,
+) -> rusqlite::Result<ClipMoveOutcome> {
+    let tx = conn.transaction()?;
+
+    let owned = |id: i64| -> rusqlite::Result<bool> {
+        tx.query_row(
+            "SELECT 1 FROM collections WHERE id = ?1 AND robot_id = ?2",
+            params![id, robot_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|row| row.is_some())
+    };
+    if !owned(collection_id)? {
+        return Ok(ClipMoveOutcome::NotFound("Collection not found"));
+    }
+    if !owned(target_collection_id)? {
+        return Ok(ClipMoveOutcome::NotFound("Target collection not found"));
+    }
+
+    let moved = tx.execute(
+        "UPDATE collection_clips SET collection_id = ?1
+         WHERE id = ?2 AND collection_id = ?3 AND robot_id = ?4",
+        params![target_collection_id, clip_id, collection_id, robot_id],
+    )?;
+    if moved == 0 {
+        return Ok(ClipMoveOutcome::NotFound("Clip not found"));
+    }
+    tx.execute(
+        "UPDATE collections SET updated_at = ?1 WHERE id IN (?2, ?3)",
+        params![now_ms, collection_id, target_collection_id],
+    )?;
+    let clip = tx.query_row(
+        &format!("SELECT {} FROM collection_clips WHERE id = ?1", ClipRow::COLUMNS),
+        params![clip_id],
+        ClipRow::from_row,
+    )?;
+    tx.commit()?;
+    Ok(ClipMoveOutcome::Moved(clip))
+}
+
+/// GET /robots/:robot_id/collections/:collection_id/download-info
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections/{collection_id}/download-info",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "Size of the collection's footage", body = DownloadInfo),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn download_info(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<DownloadInfo> {
+        let conn = db_pool.get(&robot_id)?;
+
+        let (clip_count, objects) = collection_objects(&conn, &robot_id, collection_id)?;
+        let total_bytes = objects.iter().map(|o| o.size_bytes.unwrap_or(0)).sum();
+
+        Ok(DownloadInfo {
+            total_bytes,
+            clip_count,
+        })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(info)) => Json(info).into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Clip count and the unique segment objects referenced by a collection's clips,
+/// ordered by (start_ms, id). The order is stable across calls, so the ZIP
+/// download comes out byte-identical on a re-request while the collection and
+/// its objects are unchanged.
+fn collection_objects(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    collection_id: i64,
+) -> rusqlite::Result<(i64, Vec<CollectionObject>)> {
+    // Get all segment_ids referenced by clips in this collection
+    let mut stmt = conn.prepare(
+        "SELECT segment_ids FROM collection_clips
+         WHERE collection_id = ?1 AND robot_id = ?2",
+    )?;
+    let rows = stmt.query_map(params![collection_id, robot_id], |row| row.get::<_, String>(0))?;
+
+    let mut all_seg_ids = std::collections::BTreeSet::new();
+    let mut clip_count = 0i64;
+    for row in rows {
+        all_seg_ids.extend(decode_segment_ids(&row?));
+        clip_count += 1;
+    }
+
+    let mut objects = Vec::with_capacity(all_seg_ids.len());
+    let mut stmt = conn.prepare(
+        "SELECT start_ms, s3_key, size_bytes FROM segments WHERE id = ?1 AND robot_id = ?2",
+    )?;
+    for seg_id in all_seg_ids {
+        let row = stmt
+            .query_row(params![seg_id, robot_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?))
+            })
+            .optional()?;
+        // Clips can outlive their segments (bulk delete skips them, eviction doesn't).
+        if let Some((start_ms, s3_key, size_bytes)) = row {
+            objects.push((start_ms, CollectionObject { segment_id: seg_id, s3_key, size_bytes }));
+        }
+    }
+    objects.sort_by_key(|(start_ms, o)| (*start_ms, o.segment_id));
+    Ok((clip_count, objects.into_iter().map(|(_, o)| o).collect()))
+}
+
+/// GET /robots/:robot_id/collections/:collection_id/download
+/// Streams a ZIP of every unique segment object the collection's clips use,
+/// plus the clip manifests. Objects are copied from RustFS one at a time into
+/// the response, so memory use doesn't grow with the collection.
+///
+/// There is no `Range` support, so an interrupted download starts over: the
+/// archive is built on the fly, and its offsets and the central directory's
+/// CRCs aren't known until every object has been read.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections/{collection_id}/download",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "ZIP of the collection's segments and manifests", body = [u8], content_type = "application/zip"),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn download_collection(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let segment_bucket = state.rustfs_bucket.clone();
+    let manifest_bucket = state.labelled_data_bucket.clone();
+    let result = tokio::task::spawn_blocking(
+        move || -> rusqlite::Result<Option<(String, Vec<ZipMember>)>> {
+            let conn = db_pool.get(&rid)?;
+            let name: Option<String> = conn
+                .query_row(
+                    "SELECT name FROM collections WHERE id = ?1 AND robot_id = ?2",
+                    params![collection_id, rid],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(name) = name else {
+                return Ok(None);
+            };
+            let (_, objects) = collection_objects(&conn, &rid, collection_id)?;
+            let mut stmt = conn.prepare(
+                "SELECT manifest_s3_key FROM collection_clips
+                 WHERE collection_id = ?1 AND robot_id = ?2 AND manifest_s3_key IS NOT NULL
+                 ORDER BY clip_start_ms ASC, id ASC",
+            )?;
+            let manifests = stmt
+                .query_map(params![collection_id, rid], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(Some((name, zip_members(&objects, &manifests, &segment_bucket, &manifest_bucket))))
+        },
+    )
+    .await;
+
+    let (name, members) = match result {
+        Ok(Ok(Some(found))) => found,
+        Ok(Ok(None)) => return (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let (writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(async move {
+        if let Err(e) = write_zip(&state.s3_client, &members, writer).await {
+            // The client sees a truncated archive.
+            error!(error = %e, robot_id, collection_id, "collection ZIP stream aborted");
+        }
+    });
+
+    let filename = format!("{}.zip", name.replace(['/', '"', ' '], "_"));
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+        ],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(reader)),
+    )
+        .into_response()
+}
+
+/// One file in a collection ZIP: the object `bucket`/`key`, stored as `name`.
+#[derive(Debug, PartialEq)]
+struct ZipMember {
+    bucket: String,
+    key: String,
+    name: String,
+}
+
+/// Segment objects go under `segments/` and manifests under `manifests/`, each
+/// keeping its object key so names stay unique and mirror the bucket layout.
+fn zip_members(
+    objects: &[CollectionObject],
+    manifest_keys: &[String],
+    segment_bucket: &str,
+    manifest_bucket: &str,
+) -> Vec<ZipMember> {
+    let segments = objects.iter().map(|o| (segment_bucket, "segments", o.s3_key.as_str()));
+    let manifests = manifest_keys.iter().map(|k| (manifest_bucket, "manifests", k.as_str()));
+    segments
+        .chain(manifests)
+        .map(|(bucket, dir, key)| {
+            let key = key.trim_start_matches('/');
+            ZipMember {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                name: format!("{dir}/{key}"),
+            }
+        })
+        .collect()
+}
+
+/// Copy each member from S3 into a stored (uncompressed: MP4s don't shrink)
+/// ZIP written to `out`. Members that can't be fetched are skipped.
+async fn write_zip(
+    s3: &aws_sdk_s3::Client,
+    members: &[ZipMember],
+    out: tokio::io::DuplexStream,
+) -> async_zip::error::Result<()> {
+    use tokio_util::compat::FuturesAsyncWriteCompatExt;
+
+    let mut zip = async_zip::tokio::write::ZipFileWriter::with_tokio(out);
+    for member in members {
+        let object = match s3.get_object().bucket(&member.bucket).key(&member.key).send().await {
+            Ok(object) => object,
+            Err(e) => {
+                warn!(error = %e, key = member.key, "skipping object missing from collection ZIP");
+                continue;
+            }
+        };
+        let entry = async_zip::ZipEntryBuilder::new(member.name.clone().into(), async_zip::Compression::Stored);
+        let mut entry_writer = zip.write_entry_stream(entry).await?.compat_write();
+        tokio::io::copy(&mut object.body.into_async_read(), &mut entry_writer).await?;
+        entry_writer.into_inner().close().await?;
+    }
+    zip.close().await?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Handlers — Dataset export
+// ---------------------------------------------------------------------------
+
+/// A collection's clip with the segments it references (those still in the
+/// DB, in the clip's order) and their labels.
+struct ExportClip {
+    row: ClipRow,
+    segments: Vec<SegmentInfo>,
+    labels: Vec<String>,
+}
+
+/// Name and clips (by `clip_start_ms`) of a collection, or `None` if it isn't
+/// one of `robot_id`'s.
+fn export_clips(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    collection_id: i64,
+) -> rusqlite::Result<Option<(String, Vec<ExportClip>)>> {
+    let Some(collection_name) = collection_name(conn, robot_id, collection_id).optional()? else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM collection_clips
+         WHERE collection_id = ?1 AND robot_id = ?2
+         ORDER BY clip_start_ms ASC",
+        ClipRow::COLUMNS
+    ))?;
+    let rows = stmt
+        .query_map(params![collection_id, robot_id], ClipRow::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut seg_stmt = conn.prepare(
+        "SELECT id, type, start_ms, end_ms, s3_key, size_bytes, labels
+         FROM segments WHERE id = ?1 AND robot_id = ?2",
+    )?;
+    let mut clips = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut segments = Vec::new();
+        let mut labels = Vec::new();
+        for seg_id in row.segment_ids() {
+            let found = seg_stmt
+                .query_row(params![seg_id, robot_id], |r| {
+                    Ok((
+                        SegmentInfo {
+                            segment_id: r.get(0)?,
+                            segment_type: r.get(1)?,
+                            start_ms: r.get(2)?,
+                            end_ms: r.get(3)?,
+                            source_key: r.get(4)?,
+                            size_bytes: r.get(5)?,
+                        },
+                        r.get::<_, Option<String>>(6)?,
+                    ))
+                })
+                .optional()?;
+            // Clips can outlive their segments (bulk delete skips them, eviction doesn't).
+            let Some((segment, labels_raw)) = found else {
+                continue;
+            };
+            labels.extend(
+                serde_json::from_str::<Vec<String>>(labels_raw.as_deref().unwrap_or("[]")).unwrap_or_default(),
+            );
+            segments.push(segment);
+        }
+        clips.push(ExportClip { row, segments, labels });
+    }
+    Ok(Some((collection_name, clips)))
+}
+
+/// Manifest of a whole collection in `format=native`: each clip's manifest, in
+/// the shape written when it was saved, plus its `clip_id` and
+/// `manifest_s3_key`. Clip labels aren't stored, so `labels` are the labels of
+/// the clip's segments.
+fn native_manifest(
+    robot_id: &str,
+    collection_id: i64,
+    collection_name: &str,
+    source_bucket: &str,
+    clips: &[ExportClip],
+) -> serde_json::Value {
+    let clips: Vec<serde_json::Value> = clips
+        .iter()
+        .map(|clip| {
+            let mut labels: Vec<&String> = Vec::new();
+            for label in &clip.labels {
+                if !labels.contains(&label) {
+                    labels.push(label);
+                }
+            }
+            let segments: Vec<serde_json::Value> = clip
+                .segments
+                .iter()
+                .map(|s| manifest_segment(source_bucket, &clip.row.modality, s))
+                .collect();
+            let created_at = chrono::DateTime::from_timestamp_millis(clip.row.created_at).map(|t| t.to_rfc3339());
+            serde_json::json!({
+                "clip_id": clip.row.id,
+                "manifest_s3_key": clip.row.manifest_s3_key,
+                "robot_id": robot_id,
+                "collection_id": collection_id,
+                "collection_name": collection_name,
+                "clip_start_ms": clip.row.clip_start_ms,
+                "clip_end_ms": clip.row.clip_end_ms,
+                "labels": labels,
+                "modality": clip.row.modality,
+                "segments": segments,
+                "created_at": created_at,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "robot_id": robot_id,
+        "collection_id": collection_id,
+        "collection_name": collection_name,
+        "clips": clips,
+    })
+}
+
+/// GET /robots/:robot_id/collections/:collection_id/export?format=native|lerobot
+/// The collection's manifest as JSON, built from the DB without touching any
+/// video: `native` (default) lists each clip's manifest; `lerobot` is a LeRobot
+/// `meta/info.json` with one episode per clip (see [`lerobot::dataset_manifest`]).
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/collections/{collection_id}/export",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id"), CollectionExportQuery),
+    responses(
+        (status = 200, description = "Collection manifest in the requested format", body = Object),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn export_collection(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+    Query(q): Query<CollectionExportQuery>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<(String, Vec<ExportClip>)>> {
+        let conn = db_pool.get(&rid)?;
+        export_clips(&conn, &rid, collection_id)
+    })
+    .await;
+
+    let (collection_name, clips) = match result {
+        Ok(Ok(Some(data))) => data,
+        Ok(Ok(None)) => return (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let manifest = match q.format {
+        ExportFormat::Native => {
+            native_manifest(&robot_id, collection_id, &collection_name, &state.rustfs_bucket, &clips)
+        }
+        ExportFormat::Lerobot => {
+            let clips: Vec<ManifestClip> = clips
+                .into_iter()
+                .map(|clip| ManifestClip {
+                    clip_id: clip.row.id,
+                    clip_start_ms: clip.row.clip_start_ms,
+                    clip_end_ms: clip.row.clip_end_ms,
+                    task: lerobot::task_for(&clip.labels, &collection_name),
+                    modality: clip.row.modality,
+                    segments: clip
+                        .segments
+                        .into_iter()
+                        .map(|s| ManifestSegment {
+                            segment_id: s.segment_id,
+                            segment_type: s.segment_type,
+                            start_ms: s.start_ms,
+                            end_ms: s.end_ms,
+                            s3_key: s.source_key,
+                        })
+                        .collect(),
+                })
+                .collect();
+            lerobot::dataset_manifest(&robot_id, state.recording_fps, &state.rustfs_bucket, &clips)
+        }
+    };
+    Json(manifest).into_response()
+}
+
+/// POST /robots/:robot_id/collections/:collection_id/export/lerobot
+/// Writes the collection to the labelled-data bucket as a LeRobot dataset under
+/// `{robot_id}/{collection_name}/lerobot/`, one episode per clip.
+#[utoipa::path(
+    post,
+    path = "/robots/{robot_id}/collections/{collection_id}/export/lerobot",
+    tag = "collections",
+    params(("robot_id" = String, Path, description = "Robot id"), ("collection_id" = i64, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "Dataset written", body = LeRobotExportResponse),
+        (status = 400, description = "No clips with recorded video"),
+        (status = 404, description = "No such collection"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn export_lerobot(
+    State(state): State<Arc<AppState>>,
+    AxumPath((robot_id, collection_id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<(String, Vec<ClipSource>)>> {
+        let conn = db_pool.get(&rid)?;
+        let Some((collection_name, clips)) = export_clips(&conn, &rid, collection_id)? else {
+            return Ok(None);
+        };
+        let sources = clips
+            .into_iter()
+            .map(|clip| {
+                // Idle segments are a single JPEG; only active segments carry video.
+                let mut segments: Vec<SourceSegment> = clip
+                    .segments
+                    .into_iter()
+                    .filter(|s| s.segment_type == "active")
+                    .map(|s| SourceSegment { start_ms: s.start_ms, s3_key: s.source_key })
+                    .collect();
+                segments.sort_by_key(|s| s.start_ms);
+                ClipSource {
+                    clip_id: clip.row.id,
+                    clip_start_ms: clip.row.clip_start_ms,
+                    clip_end_ms: clip.row.clip_end_ms,
+                    task: lerobot::task_for(&clip.labels, &collection_name),
+                    segments,
+                }
+            })
+            .collect();
+        Ok(Some((collection_name, sources)))
+    })
+    .await;
+
+    let (collection_name, clips) = match result {
+        Ok(Ok(Some(data))) => data,
+        Ok(Ok(None)) => return (StatusCode::NOT_FOUND, "Collection not found").into_response(),
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if clips.iter().all(|c| c.segments.is_empty()) {
+        return (StatusCode::BAD_REQUEST, "Collection has no clips with recorded video").into_response();
+    }
+
+    let safe_name = collection_name.replace(' ', "_").replace('/', "-");
+    let prefix = format!("{robot_id}/{safe_name}/lerobot/");
+    let target = ExportTarget {
+        s3: &state.s3_client,
+        source_bucket: &state.rustfs_bucket,
+        dest_bucket: &state.labelled_data_bucket,
+        prefix: &prefix,
+        robot_type: &robot_id,
+        fps: state.recording_fps,
+    };
+
+    match lerobot::export(&target, clips).await {
+        Ok(summary) => Json(summary).into_response(),
+        Err(e) => {
+            error!(error = %e, robot_id, collection_id, "LeRobot export failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Handlers — Live updates (WebSocket)
+// ---------------------------------------------------------------------------
+
+/// How often subscribed robots' DBs are polled for new segments.
+const WS_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// Max segments pushed per robot per poll.
+const WS_POLL_BATCH: i64 = 500;
+
+/// GET /ws — WebSocket upgrade for push-based live timeline updates.
+///
+/// The client sends `{ "robot_ids": [...], "since_ms": ... }` (optionally with
+/// `"action": "subscribe" | "unsubscribe"`) any number of times; the server
+/// pushes `{ "type": "segment", "segment": {...} }` for each new segment of the
+/// subscribed robots, detected by polling each DB for `id > last_seen`.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "live",
+    responses(
+        (status = 101, description = "WebSocket: send `WsClientMessage`, receive `WsServerMessage`"),
+        (status = 503, description = "Too many open WebSockets"),
+    )
+)]
+async fn ws_handler(
+    State(state): State<Arc<AppState>>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let Ok(permit) = Arc::clone(&state.websockets).try_acquire_owned() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Too many open WebSockets").into_response();
+    };
+    ws.on_upgrade(move |socket| async move {
+        ws_session(socket, state).await;
+        drop(permit);
+    })
+}
+
+async fn ws_session(mut socket: WebSocket, state: Arc<AppState>) {
+    let mut subs: std::collections::HashMap<String, WsSubscription> =
+        std::collections::HashMap::new();
+    let mut ticker = tokio::time::interval(WS_POLL_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(_)) => break,
+                };
+                let reply = match serde_json::from_str::<WsClientMessage>(&text) {
+                    Ok(req) => ws_apply_subscription(&state, &mut subs, req).await,
+                    Err(e) => WsServerMessage::Error { message: format!("invalid message: {e}") },
+                };
+                if ws_send(&mut socket, &reply).await.is_err() {
+                    break;
+                }
+            }
+            _ = state.shutdown.cancelled() => {
+                ws_close_for_shutdown(&mut socket).await;
+                break;
+            }
+            _ = ticker.tick() => {
+                if subs.is_empty() {
+                    continue;
+                }
+                if ws_push_new_segments(&state, &mut socket, &mut subs).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    info!(subscriptions = subs.len(), "WebSocket client disconnected");
+}
+
+/// Apply a subscribe/unsubscribe request to the socket's subscription set.
+async fn ws_apply_subscription(
+    state: &AppState,
+    subs: &mut std::collections::HashMap<String, WsSubscription>,
+    req: WsClientMessage,
+) -> WsServerMessage {
+    if req.action == WsAction::Unsubscribe {
+        for robot_id in &req.robot_ids {
+            subs.remove(robot_id);
+        }
+        return WsServerMessage::Unsubscribed { robot_ids: req.robot_ids };
+    }
+
+    let db_pool = Arc::clone(&state.db_pool);
+    let robot_ids = req.robot_ids.clone();
+    let unknown = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<String>> {
+        for robot_id in robot_ids {
+            if !db_pool.has_robot(&robot_id)? {
+                return Ok(Some(robot_id));
+            }
+        }
+        Ok(None)
+    })
+    .await;
+    match unknown {
+        Ok(Ok(None)) => {}
+        Ok(Ok(Some(robot_id))) => {
+            return WsServerMessage::Error { message: format!("unknown robot: {robot_id}") };
+        }
+        Ok(Err(e)) => {
+            error!(error = %e, "SQLite query failed");
+            return WsServerMessage::Error { message: e.to_string() };
+        }
+        Err(e) => {
+            error!(error = %e, "spawn_blocking failed");
+            return WsServerMessage::Error { message: "internal error".into() };
+        }
+    }
+
+    let mut subscribed = Vec::new();
+    for robot_id in req.robot_ids {
+
+        // Without since_ms, start from the current newest row so only new segments are pushed.
+        let last_seen_id = if req.since_ms.is_some() {
+            0
+        } else {
+            let db_pool = Arc::clone(&state.db_pool);
+            let rid = robot_id.clone();
+            let max_id = tokio::task::spawn_blocking(move || -> rusqlite::Result<i64> {
+                let conn = db_pool.get(&rid)?;
+                max_segment_id(&conn, &rid)
+            })
+            .await;
+            match max_id {
+                Ok(Ok(id)) => id,
+                Ok(Err(e)) => {
+                    error!(error = %e, robot_id, "SQLite query failed");
+                    return WsServerMessage::Error { message: e.to_string() };
+                }
+                Err(e) => {
+                    error!(error = %e, "spawn_blocking failed");
+                    return WsServerMessage::Error { message: "internal error".into() };
+                }
+            }
+        };
+
+        subs.insert(
+            robot_id.clone(),
+            WsSubscription {
+                last_seen_id,
+                since_ms: req.since_ms,
+            },
+        );
+        subscribed.push(robot_id);
+    }
+    WsServerMessage::Subscribed { robot_ids: subscribed }
+}
+
+/// Poll every subscribed robot for segments newer than its `last_seen_id` and push them.
+/// Returns `Err` only when the socket is gone.
+async fn ws_push_new_segments(
+    state: &AppState,
+    socket: &mut WebSocket,
+    subs: &mut std::collections::HashMap<String, WsSubscription>,
+) -> Result<(), axum::Error> {
+    for (robot_id, sub) in subs.iter_mut() {
+        let db_pool = Arc::clone(&state.db_pool);
+        let rid = robot_id.clone();
+        let last_seen_id = sub.last_seen_id;
+        let since_ms = sub.since_ms;
+        let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<Segment>> {
+            let conn = db_pool.get(&rid)?;
+            segments_after(&conn, &rid, last_seen_id, since_ms)
+        })
+        .await;
+
+        let segments = match result {
+            Ok(Ok(segments)) => segments,
+            Ok(Err(e)) => {
+                warn!(error = %e, robot_id, "WebSocket poll query failed");
+                continue;
+            }
+            Err(e) => {
+                error!(error = %e, "spawn_blocking failed");
+                continue;
+            }
+        };
+
+        for segment in segments {
+            sub.last_seen_id = sub.last_seen_id.max(segment.id);
+            ws_send(socket, &WsServerMessage::Segment { segment: Box::new(segment) }).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Newest segment id of `robot_id`, 0 if it has none.
+fn max_segment_id(conn: &rusqlite::Connection, robot_id: &str) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(id), 0) FROM segments WHERE robot_id = ?1",
+        params![robot_id],
+        |row| row.get(0),
+    )
+}
+
+/// Up to [`WS_POLL_BATCH`] segments of `robot_id` inserted after `last_seen_id`
+/// (and ending at or after `since_ms`, if given), in insertion order.
+fn segments_after(
+    conn: &rusqlite::Connection,
+    robot_id: &str,
+    last_seen_id: i64,
+    since_ms: Option<i64>,
+) -> rusqlite::Result<Vec<Segment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SEGMENT_COLUMNS}
+         FROM segments
+         WHERE robot_id = ?1 AND id > ?2 AND (?3 IS NULL OR end_ms >= ?3)
+         ORDER BY id ASC
+         LIMIT ?4",
+    ))?;
+    let rows = stmt.query_map(params![robot_id, last_seen_id, since_ms, WS_POLL_BATCH], row_to_segment)?;
+    rows.collect()
+}
 
-        let mut all_seg_ids = std::collections::HashSet::new();
-        let mut clip_count = 0i64;
-        for row in rows {
-            let seg_ids_json = row?;
-            let ids: Vec<i64> = serde_json::from_str(&seg_ids_json).unwrap_or_default();
-            for id in ids {
-                all_seg_ids.insert(id);
-            }
-            clip_count += 1;
-        }
+/// GET /robots/:robot_id/segments/live — WebSocket pushing each newly
+/// recorded segment of one robot as a bare `Segment` JSON object. Same
+/// polling as `/ws`, without the subscription protocol.
+#[utoipa::path(
+    get,
+    path = "/robots/{robot_id}/segments/live",
+    tag = "live",
+    params(("robot_id" = String, Path, description = "Robot id")),
+    responses(
+        (status = 101, description = "WebSocket pushing each new `Segment`"),
+        (status = 404, description = "Unknown robot"),
+        (status = 503, description = "Too many open WebSockets"),
+        (status = 500, description = "Database or storage error"),
+    )
+)]
+async fn live_segments(
+    State(state): State<Arc<AppState>>,
+    AxumPath(robot_id): AxumPath<String>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let Ok(permit) = Arc::clone(&state.websockets).try_acquire_owned() else {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Too many open WebSockets").into_response();
+    };
 
-        // Sum size_bytes for all unique segments
-        let mut total_bytes = 0i64;
-        for seg_id in &all_seg_ids {
-            let bytes: Option<i64> = conn
-                .query_row(
-                    "SELECT size_bytes FROM segments WHERE id = ?1",
-                    params![seg_id],
-                    |row| row.get(0),
-                )
-                .ok()
-                .flatten();
-            total_bytes += bytes.unwrap_or(0);
+    let db_pool = Arc::clone(&state.db_pool);
+    let rid = robot_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Option<i64>> {
+        if !db_pool.has_robot(&rid)? {
+            return Ok(None);
         }
-
-        Ok(DownloadInfo {
-            total_bytes,
-            clip_count,
-        })
+        let conn = db_pool.get(&rid)?;
+        max_segment_id(&conn, &rid).map(Some)
     })
     .await;
 
-    match result {
-        Ok(Ok(info)) => Json(info).into_response(),
+    let last_seen_id = match result {
+        Ok(Ok(Some(id))) => id,
+        Ok(Ok(None)) => return (StatusCode::NOT_FOUND, "Unknown robot").into_response(),
         Ok(Err(e)) => {
             error!(error = %e, "SQLite query failed");
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
         }
         Err(e) => {
             error!(error = %e, "spawn_blocking failed");
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    ws.on_upgrade(move |socket| async move {
+        live_session(socket, state, robot_id, last_seen_id).await;
+        drop(permit);
+    })
+}
+
+async fn live_session(mut socket: WebSocket, state: Arc<AppState>, robot_id: String, mut last_seen_id: i64) {
+    let mut ticker = tokio::time::interval(WS_POLL_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Nothing to receive; pings are answered by axum.
+                Some(Ok(_)) => {}
+            },
+            _ = state.shutdown.cancelled() => {
+                ws_close_for_shutdown(&mut socket).await;
+                break;
+            }
+            _ = ticker.tick() => {
+                let db_pool = Arc::clone(&state.db_pool);
+                let rid = robot_id.clone();
+                let result = tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<Segment>> {
+                    let conn = db_pool.get(&rid)?;
+                    segments_after(&conn, &rid, last_seen_id, None)
+                })
+                .await;
+                let segments = match result {
+                    Ok(Ok(segments)) => segments,
+                    Ok(Err(e)) => {
+                        warn!(error = %e, robot_id, "live segment poll failed");
+                        continue;
+                    }
+                    Err(e) => {
+                        error!(error = %e, "spawn_blocking failed");
+                        continue;
+                    }
+                };
+                let mut sent = Ok(());
+                for segment in segments {
+                    last_seen_id = last_seen_id.max(segment.id);
+                    let text = serde_json::to_string(&segment).unwrap_or_default();
+                    sent = socket.send(Message::Text(text)).await;
+                    if sent.is_err() {
+                        break;
+                    }
+                }
+                if sent.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    info!(robot_id, "live segment feed disconnected");
+}
+
+/// Tell a WebSocket client the server is going away, so it can reconnect
+/// once it is back.
+async fn ws_close_for_shutdown(socket: &mut WebSocket) {
+    let frame = CloseFrame {
+        code: close_code::AWAY,
+        reason: "server shutting down".into(),
+    };
+    let _ = socket.send(Message::Close(Some(frame))).await;
+}
+
+async fn ws_send(socket: &mut WebSocket, msg: &WsServerMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(msg).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
+
+// ---------------------------------------------------------------------------
+// Middleware
+// ---------------------------------------------------------------------------
+
+/// Answer 504 for requests that run longer than `limit` (zero disables it).
+/// Only the HTTP side stops waiting: a `spawn_blocking` DB task that already
+/// started can't be cancelled and runs to completion, its result discarded.
+async fn request_timeout(limit: std::time::Duration, req: Request, next: Next) -> Response {
+    if limit.is_zero() {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    match tokio::time::timeout(limit, next.run(req)).await {
+        Ok(resp) => resp,
+        Err(_) => {
+            warn!(path, timeout_secs = limit.as_secs(), "request timed out");
+            (StatusCode::GATEWAY_TIMEOUT, "Request timed out").into_response()
         }
     }
 }
@@ -928,6 +4245,62 @@ async fn download_info(
 // Internal types
 // ---------------------------------------------------------------------------
 
+/// A segment object referenced by a collection (see `collection_objects`).
+struct CollectionObject {
+    segment_id: i64,
+    s3_key: String,
+    size_bytes: Option<i64>,
+}
+
+#[derive(Default)]
+struct IdlePrune {
+    deleted: usize,
+    bytes_freed: i64,
+    skipped_in_clips: usize,
+    /// RustFS keys of deleted segments still in the hot tier.
+    hot_keys: Vec<String>,
+}
+
+#[derive(Default)]
+struct SegmentDeletion {
+    deleted: usize,
+    skipped_in_clips: Vec<i64>,
+    /// RustFS keys of deleted segments still in the hot tier.
+    hot_keys: Vec<String>,
+    /// Deleted active segments, whose thumbnails may be cached.
+    active_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Neighbor {
+    Next,
+    Prev,
+}
+
+enum SegmentDeleteOutcome {
+    Deleted {
+        /// RustFS key to clean up, if the segment was still in the hot tier.
+        hot_key: Option<String>,
+        /// Clips that still reference the segment (only with `force`).
+        clip_ids: Vec<i64>,
+    },
+    NotFound,
+    /// Not deleted: referenced by these clips.
+    InClips(Vec<i64>),
+}
+
+enum ClipMoveOutcome {
+    Moved(ClipRow),
+    /// Which of the source collection, target collection or clip is missing.
+    NotFound(&'static str),
+}
+
+enum MetadataPatchOutcome {
+    Updated(serde_json::Value),
+    NotFound,
+    TooLarge(usize),
+}
+
 struct SegmentInfo {
     segment_id: i64,
     segment_type: String,
@@ -960,29 +4333,78 @@ async fn ensure_bucket(client: &aws_sdk_s3::Client, bucket: &str) {
 // Health
 // ---------------------------------------------------------------------------
 
-/// GET /health — returns the consumer's health state JSON, enriched with host disk stats.
+/// GET /health — returns the consumer's health state JSON, enriched with host
+/// disk stats and live DB directory / RustFS checks. 503 when the node should
+/// be taken out of rotation (see [`health_problem`]).
+/// GET /openapi.json — OpenAPI 3 description of this API
+async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(openapi::ApiDoc::openapi())
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (status = 200, description = "Consumer health state with `db_dir_ok` and `rustfs_ok`", body = Object),
+        (status = 503, description = "Health data stale or missing, or RustFS critical"),
+    )
+)]
 async fn get_health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let path = state.health_file_path.clone();
-    match tokio::task::spawn_blocking(move || {
-        let contents = std::fs::read_to_string(&path)?;
-        let mut json: serde_json::Value = serde_json::from_str(&contents)
-            .unwrap_or(serde_json::Value::Null);
+    let db_dir = state.db_dir.clone();
+    let file = tokio::task::spawn_blocking(move || {
+        let db_dir_ok = std::fs::read_dir(&db_dir).is_ok();
+        let read = || -> std::io::Result<(serde_json::Value, std::time::Duration)> {
+            let age = std::fs::metadata(&path)?
+                .modified()?
+                .elapsed()
+                .unwrap_or_default();
+            let contents = std::fs::read_to_string(&path)?;
+            let mut json: serde_json::Value = serde_json::from_str(&contents)
+                .unwrap_or(serde_json::Value::Null);
 
-        // Enrich with host disk usage (the filesystem where data/ lives).
-        if let Some(disk) = get_disk_usage(&path) {
-            json["disk"] = disk;
+            // Enrich with host disk usage (the filesystem where data/ lives).
+            if let Some(disk) = get_disk_usage(&path) {
+                json["disk"] = disk;
+            }
+            Ok((json, age))
+        };
+        (read(), db_dir_ok)
+    });
+    let rustfs = state.s3_client.head_bucket().bucket(&state.rustfs_bucket).send();
+    let (file, rustfs) = tokio::join!(file, rustfs);
+    let rustfs_ok = match rustfs {
+        Ok(_) => true,
+        Err(e) => {
+            warn!(error = %e, bucket = state.rustfs_bucket, "health check: RustFS head_bucket failed");
+            false
         }
+    };
 
-        Ok::<serde_json::Value, std::io::Error>(json)
-    })
-    .await
-    {
-        Ok(Ok(json)) => (StatusCode::OK, Json(json)).into_response(),
-        Ok(Err(_)) => (
+    match file {
+        Ok((Ok((mut json, age)), db_dir_ok)) => {
+            let problem = health_problem(&json, age, state.health_stale_after);
+            if !json.is_object() {
+                json = serde_json::json!({});
+            }
+            json["db_dir_ok"] = db_dir_ok.into();
+            json["rustfs_ok"] = rustfs_ok.into();
+            match problem {
+                Some(reason) => {
+                    json["error"] = reason.into();
+                    (StatusCode::SERVICE_UNAVAILABLE, Json(json)).into_response()
+                }
+                None => (StatusCode::OK, Json(json)).into_response(),
+            }
+        }
+        Ok((Err(_), db_dir_ok)) => (
             StatusCode::SERVICE_UNAVAILABLE,
             Json(serde_json::json!({
                 "error": "health data not available",
-                "detail": "consumer has not written health state yet"
+                "detail": "consumer has not written health state yet",
+                "db_dir_ok": db_dir_ok,
+                "rustfs_ok": rustfs_ok
             })),
         )
             .into_response(),
@@ -993,6 +4415,22 @@ async fn get_health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     }
 }
 
+/// Why the node is unhealthy, judging by the consumer's health file: it is
+/// `age` old (stale past `stale_after`) or reports RustFS as critical.
+fn health_problem(
+    health: &serde_json::Value,
+    age: std::time::Duration,
+    stale_after: std::time::Duration,
+) -> Option<&'static str> {
+    if age > stale_after {
+        Some("health data is stale")
+    } else if health["rustfs"]["status"] == "critical" {
+        Some("rustfs storage critical")
+    } else {
+        None
+    }
+}
+
 /// Get disk usage for the filesystem containing the given path using statvfs.
 fn get_disk_usage(path: &std::path::Path) -> Option<serde_json::Value> {
     use std::ffi::CString;
@@ -1049,6 +4487,10 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    if let Err(e) = config.validate() {
+        eprintln!("{}: {e}", config_path.display());
+        std::process::exit(1);
+    }
 
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -1076,43 +4518,112 @@ async fn main() {
         .build();
     let s3_client = aws_sdk_s3::Client::from_conf(s3_config);
 
+    // AWS archive the consumer evicts to, for segment restores
+    let archive_sdk_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        .region(Region::new(config.aws_s3.region.clone()))
+        .load()
+        .await;
+    let archive_client = aws_sdk_s3::Client::new(&archive_sdk_config);
+
     // Ensure labelled-data bucket exists
     ensure_bucket(&s3_client, &config.api.labelled_data_bucket).await;
 
     let state = Arc::new(AppState {
-        db_dir: PathBuf::from(&config.database.path),
+        db_pool: Arc::new(DbPool::new(
+            PathBuf::from(&config.database.path),
+            config.database.mode,
+        )),
         rustfs_public_url: config.api.rustfs_public_url.clone(),
         rustfs_bucket: config.api.rustfs_bucket.clone(),
+        rustfs_prefix: config.rustfs.prefix.clone(),
         s3_client,
         labelled_data_bucket: config.api.labelled_data_bucket.clone(),
-        health_file_path: PathBuf::from(&config.database.path).join("storage_stats.json"),
+        archive_client,
+        archive_bucket: config.aws_s3.bucket.clone(),
+        recording_fps: config.recording.fps,
+        health_file_path: config
+            .api
+            .health_file
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(&config.database.path).join("storage_stats.json")),
+        // The consumer rewrites the file every eviction check.
+        health_stale_after: std::time::Duration::from_secs(2 * config.eviction.check_interval_secs.max(1)),
+        db_dir: PathBuf::from(&config.database.path),
+        presign_expiry: std::time::Duration::from_secs(config.api.presign_expiry_secs),
+        websockets: Arc::new(tokio::sync::Semaphore::new(config.api.max_websockets)),
+        label_cache: std::sync::Mutex::new(HashMap::new()),
+        shutdown: CancellationToken::new(),
     });
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
-        .allow_headers(Any);
+        .allow_headers(Any)
+        .expose_headers([TOTAL_COUNT]);
+    let timeout_limit = std::time::Duration::from_secs(config.api.request_timeout_secs);
+    let shutdown = state.shutdown.clone();
+    let drain_timeout = std::time::Duration::from_secs(config.api.shutdown_timeout_secs);
 
     let app = Router::new()
         // Existing segment routes
         .route("/robots", get(list_robots))
-        .route("/robots/:robot_id/segments", get(list_segments))
-        .route("/robots/:robot_id/segments/:id", get(get_segment).patch(patch_labels))
+        .route("/robots/:robot_id/segments", get(list_segments).delete(delete_segments))
+        .route("/robots/:robot_id/segments/overlaps", get(list_overlaps))
+        .route("/robots/:robot_id/segments/idle", delete(prune_idle_segments))
+        .route("/robots/:robot_id/segments/:id", get(get_segment).patch(patch_labels).delete(delete_segment))
+        .route("/robots/:robot_id/segments/:id/metadata", patch(patch_metadata))
+        .route("/robots/:robot_id/segments/:id/next", get(next_segment))
+        .route("/robots/:robot_id/segments/:id/prev", get(prev_segment))
+        .route("/robots/:robot_id/segments/labels", patch(bulk_patch_labels))
         .route("/robots/:robot_id/segments/:id/video", get(video_redirect))
+        .route("/robots/:robot_id/segments/:id/presigned", get(segment_presigned))
+        .route("/robots/:robot_id/segments/:id/stream", get(segment_stream))
+        .route("/robots/:robot_id/segments/:id/thumbnail", get(segment_thumbnail))
         // Timeline
         .route("/robots/:robot_id/timeline", get(get_timeline))
+        .route("/timeline", get(get_fleet_timeline))
+        .route("/robots/:robot_id/dates", get(list_dates))
+        .route("/robots/:robot_id/density", get(get_density))
+        .route("/robots/:robot_id/stats", get(get_stats))
+        .route("/robots/:robot_id/labels", get(list_labels))
         // Collections
         .route("/robots/:robot_id/collections", get(list_collections).post(create_collection))
-        .route("/robots/:robot_id/collections/:id", get(get_collection).delete(delete_collection))
+        .route("/robots/:robot_id/collections/search", get(search_collections))
+        .route("/robots/:robot_id/collections/:id", get(get_collection).patch(update_collection).delete(delete_collection))
         // Clips
         .route("/robots/:robot_id/collections/:collection_id/clips", get(list_clips).post(create_clip))
+        .route("/robots/:robot_id/collections/:collection_id/clips/batch", post(create_clips_batch))
         .route("/robots/:robot_id/collections/:collection_id/clips/:clip_id", delete(delete_clip))
+        .route("/robots/:robot_id/collections/:collection_id/clips/:clip_id/move", post(move_clip))
         // Download info
         .route("/robots/:robot_id/collections/:collection_id/download-info", get(download_info))
+        .route("/robots/:robot_id/collections/:collection_id/export", get(export_collection))
         // Health
         .route("/health", get(get_health))
+        // API description
+        .route("/openapi.json", get(openapi_json))
+        // Routes above are bounded by `api.request_timeout_secs`. Downloads, merges
+        // and exports take minutes and the WebSocket is long-lived, so they are added after it.
+        .layer(middleware::from_fn(move |req, next| request_timeout(timeout_limit, req, next)))
+        // Collection ZIP (streams for as long as the collection takes)
+        .route("/robots/:robot_id/collections/:collection_id/download", get(download_collection))
+        // Segment merge (downloads and re-muxes the segments)
+        .route("/robots/:robot_id/segments/merge", post(merge_segments))
+        // Segment restore (downloads the object from the AWS archive)
+        .route("/robots/:robot_id/segments/:id/restore", post(restore_segment))
+        // Dataset export
+        .route("/robots/:robot_id/collections/:collection_id/export/lerobot", post(export_lerobot))
+        // Live updates
+        .route("/ws", get(ws_handler))
+        .route("/robots/:robot_id/segments/live", get(live_segments))
         .layer(cors)
         .with_state(state);
+    let app = if config.api.swagger_ui {
+        app.merge(SwaggerUi::new("/docs").config(utoipa_swagger_ui::Config::from("/openapi.json")))
+    } else {
+        app
+    };
 
     let addr = format!("0.0.0.0:{}", config.api.port);
     info!(addr, "frame-bucket API server starting");
@@ -1121,5 +4632,865 @@ async fn main() {
         eprintln!("Failed to bind to {addr}: {e}");
         std::process::exit(1);
     });
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal(shutdown, drain_timeout))
+        .await
+        .unwrap();
+    info!("frame-bucket API server stopped");
+}
+
+/// Resolves on SIGTERM or Ctrl-C. axum then stops accepting connections and
+/// waits for in-flight requests, so e.g. a `create_clip` between its manifest
+/// upload and its DB insert gets to finish; WebSockets are asked to close. If
+/// requests are still running `drain_timeout` later, the process exits anyway.
+async fn shutdown_signal(shutdown: CancellationToken, drain_timeout: std::time::Duration) {
+    let signal = frame_bucket_common::signal::shutdown_signal().await;
+
+    info!(
+        signal,
+        drain_timeout_secs = drain_timeout.as_secs(),
+        "shutting down: no new connections, waiting for in-flight requests"
+    );
+    shutdown.cancel();
+    tokio::spawn(async move {
+        tokio::time::sleep(drain_timeout).await;
+        warn!(
+            drain_timeout_secs = drain_timeout.as_secs(),
+            "requests still running after the drain timeout, exiting"
+        );
+        std::process::exit(1);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn query_overlaps_pairs_intersecting_segments() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES
+                 (1, 'r', 'active', 0, 60000, 'a'),
+                 (2, 'r', 'active', 55000, 115000, 'b'),  -- overlaps 1 by 5s
+                 (3, 'r', 'idle', 115000, 120000, 'c'),   -- touches 2, no overlap
+                 (4, 'other', 'active', 0, 60000, 'd');   -- different robot",
+        )
+        .unwrap();
+
+        let all = OverlapQuery { start_ms: None, end_ms: None, limit: None };
+        let overlaps = query_overlaps(&conn, "r", &all).unwrap();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!((overlaps[0].a.id, overlaps[0].b.id), (1, 2));
+        assert_eq!(overlaps[0].overlap_ms, 5000);
+
+        // Segment 1 ends before the window, so the pair is excluded.
+        let window = OverlapQuery { start_ms: Some(70000), end_ms: None, limit: None };
+        assert!(query_overlaps(&conn, "r", &window).unwrap().is_empty());
+    }
+
+    #[test]
+    fn bulk_delete_skips_segments_in_clips() {
+        let mut conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, archived_at) VALUES
+                 (1, 'r', 'active', 0, 1000, 'r/a.mp4', NULL),
+                 (2, 'r', 'idle', 1000, 2000, 'r/b.jpg', NULL),
+                 (3, 'r', 'active', 2000, 3000, 'r/c.mp4', 99),    -- archived
+                 (4, 'r', 'active', 5000, 6000, 'r/d.mp4', NULL),  -- out of range
+                 (5, 'other', 'active', 0, 1000, 'o/a.mp4', NULL);
+             INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (1, 1, 'r', 1000, 2000, '[2]', 0);",
+        )
+        .unwrap();
+
+        let deletion = delete_segments_in_range(&mut conn, "r", 0, 3000, None).unwrap();
+        assert_eq!(deletion.deleted, 2);
+        assert_eq!(deletion.skipped_in_clips, [2]);
+        assert_eq!(deletion.hot_keys, ["r/a.mp4"]);
+
+        let remaining: Vec<i64> = conn
+            .prepare("SELECT id FROM segments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(remaining, [2, 4, 5]);
+    }
+
+    #[test]
+    fn idle_prune_keeps_recent_active_and_clipped_segments() {
+        let mut conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, archived_at) VALUES
+                 (1, 'r', 'idle', 0, 1000, 'r/1.jpg', 50, NULL),
+                 (2, 'r', 'idle', 0, 2000, 'r/2.jpg', 60, NULL),   -- in a clip
+                 (3, 'r', 'idle', 0, 3000, 'r/3.jpg', 70, 99),     -- archived
+                 (4, 'r', 'active', 0, 1000, 'r/4.mp4', 80, NULL),
+                 (5, 'r', 'idle', 0, 9000, 'r/5.jpg', 90, NULL),   -- too recent
+                 (6, 'other', 'idle', 0, 1000, 'o/6.jpg', 10, NULL);
+             INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (1, 1, 'r', 0, 2000, '[2, 4]', 0), (2, 1, 'r', 0, 3000, 'not json', 0);",
+        )
+        .unwrap();
+
+        let prune = delete_idle_before(&mut conn, "r", 5000).unwrap();
+        assert_eq!(prune.deleted, 2);
+        assert_eq!(prune.skipped_in_clips, 1);
+        assert_eq!(prune.hot_keys, ["r/1.jpg"]);
+        assert_eq!(prune.bytes_freed, 50);
+
+        let remaining: Vec<i64> = conn
+            .prepare("SELECT id FROM segments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(remaining, [2, 4, 5, 6]);
+    }
+
+    #[test]
+    fn collection_objects_are_unique_and_time_ordered() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, size_bytes) VALUES
+                 (7, 'r', 'active', 3000, 4000, 'r/c.mp4', 30),
+                 (2, 'r', 'active', 1000, 2000, 'r/a.mp4', 10),
+                 (5, 'r', 'idle', 2000, 3000, 'r/b.jpg', NULL);
+             INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (1, 1, 'r', 2000, 4000, '[7, 5]', 0), (2, 1, 'r', 1000, 3000, '[5, 2, 99]', 0);",
+        )
+        .unwrap();
+
+        let (clips, objects) = collection_objects(&conn, "r", 1).unwrap();
+        assert_eq!(clips, 2);
+        let keys: Vec<&str> = objects.iter().map(|o| o.s3_key.as_str()).collect();
+        assert_eq!(keys, ["r/a.mp4", "r/b.jpg", "r/c.mp4"]);
+        assert_eq!(objects.iter().filter_map(|o| o.size_bytes).sum::<i64>(), 40);
+    }
+
+    #[test]
+    fn zip_members_keep_keys_under_segments_and_manifests() {
+        let objects = [
+            CollectionObject { segment_id: 2, s3_key: "/r/a.mp4".into(), size_bytes: Some(10) },
+            CollectionObject { segment_id: 7, s3_key: "r/c.mp4".into(), size_bytes: None },
+        ];
+        let members = zip_members(&objects, &["r/pick/1_2.json".into()], "frames", "labelled");
+        let member = |bucket: &str, key: &str, name: &str| ZipMember {
+            bucket: bucket.into(),
+            key: key.into(),
+            name: name.into(),
+        };
+        assert_eq!(
+            members,
+            [
+                member("frames", "r/a.mp4", "segments/r/a.mp4"),
+                member("frames", "r/c.mp4", "segments/r/c.mp4"),
+                member("labelled", "r/pick/1_2.json", "manifests/r/pick/1_2.json"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_requests_time_out_with_504() {
+        use tower::ServiceExt;
+
+        let limit = std::time::Duration::from_millis(50);
+        let app: Router = Router::new()
+            .route("/fast", get(|| async { "ok" }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    "late"
+                }),
+            )
+            .layer(middleware::from_fn(move |req, next| request_timeout(limit, req, next)));
+
+        let get_status = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder().uri(uri).body(axum::body::Body::empty()).unwrap();
+                app.oneshot(req).await.unwrap().status()
+            }
+        };
+        assert_eq!(get_status("/fast").await, StatusCode::OK);
+        assert_eq!(get_status("/slow").await, StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[test]
+    fn recording_dates_group_by_utc_day() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES
+                 (1, 'r', 'active', 1771372800000, 1771372860000, 'a'),  -- 2026-02-18 00:00Z
+                 (2, 'r', 'idle',   1771459199000, 1771459200000, 'b'),  -- 2026-02-18 23:59:59Z
+                 (3, 'r', 'active', 1771459200000, 1771459260000, 'c'),  -- 2026-02-19 00:00Z
+                 (4, 'other', 'active', 1771200000000, 1771200060000, 'd');",
+        )
+        .unwrap();
+
+        let dates = query_recording_dates(&conn, "r").unwrap();
+        assert_eq!(
+            dates,
+            [
+                RecordingDate { date: "2026-02-18".into(), segments: 2, active: 1, idle: 1 },
+                RecordingDate { date: "2026-02-19".into(), segments: 1, active: 1, idle: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn label_counts_filter_by_prefix_and_rank_by_use() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            r#"INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, labels) VALUES
+                   (1, 'r', 'active', 0, 1, 'a', '["pick", "place"]'),
+                   (2, 'r', 'active', 0, 1, 'b', '["pick", "pick", "push"]'),
+                   (3, 'r', 'active', 0, 1, 'c', '["place", "grasp", 7]'),
+                   (4, 'r', 'active', 0, 1, 'd', '["pick"]'),
+                   (5, 'other', 'active', 0, 1, 'e', '["pour", "pour"]');
+               INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES (6, 'r', 'idle', 0, 1, 'f');"#,
+        )
+        .unwrap();
+
+        let counts = |prefix: &str, limit: usize| -> Vec<(String, i64)> {
+            query_label_counts(&conn, "r", prefix, limit)
+                .unwrap()
+                .into_iter()
+                .map(|l| (l.label, l.count))
+                .collect()
+        };
+        let owned = |pairs: &[(&str, i64)]| -> Vec<(String, i64)> {
+            pairs.iter().map(|&(l, c)| (l.to_string(), c)).collect()
+        };
+        assert_eq!(
+            counts("", 10),
+            owned(&[("pick", 3), ("place", 2), ("grasp", 1), ("push", 1)])
+        );
+        assert_eq!(counts("p", 2), owned(&[("pick", 3), ("place", 2)]));
+        assert_eq!(counts("pu", 10), owned(&[("push", 1)]));
+        assert_eq!(counts("P", 10), owned(&[]));
+        assert_eq!(counts("%", 10), owned(&[]));
+    }
+
+    #[test]
+    fn density_buckets_are_aligned_and_dense() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, size_bytes) VALUES
+                 (1, 'r', 'active', 1050, 1099, 'a', 100),
+                 (2, 'r', 'idle', 1099, 1320, 'b', NULL),
+                 (3, 'r', 'active', 1320, 1400, 'c', 40),
+                 (4, 'r', 'active', 1400, 1500, 'd', 7),
+                 (5, 'other', 'active', 1050, 1100, 'e', 9);",
+        )
+        .unwrap();
+
+        // [1010, 1400) with 100 ms buckets: 1000, 1100, 1200, 1300.
+        assert_eq!(density_bucket_count(1010, 1400, 100), 4);
+        let buckets = query_density(&conn, "r", 1010, 1400, 100).unwrap();
+        let rows: Vec<(i64, i64, i64, i64)> = buckets
+            .iter()
+            .map(|b| (b.bucket_start_ms, b.active_count, b.idle_count, b.total_bytes))
+            .collect();
+        assert_eq!(
+            rows,
+            [(1000, 1, 1, 100), (1100, 0, 0, 0), (1200, 0, 0, 0), (1300, 1, 0, 40)]
+        );
+        assert_eq!(density_bucket_count(0, 1, 100), 1);
+        assert_eq!(density_bucket_count(0, 200_000, 100), 2000);
+    }
+
+    #[test]
+    fn robot_stats_aggregate_and_handle_empty_db() {
+        let conn = db::test_conn();
+
+        let empty = query_robot_stats(&conn, "r").unwrap();
+        assert_eq!(empty.total_segments, 0);
+        assert_eq!(empty.total_bytes, 0);
+        assert_eq!((empty.earliest_ms, empty.latest_ms), (None, None));
+
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, frame_count) VALUES
+                 (1, 'r', 'active', 1000, 2000, 'a', 500, 30),
+                 (2, 'r', 'idle', 2000, 9000, 'b', 100, NULL),
+                 (3, 'other', 'active', 0, 99999, 'c', 7, 7);
+             INSERT INTO collections (id, robot_id, name, created_at, updated_at)
+                 VALUES (1, 'r', 'grasps', 0, 0), (2, 'other', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, created_at)
+                 VALUES (1, 1, 'r', 0, 1, 0), (2, 1, 'r', 1, 2, 0), (3, 2, 'other', 0, 1, 0);",
+        )
+        .unwrap();
+        assert_eq!(
+            query_robot_stats(&conn, "r").unwrap(),
+            RobotStats {
+                total_segments: 2,
+                active_count: 1,
+                idle_count: 1,
+                total_bytes: 600,
+                total_frames: 30,
+                earliest_ms: Some(1000),
+                latest_ms: Some(9000),
+                collection_count: 1,
+                clip_count: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn health_problem_flags_stale_file_and_critical_rustfs() {
+        let secs = std::time::Duration::from_secs;
+        let healthy = json!({"rustfs": {"status": "pressure"}});
+        assert_eq!(health_problem(&healthy, secs(5), secs(60)), None);
+        assert_eq!(health_problem(&healthy, secs(61), secs(60)), Some("health data is stale"));
+        let critical = json!({"rustfs": {"status": "critical"}});
+        assert_eq!(health_problem(&critical, secs(5), secs(60)), Some("rustfs storage critical"));
+        assert_eq!(health_problem(&serde_json::Value::Null, secs(5), secs(60)), None);
+    }
+
+    #[test]
+    fn fts_match_query_quotes_every_word() {
+        assert_eq!(fts_match_query("  pick  up "), Some(r#""pick"* "up"*"#.to_string()));
+        assert_eq!(fts_match_query(r#"a"b OR NEAR(c"#), Some(r#""a""b"* "OR"* "NEAR(c"*"#.to_string()));
+        assert_eq!(fts_match_query(" \t"), None);
+    }
+
+    #[test]
+    fn collection_search_ranks_matches_for_robot() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO collections (id, robot_id, name, description, created_at, updated_at) VALUES
+                 (1, 'r', 'shelf', 'grasping from the top shelf', 0, 0),
+                 (2, 'r', 'grasp failures', 'grasp grasp grasp', 0, 0),
+                 (3, 'other', 'grasp', '', 0, 0),
+                 (4, 'r', 'handoffs', '', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, created_at)
+                 VALUES (1, 2, 'r', 0, 1, 0), (2, 2, 'r', 1, 2, 0);",
+        )
+        .unwrap();
+
+        let search = |text: &str, limit| -> Vec<i64> {
+            let q = fts_match_query(text).unwrap();
+            query_collection_search(&conn, "r", &q, limit).unwrap().iter().map(|c| c.id).collect()
+        };
+        assert_eq!(search("grasp", 10), [2, 1]);
+        assert_eq!(search("grasp", 1), [2]);
+        assert_eq!(search("grasp top", 10), [1]);
+        assert_eq!(search("\"NEAR(", 10), Vec::<i64>::new());
+        let hit = query_collection_search(&conn, "r", &fts_match_query("failures").unwrap(), 10).unwrap();
+        assert_eq!(hit[0].clip_count, Some(2));
+    }
+
+    #[test]
+    fn merge_patch_merges_nested_and_removes_nulls() {
+        let mut target = json!({"operator": "ana", "task": {"id": 1, "step": "grasp"}, "score": 0.4});
+        merge_patch(&mut target, &json!({"task": {"step": null, "attempt": 2}, "score": null, "bbox": [1, 2, 3, 4]}));
+        assert_eq!(
+            target,
+            json!({"operator": "ana", "task": {"id": 1, "attempt": 2}, "bbox": [1, 2, 3, 4]})
+        );
+    }
+
+    #[test]
+    fn merge_patch_into_non_object_starts_fresh() {
+        let mut target = json!("garbage");
+        merge_patch(&mut target, &json!({"a": 1}));
+        assert_eq!(target, json!({"a": 1}));
+    }
+
+    #[test]
+    fn clip_validation_names_missing_segments_and_checks_bounds() {
+        let info = |segment_id: i64| SegmentInfo {
+            segment_id,
+            segment_type: "active".into(),
+            start_ms: 0,
+            end_ms: 1000,
+            source_key: String::new(),
+            size_bytes: None,
+        };
+        let clip = |start: i64, end: i64, segment_ids: Vec<i64>| CreateClip {
+            clip_start_ms: start,
+            clip_end_ms: end,
+            segment_ids,
+            labels: None,
+            modality: None,
+        };
+        let found = [info(1), info(3)];
+
+        assert_eq!(validate_clip(&clip(0, 1000, vec![1, 3]), &found), Ok(()));
+        assert_eq!(validate_clip(&clip(500, 500, vec![3]), &found), Ok(()));
+        assert_eq!(
+            validate_clip(&clip(0, 1000, vec![1, 2, 3, 4]), &found),
+            Err("segment_ids not found for this robot: [2, 4]".to_string())
+        );
+        assert_eq!(
+            validate_clip(&clip(0, 1000, vec![]), &found),
+            Err("segment_ids must not be empty".to_string())
+        );
+        assert_eq!(
+            validate_clip(&clip(1001, 1000, vec![1]), &found),
+            Err("clip_start_ms must not be after clip_end_ms".to_string())
+        );
+
+        let depth = CreateClip { modality: Some("depth".into()), ..clip(0, 1000, vec![1]) };
+        assert_eq!(validate_clip(&depth, &found), Ok(()));
+        let lidar = CreateClip { modality: Some("lidar".into()), ..clip(0, 1000, vec![1]) };
+        assert_eq!(
+            validate_clip(&lidar, &found),
+            Err("modality must be one of camera, depth, audio".to_string())
+        );
+    }
+
+    #[test]
+    fn batch_duplicates_point_at_the_first_clip() {
+        let clip = |start: i64, end: i64, modality: Option<&str>| CreateClip {
+            clip_start_ms: start,
+            clip_end_ms: end,
+            segment_ids: vec![1],
+            labels: None,
+            modality: modality.map(String::from),
+        };
+        let clips = [
+            clip(0, 1000, None),
+            clip(0, 1000, Some("depth")),
+            clip(0, 1000, Some("camera")),
+            clip(0, 2000, None),
+            clip(0, 1000, None),
+        ];
+        assert_eq!(batch_duplicates(&clips), [None, None, Some(0), None, Some(0)]);
+    }
+
+    #[test]
+    fn clip_batch_inserts_valid_clips_and_reports_failures() {
+        let mut conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (10, 1, 'r', 'camera', 0, 1000, '[1]', 0);",
+        )
+        .unwrap();
+
+        let clip = |start: i64, end: i64| CreateClip {
+            clip_start_ms: start,
+            clip_end_ms: end,
+            segment_ids: vec![1],
+            labels: None,
+            modality: None,
+        };
+        let pending = |index: usize, key: Option<&str>| BatchClipResult {
+            index,
+            id: None,
+            manifest_s3_key: key.map(String::from),
+            segment_ids: vec![1],
+            error: key.is_none().then(|| "segment_ids not found for this robot: [1]".to_string()),
+        };
+        let audio = CreateClip { modality: Some("audio".into()), ..clip(2000, 3000) };
+        let clips = [clip(1000, 2000), clip(0, 1000), clip(5000, 6000), audio];
+        let results = insert_clip_batch(
+            &mut conn,
+            "r",
+            1,
+            &clips,
+            vec![
+                pending(0, Some("r/grasps/1000_2000.json")),
+                pending(1, Some("r/grasps/0_1000.json")),
+                pending(2, None),
+                pending(3, Some("r/grasps/2000_3000.json")),
+            ],
+        )
+        .unwrap();
+
+        let saved: Vec<Option<i64>> = results.iter().map(|r| r.id).collect();
+        assert_eq!(saved, [Some(11), None, None, Some(12)]);
+        assert!(results[1].error.as_deref().unwrap().contains("already exists"));
+        assert_eq!(results[1].manifest_s3_key, None);
+        assert!(results[2].error.as_deref().unwrap().contains("not found"));
+        let (clip_count, updated_at): (i64, i64) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM collection_clips), updated_at FROM collections WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(clip_count, 3);
+        assert!(updated_at > 0);
+        let modality: String = conn
+            .query_row("SELECT modality FROM collection_clips WHERE id = 12", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(modality, "audio");
+    }
+
+    #[test]
+    fn collection_export_lists_clips_with_surviving_segments() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            r#"INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+               INSERT INTO collection_clips
+                   (id, collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, manifest_s3_key, created_at)
+               VALUES
+                   (20, 1, 'r', 'depth', 5000, 6000, '[3]', 'r/grasps/5000_6000.json', 0),
+                   (10, 1, 'r', 'camera', 0, 2000, '[2, 9, 1]', 'r/grasps/0_2000.json', 1700000000000);
+               INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, labels) VALUES
+                   (1, 'r', 'active', 0, 1000, 'r/1.mp4', 10, '["grasp"]'),
+                   (2, 'r', 'idle', 1000, 2000, 'r/2.jpg', 5, '["grasp","cup"]'),
+                   (3, 'r', 'active', 5000, 6000, 'r/3.mp4', 10, NULL);"#,
+        )
+        .unwrap();
+
+        assert!(export_clips(&conn, "other", 1).unwrap().is_none());
+        let (name, clips) = export_clips(&conn, "r", 1).unwrap().unwrap();
+        assert_eq!(name, "grasps");
+        let ids: Vec<i64> = clips.iter().map(|c| c.row.id).collect();
+        assert_eq!(ids, [10, 20]);
+        // Segment 9 is gone; the rest keep the clip's order.
+        let segment_ids: Vec<i64> = clips[0].segments.iter().map(|s| s.segment_id).collect();
+        assert_eq!(segment_ids, [2, 1]);
+
+        let manifest = native_manifest("r", 1, &name, "camera-frames", &clips);
+        let first = &manifest["clips"][0];
+        assert_eq!(first["clip_id"], 10);
+        assert_eq!(first["manifest_s3_key"], "r/grasps/0_2000.json");
+        assert_eq!(first["labels"], serde_json::json!(["grasp", "cup"]));
+        assert_eq!(first["segments"][1]["source_key"], "r/1.mp4");
+        assert_eq!(first["created_at"], "2023-11-14T22:13:20+00:00");
+        assert_eq!(manifest["clips"][1]["segments"][0]["modality"], "depth");
+    }
+
+    #[test]
+    fn move_clip_refiles_and_touches_both_collections() {
+        let mut conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO collections (id, robot_id, name, created_at, updated_at)
+                 VALUES (1, 'r', 'grasps', 0, 0), (2, 'r', 'handoffs', 0, 0), (3, 'other', 'x', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (10, 1, 'r', 'camera', 0, 1000, '[1]', 0),
+                        (11, 1, 'r', 'camera', 0, 2000, '[2]', 0),
+                        (12, 2, 'r', 'camera', 0, 2000, '[3]', 0);",
+        )
+        .unwrap();
+
+        let outcome = move_clip_between_collections(&mut conn, "r", 1, 10, 2, 99).unwrap();
+        let ClipMoveOutcome::Moved(clip) = outcome else {
+            panic!("clip should move");
+        };
+        assert_eq!(clip.collection_id, 2);
+        let touched: Vec<i64> = conn
+            .prepare("SELECT updated_at FROM collections ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(touched, vec![99, 99, 0]);
+
+        // Same bounds as clip 12 in the target: UNIQUE violation, nothing changes.
+        let err = move_clip_between_collections(&mut conn, "r", 1, 11, 2, 100).err().unwrap();
+        assert!(err.to_string().contains("UNIQUE"), "{err}");
+        let still: i64 = conn
+            .query_row("SELECT collection_id FROM collection_clips WHERE id = 11", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(still, 1);
+
+        // Another robot's collection, or a clip not in the source collection.
+        assert!(matches!(
+            move_clip_between_collections(&mut conn, "r", 1, 11, 3, 100).unwrap(),
+            ClipMoveOutcome::NotFound("Target collection not found")
+        ));
+        assert!(matches!(
+            move_clip_between_collections(&mut conn, "r", 1, 12, 2, 100).unwrap(),
+            ClipMoveOutcome::NotFound("Clip not found")
+        ));
+    }
+
+    #[test]
+    fn label_filter_matches_all_or_any() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            r#"INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, labels) VALUES
+                   (1, 'r', 'active', 0, 1, 'a', '["grasp","handoff"]'),
+                   (2, 'r', 'active', 0, 1, 'b', '["grasp"]'),
+                   (3, 'r', 'active', 0, 1, 'c', '["handoff"]'),
+                   (4, 'r', 'active', 0, 1, 'd', '[]'),
+                   (5, 'other', 'active', 0, 1, 'e', '["grasp","handoff"]');"#,
+        )
+        .unwrap();
+        let ids = |labels: &[&str], label_match| -> Vec<i64> {
+            let labels: Vec<String> = labels.iter().map(|l| l.to_string()).collect();
+            let (mut where_clause, mut param_values) = segment_filter("r".into(), None, None, None, None);
+            push_label_filter(&mut where_clause, &mut param_values, &labels, label_match);
+            let params: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+            conn.prepare(&format!("SELECT id FROM segments WHERE {where_clause} ORDER BY id"))
+                .unwrap()
+                .query_map(params.as_slice(), |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+
+        assert_eq!(ids(&["grasp", "handoff"], LabelMatch::All), vec![1]);
+        assert_eq!(ids(&["grasp", "handoff"], LabelMatch::Any), vec![1, 2, 3]);
+        assert_eq!(ids(&["grasp"], LabelMatch::All), vec![1, 2]);
+        assert_eq!(ids(&[], LabelMatch::All), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn minimum_filters_skip_null_frame_counts() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, size_bytes, frame_count) VALUES
+                 (1, 'r', 'active', 0, 1, 'a', 50000, 300),
+                 (2, 'r', 'active', 0, 1, 'b', 900, 2),
+                 (3, 'r', 'idle', 0, 1, 'c', 60000, NULL),
+                 (4, 'r', 'active', 0, 1, 'd', NULL, 400);",
+        )
+        .unwrap();
+        let ids = |segment_type: Option<&str>, min_frames, min_bytes| -> Vec<i64> {
+            let (mut where_clause, mut param_values) =
+                segment_filter("r".into(), None, None, segment_type.map(String::from), None);
+            push_minimum_filter(&mut where_clause, &mut param_values, "frame_count", min_frames);
+            push_minimum_filter(&mut where_clause, &mut param_values, "size_bytes", min_bytes);
+            let params: Vec<&dyn rusqlite::types::ToSql> = param_values.iter().map(|p| p.as_ref()).collect();
+            conn.prepare(&format!("SELECT id FROM segments WHERE {where_clause} ORDER BY id"))
+                .unwrap()
+                .query_map(params.as_slice(), |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap()
+        };
+
+        assert_eq!(ids(None, None, None), vec![1, 2, 3, 4]);
+        // Idle segments (NULL frame_count) drop out as soon as a minimum is set.
+        assert_eq!(ids(None, Some(0), None), vec![1, 2, 4]);
+        assert_eq!(ids(Some("active"), Some(10), None), vec![1, 4]);
+        // Idle JPEGs do have a size; unknown sizes never pass.
+        assert_eq!(ids(None, None, Some(1000)), vec![1, 3]);
+        assert_eq!(ids(Some("active"), Some(10), Some(1000)), vec![1]);
+    }
+
+    #[test]
+    fn neighbors_follow_start_then_id_order() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES
+                 (1, 'r', 'active', 0, 10, 'a'),
+                 (2, 'r', 'idle', 10, 20, 'b'),
+                 (3, 'r', 'active', 10, 20, 'c'),
+                 (4, 'r', 'active', 20, 30, 'd'),
+                 (5, 'other', 'active', 15, 25, 'e');",
+        )
+        .unwrap();
+        let step = |id, direction, segment_type| {
+            query_neighbor(&conn, "r", id, direction, segment_type)
+                .unwrap()
+                .map(|n| n.map(|s| s.id))
+        };
+
+        // Ties on start_ms are ordered by id.
+        assert_eq!(step(1, Neighbor::Next, None), Some(Some(2)));
+        assert_eq!(step(2, Neighbor::Next, None), Some(Some(3)));
+        assert_eq!(step(3, Neighbor::Prev, None), Some(Some(2)));
+        assert_eq!(step(1, Neighbor::Next, Some("active")), Some(Some(3)));
+        assert_eq!(step(4, Neighbor::Prev, Some("idle")), Some(Some(2)));
+        assert_eq!(step(4, Neighbor::Next, None), Some(None));
+        assert_eq!(step(1, Neighbor::Prev, None), Some(None));
+        assert_eq!(step(5, Neighbor::Next, None), None);
+    }
+
+    #[test]
+    fn live_feed_picks_up_rows_after_last_seen() {
+        let conn = db::test_conn();
+        assert_eq!(max_segment_id(&conn, "r").unwrap(), 0);
+
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES
+                 (1, 'r', 'active', 0, 10, 'a'), (2, 'other', 'active', 0, 10, 'b'), (3, 'r', 'idle', 10, 20, 'c');",
+        )
+        .unwrap();
+        let last_seen = max_segment_id(&conn, "r").unwrap();
+        assert_eq!(last_seen, 3);
+        assert!(segments_after(&conn, "r", last_seen, None).unwrap().is_empty());
+
+        conn.execute("INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES (4, 'r', 'active', 20, 30, 'd')", [])
+            .unwrap();
+        let ids: Vec<i64> = segments_after(&conn, "r", last_seen, None).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, [4]);
+        let ids: Vec<i64> = segments_after(&conn, "r", 0, Some(15)).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, [3, 4]);
+    }
+
+    #[test]
+    fn etag_matching_follows_if_none_match_rules() {
+        let etag = segment_etag(br#"{"id":1}"#);
+        assert_eq!(etag.len(), 34);
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_ne!(etag, segment_etag(br#"{"id":2}"#));
+
+        let matches = |v: &str| etag_matches(Some(&axum::http::HeaderValue::from_str(v).unwrap()), &etag);
+        assert!(matches(&etag));
+        assert!(matches(&format!("W/{etag}")));
+        assert!(matches(&format!("\"other\", {etag}")));
+        assert!(matches("*"));
+        assert!(!matches("\"other\""));
+        assert!(!etag_matches(None, &etag));
+    }
+
+    #[test]
+    fn byte_range_forwards_single_byte_ranges_only() {
+        let range = |v: &'static str| byte_range(Some(&axum::http::HeaderValue::from_static(v)));
+        assert_eq!(range("bytes=0-1023"), Some("bytes=0-1023".into()));
+        assert_eq!(range("bytes=500-"), Some("bytes=500-".into()));
+        assert_eq!(range("bytes=-500"), Some("bytes=-500".into()));
+        assert_eq!(range("bytes=0-1,5-9"), None);
+        assert_eq!(range("items=0-5"), None);
+        assert_eq!(range("bytes="), None);
+        assert_eq!(byte_range(None), None);
+    }
+
+    #[test]
+    fn fleet_timeline_merges_by_start_and_widens_bounds() {
+        let seg = |robot_id: &str, id: i64, start_ms: i64| Segment {
+            id,
+            robot_id: robot_id.into(),
+            segment_type: "active".into(),
+            start_ms,
+            end_ms: start_ms + 10,
+            s3_key: String::new(),
+            size_bytes: None,
+            frame_count: None,
+            labels: Vec::new(),
+            metadata: json!({}),
+            tier: SegmentTier::Hot,
+            region: None,
+            created_at: None,
+            motion_score: None,
+        };
+        let timeline = |segments: Vec<Segment>, earliest_ms, latest_ms| TimelineResponse {
+            segments,
+            time_bounds: TimeBounds { earliest_ms, latest_ms },
+        };
+
+        let merged = merge_timelines(vec![
+            timeline(vec![seg("b", 1, 100), seg("b", 2, 300)], Some(50), Some(310)),
+            timeline(vec![], None, None),
+            timeline(vec![seg("a", 7, 100), seg("a", 8, 200)], Some(100), Some(900)),
+        ]);
+        let order: Vec<(&str, i64)> = merged.segments.iter().map(|s| (s.robot_id.as_str(), s.id)).collect();
+        assert_eq!(order, [("a", 7), ("b", 1), ("a", 8), ("b", 2)]);
+        assert_eq!((merged.time_bounds.earliest_ms, merged.time_bounds.latest_ms), (Some(50), Some(900)));
+
+        let empty = merge_timelines(vec![timeline(vec![], None, None)]);
+        assert_eq!((empty.time_bounds.earliest_ms, empty.time_bounds.latest_ms), (None, None));
+    }
+
+    #[tokio::test]
+    async fn segment_query_accepts_repeated_labels() {
+        use axum::extract::FromRequestParts;
+        async fn parse(uri: &str) -> SegmentQuery {
+            let (mut parts, _) = axum::http::Request::builder().uri(uri).body(()).unwrap().into_parts();
+            let MultiQuery(q) = MultiQuery::<SegmentQuery>::from_request_parts(&mut parts, &()).await.unwrap();
+            q
+        }
+
+        let q = parse("/x?label=grasp&label=handoff&match=any").await;
+        assert_eq!(q.labels, ["grasp", "handoff"]);
+        assert_eq!(q.label_match, LabelMatch::Any);
+
+        let q = parse("/x?label=grasp").await;
+        assert_eq!(q.labels, ["grasp"]);
+        assert_eq!(q.label_match, LabelMatch::All);
+        assert!(!q.with_count);
+
+        assert!(parse("/x?limit=10&with_count=true").await.with_count);
+    }
+
+    #[test]
+    fn total_count_header_only_when_asked_for() {
+        let counted = with_total_count(Json(vec![1, 2]), Some(3412));
+        assert_eq!(counted.headers()[TOTAL_COUNT], "3412");
+        assert!(with_total_count(Json(vec![1, 2]), None).headers().get(TOTAL_COUNT).is_none());
+    }
+
+    #[test]
+    fn bulk_labels_replace_add_remove() {
+        let mut conn = db::test_conn();
+        conn.execute_batch(
+            r#"INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, labels)
+                   VALUES (1, 'r', 'active', 0, 1, 'a', '["grasp"]'), (2, 'r', 'active', 0, 1, 'b', '[]'),
+                          (3, 'other', 'active', 0, 1, 'c', '["x"]');"#,
+        )
+        .unwrap();
+        let labels_of = |conn: &rusqlite::Connection, id: i64| -> String {
+            conn.query_row("SELECT labels FROM segments WHERE id = ?1", params![id], |row| row.get(0))
+                .unwrap()
+        };
+        let strings = |v: &[&str]| -> Vec<String> { v.iter().map(|s| s.to_string()).collect() };
+
+        // Segment 3 belongs to another robot and 9 doesn't exist.
+        let n = apply_bulk_labels(&mut conn, "r", &[1, 2, 3, 9], &strings(&["handoff", "grasp"]), BulkLabelMode::Add)
+            .unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(labels_of(&conn, 1), r#"["grasp","handoff"]"#);
+        assert_eq!(labels_of(&conn, 2), r#"["handoff","grasp"]"#);
+        assert_eq!(labels_of(&conn, 3), r#"["x"]"#);
+
+        apply_bulk_labels(&mut conn, "r", &[1, 2], &strings(&["grasp"]), BulkLabelMode::Remove).unwrap();
+        assert_eq!(labels_of(&conn, 1), r#"["handoff"]"#);
+
+        apply_bulk_labels(&mut conn, "r", &[1], &strings(&["a", "a", "b"]), BulkLabelMode::Replace).unwrap();
+        assert_eq!(labels_of(&conn, 1), r#"["a","b"]"#);
+    }
+
+    #[test]
+    fn segment_object_is_only_served_while_hot() {
+        let conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, archived_at)
+                 VALUES (1, 'r', 'active', 0, 1, 'a.mp4', NULL), (2, 'r', 'active', 1, 2, 'b.mp4', 5);",
+        )
+        .unwrap();
+
+        assert_eq!(segment_object(&conn, "r", 1).unwrap(), SegmentObject::Hot("a.mp4".into()));
+        assert_eq!(segment_object(&conn, "r", 2).unwrap(), SegmentObject::Evicted);
+        assert_eq!(segment_object(&conn, "other", 1).unwrap(), SegmentObject::NotFound);
+    }
+
+    #[test]
+    fn delete_one_segment_respects_clip_references() {
+        let mut conn = db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, archived_at)
+                 VALUES (1, 'r', 'active', 0, 1, 'a.mp4', NULL), (2, 'r', 'active', 1, 2, 'b.mp4', NULL),
+                        (3, 'r', 'active', 2, 3, 'c.mp4', 5);
+             INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (7, 1, 'r', 1, 3, '[2,3]', 0);",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            delete_one_segment(&mut conn, "r", 1, false).unwrap(),
+            SegmentDeleteOutcome::Deleted { hot_key: Some(ref k), ref clip_ids } if k == "a.mp4" && clip_ids.is_empty()
+        ));
+        assert!(matches!(delete_one_segment(&mut conn, "r", 1, false).unwrap(), SegmentDeleteOutcome::NotFound));
+        assert!(matches!(delete_one_segment(&mut conn, "other", 2, false).unwrap(), SegmentDeleteOutcome::NotFound));
+
+        assert!(matches!(
+            delete_one_segment(&mut conn, "r", 2, false).unwrap(),
+            SegmentDeleteOutcome::InClips(ref ids) if ids == &[7]
+        ));
+        // Archived: nothing left in RustFS to delete.
+        assert!(matches!(
+            delete_one_segment(&mut conn, "r", 3, true).unwrap(),
+            SegmentDeleteOutcome::Deleted { hot_key: None, ref clip_ids } if clip_ids == &[7]
+        ));
+        let left: i64 = conn.query_row("SELECT COUNT(*) FROM segments", [], |row| row.get(0)).unwrap();
+        assert_eq!(left, 1);
+    }
 }
@@ -0,0 +1,358 @@
+//! Merging back-to-back active segments into one.
+//!
+//! Rolling segments split a continuous recording every
+//! `recording.segment_duration_secs`. Merging joins them again: the videos come
+//! from the same encoder settings, so they are concatenated with ffmpeg's
+//! concat demuxer (stream copy) and uploaded as one new object in the same
+//! container (MP4 or WebM). The rows are
+//! then swapped in one transaction and clips are repointed at the new segment.
+
+use std::path::Path;
+
+use aws_sdk_s3::primitives::ByteStream;
+use frame_bucket_common::api_types::{Segment, SegmentTier};
+use frame_bucket_common::db_types::{decode_segment_ids, encode_segment_ids};
+use rusqlite::params;
+use tracing::warn;
+
+use crate::lerobot::{self, ExportError};
+
+/// Largest gap (or overlap) between one segment's end and the next one's
+/// start that still counts as contiguous. Rolled segments meet within a frame
+/// or two.
+pub const MAX_GAP_MS: i64 = 1_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    #[error("failed to download {key}: {message}")]
+    Download { key: String, message: String },
+    #[error("failed to upload {key}: {message}")]
+    Upload { key: String, message: String },
+    #[error(transparent)]
+    Video(#[from] ExportError),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Sort `segments` by start time and check they can be merged: all active,
+/// still in RustFS, from the same region and container, each starting within
+/// [`MAX_GAP_MS`] of the previous one's end.
+pub fn check_mergeable(segments: &mut [Segment]) -> Result<(), String> {
+    segments.sort_by_key(|s| (s.start_ms, s.id));
+    for s in segments.iter() {
+        if s.segment_type != "active" {
+            return Err(format!("segment {} is {}; only active segments can be merged", s.id, s.segment_type));
+        }
+        if s.tier != SegmentTier::Hot {
+            return Err(format!("segment {} is no longer in RustFS", s.id));
+        }
+    }
+    for pair in segments.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if a.region != b.region {
+            return Err(format!("segments {} and {} are from different regions", a.id, b.id));
+        }
+        if extension(&a.s3_key) != extension(&b.s3_key) {
+            return Err(format!("segments {} and {} are in different containers", a.id, b.id));
+        }
+        let gap = b.start_ms - a.end_ms;
+        if gap.abs() > MAX_GAP_MS {
+            return Err(format!("segments {} and {} are not contiguous ({gap} ms apart)", a.id, b.id));
+        }
+    }
+    Ok(())
+}
+
+/// Key for the merged object: next to the first segment's, in the recorder's
+/// `{start}_{end}_{suffix}.{ext}` shape, with `m{first id}` as the suffix.
+pub fn merged_key(first: &Segment, end_ms: i64) -> String {
+    let dir = first
+        .s3_key
+        .rsplit_once('/')
+        .map(|(dir, _)| format!("{dir}/"))
+        .unwrap_or_default();
+    let ext = extension(&first.s3_key);
+    format!("{dir}{}_{}_m{}.{ext}", fmt_ts(first.start_ms), fmt_ts(end_ms), first.id)
+}
+
+/// Container extension of a segment object: `webm` or `mp4` (the recorder's
+/// two, and the default).
+fn extension(key: &str) -> &'static str {
+    if key.ends_with(".webm") {
+        "webm"
+    } else {
+        "mp4"
+    }
+}
+
+fn fmt_ts(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%S%3fZ")
+        .to_string()
+}
+
+/// Download `segments` (in order) from `bucket`, join them and upload the
+/// result as `dest_key`. Returns the merged object's size.
+pub async fn concat_objects(
+    s3: &aws_sdk_s3::Client,
+    bucket: &str,
+    segments: &[Segment],
+    dest_key: &str,
+) -> Result<i64, MergeError> {
+    let work_dir = std::env::temp_dir().join(format!(
+        "segment-merge-{}-{}",
+        std::process::id(),
+        segments.first().map_or(0, |s| s.id)
+    ));
+    tokio::fs::create_dir_all(&work_dir).await?;
+
+    let result = concat_in(s3, bucket, segments, dest_key, &work_dir).await;
+
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        warn!(path = work_dir.display().to_string(), error = %e, "failed to remove merge work dir");
+    }
+    result
+}
+
+async fn concat_in(
+    s3: &aws_sdk_s3::Client,
+    bucket: &str,
+    segments: &[Segment],
+    dest_key: &str,
+    work_dir: &Path,
+) -> Result<i64, MergeError> {
+    let ext = extension(dest_key);
+    let mut inputs = Vec::with_capacity(segments.len());
+    for (i, seg) in segments.iter().enumerate() {
+        let key = seg.s3_key.trim_start_matches('/');
+        let err = |message: String| MergeError::Download { key: key.to_string(), message };
+        let resp = s3
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| err(e.to_string()))?;
+        let data = resp.body.collect().await.map_err(|e| err(e.to_string()))?;
+        let path = work_dir.join(format!("{i:04}.{ext}"));
+        tokio::fs::write(&path, data.into_bytes()).await?;
+        inputs.push(path);
+    }
+
+    let output = work_dir.join(format!("merged.{ext}"));
+    lerobot::concat(&inputs, &output).await?;
+    let body = tokio::fs::read(&output).await?;
+    let size_bytes = body.len() as i64;
+    s3.put_object()
+        .bucket(bucket)
+        .key(dest_key)
+        .content_type(format!("video/{ext}"))
+        .body(ByteStream::from(body))
+        .send()
+        .await
+        .map_err(|e| MergeError::Upload { key: dest_key.to_string(), message: e.to_string() })?;
+    Ok(size_bytes)
+}
+
+/// Swap `merged_from` (sorted, as checked by [`check_mergeable`]) for one row
+/// pointing at the merged object. Labels are unioned, metadata comes from the
+/// first segment and frame counts and motion scores are summed (unknown if
+/// any is). Clips that referenced the originals are repointed at the new
+/// segment. `None`, with nothing changed, if any original was deleted in the
+/// meantime.
+pub fn replace_with_merged(
+    conn: &mut rusqlite::Connection,
+    robot_id: &str,
+    merged_from: &[Segment],
+    s3_key: &str,
+    size_bytes: i64,
+) -> rusqlite::Result<Option<Segment>> {
+    let (Some(first), Some(last)) = (merged_from.first(), merged_from.last()) else {
+        return Ok(None);
+    };
+    let old_ids: Vec<i64> = merged_from.iter().map(|s| s.id).collect();
+
+    let tx = conn.transaction()?;
+    let mut deleted = 0;
+    for id in &old_ids {
+        deleted += tx.execute("DELETE FROM segments WHERE id = ?1 AND robot_id = ?2", params![id, robot_id])?;
+    }
+    if deleted != old_ids.len() {
+        return Ok(None);
+    }
+
+    let mut labels: Vec<String> = Vec::new();
+    for label in merged_from.iter().flat_map(|s| &s.labels) {
+        if !labels.contains(label) {
+            labels.push(label.clone());
+        }
+    }
+    let frame_count: Option<i64> = merged_from.iter().map(|s| s.frame_count).sum();
+    let motion_score: Option<f64> = merged_from.iter().map(|s| s.motion_score).sum();
+    let created_at = chrono::Utc::now().timestamp_millis();
+    tx.execute(
+        "INSERT INTO segments
+             (robot_id, type, start_ms, end_ms, s3_key, size_bytes, frame_count, labels, metadata, region, created_at,
+              motion_score)
+         VALUES (?1, 'active', ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            robot_id,
+            first.start_ms,
+            last.end_ms,
+            s3_key,
+            size_bytes,
+            frame_count,
+            serde_json::to_string(&labels).unwrap(),
+            first.metadata.to_string(),
+            first.region,
+            created_at,
+            motion_score,
+        ],
+    )?;
+    let id = tx.last_insert_rowid();
+
+    let clips: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare("SELECT id, segment_ids FROM collection_clips WHERE robot_id = ?1")?;
+        let rows = stmt.query_map(params![robot_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+    for (clip_id, raw) in clips {
+        let segment_ids = decode_segment_ids(&raw);
+        if !segment_ids.iter().any(|s| old_ids.contains(s)) {
+            continue;
+        }
+        let mut repointed = Vec::with_capacity(segment_ids.len());
+        for s in segment_ids {
+            let s = if old_ids.contains(&s) { id } else { s };
+            if !repointed.contains(&s) {
+                repointed.push(s);
+            }
+        }
+        tx.execute(
+            "UPDATE collection_clips SET segment_ids = ?1 WHERE id = ?2",
+            params![encode_segment_ids(&repointed), clip_id],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(Some(Segment {
+        id,
+        robot_id: robot_id.to_string(),
+        segment_type: "active".into(),
+        start_ms: first.start_ms,
+        end_ms: last.end_ms,
+        s3_key: s3_key.to_string(),
+        size_bytes: Some(size_bytes),
+        frame_count,
+        labels,
+        metadata: first.metadata.clone(),
+        tier: SegmentTier::Hot,
+        region: first.region.clone(),
+        created_at: Some(created_at),
+        motion_score,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn segment(id: i64, start_ms: i64, end_ms: i64, labels: &[&str]) -> Segment {
+        Segment {
+            id,
+            robot_id: "r".into(),
+            segment_type: "active".into(),
+            start_ms,
+            end_ms,
+            s3_key: format!("frames/r/camera/2026-02-18/{id}.mp4"),
+            size_bytes: Some(100),
+            frame_count: Some(30),
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            metadata: json!({"operator": id}),
+            tier: SegmentTier::Hot,
+            region: None,
+            created_at: None,
+            motion_score: None,
+        }
+    }
+
+    #[test]
+    fn check_mergeable_sorts_and_rejects_gaps_and_mixed_segments() {
+        let mut segments = [segment(2, 60_000, 120_000, &[]), segment(1, 0, 60_033, &[])];
+        assert_eq!(check_mergeable(&mut segments), Ok(()));
+        assert_eq!(segments[0].id, 1);
+
+        let mut gap = [segment(1, 0, 60_000, &[]), segment(2, 65_000, 120_000, &[])];
+        assert!(check_mergeable(&mut gap).unwrap_err().contains("not contiguous"));
+
+        let mut idle = [segment(1, 0, 60_000, &[]), segment(2, 60_000, 120_000, &[])];
+        idle[1].segment_type = "idle".into();
+        assert!(check_mergeable(&mut idle).unwrap_err().contains("only active"));
+
+        let mut regions = [segment(1, 0, 60_000, &[]), segment(2, 60_000, 120_000, &[])];
+        regions[1].region = Some("bench".into());
+        assert!(check_mergeable(&mut regions).unwrap_err().contains("regions"));
+
+        let mut archived = [segment(1, 0, 60_000, &[]), segment(2, 60_000, 120_000, &[])];
+        archived[0].tier = SegmentTier::Archived;
+        assert!(check_mergeable(&mut archived).is_err());
+
+        let mut containers = [segment(1, 0, 60_000, &[]), segment(2, 60_000, 120_000, &[])];
+        containers[1].s3_key = "frames/r/camera/2026-02-18/2.webm".into();
+        assert!(check_mergeable(&mut containers).unwrap_err().contains("containers"));
+    }
+
+    #[test]
+    fn merged_key_sits_next_to_first_segment() {
+        let first = segment(7, 1771372800000, 1771372860000, &[]);
+        assert_eq!(
+            merged_key(&first, 1771372920000),
+            "frames/r/camera/2026-02-18/20260218T000000000Z_20260218T000200000Z_m7.mp4"
+        );
+
+        let mut first = first;
+        first.s3_key = "frames/r/camera/2026-02-18/7.webm".into();
+        assert!(merged_key(&first, 1771372920000).ends_with("_m7.webm"));
+    }
+
+    #[test]
+    fn replace_with_merged_swaps_rows_and_repoints_clips() {
+        let mut conn = crate::db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key) VALUES
+                 (1, 'r', 'active', 0, 60000, 'a'), (2, 'r', 'active', 60000, 120000, 'b'),
+                 (3, 'r', 'active', 120000, 180000, 'c');
+             INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'r', 'grasps', 0, 0);
+             INSERT INTO collection_clips (id, collection_id, robot_id, clip_start_ms, clip_end_ms, segment_ids, created_at)
+                 VALUES (10, 1, 'r', 0, 180000, '[1, 2, 3]', 0), (11, 1, 'r', 120000, 180000, '[3]', 0);",
+        )
+        .unwrap();
+
+        let from = [segment(1, 0, 60_000, &["grasp"]), segment(2, 60_000, 120_000, &["grasp", "lift"])];
+        let merged = replace_with_merged(&mut conn, "r", &from, "merged.mp4", 180).unwrap().unwrap();
+        assert_eq!((merged.start_ms, merged.end_ms), (0, 120_000));
+        assert_eq!(merged.labels, ["grasp", "lift"]);
+        assert_eq!(merged.frame_count, Some(60));
+        assert_eq!(merged.metadata, json!({"operator": 1}));
+
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM segments ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(ids, [3, merged.id]);
+        let clip: String = conn
+            .query_row("SELECT segment_ids FROM collection_clips WHERE id = 10", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(decode_segment_ids(&clip), [merged.id, 3]);
+
+        // The originals are gone now, so a second merge of them is refused.
+        assert!(replace_with_merged(&mut conn, "r", &from, "again.mp4", 1).unwrap().is_none());
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM segments", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+    }
+}
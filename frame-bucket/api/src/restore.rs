@@ -0,0 +1,136 @@
+//! Bringing evicted segments back from the AWS archive into RustFS.
+//!
+//! Eviction uploads each object to `aws_s3.bucket` at `aws_s3.prefix` + its
+//! RustFS key, deletes it from RustFS and records that archive key on the
+//! segment's row. Restoring copies the archive object back to the original
+//! RustFS key and clears the row's archive columns, so the segment is hot
+//! again and plays through the usual routes. The archive copy is kept.
+//!
+//! The consumer needs no notice: eviction lists the bucket rather than its own
+//! index, so a restored object becomes a candidate again once it's among the
+//! oldest.
+
+use aws_sdk_s3::primitives::ByteStream;
+use frame_bucket_common::api_types::Segment;
+use rusqlite::{params, OptionalExtension};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RestoreError {
+    #[error("failed to download {key} from the archive: {message}")]
+    Download { key: String, message: String },
+    #[error("failed to upload {key} to RustFS: {message}")]
+    Upload { key: String, message: String },
+}
+
+/// Whether a segment can be restored, from its row.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Restorable {
+    NotFound,
+    /// Still in RustFS.
+    Hot,
+    /// Evicted without an archive copy (fallback delete-only eviction).
+    NoArchiveCopy,
+    Archived { s3_key: String, archive_key: String },
+}
+
+pub fn lookup(conn: &rusqlite::Connection, robot_id: &str, id: i64) -> rusqlite::Result<Restorable> {
+    let row: Option<(String, Option<i64>, Option<String>)> = conn
+        .query_row(
+            "SELECT s3_key, archived_at, archive_key FROM segments WHERE id = ?1 AND robot_id = ?2",
+            params![id, robot_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    Ok(match row {
+        None => Restorable::NotFound,
+        Some((_, None, _)) => Restorable::Hot,
+        Some((_, Some(_), None)) => Restorable::NoArchiveCopy,
+        Some((s3_key, Some(_), Some(archive_key))) => Restorable::Archived { s3_key, archive_key },
+    })
+}
+
+/// Copy `archive_key` from the archive bucket to `s3_key` in RustFS, keeping
+/// its content type. Returns the object's size.
+pub async fn copy_from_archive(
+    archive: &aws_sdk_s3::Client,
+    archive_bucket: &str,
+    archive_key: &str,
+    rustfs: &aws_sdk_s3::Client,
+    rustfs_bucket: &str,
+    s3_key: &str,
+) -> Result<i64, RestoreError> {
+    let download = |message: String| RestoreError::Download { key: archive_key.to_string(), message };
+    let resp = archive
+        .get_object()
+        .bucket(archive_bucket)
+        .key(archive_key)
+        .send()
+        .await
+        .map_err(|e| download(e.to_string()))?;
+    let content_type = resp.content_type().map(str::to_string);
+    let data = resp.body.collect().await.map_err(|e| download(e.to_string()))?.into_bytes();
+    let size = data.len() as i64;
+
+    let key = s3_key.trim_start_matches('/');
+    rustfs
+        .put_object()
+        .bucket(rustfs_bucket)
+        .key(key)
+        .set_content_type(content_type)
+        .body(ByteStream::from(data))
+        .send()
+        .await
+        .map_err(|e| RestoreError::Upload { key: key.to_string(), message: e.to_string() })?;
+    Ok(size)
+}
+
+/// Mark segment `id` hot again. `None` if it was deleted (or restored) in
+/// the meantime.
+pub fn mark_restored(conn: &rusqlite::Connection, robot_id: &str, id: i64) -> rusqlite::Result<Option<Segment>> {
+    let updated = conn.execute(
+        "UPDATE segments SET archived_at = NULL, archive_key = NULL
+         WHERE id = ?1 AND robot_id = ?2 AND archived_at IS NOT NULL",
+        params![id, robot_id],
+    )?;
+    if updated == 0 {
+        return Ok(None);
+    }
+    conn.query_row(
+        &format!("SELECT {} FROM segments WHERE id = ?1", crate::SEGMENT_COLUMNS),
+        params![id],
+        crate::row_to_segment,
+    )
+    .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_bucket_common::api_types::SegmentTier;
+
+    #[test]
+    fn lookup_and_mark_restored_follow_the_archive_columns() {
+        let conn = crate::db::test_conn();
+        conn.execute_batch(
+            "INSERT INTO segments (id, robot_id, type, start_ms, end_ms, s3_key, archived_at, archive_key) VALUES
+                 (1, 'r', 'active', 0, 1000, 'r/a.mp4', NULL, NULL),
+                 (2, 'r', 'active', 1000, 2000, 'r/b.mp4', 99, 'archive/r/b.mp4'),
+                 (3, 'r', 'idle', 2000, 3000, 'r/c.jpg', 99, NULL);",
+        )
+        .unwrap();
+
+        assert_eq!(lookup(&conn, "r", 1).unwrap(), Restorable::Hot);
+        assert_eq!(lookup(&conn, "r", 3).unwrap(), Restorable::NoArchiveCopy);
+        assert_eq!(lookup(&conn, "other", 2).unwrap(), Restorable::NotFound);
+        assert_eq!(
+            lookup(&conn, "r", 2).unwrap(),
+            Restorable::Archived { s3_key: "r/b.mp4".into(), archive_key: "archive/r/b.mp4".into() }
+        );
+
+        let restored = mark_restored(&conn, "r", 2).unwrap().unwrap();
+        assert_eq!(restored.tier, SegmentTier::Hot);
+        assert_eq!(lookup(&conn, "r", 2).unwrap(), Restorable::Hot);
+        // Already hot: nothing to mark.
+        assert!(mark_restored(&conn, "r", 2).unwrap().is_none());
+    }
+}
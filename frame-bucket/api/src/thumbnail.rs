@@ -0,0 +1,94 @@
+//! Poster images for active segments.
+//!
+//! A thumbnail is one frame from the middle of the segment's MP4, scaled to
+//! [`THUMB_WIDTH`] and encoded as JPEG by ffmpeg. Rendered thumbnails are
+//! stored back in the RustFS bucket at
+//! [`thumbnail_key`](frame_bucket_common::config::thumbnail_key), so each is
+//! made once. Eviction leaves them alone; they're deleted with their segment.
+
+use std::path::Path;
+
+use tokio::process::Command;
+use tracing::warn;
+
+/// Width of rendered thumbnails; height keeps the aspect ratio.
+pub const THUMB_WIDTH: u32 = 320;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThumbnailError {
+    #[error("failed to download {key}: {message}")]
+    Download { key: String, message: String },
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Offset into a segment of the frame to show: its midpoint.
+pub fn poster_offset_ms(start_ms: i64, end_ms: i64) -> i64 {
+    (end_ms - start_ms).max(0) / 2
+}
+
+/// Download the MP4 at `video_key` and render a JPEG of the frame
+/// `offset_ms` into it.
+pub async fn render(
+    s3: &aws_sdk_s3::Client,
+    bucket: &str,
+    video_key: &str,
+    offset_ms: i64,
+) -> Result<Vec<u8>, ThumbnailError> {
+    let err = |message: String| ThumbnailError::Download { key: video_key.to_string(), message };
+    let resp = s3
+        .get_object()
+        .bucket(bucket)
+        .key(video_key.trim_start_matches('/'))
+        .send()
+        .await
+        .map_err(|e| err(e.to_string()))?;
+    let data = resp.body.collect().await.map_err(|e| err(e.to_string()))?;
+
+    // The MP4 index may be at the end of the file, so ffmpeg needs a seekable
+    // input rather than a pipe.
+    let video_path = std::env::temp_dir().join(format!(
+        "thumbnail-{}-{:08x}.mp4",
+        std::process::id(),
+        fastrand::u32(..)
+    ));
+    tokio::fs::write(&video_path, data.into_bytes()).await?;
+    let result = extract_frame(&video_path, offset_ms).await;
+    if let Err(e) = tokio::fs::remove_file(&video_path).await {
+        warn!(path = video_path.display().to_string(), error = %e, "failed to remove thumbnail input");
+    }
+    result
+}
+
+async fn extract_frame(video: &Path, offset_ms: i64) -> Result<Vec<u8>, ThumbnailError> {
+    let offset = format!("{:.3}", offset_ms as f64 / 1000.0);
+    let scale = format!("scale={THUMB_WIDTH}:-2");
+    let out = Command::new("ffmpeg")
+        .args(["-v", "error", "-ss", &offset, "-i"])
+        .arg(video)
+        .args(["-frames:v", "1", "-vf", &scale, "-q:v", "4", "-f", "image2", "-c:v", "mjpeg", "pipe:1"])
+        .output()
+        .await
+        .map_err(|e| ThumbnailError::Ffmpeg(e.to_string()))?;
+    if !out.status.success() {
+        return Err(ThumbnailError::Ffmpeg(String::from_utf8_lossy(&out.stderr).into_owned()));
+    }
+    if out.stdout.is_empty() {
+        return Err(ThumbnailError::Ffmpeg(format!("no frame at {offset}s")));
+    }
+    Ok(out.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnails_are_taken_midway() {
+        assert_eq!(poster_offset_ms(10_000, 70_000), 30_000);
+        assert_eq!(poster_offset_ms(5_000, 5_000), 0);
+        assert_eq!(poster_offset_ms(5_000, 4_000), 0);
+    }
+}
@@ -0,0 +1,638 @@
+//! Request/response types for the frame-bucket HTTP API.
+//!
+//! Shared between the API server and anything that talks to it (clients,
+//! tests, tooling), so the JSON shapes are defined in one place.
+
+use serde::{Deserialize, Serialize};
+
+// ---------------------------------------------------------------------------
+// Types — Segments
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Segment {
+    pub id: i64,
+    pub robot_id: String,
+    #[serde(rename = "type")]
+    pub segment_type: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub s3_key: String,
+    pub size_bytes: Option<i64>,
+    pub frame_count: Option<i64>,
+    pub labels: Vec<String>,
+    /// Structured per-segment metadata (operator, task id, bounding boxes, ...). Always a JSON object.
+    pub metadata: serde_json::Value,
+    /// Where the segment's object currently lives. Clients should not try to
+    /// play segments that are not `hot`.
+    #[serde(default)]
+    pub tier: SegmentTier,
+    /// Motion region the segment was recorded for (`recording.regions`);
+    /// `None` for whole-frame recording.
+    #[serde(default)]
+    pub region: Option<String>,
+    /// When the segment row was written (Unix millis). Compare with `end_ms`
+    /// for ingestion lag.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    /// How much happened in an active segment, for ranking: summed aHash
+    /// distances between consecutive frames (JPEG) or P-frame sizes relative
+    /// to their moving average (H.264). Grows with length; JPEG and H.264
+    /// scores aren't comparable. `None` for idle segments and older rows.
+    #[serde(default)]
+    pub motion_score: Option<f64>,
+}
+
+/// Response of `GET /robots/:robot_id/segments/:id/presigned`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PresignedUrl {
+    pub url: String,
+    /// When the URL stops working (Unix millis).
+    pub expires_at: i64,
+}
+
+/// Storage tier of a segment's object.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentTier {
+    /// In RustFS, playable directly.
+    #[default]
+    Hot,
+    /// Evicted from RustFS; only the copy in the AWS archive remains.
+    Archived,
+    /// Evicted from RustFS without an archive copy (fallback delete-only eviction).
+    Missing,
+}
+
+impl SegmentTier {
+    /// Tier from a `segments` row's `archived_at` / `archive_key` columns, which
+    /// eviction sets when it removes the object from RustFS.
+    pub fn from_archive_columns(archived_at: Option<i64>, archive_key: Option<&str>) -> Self {
+        match (archived_at, archive_key) {
+            (None, _) => SegmentTier::Hot,
+            (Some(_), Some(_)) => SegmentTier::Archived,
+            (Some(_), None) => SegmentTier::Missing,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct SegmentQuery {
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    #[serde(rename = "type")]
+    pub segment_type: Option<String>,
+    /// Only segments of this motion region.
+    pub region: Option<String>,
+    /// Only segments carrying these labels (`?label=grasp&label=handoff`).
+    #[serde(default, rename = "label")]
+    pub labels: Vec<String>,
+    /// Whether a segment needs `all` (default) or `any` of `labels`.
+    #[serde(default, rename = "match")]
+    pub label_match: LabelMatch,
+    /// Only segments with at least this many frames. Idle segments have no
+    /// frame count, so setting this excludes them.
+    pub min_frame_count: Option<i64>,
+    /// Only segments whose object is at least this large. Segments without a
+    /// recorded size are excluded.
+    pub min_size_bytes: Option<i64>,
+    /// Only segments with at least this `motion_score`. Segments without a
+    /// score (idle ones included) are excluded.
+    pub min_motion: Option<f64>,
+    #[serde(default)]
+    pub sort: SegmentSort,
+    pub limit: Option<i64>,
+    /// Also return the number of matching segments, ignoring `limit`, in an
+    /// `X-Total-Count` header. Costs a second query.
+    #[serde(default)]
+    pub with_count: bool,
+}
+
+/// Order of `GET /robots/:robot_id/segments` results.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentSort {
+    /// Capture time, oldest first.
+    #[default]
+    StartMs,
+    /// Time the row was written, newest first.
+    CreatedAt,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LabelMatch {
+    #[default]
+    All,
+    Any,
+}
+
+/// Query for `DELETE /robots/:robot_id/segments`. Both time bounds are
+/// required and `confirm` must be `true`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct BulkDeleteQuery {
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    #[serde(rename = "type")]
+    pub segment_type: Option<String>,
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Query for `GET /robots/:robot_id/segments/:id/next` and `.../prev`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct NeighborQuery {
+    /// Only step to segments of this type.
+    #[serde(rename = "type")]
+    pub segment_type: Option<String>,
+}
+
+/// Query for `DELETE /robots/:robot_id/segments/:id`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct DeleteSegmentQuery {
+    /// Delete even if saved clips reference the segment.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkDeleteResponse {
+    /// Segment rows deleted.
+    pub deleted: usize,
+    /// Matching segments left in place because saved clips reference them.
+    pub skipped_in_clips: Vec<i64>,
+    /// RustFS objects that could not be deleted (their rows are gone regardless).
+    pub object_delete_failures: usize,
+}
+
+/// Query for `DELETE /robots/:robot_id/segments/idle`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct IdlePruneQuery {
+    /// Required: delete idle segments ending before this.
+    pub before_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IdlePruneResponse {
+    /// Idle segment rows deleted.
+    pub deleted: usize,
+    /// Recorded size of the deleted segments whose objects were in RustFS.
+    pub bytes_freed: i64,
+    /// Old idle segments left in place because saved clips reference them.
+    pub skipped_in_clips: usize,
+    /// RustFS objects that could not be deleted (their rows are gone regardless).
+    pub object_delete_failures: usize,
+}
+
+/// Query for `GET /robots/:robot_id/segments/overlaps`. Both segments of a
+/// pair must intersect `[start_ms, end_ms]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct OverlapQuery {
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// One side of an overlapping pair.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OverlapSide {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub segment_type: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+/// Two segments whose time ranges overlap, with `a.id < b.id`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SegmentOverlap {
+    pub a: OverlapSide,
+    pub b: OverlapSide,
+    pub overlap_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PatchLabels {
+    pub labels: Vec<String>,
+}
+
+/// Body of `PATCH /robots/:robot_id/segments/labels`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkLabels {
+    pub segment_ids: Vec<i64>,
+    pub labels: Vec<String>,
+    pub mode: BulkLabelMode,
+}
+
+/// How [`BulkLabels::labels`] are applied to each segment's existing labels.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BulkLabelMode {
+    /// Set the labels to exactly these.
+    Replace,
+    /// Add those not already present, keeping existing order.
+    Add,
+    /// Remove these, keeping the rest.
+    Remove,
+}
+
+/// Body of `POST /robots/:robot_id/segments/merge`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MergeSegments {
+    /// Contiguous active segments to join, in any order.
+    pub segment_ids: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkLabelsResponse {
+    /// Segments found and updated; ids of other robots or unknown ids are ignored.
+    pub updated: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PatchMetadata {
+    pub metadata: serde_json::Value,
+    /// Replace the stored object instead of merging into it.
+    #[serde(default)]
+    pub replace: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Types — Collections
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CollectionResponse {
+    pub id: i64,
+    pub robot_id: String,
+    pub name: String,
+    pub description: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub clip_count: Option<i64>,
+}
+
+/// Query for `GET /robots/:robot_id/collections`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ListQuery {
+    /// Also return the number of results in an `X-Total-Count` header.
+    #[serde(default)]
+    pub with_count: bool,
+}
+
+/// Query for `GET /robots/:robot_id/collections/search`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct CollectionSearchQuery {
+    /// Words to find in collection names and descriptions (all must match;
+    /// each also matches as a prefix).
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateCollection {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Body of `PATCH /robots/:robot_id/collections/:id`. Omitted fields are left unchanged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateCollection {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Types — Clips
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClipResponse {
+    pub id: i64,
+    pub collection_id: i64,
+    pub robot_id: String,
+    pub modality: String,
+    pub clip_start_ms: i64,
+    pub clip_end_ms: i64,
+    pub segment_ids: Vec<i64>,
+    pub manifest_s3_key: Option<String>,
+    pub created_at: i64,
+}
+
+/// Sensor modalities a clip can be saved as.
+pub const CLIP_MODALITIES: &[&str] = &["camera", "depth", "audio"];
+
+/// Modality of clips saved without one.
+pub const DEFAULT_CLIP_MODALITY: &str = "camera";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateClip {
+    pub clip_start_ms: i64,
+    pub clip_end_ms: i64,
+    pub segment_ids: Vec<i64>,
+    pub labels: Option<Vec<String>>,
+    /// One of [`CLIP_MODALITIES`]; defaults to `camera`.
+    pub modality: Option<String>,
+}
+
+impl CreateClip {
+    pub fn modality(&self) -> &str {
+        self.modality.as_deref().unwrap_or(DEFAULT_CLIP_MODALITY)
+    }
+}
+
+/// Query for `GET /robots/:robot_id/collections/:collection_id/clips`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct ClipQuery {
+    /// Only clips of this modality.
+    pub modality: Option<String>,
+    /// Also return the number of results in an `X-Total-Count` header.
+    #[serde(default)]
+    pub with_count: bool,
+}
+
+/// Outcome of one clip in `POST /robots/:robot_id/collections/:collection_id/clips/batch`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchClipResult {
+    /// Position of the clip in the request.
+    pub index: usize,
+    /// Set when the clip was saved.
+    pub id: Option<i64>,
+    pub manifest_s3_key: Option<String>,
+    pub segment_ids: Vec<i64>,
+    /// Set when the clip was not saved.
+    pub error: Option<String>,
+}
+
+/// Body of `POST /robots/:robot_id/collections/:collection_id/clips/:clip_id/move`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MoveClip {
+    pub target_collection_id: i64,
+}
+
+// ---------------------------------------------------------------------------
+// Types — Timeline
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TimelineResponse {
+    pub segments: Vec<Segment>,
+    pub time_bounds: TimeBounds,
+}
+
+/// Query for `GET /timeline`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct FleetTimelineQuery {
+    /// Comma-separated robot ids.
+    pub robot_ids: String,
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    /// Max segments per robot.
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TimeBounds {
+    pub earliest_ms: Option<i64>,
+    pub latest_ms: Option<i64>,
+}
+
+/// One entry of `GET /robots/:robot_id/dates`: a UTC day with recorded segments.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RecordingDate {
+    /// `YYYY-MM-DD`, from the segment's `start_ms` in UTC.
+    pub date: String,
+    pub segments: i64,
+    pub active: i64,
+    pub idle: i64,
+}
+
+/// Query for `GET /robots/:robot_id/density`. All three are required.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct DensityQuery {
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    /// Bucket width; the range may span at most 2000 buckets.
+    pub bucket_ms: Option<i64>,
+}
+
+/// One bucket of `GET /robots/:robot_id/density`: segments starting in
+/// `[bucket_start_ms, bucket_start_ms + bucket_ms)`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DensityBucket {
+    pub bucket_start_ms: i64,
+    pub active_count: i64,
+    pub idle_count: i64,
+    pub total_bytes: i64,
+}
+
+/// Query for `GET /robots/:robot_id/labels`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct LabelQuery {
+    /// Only labels starting with this (case-sensitive).
+    pub prefix: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// One entry of `GET /robots/:robot_id/labels`: a label and how many
+/// segments carry it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LabelCount {
+    pub label: String,
+    pub count: i64,
+}
+
+/// `GET /robots/:robot_id/stats`: totals for a dashboard summary.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RobotStats {
+    pub total_segments: i64,
+    pub active_count: i64,
+    pub idle_count: i64,
+    pub total_bytes: i64,
+    pub total_frames: i64,
+    /// Earliest segment start; `None` when there are no segments.
+    pub earliest_ms: Option<i64>,
+    /// Latest segment end; `None` when there are no segments.
+    pub latest_ms: Option<i64>,
+    pub collection_count: i64,
+    pub clip_count: i64,
+}
+
+// ---------------------------------------------------------------------------
+// Types — Live updates (WebSocket)
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum WsAction {
+    #[default]
+    Subscribe,
+    Unsubscribe,
+}
+
+/// Client → server message on `/ws`. `action` defaults to `subscribe`, so a bare
+/// `{ "robot_ids": [...], "since_ms": ... }` subscribes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WsClientMessage {
+    #[serde(default)]
+    pub action: WsAction,
+    pub robot_ids: Vec<String>,
+    /// Replay segments ending at or after this time. When omitted, only
+    /// segments recorded after subscribing are pushed.
+    pub since_ms: Option<i64>,
+}
+
+/// Server → client message on `/ws`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    Subscribed { robot_ids: Vec<String> },
+    Unsubscribed { robot_ids: Vec<String> },
+    Segment { segment: Box<Segment> },
+    Error { message: String },
+}
+
+// ---------------------------------------------------------------------------
+// Types — Download info
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DownloadInfo {
+    pub total_bytes: i64,
+    pub clip_count: i64,
+}
+
+// ---------------------------------------------------------------------------
+// Types — Dataset export
+// ---------------------------------------------------------------------------
+
+/// Query for `GET /robots/:robot_id/collections/:collection_id/export`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::IntoParams), into_params(parameter_in = Query))]
+pub struct CollectionExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Shape of a collection manifest.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// The clips' own manifests, as written to the labelled-data bucket.
+    #[default]
+    Native,
+    /// A LeRobot `meta/info.json` with the clips as episodes.
+    Lerobot,
+}
+
+/// Result of exporting a collection as a LeRobot dataset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LeRobotExportResponse {
+    pub bucket: String,
+    /// Dataset root within `bucket`; contains `meta/`, `data/` and `videos/`.
+    pub prefix: String,
+    pub episodes: usize,
+    pub total_frames: u64,
+    /// Clips with no video to export (idle-only), left out of the dataset.
+    pub skipped_clip_ids: Vec<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_uses_type_key_on_the_wire() {
+        let seg = Segment {
+            id: 1,
+            robot_id: "reachy-001".into(),
+            segment_type: "active".into(),
+            start_ms: 1000,
+            end_ms: 2000,
+            s3_key: "reachy-001/camera/x.mp4".into(),
+            size_bytes: Some(10),
+            frame_count: Some(3),
+            labels: vec!["grasp".into()],
+            metadata: serde_json::json!({"operator": "ana"}),
+            tier: SegmentTier::Archived,
+            region: Some("bench".into()),
+            created_at: Some(2500),
+            motion_score: Some(12.0),
+        };
+        let json = serde_json::to_value(&seg).unwrap();
+        assert_eq!(json["type"], "active");
+        assert!(json.get("segment_type").is_none());
+        assert_eq!(json["tier"], "archived");
+        assert_eq!(json["region"], "bench");
+
+        let back: Segment = serde_json::from_value(json).unwrap();
+        assert_eq!(back.segment_type, "active");
+        assert_eq!(back.metadata["operator"], "ana");
+    }
+
+    #[test]
+    fn tier_from_archive_columns() {
+        assert_eq!(SegmentTier::from_archive_columns(None, None), SegmentTier::Hot);
+        assert_eq!(
+            SegmentTier::from_archive_columns(Some(1), Some("archive/r/x.mp4")),
+            SegmentTier::Archived
+        );
+        assert_eq!(SegmentTier::from_archive_columns(Some(1), None), SegmentTier::Missing);
+    }
+
+    #[test]
+    fn bare_ws_message_defaults_to_subscribe() {
+        let msg: WsClientMessage = serde_json::from_str(r#"{"robot_ids": ["a"]}"#).unwrap();
+        assert_eq!(msg.action, WsAction::Subscribe);
+        assert!(msg.since_ms.is_none());
+
+        let out = serde_json::to_value(WsServerMessage::Error { message: "x".into() }).unwrap();
+        assert_eq!(out["type"], "error");
+    }
+}
@@ -0,0 +1,187 @@
+//! Stored row shapes for the collection tables in the per-robot SQLite DB.
+//!
+//! These mirror the columns exactly (e.g. `segment_ids` is the raw JSON text
+//! column). API responses are derived from them via the conversions below, so
+//! the consumer and the API agree on one definition of the tables in
+//! `schema`.
+
+use crate::api_types::{ClipResponse, CollectionResponse};
+
+/// A row of the `collections` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionRow {
+    pub id: i64,
+    pub robot_id: String,
+    pub name: String,
+    pub description: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A row of the `collection_clips` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipRow {
+    pub id: i64,
+    pub collection_id: i64,
+    pub robot_id: String,
+    pub modality: String,
+    pub clip_start_ms: i64,
+    pub clip_end_ms: i64,
+    /// JSON array of segment ids, as stored. Use [`ClipRow::segment_ids`] to parse.
+    pub segment_ids_json: String,
+    pub manifest_s3_key: Option<String>,
+    pub created_at: i64,
+}
+
+impl CollectionRow {
+    /// Column list matching [`CollectionRow::from_row`], for `SELECT {COLUMNS} FROM collections`.
+    pub const COLUMNS: &'static str = "id, robot_id, name, description, created_at, updated_at";
+
+    pub fn into_response(self, clip_count: Option<i64>) -> CollectionResponse {
+        CollectionResponse {
+            id: self.id,
+            robot_id: self.robot_id,
+            name: self.name,
+            description: self.description,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            clip_count,
+        }
+    }
+}
+
+impl ClipRow {
+    /// Column list matching [`ClipRow::from_row`], for `SELECT {COLUMNS} FROM collection_clips`.
+    pub const COLUMNS: &'static str = "id, collection_id, robot_id, modality, clip_start_ms, \
+         clip_end_ms, segment_ids, manifest_s3_key, created_at";
+
+    /// Parsed `segment_ids`. A malformed column yields an empty list.
+    pub fn segment_ids(&self) -> Vec<i64> {
+        decode_segment_ids(&self.segment_ids_json)
+    }
+}
+
+impl From<ClipRow> for ClipResponse {
+    fn from(row: ClipRow) -> Self {
+        let segment_ids = row.segment_ids();
+        ClipResponse {
+            id: row.id,
+            collection_id: row.collection_id,
+            robot_id: row.robot_id,
+            modality: row.modality,
+            clip_start_ms: row.clip_start_ms,
+            clip_end_ms: row.clip_end_ms,
+            segment_ids,
+            manifest_s3_key: row.manifest_s3_key,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Encode segment ids for the `collection_clips.segment_ids` column.
+pub fn encode_segment_ids(ids: &[i64]) -> String {
+    serde_json::to_string(ids).expect("serializing i64s cannot fail")
+}
+
+/// Decode the `collection_clips.segment_ids` column.
+pub fn decode_segment_ids(raw: &str) -> Vec<i64> {
+    serde_json::from_str(raw).unwrap_or_default()
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::{ClipRow, CollectionRow};
+    use rusqlite::{params, Connection, Row};
+
+    impl CollectionRow {
+        /// Map a row selected with [`CollectionRow::COLUMNS`] (columns 0..6).
+        pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+            Ok(Self {
+                id: row.get(0)?,
+                robot_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        }
+
+        /// Set the provided fields of collection `id` (owned by `robot_id`) and
+        /// touch `updated_at`. Returns the updated row, or `None` if there is no
+        /// such collection. Renaming onto another collection's name fails with
+        /// the `idx_collections_name` UNIQUE constraint error.
+        pub fn update(
+            conn: &Connection,
+            robot_id: &str,
+            id: i64,
+            name: Option<&str>,
+            description: Option<&str>,
+            now_ms: i64,
+        ) -> rusqlite::Result<Option<Self>> {
+            let updated = conn.execute(
+                "UPDATE collections
+                 SET name = COALESCE(?1, name), description = COALESCE(?2, description), updated_at = ?3
+                 WHERE id = ?4 AND robot_id = ?5",
+                params![name, description, now_ms, id, robot_id],
+            )?;
+            if updated == 0 {
+                return Ok(None);
+            }
+            conn.query_row(
+                &format!("SELECT {} FROM collections WHERE id = ?1", Self::COLUMNS),
+                params![id],
+                Self::from_row,
+            )
+            .map(Some)
+        }
+    }
+
+    impl ClipRow {
+        /// Map a row selected with [`ClipRow::COLUMNS`] (columns 0..9).
+        pub fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+            Ok(Self {
+                id: row.get(0)?,
+                collection_id: row.get(1)?,
+                robot_id: row.get(2)?,
+                modality: row.get(3)?,
+                clip_start_ms: row.get(4)?,
+                clip_end_ms: row.get(5)?,
+                segment_ids_json: row.get(6)?,
+                manifest_s3_key: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_ids_roundtrip() {
+        let encoded = encode_segment_ids(&[3, 1, 2]);
+        assert_eq!(encoded, "[3,1,2]");
+        assert_eq!(decode_segment_ids(&encoded), vec![3, 1, 2]);
+        assert!(decode_segment_ids("not json").is_empty());
+    }
+
+    #[test]
+    fn clip_row_into_response_parses_segment_ids() {
+        let row = ClipRow {
+            id: 7,
+            collection_id: 2,
+            robot_id: "reachy-001".into(),
+            modality: "camera".into(),
+            clip_start_ms: 100,
+            clip_end_ms: 200,
+            segment_ids_json: "[4,5]".into(),
+            manifest_s3_key: None,
+            created_at: 1,
+        };
+        let resp = ClipResponse::from(row);
+        assert_eq!(resp.segment_ids, vec![4, 5]);
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["segment_ids"], serde_json::json!([4, 5]));
+    }
+}
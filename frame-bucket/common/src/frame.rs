@@ -27,16 +27,40 @@ pub enum FramePayload {
 ///   [10..18] seq             (u64 big-endian)
 ///   [18..22] h264_len        (u32 big-endian)
 ///   [22..22+h264_len] h264_data (Annex B access unit)
+///
+/// v3 (either payload, with the robot id; written when `robot_id` is set):
+///   [0]      version = 0x03
+///   [1]      modality        (0x01 = JPEG, 0x02 = H.264), | 0x80 when the
+///            payload is zstd-compressed
+///   [2]      nal_type        (0 for JPEG)
+///   [3..11]  captured_at_ms  (i64 big-endian)
+///   [11..19] seq             (u64 big-endian)
+///   [19..21] robot_id_len    (u16 big-endian)
+///   [21..21+robot_id_len] robot_id (UTF-8)
+///   then     payload_len     (u32 big-endian) and the payload bytes
+///   [-4..]   crc32           (u32 big-endian, CRC-32 of every byte before it)
 #[derive(Debug, Clone)]
 pub struct TimestampedFrame {
     pub payload: FramePayload,
     pub captured_at_ms: i64,
     pub seq: u64,
+    /// Robot that captured the frame, carried by v3 payloads. `None` for v1/v2,
+    /// whose robot id only lives in the Kafka message key.
+    pub robot_id: Option<String>,
 }
 
 const V1_HEADER_SIZE: usize = 16; // 8 bytes timestamp + 8 bytes seq
 const V2_HEADER_SIZE: usize = 22; // 1 version + 1 nal_type + 8 ts + 8 seq + 4 h264_len
 const V2_MARKER: u8 = 0x02;
+const V3_HEADER_SIZE: usize = 21; // 1 version + 1 modality + 1 nal_type + 8 ts + 8 seq + 2 robot_id_len
+const V3_CHECKSUM_SIZE: usize = 4;
+const V3_MARKER: u8 = 0x03;
+const MODALITY_JPEG: u8 = 0x01;
+const MODALITY_H264: u8 = 0x02;
+/// Modality bit marking a zstd-compressed v3 payload.
+const FLAG_ZSTD: u8 = 0x80;
+/// Largest payload a compressed frame may expand to.
+const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
 
 impl TimestampedFrame {
     /// Create a new JPEG frame (used by MJPEG/polling producer).
@@ -45,6 +69,7 @@ impl TimestampedFrame {
             payload: FramePayload::Jpeg(jpeg_data),
             captured_at_ms,
             seq,
+            robot_id: None,
         }
     }
 
@@ -57,9 +82,16 @@ impl TimestampedFrame {
             },
             captured_at_ms,
             seq,
+            robot_id: None,
         }
     }
 
+    /// Embed `robot_id` in the frame, so it serializes as v3.
+    pub fn with_robot_id(mut self, robot_id: &str) -> Self {
+        self.robot_id = Some(robot_id.to_string());
+        self
+    }
+
     // -- Convenience accessors --------------------------------------------------
 
     /// Returns the JPEG data if this is a JPEG frame.
@@ -101,8 +133,12 @@ impl TimestampedFrame {
 
     // -- Serialization ----------------------------------------------------------
 
-    /// Serialize to binary format for Kafka payload.
+    /// Serialize to binary format for Kafka payload: v3 when the frame has a
+    /// robot id, v1 (JPEG) or v2 (H.264) otherwise.
     pub fn serialize(&self) -> Vec<u8> {
+        if let Some(robot_id) = &self.robot_id {
+            return self.serialize_v3(robot_id, false);
+        }
         match &self.payload {
             FramePayload::Jpeg(jpeg_data) => {
                 // v1 format: [ts][seq][jpeg_bytes]
@@ -126,7 +162,48 @@ impl TimestampedFrame {
         }
     }
 
-    /// Deserialize from binary Kafka payload. Auto-detects v1 (JPEG) vs v2 (H.264).
+    /// Like [`Self::serialize`], with the payload zstd-compressed when that
+    /// makes it smaller. Only v3 can flag compression, so frames without a
+    /// robot id are written uncompressed.
+    pub fn serialize_compressed(&self) -> Vec<u8> {
+        match &self.robot_id {
+            Some(robot_id) => self.serialize_v3(robot_id, true),
+            None => self.serialize(),
+        }
+    }
+
+    fn serialize_v3(&self, robot_id: &str, compress: bool) -> Vec<u8> {
+        // v3 format: [0x03][modality][nal_type][ts][seq][id_len][id][payload_len][payload][crc32]
+        let (mut modality, nal_type) = match &self.payload {
+            FramePayload::Jpeg(_) => (MODALITY_JPEG, 0),
+            FramePayload::H264 { nal_type, .. } => (MODALITY_H264, *nal_type),
+        };
+        let id = &robot_id.as_bytes()[..robot_id.len().min(u16::MAX as usize)];
+        let compressed = compress
+            .then(|| zstd::bulk::compress(self.payload_bytes(), zstd::DEFAULT_COMPRESSION_LEVEL).ok())
+            .flatten()
+            .filter(|c| c.len() < self.payload_size());
+        if compressed.is_some() {
+            modality |= FLAG_ZSTD;
+        }
+        let data = compressed.as_deref().unwrap_or(self.payload_bytes());
+        let mut buf = Vec::with_capacity(V3_HEADER_SIZE + id.len() + 4 + data.len() + V3_CHECKSUM_SIZE);
+        buf.push(V3_MARKER);
+        buf.push(modality);
+        buf.push(nal_type);
+        buf.extend_from_slice(&self.captured_at_ms.to_be_bytes());
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.extend_from_slice(&(id.len() as u16).to_be_bytes());
+        buf.extend_from_slice(id);
+        buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        buf.extend_from_slice(data);
+        let checksum = crc32fast::hash(&buf);
+        buf.extend_from_slice(&checksum.to_be_bytes());
+        buf
+    }
+
+    /// Deserialize from binary Kafka payload. Auto-detects v1 (JPEG), v2
+    /// (H.264) and v3.
     pub fn deserialize(data: &[u8]) -> Result<Self, FrameError> {
         if data.is_empty() {
             return Err(FrameError::TooShort {
@@ -135,7 +212,9 @@ impl TimestampedFrame {
             });
         }
 
-        if data[0] == V2_MARKER {
+        if data[0] == V3_MARKER {
+            Self::deserialize_v3(data)
+        } else if data[0] == V2_MARKER {
             // v2 format (H.264)
             if data.len() < V2_HEADER_SIZE {
                 return Err(FrameError::TooShort {
@@ -161,6 +240,7 @@ impl TimestampedFrame {
                 },
                 captured_at_ms,
                 seq,
+                robot_id: None,
             })
         } else {
             // v1 format (JPEG)
@@ -177,21 +257,55 @@ impl TimestampedFrame {
                 payload: FramePayload::Jpeg(jpeg_data),
                 captured_at_ms,
                 seq,
+                robot_id: None,
             })
         }
     }
 
-    /// Generate an object key for storage.
-    pub fn object_key(&self, prefix: &str) -> String {
-        let dt = chrono::DateTime::from_timestamp_millis(self.captured_at_ms)
-            .unwrap_or_else(|| chrono::Utc::now());
-        let date = dt.format("%Y-%m-%d");
-        let ts = dt.format("%Y%m%dT%H%M%S%3fZ");
-        let ext = match &self.payload {
-            FramePayload::Jpeg(_) => "jpg",
-            FramePayload::H264 { .. } => "h264",
+    fn deserialize_v3(data: &[u8]) -> Result<Self, FrameError> {
+        let too_short = |expected| FrameError::TooShort { got: data.len(), expected };
+        if data.len() < V3_HEADER_SIZE {
+            return Err(too_short(V3_HEADER_SIZE));
+        }
+        let modality = data[1];
+        let nal_type = data[2];
+        let captured_at_ms = i64::from_be_bytes(data[3..11].try_into().unwrap());
+        let seq = u64::from_be_bytes(data[11..19].try_into().unwrap());
+        let id_len = u16::from_be_bytes(data[19..21].try_into().unwrap()) as usize;
+        let len_at = V3_HEADER_SIZE + id_len;
+        if data.len() < len_at + 4 {
+            return Err(too_short(len_at + 4));
+        }
+        let robot_id = std::str::from_utf8(&data[V3_HEADER_SIZE..len_at])
+            .map_err(|_| FrameError::InvalidRobotId)?
+            .to_string();
+        let payload_len = u32::from_be_bytes(data[len_at..len_at + 4].try_into().unwrap()) as usize;
+        let payload_at = len_at + 4;
+        let checksum_at = payload_at + payload_len;
+        if data.len() < checksum_at + V3_CHECKSUM_SIZE {
+            return Err(too_short(checksum_at + V3_CHECKSUM_SIZE));
+        }
+        let expected = u32::from_be_bytes(data[checksum_at..checksum_at + V3_CHECKSUM_SIZE].try_into().unwrap());
+        let actual = crc32fast::hash(&data[..checksum_at]);
+        if actual != expected {
+            return Err(FrameError::ChecksumMismatch { expected, actual });
+        }
+        let mut bytes = data[payload_at..payload_at + payload_len].to_vec();
+        if modality & FLAG_ZSTD != 0 {
+            bytes = zstd::bulk::decompress(&bytes, MAX_DECOMPRESSED_SIZE)
+                .map_err(|e| FrameError::Decompress(e.to_string()))?;
+        }
+        let payload = match modality & !FLAG_ZSTD {
+            MODALITY_JPEG => FramePayload::Jpeg(bytes),
+            MODALITY_H264 => FramePayload::H264 { data: bytes, nal_type },
+            other => return Err(FrameError::UnknownModality(other)),
         };
-        format!("{prefix}{date}/{ts}_{seq:06}.{ext}", seq = self.seq)
+        Ok(Self {
+            payload,
+            captured_at_ms,
+            seq,
+            robot_id: Some(robot_id),
+        })
     }
 }
 
@@ -199,6 +313,21 @@ impl TimestampedFrame {
 pub enum FrameError {
     #[error("frame payload too short: got {got} bytes, expected at least {expected}")]
     TooShort { got: usize, expected: usize },
+    #[error("unknown frame modality {0:#04x}")]
+    UnknownModality(u8),
+    #[error("frame robot id is not valid UTF-8")]
+    InvalidRobotId,
+    #[error("frame checksum mismatch: expected {expected:#010x}, computed {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+    #[error("failed to decompress frame payload: {0}")]
+    Decompress(String),
+}
+
+impl FrameError {
+    /// Whether the payload was damaged in transit, rather than malformed.
+    pub fn is_corrupt(&self) -> bool {
+        matches!(self, FrameError::ChecksumMismatch { .. })
+    }
 }
 
 #[cfg(test)]
@@ -231,29 +360,91 @@ mod tests {
     }
 
     #[test]
-    fn h264_p_frame_not_keyframe() {
-        let frame = TimestampedFrame::new_h264(vec![0x00, 0x01], 1, 1000, 1);
-        assert!(!frame.is_keyframe());
+    fn roundtrip_jpeg_v3() {
+        let frame = TimestampedFrame::new(vec![0xFF, 0xD8, 0xFF, 0xE0], 1708300000000, 42).with_robot_id("reachy-001");
+        let bytes = frame.serialize();
+        assert_eq!(bytes[0], V3_MARKER);
+        let decoded = TimestampedFrame::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.robot_id.as_deref(), Some("reachy-001"));
+        assert_eq!(decoded.captured_at_ms, 1708300000000);
+        assert_eq!(decoded.seq, 42);
+        assert_eq!(decoded.jpeg_data().unwrap(), &[0xFF, 0xD8, 0xFF, 0xE0]);
     }
 
     #[test]
-    fn deserialize_too_short() {
-        let result = TimestampedFrame::deserialize(&[0; 10]);
-        assert!(result.is_err());
+    fn roundtrip_h264_v3() {
+        let h264 = vec![0x00, 0x00, 0x00, 0x01, 0x65, 0xAA, 0xBB];
+        let frame = TimestampedFrame::new_h264(h264.clone(), 5, 1708300000000, 99).with_robot_id("bracketbot");
+        let decoded = TimestampedFrame::deserialize(&frame.serialize()).unwrap();
+        assert_eq!(decoded.robot_id.as_deref(), Some("bracketbot"));
+        assert_eq!(decoded.seq, 99);
+        assert_eq!(decoded.h264_data().unwrap(), &h264);
+        assert!(decoded.is_keyframe());
+    }
+
+    #[test]
+    fn v3_rejects_truncation_and_unknown_modality() {
+        let bytes = TimestampedFrame::new(vec![1, 2, 3], 1000, 1).with_robot_id("r").serialize();
+        assert!(matches!(
+            TimestampedFrame::deserialize(&bytes[..bytes.len() - 1]),
+            Err(FrameError::TooShort { .. })
+        ));
+
+        // Re-sign the edited frame so it gets past the checksum.
+        let mut bytes = bytes[..bytes.len() - V3_CHECKSUM_SIZE].to_vec();
+        bytes[1] = 0x7F;
+        let checksum = crc32fast::hash(&bytes);
+        bytes.extend_from_slice(&checksum.to_be_bytes());
+        assert!(matches!(TimestampedFrame::deserialize(&bytes), Err(FrameError::UnknownModality(0x7F))));
+    }
+
+    #[test]
+    fn v3_checksum_catches_a_flipped_byte() {
+        let bytes = TimestampedFrame::new(vec![0xFF, 0xD8, 0xFF, 0xE0], 1000, 1).with_robot_id("r").serialize();
+        // Modality, seq, payload and checksum bytes (the marker would select another format).
+        for i in [1, 12, bytes.len() - 6, bytes.len() - 1] {
+            let mut damaged = bytes.clone();
+            damaged[i] ^= 0x10;
+            let err = TimestampedFrame::deserialize(&damaged).unwrap_err();
+            assert!(err.is_corrupt(), "byte {i}: {err}");
+        }
     }
 
     #[test]
-    fn object_key_jpeg() {
-        let frame = TimestampedFrame::new(vec![], 1708300000000, 7);
-        let key = frame.object_key("frames/");
-        assert!(key.starts_with("frames/"));
-        assert!(key.ends_with("_000007.jpg"));
+    fn compressed_payload_decodes_transparently() {
+        let mut jpeg = vec![0x42; 4096];
+        jpeg[..2].copy_from_slice(&[0xFF, 0xD8]);
+        let frame = TimestampedFrame::new(jpeg.clone(), 1000, 1).with_robot_id("r");
+        let bytes = frame.serialize_compressed();
+        assert_eq!(bytes[1], MODALITY_JPEG | FLAG_ZSTD);
+        assert!(bytes.len() < frame.serialize().len());
+        let decoded = TimestampedFrame::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.jpeg_data().unwrap(), &jpeg);
+
+        // Incompressible payloads, and frames without a robot id, go out as is.
+        let tiny = TimestampedFrame::new(vec![0xFF, 0xD8], 1000, 2).with_robot_id("r");
+        assert_eq!(tiny.serialize_compressed(), tiny.serialize());
+        let v1 = TimestampedFrame::new(jpeg, 1000, 3);
+        assert_eq!(v1.serialize_compressed(), v1.serialize());
+    }
+
+    #[test]
+    fn v1_and_v2_have_no_robot_id() {
+        let jpeg = TimestampedFrame::new(vec![0xFF], 1000, 1).serialize();
+        let h264 = TimestampedFrame::new_h264(vec![0x00], 1, 1000, 1).serialize();
+        assert!(TimestampedFrame::deserialize(&jpeg).unwrap().robot_id.is_none());
+        assert!(TimestampedFrame::deserialize(&h264).unwrap().robot_id.is_none());
+    }
+
+    #[test]
+    fn h264_p_frame_not_keyframe() {
+        let frame = TimestampedFrame::new_h264(vec![0x00, 0x01], 1, 1000, 1);
+        assert!(!frame.is_keyframe());
     }
 
     #[test]
-    fn object_key_h264() {
-        let frame = TimestampedFrame::new_h264(vec![], 5, 1708300000000, 7);
-        let key = frame.object_key("frames/");
-        assert!(key.ends_with("_000007.h264"));
+    fn deserialize_too_short() {
+        let result = TimestampedFrame::deserialize(&[0; 10]);
+        assert!(result.is_err());
     }
 }
@@ -1,2 +1,7 @@
+pub mod api_types;
 pub mod config;
+pub mod db_types;
 pub mod frame;
+#[cfg(feature = "sqlite")]
+pub mod schema;
+pub mod signal;
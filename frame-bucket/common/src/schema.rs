@@ -0,0 +1,235 @@
+//! The per-robot SQLite schema, written by the consumer and read by the API.
+//!
+//! [`init_schema`] brings a database to the latest version; each connection
+//! that opens one calls it, so either process can create the file.
+
+use rusqlite::{Connection, Result as SqlResult, Transaction, TransactionBehavior};
+use tracing::{info, warn};
+
+/// Schema migrations in order; migration `n` (1-based) brings a database to
+/// `PRAGMA user_version = n`. Append new steps (an `ALTER TABLE ... ADD
+/// COLUMN`, a new index) rather than editing applied ones.
+const MIGRATIONS: &[fn(&Connection) -> SqlResult<()>] = &[initial_schema, clip_bounds_per_modality];
+
+/// Bring the database to the latest schema, applying the migrations past its
+/// `user_version` in one transaction. `BEGIN IMMEDIATE` makes a second
+/// connection opening the same file (shared mode) wait and then find the
+/// migrations already applied.
+pub fn init_schema(conn: &Connection) -> SqlResult<()> {
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    let version: usize = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        warn!(version, known = MIGRATIONS.len(), "database schema is newer than this build");
+    }
+    for (n, migrate) in MIGRATIONS.iter().enumerate().skip(version) {
+        migrate(&tx)?;
+        tx.pragma_update(None, "user_version", n + 1)?;
+        info!(version = n + 1, "migrated: applied schema migration");
+    }
+    tx.commit()
+}
+
+/// Migration 1: the schema as of versioning. Databases from before then have
+/// `user_version` 0 and some older shape of it, so this step only creates
+/// what's missing.
+fn initial_schema(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS segments (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            robot_id    TEXT    NOT NULL,
+            type        TEXT    NOT NULL CHECK(type IN ('active','idle')),
+            start_ms    INTEGER NOT NULL,
+            end_ms      INTEGER NOT NULL,
+            s3_key      TEXT    NOT NULL,
+            size_bytes  INTEGER,
+            frame_count INTEGER,
+            labels      TEXT    DEFAULT '[]',
+            metadata    TEXT    NOT NULL DEFAULT '{}',
+            archived_at INTEGER,
+            archive_key TEXT,
+            region      TEXT,
+            created_at  INTEGER DEFAULT (CAST(strftime('%s','now') AS INTEGER) * 1000)
+        );
+        CREATE INDEX IF NOT EXISTS idx_time
+            ON segments(robot_id, start_ms, end_ms);
+
+        CREATE TABLE IF NOT EXISTS collections (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            robot_id    TEXT    NOT NULL,
+            name        TEXT    NOT NULL,
+            description TEXT    DEFAULT '',
+            created_at  INTEGER NOT NULL,
+            updated_at  INTEGER NOT NULL
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_collections_name
+            ON collections(robot_id, name);
+
+        CREATE TABLE IF NOT EXISTS collection_clips (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            collection_id   INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+            robot_id        TEXT    NOT NULL,
+            modality        TEXT    NOT NULL DEFAULT 'camera',
+            clip_start_ms   INTEGER NOT NULL,
+            clip_end_ms     INTEGER NOT NULL,
+            segment_ids     TEXT    NOT NULL DEFAULT '[]',
+            manifest_s3_key TEXT,
+            created_at      INTEGER NOT NULL,
+            UNIQUE(collection_id, clip_start_ms, clip_end_ms)
+        );
+        CREATE INDEX IF NOT EXISTS idx_clips_collection
+            ON collection_clips(collection_id);",
+    )?;
+
+    // Columns added after the initial schema; CREATE TABLE IF NOT EXISTS won't add them.
+    ensure_column(conn, "segments", "metadata", "TEXT NOT NULL DEFAULT '{}'")?;
+    // Set when eviction removes the object from RustFS; `archive_key` stays NULL
+    // if it was deleted without an archive copy.
+    ensure_column(conn, "segments", "archived_at", "INTEGER")?;
+    ensure_column(conn, "segments", "archive_key", "TEXT")?;
+    // Motion region id (`recording.regions`); NULL for whole-frame recording.
+    ensure_column(conn, "segments", "region", "TEXT")?;
+    // How much happened in an active segment (see `RecordingState::Active`);
+    // NULL for idle segments and ones recorded before it existed.
+    ensure_column(conn, "segments", "motion_score", "REAL")?;
+    // When the row was written (ms). SQLite can't add a column with a
+    // non-constant default, so migrated rows are backfilled with `end_ms`,
+    // about when the recorder would have inserted them.
+    if ensure_column(conn, "segments", "created_at", "INTEGER")? {
+        conn.execute("UPDATE segments SET created_at = end_ms WHERE created_at IS NULL", [])?;
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_created ON segments(robot_id, created_at);")?;
+    ensure_collections_fts(conn)?;
+    Ok(())
+}
+
+/// Migration 2: clip bounds are unique per modality, so a collection can hold
+/// a camera and a depth clip over the same span. SQLite can't alter a table
+/// constraint, so the table is rebuilt with its rows (and ids) copied over.
+fn clip_bounds_per_modality(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE collection_clips_new (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            collection_id   INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+            robot_id        TEXT    NOT NULL,
+            modality        TEXT    NOT NULL DEFAULT 'camera',
+            clip_start_ms   INTEGER NOT NULL,
+            clip_end_ms     INTEGER NOT NULL,
+            segment_ids     TEXT    NOT NULL DEFAULT '[]',
+            manifest_s3_key TEXT,
+            created_at      INTEGER NOT NULL,
+            UNIQUE(collection_id, modality, clip_start_ms, clip_end_ms)
+        );
+        INSERT INTO collection_clips_new
+            (id, collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, manifest_s3_key, created_at)
+            SELECT id, collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, manifest_s3_key, created_at
+            FROM collection_clips;
+        DROP TABLE collection_clips;
+        ALTER TABLE collection_clips_new RENAME TO collection_clips;
+        CREATE INDEX idx_clips_collection ON collection_clips(collection_id);",
+    )
+}
+
+/// Full-text index over `collections(name, description)` for collection
+/// search, kept in sync by triggers. Databases created before it existed get
+/// the index built from their current rows.
+fn ensure_collections_fts(conn: &Connection) -> SqlResult<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'collections_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS collections_fts
+            USING fts5(name, description, content='collections', content_rowid='id');
+
+        CREATE TRIGGER IF NOT EXISTS collections_fts_insert AFTER INSERT ON collections BEGIN
+            INSERT INTO collections_fts(rowid, name, description) VALUES (new.id, new.name, new.description);
+        END;
+        CREATE TRIGGER IF NOT EXISTS collections_fts_delete AFTER DELETE ON collections BEGIN
+            INSERT INTO collections_fts(collections_fts, rowid, name, description)
+                VALUES ('delete', old.id, old.name, old.description);
+        END;
+        CREATE TRIGGER IF NOT EXISTS collections_fts_update AFTER UPDATE OF name, description ON collections BEGIN
+            INSERT INTO collections_fts(collections_fts, rowid, name, description)
+                VALUES ('delete', old.id, old.name, old.description);
+            INSERT INTO collections_fts(rowid, name, description) VALUES (new.id, new.name, new.description);
+        END;",
+    )?;
+    if !exists {
+        conn.execute("INSERT INTO collections_fts(collections_fts) VALUES ('rebuild')", [])?;
+        info!("migrated: built collections_fts index");
+    }
+    Ok(())
+}
+
+/// Add `column` to `table` if an older database was created without it.
+/// Returns whether it was added.
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> SqlResult<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+        info!(table, column, "migrated: added column");
+    }
+    Ok(!exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::params;
+
+    fn version(conn: &Connection) -> usize {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+    }
+
+    fn insert_clip(conn: &Connection, collection_id: i64, modality: &str) -> SqlResult<usize> {
+        conn.execute(
+            "INSERT INTO collection_clips
+             (collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, created_at)
+             VALUES (?1, 'reachy-001', ?2, 0, 1000, '[1]', 4)",
+            params![collection_id, modality],
+        )
+    }
+
+    #[test]
+    fn migrations_run_once_and_set_user_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(version(&conn), 0);
+        init_schema(&conn).unwrap();
+        assert_eq!(version(&conn), MIGRATIONS.len());
+
+        // At the latest version nothing is reapplied: a dropped index stays gone.
+        conn.execute_batch("DROP INDEX idx_created;").unwrap();
+        init_schema(&conn).unwrap();
+        let idx: i64 = conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'idx_created'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(idx, 0);
+    }
+
+    #[test]
+    fn clip_modality_migration_keeps_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        initial_schema(&conn).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        conn.execute(
+            "INSERT INTO collections (id, robot_id, name, created_at, updated_at) VALUES (1, 'reachy-001', 'grasps', 1, 1)",
+            [],
+        )
+        .unwrap();
+        insert_clip(&conn, 1, "camera").unwrap();
+        let clip = conn.last_insert_rowid();
+
+        init_schema(&conn).unwrap();
+        let kept: i64 = conn
+            .query_row("SELECT COUNT(*) FROM collection_clips WHERE id = ?1", params![clip], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kept, 1);
+        insert_clip(&conn, 1, "audio").unwrap();
+        assert!(insert_clip(&conn, 1, "camera").is_err());
+    }
+}
@@ -1,12 +1,30 @@
+use frame_bucket_common::config::DatabaseMode;
+use frame_bucket_common::schema::init_schema;
 use rusqlite::{Connection, Result as SqlResult, params};
 use std::path::Path;
 use std::sync::Mutex;
 use tracing::{debug, info};
 
+/// A completed active segment, as [`SegmentDb::insert_active`] records it.
+pub struct NewActiveSegment<'a> {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub s3_key: &'a str,
+    pub size_bytes: u64,
+    pub frame_count: u32,
+    pub motion_score: Option<f64>,
+    /// Motion region it was recorded for, `None` for whole-frame recording.
+    pub region: Option<&'a str>,
+}
+
 /// Per-robot SQLite database for segment metadata.
 ///
-/// One file per robot: `{db_dir}/{robot_id}.db`
+/// One file per robot: `{db_dir}/{robot_id}.db`, or a single shared
+/// `{db_dir}/frame-bucket.db` in [`DatabaseMode::Shared`]. Every row carries
+/// `robot_id`, so the schema is the same either way.
 /// Schema: a single `segments` table indexed by (robot_id, start_ms, end_ms).
+/// Opening it applies any schema migrations the file is missing, tracked in
+/// `PRAGMA user_version`.
 ///
 /// WAL mode is enabled so the consumer (writer) and API server (reader) can
 /// operate concurrently without blocking each other.
@@ -18,59 +36,18 @@ pub struct SegmentDb {
 impl SegmentDb {
     /// Open (or create) the SQLite database for a given robot.
     /// Creates `db_dir` if it does not exist.
-    pub fn open(db_dir: &Path, robot_id: &str) -> SqlResult<Self> {
+    pub fn open(db_dir: &Path, mode: DatabaseMode, robot_id: &str) -> SqlResult<Self> {
         std::fs::create_dir_all(db_dir)
             .map_err(|_e| rusqlite::Error::InvalidPath(db_dir.into()))?;
 
-        let db_path = db_dir.join(format!("{robot_id}.db"));
+        let db_path = mode.db_file(db_dir, robot_id);
         let conn = Connection::open(&db_path)?;
 
-        // Enable WAL for concurrent reader (API) + writer (consumer)
-        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
-
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS segments (
-                id          INTEGER PRIMARY KEY AUTOINCREMENT,
-                robot_id    TEXT    NOT NULL,
-                type        TEXT    NOT NULL CHECK(type IN ('active','idle')),
-                start_ms    INTEGER NOT NULL,
-                end_ms      INTEGER NOT NULL,
-                s3_key      TEXT    NOT NULL,
-                size_bytes  INTEGER,
-                frame_count INTEGER,
-                labels      TEXT    DEFAULT '[]'
-            );
-            CREATE INDEX IF NOT EXISTS idx_time
-                ON segments(robot_id, start_ms, end_ms);
-
-            CREATE TABLE IF NOT EXISTS collections (
-                id          INTEGER PRIMARY KEY AUTOINCREMENT,
-                robot_id    TEXT    NOT NULL,
-                name        TEXT    NOT NULL,
-                description TEXT    DEFAULT '',
-                created_at  INTEGER NOT NULL,
-                updated_at  INTEGER NOT NULL
-            );
-            CREATE UNIQUE INDEX IF NOT EXISTS idx_collections_name
-                ON collections(robot_id, name);
-
-            CREATE TABLE IF NOT EXISTS collection_clips (
-                id              INTEGER PRIMARY KEY AUTOINCREMENT,
-                collection_id   INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
-                robot_id        TEXT    NOT NULL,
-                modality        TEXT    NOT NULL DEFAULT 'camera',
-                clip_start_ms   INTEGER NOT NULL,
-                clip_end_ms     INTEGER NOT NULL,
-                segment_ids     TEXT    NOT NULL DEFAULT '[]',
-                manifest_s3_key TEXT,
-                created_at      INTEGER NOT NULL,
-                UNIQUE(collection_id, clip_start_ms, clip_end_ms)
-            );
-            CREATE INDEX IF NOT EXISTS idx_clips_collection
-                ON collection_clips(collection_id);
+        // Enable WAL for concurrent reader (API) + writer (consumer). Foreign keys
+        // are per-connection and must be on before anything relies on ON DELETE CASCADE.
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL; PRAGMA foreign_keys=ON;")?;
 
-            PRAGMA foreign_keys = ON;",
-        )?;
+        init_schema(&conn)?;
 
         info!(path = db_path.display().to_string(), robot_id, "SQLite database opened");
 
@@ -80,20 +57,45 @@ impl SegmentDb {
         })
     }
 
+    /// Open a private in-memory database with the same schema, for tests.
+    #[cfg(test)]
+    pub fn open_in_memory(robot_id: &str) -> SqlResult<Self> {
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+        init_schema(&conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            robot_id: robot_id.to_string(),
+        })
+    }
+
     /// Insert a completed active (MP4) segment. Returns the new row id.
-    pub fn insert_active(
-        &self,
-        start_ms: i64,
-        end_ms: i64,
-        s3_key: &str,
-        size_bytes: u64,
-        frame_count: u32,
-    ) -> SqlResult<i64> {
+    pub fn insert_active(&self, segment: &NewActiveSegment<'_>) -> SqlResult<i64> {
+        let &NewActiveSegment {
+            start_ms,
+            end_ms,
+            s3_key,
+            size_bytes,
+            frame_count,
+            motion_score,
+            region,
+        } = segment;
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key, size_bytes, frame_count)
-             VALUES (?1, 'active', ?2, ?3, ?4, ?5, ?6)",
-            params![self.robot_id, start_ms, end_ms, s3_key, size_bytes as i64, frame_count as i64],
+            "INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key, size_bytes, frame_count, motion_score,
+                                   region, created_at)
+             VALUES (?1, 'active', ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                self.robot_id,
+                start_ms,
+                end_ms,
+                s3_key,
+                size_bytes as i64,
+                frame_count as i64,
+                motion_score,
+                region,
+                chrono::Utc::now().timestamp_millis(),
+            ],
         )?;
         let id = conn.last_insert_rowid();
         debug!(id, start_ms, end_ms, s3_key, "inserted active segment");
@@ -107,16 +109,424 @@ impl SegmentDb {
         end_ms: i64,
         s3_key: &str,
         size_bytes: u64,
+        region: Option<&str>,
     ) -> SqlResult<i64> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key, size_bytes)
-             VALUES (?1, 'idle', ?2, ?3, ?4, ?5)",
-            params![self.robot_id, start_ms, end_ms, s3_key, size_bytes as i64],
+            "INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key, size_bytes, region, created_at)
+             VALUES (?1, 'idle', ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.robot_id,
+                start_ms,
+                end_ms,
+                s3_key,
+                size_bytes as i64,
+                region,
+                chrono::Utc::now().timestamp_millis(),
+            ],
         )?;
         let id = conn.last_insert_rowid();
         debug!(id, start_ms, end_ms, s3_key, "inserted idle segment");
         Ok(id)
     }
 
+    /// Record that eviction removed the object at `s3_key` from RustFS.
+    /// `archive_key` is where it now lives in AWS S3, or `None` if it was
+    /// deleted without a copy. Returns the number of rows updated: 0 for
+    /// objects with no segment row (e.g. raw debug frames).
+    pub fn mark_archived(
+        &self,
+        s3_key: &str,
+        archived_at_ms: i64,
+        archive_key: Option<&str>,
+    ) -> SqlResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE segments SET archived_at = ?1, archive_key = ?2
+             WHERE robot_id = ?3 AND s3_key = ?4",
+            params![archived_at_ms, archive_key, self.robot_id, s3_key],
+        )?;
+        debug!(s3_key, archive_key, updated, "marked segment archived");
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame_bucket_common::api_types::ClipResponse;
+    use frame_bucket_common::db_types::{encode_segment_ids, ClipRow, CollectionRow};
+
+    fn mem_db() -> SegmentDb {
+        SegmentDb::open_in_memory("reachy-001").unwrap()
+    }
+
+    fn insert_collection(conn: &Connection, name: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO collections (robot_id, name, description, created_at, updated_at)
+             VALUES ('reachy-001', ?1, 'desc', 1, 2)",
+            params![name],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn insert_clip(conn: &Connection, collection_id: i64, start_ms: i64, end_ms: i64, ids: &[i64]) -> SqlResult<i64> {
+        conn.execute(
+            "INSERT INTO collection_clips
+             (collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, created_at)
+             VALUES (?1, 'reachy-001', 'camera', ?2, ?3, ?4, 4)",
+            params![collection_id, start_ms, end_ms, encode_segment_ids(ids)],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn count(conn: &Connection, sql: &str, id: i64) -> i64 {
+        conn.query_row(sql, params![id], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn inserted_segments_get_schema_defaults() {
+        let db = mem_db();
+        let a = db
+            .insert_active(&NewActiveSegment {
+                start_ms: 1000,
+                end_ms: 2000,
+                s3_key: "a.mp4",
+                size_bytes: 10,
+                frame_count: 3,
+                motion_score: Some(41.5),
+                region: None,
+            })
+            .unwrap();
+        let b = db.insert_idle(2000, 3000, "b.jpg", 5, None).unwrap();
+
+        let conn = db.conn.lock().unwrap();
+        let (kind, frames, labels, metadata, motion): (String, Option<i64>, String, String, Option<f64>) = conn
+            .query_row(
+                "SELECT type, frame_count, labels, metadata, motion_score FROM segments WHERE id = ?1",
+                params![a],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!((kind.as_str(), frames, motion), ("active", Some(3), Some(41.5)));
+        assert_eq!((labels.as_str(), metadata.as_str()), ("[]", "{}"));
+
+        let (kind, frames): (String, Option<i64>) = conn
+            .query_row("SELECT type, frame_count FROM segments WHERE id = ?1", params![b], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((kind.as_str(), frames), ("idle", None));
+    }
+
+    #[test]
+    fn created_at_is_set_on_insert_and_backfilled_on_migration() {
+        let before = chrono::Utc::now().timestamp_millis();
+        let db = mem_db();
+        let a = db
+            .insert_active(&NewActiveSegment {
+                start_ms: 1000,
+                end_ms: 2000,
+                s3_key: "a.mp4",
+                size_bytes: 10,
+                frame_count: 3,
+                motion_score: None,
+                region: None,
+            })
+            .unwrap();
+        let conn = db.conn.lock().unwrap();
+        let created: i64 = conn
+            .query_row("SELECT created_at FROM segments WHERE id = ?1", params![a], |row| row.get(0))
+            .unwrap();
+        assert!(created >= before);
+
+        // Rows inserted without it get the column default.
+        conn.execute(
+            "INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key) VALUES ('reachy-001', 'idle', 0, 1, 'x')",
+            [],
+        )
+        .unwrap();
+        let defaulted: i64 = conn
+            .query_row("SELECT created_at FROM segments WHERE s3_key = 'x'", [], |row| row.get(0))
+            .unwrap();
+        assert!(defaulted / 1000 >= before / 1000);
+
+        let old = Connection::open_in_memory().unwrap();
+        old.execute_batch(
+            "CREATE TABLE segments (id INTEGER PRIMARY KEY AUTOINCREMENT, robot_id TEXT NOT NULL, type TEXT NOT NULL,
+                                    start_ms INTEGER NOT NULL, end_ms INTEGER NOT NULL, s3_key TEXT NOT NULL,
+                                    size_bytes INTEGER, frame_count INTEGER, labels TEXT DEFAULT '[]');
+             INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key) VALUES ('reachy-001', 'active', 5000, 9000, 'o');",
+        )
+        .unwrap();
+        init_schema(&old).unwrap();
+        let backfilled: i64 = old.query_row("SELECT created_at FROM segments", [], |row| row.get(0)).unwrap();
+        assert_eq!(backfilled, 9000);
+    }
+
+    #[test]
+    fn mark_archived_sets_archive_columns_by_key() {
+        let db = mem_db();
+        let a = db
+            .insert_active(&NewActiveSegment {
+                start_ms: 1000,
+                end_ms: 2000,
+                s3_key: "a.mp4",
+                size_bytes: 10,
+                frame_count: 3,
+                motion_score: None,
+                region: None,
+            })
+            .unwrap();
+        let b = db.insert_idle(2000, 3000, "b.jpg", 5, None).unwrap();
+
+        assert_eq!(db.mark_archived("a.mp4", 5000, Some("archive/a.mp4")).unwrap(), 1);
+        assert_eq!(db.mark_archived("b.jpg", 6000, None).unwrap(), 1);
+        assert_eq!(db.mark_archived("raw/000001.jpg", 7000, None).unwrap(), 0);
+
+        let conn = db.conn.lock().unwrap();
+        let archive = |id: i64| -> (Option<i64>, Option<String>) {
+            conn.query_row(
+                "SELECT archived_at, archive_key FROM segments WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+        };
+        assert_eq!(archive(a), (Some(5000), Some("archive/a.mp4".to_string())));
+        assert_eq!(archive(b), (Some(6000), None));
+    }
+
+    #[test]
+    fn clip_rows_roundtrip_through_schema_and_api_shape() {
+        let db = mem_db();
+        let a = db
+            .insert_active(&NewActiveSegment {
+                start_ms: 1000,
+                end_ms: 2000,
+                s3_key: "a.mp4",
+                size_bytes: 10,
+                frame_count: 3,
+                motion_score: None,
+                region: None,
+            })
+            .unwrap();
+        let b = db.insert_idle(2000, 3000, "b.jpg", 5, None).unwrap();
+
+        let conn = db.conn.lock().unwrap();
+        let collection_id = insert_collection(&conn, "grasps");
+        insert_clip(&conn, collection_id, 1000, 3000, &[a, b]).unwrap();
+
+        let collection = conn
+            .query_row(
+                &format!("SELECT {} FROM collections WHERE id = ?1", CollectionRow::COLUMNS),
+                params![collection_id],
+                CollectionRow::from_row,
+            )
+            .unwrap();
+        assert_eq!(collection.name, "grasps");
+        let collection_json = serde_json::to_value(collection.into_response(Some(1))).unwrap();
+        assert_eq!(collection_json["clip_count"], 1);
+
+        let clip = conn
+            .query_row(
+                &format!("SELECT {} FROM collection_clips WHERE collection_id = ?1", ClipRow::COLUMNS),
+                params![collection_id],
+                ClipRow::from_row,
+            )
+            .unwrap();
+        assert_eq!(clip.segment_ids(), vec![a, b]);
+
+        let api_json = serde_json::to_value(ClipResponse::from(clip)).unwrap();
+        assert_eq!(api_json["segment_ids"], serde_json::json!([a, b]));
+        assert_eq!(api_json["collection_id"], collection_id);
+    }
+
+    #[test]
+    fn update_collection_changes_only_given_fields() {
+        let db = mem_db();
+        let conn = db.conn.lock().unwrap();
+        let (grasps, kept) = (insert_collection(&conn, "grasps"), insert_collection(&conn, "kept"));
+        let update = |id, name, description| CollectionRow::update(&conn, "reachy-001", id, name, description, 3);
+
+        let row = update(grasps, Some("grasp"), None).unwrap().unwrap();
+        assert_eq!((row.name.as_str(), row.description.as_str()), ("grasp", "desc"));
+        assert_eq!(row.updated_at, 3);
+
+        let row = update(grasps, None, Some("fixed")).unwrap().unwrap();
+        assert_eq!((row.name.as_str(), row.description.as_str()), ("grasp", "fixed"));
+
+        assert!(update(999, Some("x"), None).unwrap().is_none());
+        let err = update(kept, Some("grasp"), None).unwrap_err();
+        assert!(err.to_string().contains("UNIQUE"), "{err}");
+    }
+
+    #[test]
+    fn collections_fts_follows_writes_and_is_built_for_old_dbs() {
+        let search = |conn: &Connection, q: &str| -> Vec<i64> {
+            conn.prepare("SELECT rowid FROM collections_fts WHERE collections_fts MATCH ?1 ORDER BY rowid")
+                .unwrap()
+                .query_map(params![q], |row| row.get(0))
+                .unwrap()
+                .collect::<SqlResult<_>>()
+                .unwrap()
+        };
+
+        let db = mem_db();
+        let conn = db.conn.lock().unwrap();
+        let grasps = insert_collection(&conn, "grasps");
+        insert_collection(&conn, "handoffs");
+        CollectionRow::update(&conn, "reachy-001", grasps, Some("pick attempts"), None, 3).unwrap();
+        assert_eq!(search(&conn, "pick"), [grasps]);
+        assert!(search(&conn, "grasps").is_empty());
+        conn.execute("DELETE FROM collections WHERE id = ?1", params![grasps]).unwrap();
+        assert!(search(&conn, "pick").is_empty());
+
+        // A database from before the index: rows already there get indexed.
+        conn.execute_batch(
+            "DROP TRIGGER collections_fts_insert;
+             DROP TRIGGER collections_fts_delete;
+             DROP TRIGGER collections_fts_update;
+             DROP TABLE collections_fts;
+             PRAGMA user_version = 0;",
+        )
+        .unwrap();
+        insert_collection(&conn, "old shelf");
+        init_schema(&conn).unwrap();
+        assert_eq!(search(&conn, "shelf").len(), 1);
+        assert_eq!(search(&conn, "desc").len(), 2);
+    }
+
+    #[test]
+    fn deleting_collection_cascades_to_its_clips() {
+        let db = mem_db();
+        let conn = db.conn.lock().unwrap();
+        let doomed = insert_collection(&conn, "doomed");
+        let kept = insert_collection(&conn, "kept");
+        insert_clip(&conn, doomed, 0, 1000, &[1]).unwrap();
+        insert_clip(&conn, doomed, 1000, 2000, &[2]).unwrap();
+        insert_clip(&conn, kept, 0, 1000, &[1]).unwrap();
+
+        conn.execute("DELETE FROM collections WHERE id = ?1", params![doomed]).unwrap();
+
+        let clips_sql = "SELECT COUNT(*) FROM collection_clips WHERE collection_id = ?1";
+        assert_eq!(count(&conn, clips_sql, doomed), 0);
+        assert_eq!(count(&conn, clips_sql, kept), 1);
+    }
+
+    #[test]
+    fn open_enables_foreign_keys() {
+        let dir = std::env::temp_dir().join(format!("frame-bucket-consumer-fk-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let db = SegmentDb::open(&dir, DatabaseMode::PerRobot, "reachy-001").unwrap();
+
+        let enabled: i64 = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(enabled, 1);
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shared_mode_keeps_robots_apart_in_one_file() {
+        let dir = std::env::temp_dir().join(format!("frame-bucket-consumer-shared-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let a = SegmentDb::open(&dir, DatabaseMode::Shared, "robot-a").unwrap();
+        let b = SegmentDb::open(&dir, DatabaseMode::Shared, "robot-b").unwrap();
+        a
+            .insert_active(&NewActiveSegment {
+                start_ms: 0,
+                end_ms: 1000,
+                s3_key: "a.mp4",
+                size_bytes: 1,
+                frame_count: 1,
+                motion_score: None,
+                region: None,
+            })
+            .unwrap();
+        b.insert_idle(0, 1000, "b.jpg", 1, None).unwrap();
+        b.insert_idle(1000, 2000, "b2.jpg", 1, None).unwrap();
+
+        assert!(dir.join(DatabaseMode::SHARED_FILE).exists());
+        assert!(!dir.join("robot-a.db").exists());
+        let counts: Vec<(String, i64)> = {
+            let conn = a.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT robot_id, COUNT(*) FROM segments GROUP BY robot_id ORDER BY robot_id")
+                .unwrap();
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+            rows.collect::<SqlResult<_>>().unwrap()
+        };
+        assert_eq!(counts, vec![("robot-a".to_string(), 1), ("robot-b".to_string(), 2)]);
+
+        drop((a, b));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clip_must_reference_existing_collection() {
+        let db = mem_db();
+        let conn = db.conn.lock().unwrap();
+        assert!(insert_clip(&conn, 42, 0, 1000, &[1]).is_err());
+    }
+
+    #[test]
+    fn uniqueness_constraints_reject_duplicates() {
+        let db = mem_db();
+        let conn = db.conn.lock().unwrap();
+        let id = insert_collection(&conn, "grasps");
+
+        let dup_name = conn.execute(
+            "INSERT INTO collections (robot_id, name, created_at, updated_at)
+             VALUES ('reachy-001', 'grasps', 1, 1)",
+            [],
+        );
+        assert!(dup_name.is_err());
+
+        insert_clip(&conn, id, 0, 1000, &[1]).unwrap();
+        assert!(insert_clip(&conn, id, 0, 1000, &[2]).is_err());
+        assert!(insert_clip(&conn, id, 0, 2000, &[2]).is_ok());
+
+        // Same bounds in another modality is a different clip.
+        conn.execute(
+            "INSERT INTO collection_clips
+             (collection_id, robot_id, modality, clip_start_ms, clip_end_ms, segment_ids, created_at)
+             VALUES (?1, 'reachy-001', 'depth', 0, 1000, '[3]', 4)",
+            params![id],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn init_schema_adds_missing_columns() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE segments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, robot_id TEXT NOT NULL,
+                type TEXT NOT NULL, start_ms INTEGER NOT NULL, end_ms INTEGER NOT NULL,
+                s3_key TEXT NOT NULL, size_bytes INTEGER, frame_count INTEGER,
+                labels TEXT DEFAULT '[]'
+            );
+            INSERT INTO segments (robot_id, type, start_ms, end_ms, s3_key)
+            VALUES ('reachy-001', 'idle', 0, 1, 'old.jpg');",
+        )
+        .unwrap();
+
+        init_schema(&conn).unwrap();
+
+        let (metadata, archived_at, region): (String, Option<i64>, Option<String>) = conn
+            .query_row("SELECT metadata, archived_at, region FROM segments", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(metadata, "{}");
+        assert_eq!(archived_at, None);
+        assert_eq!(region, None);
+    }
 }
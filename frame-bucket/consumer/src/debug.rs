@@ -0,0 +1,173 @@
+//! Localhost-only admin server for inspecting eviction state.
+//!
+//! `GET /debug/index[?sample=N]` dumps the in-memory object index (size,
+//! bytes, oldest/newest timestamps, the N oldest entries) alongside the
+//! baseline objects the eviction loop tracks outside the index.
+//! `GET /debug/dlq` reports dead-letter publishes (`kafka.dlq_topic`).
+
+use crate::dlq::{DlqSnapshot, DlqStats};
+use crate::eviction::BaselineStats;
+use crate::storage::{ObjectEntry, RustfsStorage};
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tracing::{error, info};
+
+const DEFAULT_SAMPLE: usize = 20;
+const MAX_SAMPLE: usize = 1000;
+
+#[derive(Clone)]
+struct AdminState {
+    storage: Arc<RustfsStorage>,
+    baseline: Arc<BaselineStats>,
+    dlq: Arc<DlqStats>,
+    dlq_topic: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexQuery {
+    sample: Option<usize>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct IndexSnapshot {
+    objects: usize,
+    total_bytes: u64,
+    oldest_ms: Option<i64>,
+    newest_ms: Option<i64>,
+    /// Oldest entries first, i.e. the next eviction candidates.
+    sample: Vec<IndexSample>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct IndexSample {
+    captured_at_ms: i64,
+    key: String,
+    size_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct BaselineSnapshot {
+    objects: usize,
+    bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct DebugIndexResponse {
+    /// Objects written (or indexed) by this process.
+    session: IndexSnapshot,
+    /// Objects already in the bucket at startup, not tracked in the index.
+    baseline: BaselineSnapshot,
+    total_objects: usize,
+    total_bytes: u64,
+}
+
+/// Serve the admin endpoints on `127.0.0.1:{port}` until the process exits.
+pub async fn serve(
+    port: u16,
+    storage: Arc<RustfsStorage>,
+    baseline: Arc<BaselineStats>,
+    dlq: Arc<DlqStats>,
+    dlq_topic: Option<String>,
+) {
+    let app = Router::new()
+        .route("/debug/index", get(debug_index))
+        .route("/debug/dlq", get(debug_dlq))
+        .with_state(AdminState { storage, baseline, dlq, dlq_topic });
+
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!(error = %e, %addr, "failed to bind consumer admin server");
+            return;
+        }
+    };
+    info!(%addr, "consumer admin server listening");
+    if let Err(e) = axum::serve(listener, app).await {
+        error!(error = %e, "consumer admin server stopped");
+    }
+}
+
+async fn debug_index(
+    State(state): State<AdminState>,
+    Query(query): Query<IndexQuery>,
+) -> Json<DebugIndexResponse> {
+    let sample_n = query.sample.unwrap_or(DEFAULT_SAMPLE).min(MAX_SAMPLE);
+    let session = {
+        let idx = state.storage.index.lock().await;
+        index_snapshot(&idx, sample_n)
+    };
+    let (baseline_objects, baseline_bytes) = state.baseline.get();
+
+    Json(DebugIndexResponse {
+        total_objects: session.objects + baseline_objects,
+        total_bytes: session.total_bytes + baseline_bytes,
+        session,
+        baseline: BaselineSnapshot {
+            objects: baseline_objects,
+            bytes: baseline_bytes,
+        },
+    })
+}
+
+async fn debug_dlq(State(state): State<AdminState>) -> Json<DlqSnapshot> {
+    Json(state.dlq.snapshot(state.dlq_topic.clone()))
+}
+
+fn index_snapshot(index: &BTreeMap<String, ObjectEntry>, sample_n: usize) -> IndexSnapshot {
+    let mut by_start: Vec<_> = index.iter().collect();
+    by_start.sort_by_key(|(_, entry)| entry.start_ms);
+    IndexSnapshot {
+        objects: index.len(),
+        total_bytes: index.values().map(|e| e.size_bytes).sum(),
+        oldest_ms: by_start.first().map(|(_, e)| e.start_ms),
+        newest_ms: by_start.last().map(|(_, e)| e.start_ms),
+        sample: by_start
+            .into_iter()
+            .take(sample_n)
+            .map(|(key, entry)| IndexSample {
+                captured_at_ms: entry.start_ms,
+                key: key.clone(),
+                size_bytes: entry.size_bytes,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start_ms: i64, size_bytes: u64) -> ObjectEntry {
+        ObjectEntry {
+            start_ms,
+            size_bytes,
+        }
+    }
+
+    #[test]
+    fn index_snapshot_reports_bounds_and_oldest_sample() {
+        let mut index = BTreeMap::new();
+        index.insert("c".to_string(), entry(1_000, 30));
+        index.insert("a".to_string(), entry(3_000, 10));
+        index.insert("b".to_string(), entry(2_000, 20));
+
+        let snap = index_snapshot(&index, 2);
+        assert_eq!(snap.objects, 3);
+        assert_eq!(snap.total_bytes, 60);
+        assert_eq!(snap.oldest_ms, Some(1_000));
+        assert_eq!(snap.newest_ms, Some(3_000));
+        let keys: Vec<&str> = snap.sample.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, ["c", "b"]);
+
+        let empty = index_snapshot(&BTreeMap::new(), 5);
+        assert_eq!(empty.objects, 0);
+        assert_eq!(empty.oldest_ms, None);
+        assert!(empty.sample.is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+//! Dead-letter topic for Kafka messages the consumer can't decode
+//! (`kafka.dlq_topic`).
+//!
+//! The raw payload and key are republished unchanged; why and where it
+//! failed travels in headers, so a malformed producer can be debugged from
+//! the exact bytes it sent.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use frame_bucket_common::config::KafkaConfig;
+use rdkafka::message::{BorrowedMessage, Header, Message, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use serde::Serialize;
+use tracing::warn;
+
+/// Header with the decode error.
+pub const HEADER_ERROR: &str = "dlq.error";
+/// Headers locating the original message.
+pub const HEADER_TOPIC: &str = "dlq.topic";
+pub const HEADER_PARTITION: &str = "dlq.partition";
+pub const HEADER_OFFSET: &str = "dlq.offset";
+
+/// How long a dead letter may wait for space in the producer queue.
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Dead-letter counters, shared with the admin server.
+#[derive(Debug, Default)]
+pub struct DlqStats {
+    /// Messages published to the dead-letter topic.
+    pub published: AtomicU64,
+    /// Messages that could not be published (and were dropped).
+    pub failed: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DlqSnapshot {
+    pub topic: Option<String>,
+    pub published: u64,
+    pub failed: u64,
+}
+
+impl DlqStats {
+    pub fn snapshot(&self, topic: Option<String>) -> DlqSnapshot {
+        DlqSnapshot {
+            topic,
+            published: self.published.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct DeadLetterQueue {
+    producer: FutureProducer,
+    topic: String,
+    stats: Arc<DlqStats>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(kafka: &KafkaConfig, topic: String, stats: Arc<DlqStats>) -> Result<Self, rdkafka::error::KafkaError> {
+        let mut client = ClientConfig::new();
+        client
+            .set("bootstrap.servers", &kafka.brokers)
+            .set("message.timeout.ms", "30000");
+        for (name, value) in kafka.security_properties() {
+            client.set(name, value);
+        }
+        let producer = client.create()?;
+        Ok(Self { producer, topic, stats })
+    }
+
+    /// Republish `msg` with `error` to the dead-letter topic. Failures are
+    /// logged and counted only.
+    pub async fn publish(&self, msg: &BorrowedMessage<'_>, error: &str) {
+        let headers = dlq_headers(error, msg.topic(), msg.partition(), msg.offset());
+        let mut record = FutureRecord::<[u8], [u8]>::to(&self.topic)
+            .payload(msg.payload().unwrap_or_default())
+            .headers(headers);
+        if let Some(key) = msg.key() {
+            record = record.key(key);
+        }
+        match self.producer.send(record, QUEUE_TIMEOUT).await {
+            Ok(_) => {
+                self.stats.published.fetch_add(1, Ordering::Relaxed);
+            }
+            Err((e, _)) => {
+                self.stats.failed.fetch_add(1, Ordering::Relaxed);
+                warn!(error = %e, topic = self.topic, offset = msg.offset(), "failed to publish dead letter");
+            }
+        }
+    }
+}
+
+fn dlq_headers(error: &str, topic: &str, partition: i32, offset: i64) -> OwnedHeaders {
+    let partition = partition.to_string();
+    let offset = offset.to_string();
+    OwnedHeaders::new()
+        .insert(Header { key: HEADER_ERROR, value: Some(error) })
+        .insert(Header { key: HEADER_TOPIC, value: Some(topic) })
+        .insert(Header { key: HEADER_PARTITION, value: Some(&partition) })
+        .insert(Header { key: HEADER_OFFSET, value: Some(&offset) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::message::Headers;
+
+    #[test]
+    fn headers_locate_the_original_message() {
+        let headers = dlq_headers("unexpected end of input", "camera.frames", 3, 1042);
+        let values: Vec<(&str, &[u8])> = headers
+            .iter()
+            .map(|h| (h.key, h.value.unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (HEADER_ERROR, b"unexpected end of input".as_slice()),
+                (HEADER_TOPIC, b"camera.frames".as_slice()),
+                (HEADER_PARTITION, b"3".as_slice()),
+                (HEADER_OFFSET, b"1042".as_slice()),
+            ]
+        );
+    }
+}
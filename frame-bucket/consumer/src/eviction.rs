@@ -1,12 +1,19 @@
+use aws_sdk_s3::error::ProvideErrorMetadata;
 use aws_sdk_s3::primitives::ByteStream;
 use aws_sdk_s3::types::{BucketLocationConstraint, CreateBucketConfiguration};
 use aws_types::region::Region;
-use frame_bucket_common::config::{AwsS3Config, EvictionConfig};
+use base64::Engine;
+use md5::{Digest, Md5};
+use futures_util::stream::{self, StreamExt};
+use frame_bucket_common::config::{AwsS3Config, DatabaseConfig, DatabaseMode, EvictionConfig, EvictionPolicy};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+use crate::db::SegmentDb;
 use crate::storage::RustfsStorage;
 
 /// Monitors local RustFS storage usage and evicts oldest objects to AWS S3.
@@ -15,8 +22,12 @@ pub async fn run_eviction_loop(
     storage: Arc<RustfsStorage>,
     eviction_config: &EvictionConfig,
     aws_config: &AwsS3Config,
+    database: &DatabaseConfig,
+    rustfs_prefix: &str,
     stats_path: PathBuf,
+    baseline_stats: Arc<BaselineStats>,
 ) {
+    let mut ledger = ArchiveLedger::new(Path::new(&database.path), database.mode, rustfs_prefix);
     let aws_s3_client = create_aws_s3_client(aws_config).await;
     ensure_aws_bucket(&aws_s3_client, aws_config).await;
     let interval = Duration::from_secs(eviction_config.check_interval_secs);
@@ -34,6 +45,10 @@ pub async fn run_eviction_loop(
     let fallback_target_bytes = fallback_threshold_bytes
         .saturating_sub(threshold_bytes.saturating_sub(target_bytes));
 
+    let mut trigger = EvictionTrigger::new(eviction_config);
+    let mut status_watch = StatusWatch::default();
+    let http_client = reqwest::Client::new();
+
     // Fallback state
     let mut fallback_mode = false;
     let mut fallback_entered_at: Option<Instant> = None;
@@ -43,27 +58,36 @@ pub async fn run_eviction_loop(
     let mut s3_upload_failures: u64 = 0;
     let mut last_successful_upload: Option<chrono::DateTime<chrono::Utc>> = None;
     let mut objects_deleted_without_backup: u64 = 0;
+    let mut would_evict = WouldEvict::default();
+    if eviction_config.dry_run {
+        warn!("eviction.dry_run is set: candidates are only logged, nothing is uploaded or deleted");
+    }
 
     // Always scan the bucket on startup to get the true baseline.
-    let (mut baseline_objects, mut baseline_bytes) = storage.bucket_stats().await;
+    let (mut baseline_objects, mut baseline_bytes) = storage.bucket_stats().await.unwrap_or_default();
+    let rescan_interval = Duration::from_secs(eviction_config.rescan_interval_secs);
+    let mut last_rescan = Instant::now();
     info!(
         objects = baseline_objects,
         total_gb = format!("{:.3}", baseline_bytes as f64 / 1_073_741_824.0),
         "scanned bucket for baseline storage stats"
     );
+    baseline_stats.publish(baseline_objects, baseline_bytes);
     write_health_file(
         &stats_path,
-        baseline_objects,
-        baseline_bytes,
-        eviction_config.threshold_gb,
-        eviction_config.fallback_threshold_gb,
-        consecutive_failures,
-        fallback_mode,
-        s3_upload_successes,
-        s3_upload_failures,
-        last_successful_upload,
-        objects_deleted_without_backup,
-        false,
+        &HealthReport {
+            objects: baseline_objects,
+            total_bytes: baseline_bytes,
+            config: eviction_config,
+            consecutive_failures,
+            fallback_mode,
+            s3_upload_successes,
+            s3_upload_failures,
+            last_successful_upload,
+            objects_deleted_without_backup,
+            is_evicting: false,
+            would_evict: &would_evict,
+        },
     );
 
     loop {
@@ -92,6 +116,20 @@ pub async fn run_eviction_loop(
             }
         }
 
+        // The session index only sees this process's writes and deletes.
+        // Objects put by others (the API's restores) only show up in a bucket
+        // listing, and the rescan drops indexed objects the API has deleted.
+        if !rescan_interval.is_zero() && last_rescan.elapsed() >= rescan_interval {
+            last_rescan = Instant::now();
+            if let Some((objects, bytes)) = storage.rescan().await {
+                let (session_objects, session_bytes) = storage.stats().await;
+                baseline_objects = objects.saturating_sub(session_objects);
+                baseline_bytes = bytes.saturating_sub(session_bytes);
+                baseline_stats.publish(baseline_objects, baseline_bytes);
+                debug!(baseline_objects, baseline_bytes, "rescanned bucket");
+            }
+        }
+
         // Current session objects (added since last restart).
         let (session_objects, session_bytes) = storage.stats().await;
 
@@ -117,27 +155,42 @@ pub async fn run_eviction_loop(
             "storage check"
         );
 
+        // Alert on RustFS status changes (edge-triggered, not every check).
+        let status = rustfs_status(usage_pct(total_bytes, eviction_config.threshold_gb));
+        if let Some(previous) = status_watch.observe(status) {
+            notify_status_change(
+                &http_client,
+                eviction_config.alert_webhook_url.as_deref(),
+                previous,
+                status,
+                total_bytes,
+                eviction_config.threshold_gb,
+            );
+        }
+
         // Persist health state to disk every check.
         write_health_file(
             &stats_path,
-            total_objects,
-            total_bytes,
-            eviction_config.threshold_gb,
-            eviction_config.fallback_threshold_gb,
-            consecutive_failures,
-            fallback_mode,
-            s3_upload_successes,
-            s3_upload_failures,
-            last_successful_upload,
-            objects_deleted_without_backup,
-            is_over_threshold,
+            &HealthReport {
+                objects: total_objects,
+                total_bytes,
+                config: eviction_config,
+                consecutive_failures,
+                fallback_mode,
+                s3_upload_successes,
+                s3_upload_failures,
+                last_successful_upload,
+                objects_deleted_without_backup,
+                is_evicting: is_over_threshold,
+                would_evict: &would_evict,
+            },
         );
 
         // Log stats at info level every 10 intervals (~5 min at 30s interval).
         {
             static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
             let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-            if count % 10 == 0 {
+            if count.is_multiple_of(10) {
                 info!(
                     objects = total_objects,
                     total_mb = format!("{:.1}", total_bytes as f64 / 1_048_576.0),
@@ -151,7 +204,15 @@ pub async fn run_eviction_loop(
             }
         }
 
-        if is_over_threshold {
+        // Fallback mode deletes as soon as it's over its threshold; normal mode
+        // goes through the margin/cooldown hysteresis.
+        let should_evict = if fallback_mode {
+            is_over_threshold
+        } else {
+            trigger.should_evict(total_bytes, Instant::now())
+        };
+
+        if should_evict {
             if fallback_mode {
                 // ── FALLBACK: delete locally without S3 backup ──
                 warn!(
@@ -163,10 +224,14 @@ pub async fn run_eviction_loop(
 
                 match fallback_evict_batch(
                     &storage,
+                    &mut ledger,
                     eviction_config,
-                    fallback_target_bytes,
-                    &mut baseline_bytes,
-                    &mut baseline_objects,
+                    BatchUsage {
+                        target_bytes: fallback_target_bytes,
+                        baseline_bytes: &mut baseline_bytes,
+                        baseline_objects: &mut baseline_objects,
+                        would_evict: &mut would_evict,
+                    },
                     &mut objects_deleted_without_backup,
                 )
                 .await
@@ -184,17 +249,19 @@ pub async fn run_eviction_loop(
                         );
                         write_health_file(
                             &stats_path,
-                            new_total_objects,
-                            new_total_bytes,
-                            eviction_config.threshold_gb,
-                            eviction_config.fallback_threshold_gb,
-                            consecutive_failures,
-                            fallback_mode,
-                            s3_upload_successes,
-                            s3_upload_failures,
-                            last_successful_upload,
-                            objects_deleted_without_backup,
-                            new_total_bytes > fallback_threshold_bytes,
+                            &HealthReport {
+                                objects: new_total_objects,
+                                total_bytes: new_total_bytes,
+                                config: eviction_config,
+                                consecutive_failures,
+                                fallback_mode,
+                                s3_upload_successes,
+                                s3_upload_failures,
+                                last_successful_upload,
+                                objects_deleted_without_backup,
+                                is_evicting: new_total_bytes > fallback_threshold_bytes,
+                                would_evict: &would_evict,
+                            },
                         );
                     }
                     Err(e) => {
@@ -206,18 +273,23 @@ pub async fn run_eviction_loop(
                 info!(
                     total_gb = format!("{:.3}", total_gb),
                     threshold_gb = format!("{:.1}", eviction_config.threshold_gb),
+                    target_gb = format!("{:.1}", eviction_config.target_gb),
                     objects = total_objects,
-                    "storage exceeds threshold, starting eviction"
+                    "storage over eviction trigger, evicting toward target"
                 );
 
                 match evict_batch(
                     &storage,
+                    &mut ledger,
                     &aws_s3_client,
                     aws_config,
                     eviction_config,
-                    target_bytes,
-                    &mut baseline_bytes,
-                    &mut baseline_objects,
+                    BatchUsage {
+                        target_bytes,
+                        baseline_bytes: &mut baseline_bytes,
+                        baseline_objects: &mut baseline_objects,
+                        would_evict: &mut would_evict,
+                    },
                 )
                 .await
                 {
@@ -231,6 +303,7 @@ pub async fn run_eviction_loop(
                         let (so, sb) = storage.stats().await;
                         let new_total_objects = baseline_objects + so;
                         let new_total_bytes = baseline_bytes + sb;
+                        trigger.observe(new_total_bytes, Instant::now());
                         info!(
                             evicted = count,
                             remaining_objects = new_total_objects,
@@ -240,17 +313,19 @@ pub async fn run_eviction_loop(
                         );
                         write_health_file(
                             &stats_path,
-                            new_total_objects,
-                            new_total_bytes,
-                            eviction_config.threshold_gb,
-                            eviction_config.fallback_threshold_gb,
-                            consecutive_failures,
-                            fallback_mode,
-                            s3_upload_successes,
-                            s3_upload_failures,
-                            last_successful_upload,
-                            objects_deleted_without_backup,
-                            new_total_bytes > threshold_bytes,
+                            &HealthReport {
+                                objects: new_total_objects,
+                                total_bytes: new_total_bytes,
+                                config: eviction_config,
+                                consecutive_failures,
+                                fallback_mode,
+                                s3_upload_successes,
+                                s3_upload_failures,
+                                last_successful_upload,
+                                objects_deleted_without_backup,
+                                is_evicting: new_total_bytes > threshold_bytes,
+                                would_evict: &would_evict,
+                            },
                         );
                     }
                     Err(e) => {
@@ -277,17 +352,168 @@ pub async fn run_eviction_loop(
                     }
                 }
             }
+            baseline_stats.publish(baseline_objects, baseline_bytes);
         }
     }
 }
 
-/// Write the extended health/stats JSON file to disk.
-fn write_health_file(
-    path: &Path,
+/// Objects that were already in the bucket at startup (not in the session
+/// index), as tracked by the eviction loop. Shared with the admin endpoint;
+/// updated at startup, on each rescan and after each eviction batch.
+#[derive(Default)]
+pub struct BaselineStats {
+    objects: AtomicUsize,
+    bytes: AtomicU64,
+}
+
+impl BaselineStats {
+    fn publish(&self, objects: usize, bytes: u64) {
+        self.objects.store(objects, Ordering::Relaxed);
+        self.bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// (objects, bytes)
+    pub fn get(&self) -> (usize, u64) {
+        (self.objects.load(Ordering::Relaxed), self.bytes.load(Ordering::Relaxed))
+    }
+}
+
+/// Hysteresis for normal-mode eviction. A run starts once usage exceeds
+/// `threshold + margin` and any cooldown since the previous run has elapsed,
+/// then continues across checks until usage drops below target. Without it,
+/// usage hovering at the threshold re-triggers a small batch on every check.
+struct EvictionTrigger {
+    start_bytes: u64,
+    target_bytes: u64,
+    cooldown: Duration,
+    draining: bool,
+    drained_at: Option<Instant>,
+}
+
+impl EvictionTrigger {
+    fn new(config: &EvictionConfig) -> Self {
+        let gb = 1_073_741_824.0;
+        Self {
+            start_bytes: ((config.threshold_gb + config.threshold_margin_gb.max(0.0)) * gb) as u64,
+            target_bytes: (config.target_gb * gb) as u64,
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            draining: false,
+            drained_at: None,
+        }
+    }
+
+    /// Whether to run an eviction batch at this check.
+    fn should_evict(&mut self, total_bytes: u64, now: Instant) -> bool {
+        self.observe(total_bytes, now);
+        if self.draining {
+            return true;
+        }
+        if total_bytes <= self.start_bytes {
+            return false;
+        }
+        if let Some(drained) = self.drained_at {
+            if now.duration_since(drained) < self.cooldown {
+                debug!(
+                    remaining_secs = (self.cooldown - now.duration_since(drained)).as_secs(),
+                    "over eviction trigger but in cooldown"
+                );
+                return false;
+            }
+        }
+        self.draining = true;
+        true
+    }
+
+    /// Record current usage; ends the run once usage is below target.
+    fn observe(&mut self, total_bytes: u64, now: Instant) {
+        if self.draining && total_bytes < self.target_bytes {
+            self.draining = false;
+            self.drained_at = Some(now);
+        }
+    }
+}
+
+/// Writes eviction results back to the robots' segment DBs (`archived_at`,
+/// `archive_key`), so the API can tell hot segments from archived ones.
+/// DBs are opened on first use and kept open.
+struct ArchiveLedger {
+    db_dir: PathBuf,
+    mode: DatabaseMode,
+    rustfs_prefix: String,
+    dbs: HashMap<String, SegmentDb>,
+}
+
+impl ArchiveLedger {
+    fn new(db_dir: &Path, mode: DatabaseMode, rustfs_prefix: &str) -> Self {
+        Self {
+            db_dir: db_dir.to_path_buf(),
+            mode,
+            rustfs_prefix: rustfs_prefix.to_string(),
+            dbs: HashMap::new(),
+        }
+    }
+
+    /// Mark the segment stored at `key` as evicted, archived to `archive_key`
+    /// (`None`: deleted without a copy). Objects whose robot DB can't be found
+    /// are logged and skipped. Returns the number of segment rows updated.
+    fn record(&mut self, key: &str, archive_key: Option<&str>) -> usize {
+        let Some(robot_id) = robot_id_from_key(&self.rustfs_prefix, key) else {
+            warn!(key, "evicted object has no robot_id in its key, not recording archival");
+            return 0;
+        };
+        let Some(db) = self.db_for(robot_id) else {
+            return 0;
+        };
+        let archived_at_ms = chrono::Utc::now().timestamp_millis();
+        match db.mark_archived(key, archived_at_ms, archive_key) {
+            Ok(0) => {
+                debug!(key, robot_id, "no segment row for evicted object");
+                0
+            }
+            Ok(n) => n,
+            Err(e) => {
+                warn!(error = %e, key, robot_id, "failed to record archival in segment DB");
+                0
+            }
+        }
+    }
+
+    fn db_for(&mut self, robot_id: &str) -> Option<&SegmentDb> {
+        if !self.dbs.contains_key(robot_id) {
+            // Never create a DB here: an unknown robot means there are no rows to update.
+            let path = self.mode.db_file(&self.db_dir, robot_id);
+            if !path.exists() {
+                warn!(robot_id, path = %path.display(), "no segment DB for evicted object's robot, skipping");
+                return None;
+            }
+            match SegmentDb::open(&self.db_dir, self.mode, robot_id) {
+                Ok(db) => {
+                    self.dbs.insert(robot_id.to_string(), db);
+                }
+                Err(e) => {
+                    warn!(error = %e, robot_id, "failed to open segment DB, skipping archival record");
+                    return None;
+                }
+            }
+        }
+        self.dbs.get(robot_id)
+    }
+}
+
+/// Robot id of an object key laid out as `{prefix}{robot_id}/{modality}/...`.
+fn robot_id_from_key<'a>(prefix: &str, key: &'a str) -> Option<&'a str> {
+    let rest = key.strip_prefix(prefix).unwrap_or(key);
+    match rest.split_once('/') {
+        Some((robot_id, _)) if !robot_id.is_empty() => Some(robot_id),
+        _ => None,
+    }
+}
+
+/// What the health/stats file reports.
+struct HealthReport<'a> {
     objects: usize,
     total_bytes: u64,
-    threshold_gb: f64,
-    fallback_threshold_gb: f64,
+    config: &'a EvictionConfig,
     consecutive_failures: u32,
     fallback_mode: bool,
     s3_upload_successes: u64,
@@ -295,15 +521,30 @@ fn write_health_file(
     last_successful_upload: Option<chrono::DateTime<chrono::Utc>>,
     objects_deleted_without_backup: u64,
     is_evicting: bool,
-) {
+    /// Only reported with `eviction.dry_run`.
+    would_evict: &'a WouldEvict,
+}
+
+/// Write the extended health/stats JSON file to disk.
+fn write_health_file(path: &Path, report: &HealthReport<'_>) {
+    let &HealthReport {
+        objects,
+        total_bytes,
+        config,
+        consecutive_failures,
+        fallback_mode,
+        s3_upload_successes,
+        s3_upload_failures,
+        last_successful_upload,
+        objects_deleted_without_backup,
+        is_evicting,
+        would_evict,
+    } = report;
+    let (threshold_gb, fallback_threshold_gb) = (config.threshold_gb, config.fallback_threshold_gb);
+    let would_evict = config.dry_run.then_some(would_evict);
     let total_mb = total_bytes as f64 / 1_048_576.0;
     let total_gb = total_bytes as f64 / 1_073_741_824.0;
-    let threshold_bytes = threshold_gb * 1_073_741_824.0;
-    let usage_pct = if threshold_bytes > 0.0 {
-        (total_bytes as f64 / threshold_bytes) * 100.0
-    } else {
-        0.0
-    };
+    let usage_pct = usage_pct(total_bytes, threshold_gb);
 
     // Derive status strings
     let eviction_state = if fallback_mode {
@@ -322,13 +563,7 @@ fn write_health_file(
         "healthy"
     };
 
-    let rustfs_status = if usage_pct > 100.0 {
-        "critical"
-    } else if usage_pct > 80.0 {
-        "pressure"
-    } else {
-        "healthy"
-    };
+    let rustfs_status = rustfs_status(usage_pct);
 
     let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
 
@@ -353,7 +588,10 @@ fn write_health_file(
             "state": eviction_state,
             "fallback_mode": fallback_mode,
             "consecutive_failures": consecutive_failures,
-            "objects_deleted_without_backup": objects_deleted_without_backup
+            "objects_deleted_without_backup": objects_deleted_without_backup,
+            "dry_run": would_evict.is_some(),
+            "would_evict_objects": would_evict.map(|w| w.objects),
+            "would_evict_bytes": would_evict.map(|w| w.bytes)
         },
         "s3": {
             "upload_successes": s3_upload_successes,
@@ -374,89 +612,220 @@ fn write_health_file(
     }
 }
 
+/// Storage usage as a percentage of the eviction threshold.
+fn usage_pct(total_bytes: u64, threshold_gb: f64) -> f64 {
+    let threshold_bytes = threshold_gb * 1_073_741_824.0;
+    if threshold_bytes > 0.0 {
+        (total_bytes as f64 / threshold_bytes) * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// RustFS status reported in the health file: `healthy` up to 80% of the
+/// threshold, `pressure` up to 100%, `critical` beyond.
+fn rustfs_status(usage_pct: f64) -> &'static str {
+    if usage_pct > 100.0 {
+        "critical"
+    } else if usage_pct > 80.0 {
+        "pressure"
+    } else {
+        "healthy"
+    }
+}
+
+/// Tracks the RustFS status across checks so alerts fire only on transitions.
+#[derive(Default)]
+struct StatusWatch {
+    last: Option<&'static str>,
+}
+
+impl StatusWatch {
+    /// Record `status`; returns the previous status if it changed. The first
+    /// observation counts as a change from `healthy`, so starting up already
+    /// under pressure still alerts.
+    fn observe(&mut self, status: &'static str) -> Option<&'static str> {
+        let previous = self.last.replace(status).unwrap_or("healthy");
+        (previous != status).then_some(previous)
+    }
+}
+
+/// Log a RustFS status transition and, if configured, POST it to the alert
+/// webhook in the background so a slow endpoint can't stall eviction.
+fn notify_status_change(
+    client: &reqwest::Client,
+    webhook_url: Option<&str>,
+    from: &'static str,
+    to: &'static str,
+    total_bytes: u64,
+    threshold_gb: f64,
+) {
+    let total_gb = total_bytes as f64 / 1_073_741_824.0;
+    let usage_pct = usage_pct(total_bytes, threshold_gb);
+    if to == "healthy" {
+        info!(from, to, total_gb = format!("{:.3}", total_gb), "RustFS storage status recovered");
+    } else {
+        warn!(from, to, total_gb = format!("{:.3}", total_gb), "RustFS storage status changed");
+    }
+
+    let Some(url) = webhook_url else {
+        return;
+    };
+    let body = serde_json::json!({
+        "event": "rustfs_status_changed",
+        "from": from,
+        "to": to,
+        "total_bytes": total_bytes,
+        "threshold_gb": threshold_gb,
+        "usage_pct": (usage_pct * 10.0).round() / 10.0,
+        "at": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    });
+    let request = client.post(url).timeout(Duration::from_secs(10)).json(&body);
+    tokio::spawn(async move {
+        match request.send().await {
+            Ok(resp) if !resp.status().is_success() => {
+                warn!(status = %resp.status(), "alert webhook returned an error status");
+            }
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "failed to send alert webhook"),
+        }
+    });
+}
+
+/// The next `batch_size` objects to evict, in `eviction.policy` order.
+async fn eviction_candidates(storage: &RustfsStorage, config: &EvictionConfig) -> Vec<(String, u64, i64)> {
+    match config.policy {
+        EvictionPolicy::Oldest => storage.list_oldest_from_bucket(config.batch_size).await,
+        EvictionPolicy::IdleFirst => storage.list_idle_first_from_bucket(config.batch_size).await,
+    }
+}
+
+/// What the latest dry-run batch would have evicted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct WouldEvict {
+    objects: u64,
+    bytes: u64,
+}
+
+/// The leading `entries` that eviction would remove to bring `total_bytes`
+/// below `target_bytes`, as it stops after the object that gets there.
+fn would_evict_entries(entries: &[(String, u64, i64)], total_bytes: u64, target_bytes: u64) -> &[(String, u64, i64)] {
+    let mut remaining = total_bytes;
+    for (i, (_, size, _)) in entries.iter().enumerate() {
+        remaining = remaining.saturating_sub(*size);
+        if remaining < target_bytes {
+            return &entries[..=i];
+        }
+    }
+    entries
+}
+
+/// Dry run: log the objects this batch would evict and record their totals.
+async fn log_would_evict(
+    storage: &RustfsStorage,
+    entries: &[(String, u64, i64)],
+    target_bytes: u64,
+    baseline_bytes: u64,
+    would_evict: &mut WouldEvict,
+) {
+    let (_, session_bytes) = storage.stats().await;
+    let batch = would_evict_entries(entries, baseline_bytes + session_bytes, target_bytes);
+    for (key, size, _) in batch {
+        info!(key, size, "DRY RUN: would evict");
+    }
+    *would_evict = WouldEvict {
+        objects: batch.len() as u64,
+        bytes: batch.iter().map(|(_, size, _)| size).sum(),
+    };
+    info!(
+        objects = would_evict.objects,
+        would_evict_gb = format!("{:.3}", would_evict.bytes as f64 / 1_073_741_824.0),
+        "DRY RUN: nothing uploaded or deleted"
+    );
+}
+
+/// The bucket usage an eviction batch works toward and keeps current.
+struct BatchUsage<'a> {
+    /// The batch stops once total usage is below this.
+    target_bytes: u64,
+    /// Usage of the objects the session index doesn't track (those already in
+    /// the bucket at startup or the last rescan).
+    baseline_bytes: &'a mut u64,
+    baseline_objects: &'a mut usize,
+    /// What the batch would evict, recorded instead with `eviction.dry_run`.
+    would_evict: &'a mut WouldEvict,
+}
+
 /// Evict a batch of objects: upload to S3, then delete from RustFS.
 async fn evict_batch(
     storage: &RustfsStorage,
+    ledger: &mut ArchiveLedger,
     aws_client: &aws_sdk_s3::Client,
     aws_config: &AwsS3Config,
     eviction_config: &EvictionConfig,
-    target_bytes: u64,
-    baseline_bytes: &mut u64,
-    baseline_objects: &mut usize,
+    usage: BatchUsage<'_>,
 ) -> Result<usize, EvictionError> {
+    let BatchUsage {
+        target_bytes,
+        baseline_bytes,
+        baseline_objects,
+        would_evict,
+    } = usage;
     // Always list from the bucket to find the truly oldest objects,
     // regardless of whether they were added this session or before a restart.
-    let entries = storage
-        .list_oldest_from_bucket(eviction_config.batch_size)
-        .await;
+    let entries = eviction_candidates(storage, eviction_config).await;
 
     if entries.is_empty() {
         debug!("no objects to evict");
         return Ok(0);
     }
+    if eviction_config.dry_run {
+        log_would_evict(storage, &entries, target_bytes, *baseline_bytes, would_evict).await;
+        return Ok(0);
+    }
 
-    let mut evicted = 0;
-
-    for (key, size, ts) in &entries {
-        // Check if this object exists in the current session's in-memory index.
-        let in_session_index = {
-            let idx = storage.index.lock().await;
-            idx.contains_key(ts)
-        };
-
-        // Download from RustFS
-        let data = storage
-            .get_object(key)
-            .await
-            .map_err(|e| EvictionError::Download(e.to_string()))?;
+    // Download and upload up to `upload_concurrency` objects at once; each
+    // object is deleted from RustFS as its upload completes.
+    let mut archived = stream::iter(entries)
+        .map(|(key, size, _)| async move {
+            let result = archive_object(storage, aws_client, aws_config, &key, size).await;
+            (key, size, result)
+        })
+        .buffer_unordered(eviction_config.upload_concurrency.max(1));
 
-        // Upload to AWS S3 — mirror RustFS path under the archive prefix
-        let aws_key = format!("{}{}", aws_config.prefix, key);
+    let mut evicted = 0;
 
-        let content_type = if key.ends_with(".mp4") {
-            "video/mp4"
-        } else {
-            "image/jpeg"
+    while let Some((key, size, result)) = archived.next().await {
+        let (aws_key, in_session_index) = match result {
+            Ok(archived) => archived,
+            Err(e) => {
+                error!(error = %e, key, "failed to archive to AWS S3, keeping in RustFS");
+                // Uploads still in flight are dropped; their objects stay in
+                // RustFS and are picked again by the next batch.
+                return Err(e);
+            }
         };
 
-        let put_result = aws_client
-            .put_object()
-            .bucket(&aws_config.bucket)
-            .key(&aws_key)
-            .content_type(content_type)
-            .body(ByteStream::from(data))
-            .send()
-            .await;
-
-        match put_result {
-            Ok(resp) => {
-                let etag = resp.e_tag().unwrap_or("none");
-                debug!(aws_key, etag, "uploaded to AWS S3, deleting from RustFS");
-
-                // Delete from RustFS (also removes from in-memory index if present)
-                if let Err(e) = storage.delete_object(key, *ts).await {
-                    warn!(error = %e, key, "failed to delete from RustFS after S3 upload");
-                }
-
-                // Only adjust baseline for pre-existing objects not in the session index.
-                // Session objects are already tracked by the index and removed by delete_object.
-                if !in_session_index {
-                    *baseline_bytes = baseline_bytes.saturating_sub(*size);
-                    *baseline_objects = baseline_objects.saturating_sub(1);
-                }
-
-                evicted += 1;
+        // Delete from RustFS (also removes from in-memory index if present).
+        // Only a successful delete moves the segment out of the hot tier.
+        match storage.delete_object(&key).await {
+            Ok(()) => {
+                ledger.record(&key, Some(&aws_key));
             }
             Err(e) => {
-                error!(
-                    error = %e,
-                    key,
-                    "failed to upload to AWS S3, keeping in RustFS"
-                );
-                return Err(EvictionError::Upload(e.to_string()));
+                warn!(error = %e, key, "failed to delete from RustFS after S3 upload");
             }
         }
 
+        // Only adjust baseline for pre-existing objects not in the session index.
+        // Session objects are already tracked by the index and removed by delete_object.
+        if !in_session_index {
+            *baseline_bytes = baseline_bytes.saturating_sub(size);
+            *baseline_objects = baseline_objects.saturating_sub(1);
+        }
+
+        evicted += 1;
+
         // Check if we've brought usage below target
         let (_, session_bytes) = storage.stats().await;
         let current_total = *baseline_bytes + session_bytes;
@@ -473,39 +842,111 @@ async fn evict_batch(
     Ok(evicted)
 }
 
+/// Copy one object from RustFS to AWS S3, mirroring its key under the archive
+/// prefix. Returns the S3 key and whether the object is in this session's
+/// in-memory index (checked before it's deleted).
+///
+/// The copy is verified before the caller deletes anything: the download must
+/// be the listed `size`, and the upload carries `Content-MD5`, so S3 rejects a
+/// body that was corrupted on the way. ETags aren't compared, since they
+/// aren't the MD5 in KMS-encrypted buckets.
+async fn archive_object(
+    storage: &RustfsStorage,
+    aws_client: &aws_sdk_s3::Client,
+    aws_config: &AwsS3Config,
+    key: &str,
+    size: u64,
+) -> Result<(String, bool), EvictionError> {
+    // Check if this object exists in the current session's in-memory index.
+    let in_session_index = {
+        let idx = storage.index.lock().await;
+        idx.contains_key(key)
+    };
+
+    // Download from RustFS
+    let data = storage
+        .get_object(key)
+        .await
+        .map_err(|e| EvictionError::Download(e.to_string()))?;
+    if data.len() as u64 != size {
+        return Err(EvictionError::Integrity(format!(
+            "{key}: downloaded {} bytes, listed as {size}",
+            data.len()
+        )));
+    }
+    let md5 = content_md5(&data);
+
+    // Upload to AWS S3 — mirror RustFS path under the archive prefix
+    let aws_key = format!("{}{}", aws_config.prefix, key);
+
+    let content_type = if key.ends_with(".mp4") {
+        "video/mp4"
+    } else if key.ends_with(".webm") {
+        "video/webm"
+    } else {
+        "image/jpeg"
+    };
+
+    let resp = aws_client
+        .put_object()
+        .bucket(&aws_config.bucket)
+        .key(&aws_key)
+        .content_type(content_type)
+        .content_md5(md5)
+        .body(ByteStream::from(data))
+        .send()
+        .await
+        .map_err(|e| match e.code() {
+            Some("BadDigest") => EvictionError::Integrity(format!("{aws_key}: S3 received a corrupted body")),
+            _ => EvictionError::Upload(e.to_string()),
+        })?;
+
+    let etag = resp.e_tag().unwrap_or("none");
+    debug!(aws_key, etag, "uploaded to AWS S3, deleting from RustFS");
+    Ok((aws_key, in_session_index))
+}
+
 /// Fallback eviction: delete from RustFS without uploading to S3.
 /// Used when S3 is unreachable to prevent local disk exhaustion.
 async fn fallback_evict_batch(
     storage: &RustfsStorage,
+    ledger: &mut ArchiveLedger,
     eviction_config: &EvictionConfig,
-    target_bytes: u64,
-    baseline_bytes: &mut u64,
-    baseline_objects: &mut usize,
+    usage: BatchUsage<'_>,
     objects_deleted_without_backup: &mut u64,
 ) -> Result<usize, EvictionError> {
-    let entries = storage
-        .list_oldest_from_bucket(eviction_config.batch_size)
-        .await;
+    let BatchUsage {
+        target_bytes,
+        baseline_bytes,
+        baseline_objects,
+        would_evict,
+    } = usage;
+    let entries = eviction_candidates(storage, eviction_config).await;
 
     if entries.is_empty() {
         debug!("no objects to evict in fallback mode");
         return Ok(0);
     }
+    if eviction_config.dry_run {
+        log_would_evict(storage, &entries, target_bytes, *baseline_bytes, would_evict).await;
+        return Ok(0);
+    }
 
     let mut evicted = 0;
 
-    for (key, size, ts) in &entries {
+    for (key, size, _) in &entries {
         let in_session_index = {
             let idx = storage.index.lock().await;
-            idx.contains_key(ts)
+            idx.contains_key(key)
         };
 
         warn!(key, size, "FALLBACK: deleting from RustFS WITHOUT S3 backup");
 
-        if let Err(e) = storage.delete_object(key, *ts).await {
+        if let Err(e) = storage.delete_object(key).await {
             warn!(error = %e, key, "failed to delete from RustFS in fallback mode");
             continue;
         }
+        ledger.record(key, None);
 
         if !in_session_index {
             *baseline_bytes = baseline_bytes.saturating_sub(*size);
@@ -614,4 +1055,135 @@ pub enum EvictionError {
     Download(String),
     #[error("failed to upload to AWS S3: {0}")]
     Upload(String),
+    #[error("archive copy failed verification: {0}")]
+    Integrity(String),
+}
+
+/// Base64 MD5 of `data`, as sent in the `Content-MD5` header.
+fn content_md5(data: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(Md5::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::NewActiveSegment;
+
+    const GB: u64 = 1_073_741_824;
+
+    fn config(margin_gb: f64, cooldown_secs: u64) -> EvictionConfig {
+        EvictionConfig {
+            check_interval_secs: 30,
+            threshold_gb: 50.0,
+            target_gb: 40.0,
+            batch_size: 50,
+            fallback_after_failures: 5,
+            fallback_retry_secs: 600,
+            fallback_threshold_gb: 0.0,
+            threshold_margin_gb: margin_gb,
+            cooldown_secs,
+            rescan_interval_secs: 300,
+            alert_webhook_url: None,
+            policy: EvictionPolicy::Oldest,
+            upload_concurrency: 4,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn usage_hovering_at_threshold_does_not_churn() {
+        let mut trigger = EvictionTrigger::new(&config(2.0, 300));
+        let t0 = Instant::now();
+        let at = |secs: u64| t0 + Duration::from_secs(secs);
+
+        // Just over the threshold but within the margin: no eviction.
+        assert!(!trigger.should_evict(50 * GB + GB / 2, at(0)));
+        assert!(!trigger.should_evict(51 * GB, at(30)));
+
+        // Past threshold + margin: run starts and continues below the threshold
+        // (each batch is capped) until usage drops under target.
+        assert!(trigger.should_evict(53 * GB, at(60)));
+        trigger.observe(48 * GB, at(61));
+        assert!(trigger.should_evict(48 * GB, at(90)));
+        trigger.observe(39 * GB, at(91));
+        assert!(!trigger.should_evict(39 * GB, at(120)));
+
+        // Back over the trigger quickly: held off by the cooldown.
+        assert!(!trigger.should_evict(53 * GB, at(200)));
+        assert!(trigger.should_evict(53 * GB, at(400)));
+    }
+
+    #[test]
+    fn status_alerts_fire_only_on_transitions() {
+        let mut watch = StatusWatch::default();
+        assert_eq!(watch.observe(rustfs_status(usage_pct(GB, 50.0))), None);
+        assert_eq!(watch.observe(rustfs_status(usage_pct(45 * GB, 50.0))), Some("healthy"));
+        assert_eq!(watch.observe("pressure"), None);
+        assert_eq!(watch.observe(rustfs_status(usage_pct(51 * GB, 50.0))), Some("pressure"));
+        assert_eq!(watch.observe("healthy"), Some("critical"));
+
+        // Starting up already critical alerts immediately.
+        assert_eq!(StatusWatch::default().observe("critical"), Some("healthy"));
+    }
+
+    #[test]
+    fn robot_id_is_taken_from_key_after_prefix() {
+        let key = "frames/reachy-001/camera/2026-02-18/20260218T093000000Z_20260218T094000000Z_3f9a01c2.mp4";
+        assert_eq!(robot_id_from_key("frames/", key), Some("reachy-001"));
+        assert_eq!(robot_id_from_key("", "reachy-001/camera/x.jpg"), Some("reachy-001"));
+        assert_eq!(robot_id_from_key("frames/", "frames/stray.jpg"), None);
+    }
+
+    #[test]
+    fn ledger_marks_known_robots_and_skips_unknown() {
+        let dir = std::env::temp_dir().join(format!("frame-bucket-ledger-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let db = SegmentDb::open(&dir, DatabaseMode::PerRobot, "reachy-001").unwrap();
+        db
+            .insert_active(&NewActiveSegment {
+                start_ms: 0,
+                end_ms: 1000,
+                s3_key: "frames/reachy-001/camera/d/a.mp4",
+                size_bytes: 10,
+                frame_count: 3,
+                motion_score: None,
+                region: None,
+            })
+            .unwrap();
+        drop(db);
+
+        let mut ledger = ArchiveLedger::new(&dir, DatabaseMode::PerRobot, "frames/");
+        assert_eq!(
+            ledger.record("frames/reachy-001/camera/d/a.mp4", Some("archive/frames/reachy-001/camera/d/a.mp4")),
+            1
+        );
+        assert_eq!(ledger.record("frames/ghost/camera/d/b.mp4", None), 0);
+        assert!(!dir.join("ghost.db").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dry_run_counts_objects_up_to_the_one_reaching_target() {
+        let entries: Vec<_> = (0..4).map(|i| (format!("k{i}"), 10 * GB, i)).collect();
+        let keys = |batch: &[(String, u64, i64)]| batch.iter().map(|(k, _, _)| k.clone()).collect::<Vec<_>>();
+        // 45 GB -> 35 -> 25 (< 30): stops after the second object.
+        assert_eq!(keys(would_evict_entries(&entries, 45 * GB, 30 * GB)), ["k0", "k1"]);
+        // Not enough candidates to reach target: the whole batch.
+        assert_eq!(would_evict_entries(&entries, 100 * GB, 30 * GB).len(), 4);
+    }
+
+    #[test]
+    fn content_md5_is_base64_of_the_digest() {
+        assert_eq!(content_md5(b""), "1B2M2Y8AsgTpgAmY7PhCfg==");
+        assert_eq!(content_md5(b"The quick brown fox jumps over the lazy dog"), "nhB9nTcrtoJr2B01QqQZ1g==");
+    }
+
+    #[test]
+    fn defaults_start_eviction_just_over_threshold() {
+        let mut trigger = EvictionTrigger::new(&config(0.0, 0));
+        let now = Instant::now();
+        assert!(!trigger.should_evict(50 * GB, now));
+        assert!(trigger.should_evict(50 * GB + 1, now));
+    }
 }
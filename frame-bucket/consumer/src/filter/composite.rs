@@ -0,0 +1,111 @@
+use frame_bucket_common::config::CompositeMode;
+use tracing::debug;
+
+use super::traits::FrameFilter;
+
+/// Combines several filters: with [`CompositeMode::Any`] a frame is stored if
+/// any of them says the scene changed, with [`CompositeMode::All`] only if all
+/// of them do.
+///
+/// Every filter sees every frame (no short-circuiting), so each keeps its own
+/// baseline up to date. A filter's baseline is the last frame *it* accepted,
+/// which may differ from the last frame the composite stored.
+pub struct CompositeFilter {
+    filters: Vec<Box<dyn FrameFilter>>,
+    mode: CompositeMode,
+}
+
+impl CompositeFilter {
+    pub fn new(filters: Vec<Box<dyn FrameFilter>>, mode: CompositeMode) -> Self {
+        Self { filters, mode }
+    }
+}
+
+impl FrameFilter for CompositeFilter {
+    fn should_store(&mut self, jpeg_data: &[u8]) -> bool {
+        let votes: Vec<bool> = self
+            .filters
+            .iter_mut()
+            .map(|f| f.should_store(jpeg_data))
+            .collect();
+        let accepted = match self.mode {
+            CompositeMode::Any => votes.iter().any(|&v| v),
+            CompositeMode::All => !votes.is_empty() && votes.iter().all(|&v| v),
+        };
+        debug!(?votes, mode = ?self.mode, accepted, "composite comparison");
+        accepted
+    }
+
+    /// Sum of the filters' distances, so a score mixes their units.
+    fn last_distance(&self) -> Option<f64> {
+        self.filters.iter().map(|f| f.last_distance()).sum()
+    }
+
+    fn name(&self) -> &str {
+        "composite"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers from a fixed script, one per frame.
+    struct Scripted(std::vec::IntoIter<bool>);
+
+    impl FrameFilter for Scripted {
+        fn should_store(&mut self, _jpeg_data: &[u8]) -> bool {
+            self.0.next().unwrap()
+        }
+    }
+
+    fn composite(mode: CompositeMode) -> CompositeFilter {
+        CompositeFilter::new(
+            vec![
+                Box::new(Scripted(vec![true, true, false, false].into_iter())),
+                Box::new(Scripted(vec![true, false, true, false].into_iter())),
+            ],
+            mode,
+        )
+    }
+
+    #[test]
+    fn any_and_all_combine_votes() {
+        let mut any = composite(CompositeMode::Any);
+        let stored: Vec<bool> = (0..4).map(|_| any.should_store(b"")).collect();
+        assert_eq!(stored, [true, true, true, false]);
+
+        let mut all = composite(CompositeMode::All);
+        let stored: Vec<bool> = (0..4).map(|_| all.should_store(b"")).collect();
+        assert_eq!(stored, [true, false, false, false]);
+    }
+
+    #[test]
+    fn every_filter_sees_every_frame() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        struct Counting(Arc<AtomicUsize>);
+        impl FrameFilter for Counting {
+            fn should_store(&mut self, _jpeg_data: &[u8]) -> bool {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut any = CompositeFilter::new(
+            vec![Box::new(Counting(Arc::clone(&calls))), Box::new(Counting(Arc::clone(&calls)))],
+            CompositeMode::Any,
+        );
+        any.should_store(b"");
+        any.should_store(b"");
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn empty_all_stores_nothing() {
+        let mut all = CompositeFilter::new(Vec::new(), CompositeMode::All);
+        assert!(!all.should_store(b""));
+    }
+}
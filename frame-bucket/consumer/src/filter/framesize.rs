@@ -87,6 +87,15 @@ impl FrameSizeFilter {
         (frame_size as f64) <= self.spike_ratio * self.avg_p_frame_size
     }
 
+    /// How much motion a frame carries: a P-frame's size relative to the
+    /// EMA. 0 for keyframes and before any P-frame was seen.
+    pub fn motion(&self, frame_size: usize, nal_type: u8) -> f64 {
+        if nal_type == 5 || self.avg_p_frame_size <= 0.0 {
+            return 0.0;
+        }
+        frame_size as f64 / self.avg_p_frame_size
+    }
+
     fn update_ema(&mut self, frame_size: usize) {
         let size = frame_size as f64;
         if self.avg_p_frame_size == 0.0 {
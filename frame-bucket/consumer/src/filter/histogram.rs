@@ -9,50 +9,71 @@ const DOWNSAMPLE_SIZE: u32 = 64;
 
 /// Histogram-based scene change filter.
 ///
-/// Downsamples to 64x64 grayscale, computes a 64-bin histogram,
-/// then compares via chi-squared distance.
+/// Downsamples to 64x64, computes a 64-bin histogram, then compares via
+/// chi-squared distance. By default the histogram is of grayscale values; in
+/// color mode there is one per R/G/B channel and the distances are summed, so
+/// a change of hue at the same luminance (e.g. a screen flipping red/green)
+/// also counts.
 pub struct HistogramFilter {
-    last_histogram: Option<[f64; NUM_BINS]>,
+    /// One histogram per channel: 1 (gray) or 3 (R, G, B).
+    last_histogram: Option<Vec<[f64; NUM_BINS]>>,
     threshold: f64,
+    color: bool,
+    last_distance: Option<f64>,
 }
 
 impl HistogramFilter {
-    pub fn new(threshold: f64) -> Self {
+    pub fn new(threshold: f64, color: bool) -> Self {
         Self {
             last_histogram: None,
             threshold,
+            color,
+            last_distance: None,
         }
     }
 
-    fn compute_histogram(jpeg_data: &[u8]) -> Option<[f64; NUM_BINS]> {
+    fn compute_histogram(jpeg_data: &[u8], color: bool) -> Option<Vec<[f64; NUM_BINS]>> {
         let img = ImageReader::new(Cursor::new(jpeg_data))
             .with_guessed_format()
             .ok()?
             .decode()
-            .ok()?;
-
-        let gray = img
+            .ok()?
             .resize_exact(
                 DOWNSAMPLE_SIZE,
                 DOWNSAMPLE_SIZE,
                 image::imageops::FilterType::Nearest,
+            );
+
+        if color {
+            let rgb = img.to_rgb8();
+            Some(
+                (0..3)
+                    .map(|c| Self::normalized_bins(rgb.pixels().map(|p| p.0[c])))
+                    .collect(),
             )
-            .to_luma8();
+        } else {
+            let gray = img.to_luma8();
+            Some(vec![Self::normalized_bins(gray.pixels().map(|p| p.0[0]))])
+        }
+    }
 
+    /// Histogram of 8-bit values, normalized to sum to 1.
+    fn normalized_bins(values: impl Iterator<Item = u8>) -> [f64; NUM_BINS] {
         let mut bins = [0u64; NUM_BINS];
-        let total_pixels = gray.pixels().len() as f64;
+        let mut total_pixels = 0u64;
 
-        for pixel in gray.pixels() {
-            let bin = (pixel.0[0] as usize * NUM_BINS) / 256;
+        for value in values {
+            let bin = (value as usize * NUM_BINS) / 256;
             bins[bin.min(NUM_BINS - 1)] += 1;
+            total_pixels += 1;
         }
 
         // Normalize
         let mut hist = [0.0f64; NUM_BINS];
         for (i, &count) in bins.iter().enumerate() {
-            hist[i] = count as f64 / total_pixels;
+            hist[i] = count as f64 / total_pixels.max(1) as f64;
         }
-        Some(hist)
+        hist
     }
 
     /// Chi-squared distance between two histograms.
@@ -71,10 +92,11 @@ impl HistogramFilter {
 
 impl FrameFilter for HistogramFilter {
     fn should_store(&mut self, jpeg_data: &[u8]) -> bool {
-        let hist = match Self::compute_histogram(jpeg_data) {
+        let hist = match Self::compute_histogram(jpeg_data, self.color) {
             Some(h) => h,
             None => {
                 warn!("failed to compute histogram, skipping frame");
+                self.last_distance = None;
                 return false;
             }
         };
@@ -83,10 +105,16 @@ impl FrameFilter for HistogramFilter {
             None => {
                 debug!("first frame, accepting unconditionally");
                 self.last_histogram = Some(hist);
+                self.last_distance = Some(0.0);
                 true
             }
             Some(prev) => {
-                let distance = Self::chi_squared(prev, &hist);
+                let distance: f64 = prev
+                    .iter()
+                    .zip(&hist)
+                    .map(|(a, b)| Self::chi_squared(a, b))
+                    .sum();
+                self.last_distance = Some(distance);
                 let accepted = distance > self.threshold;
                 debug!(
                     distance = format!("{:.4}", distance),
@@ -102,7 +130,45 @@ impl FrameFilter for HistogramFilter {
         }
     }
 
+    fn last_distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+
     fn name(&self) -> &str {
         "histogram"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{Rgb, RgbImage};
+
+    /// A solid-color JPEG.
+    fn jpeg(color: [u8; 3]) -> Vec<u8> {
+        let img = RgbImage::from_pixel(64, 64, Rgb(color));
+        let mut buf = Vec::new();
+        JpegEncoder::new_with_quality(&mut buf, 95).encode_image(&img).unwrap();
+        buf
+    }
+
+    // Red and green of (nearly) equal luminance: 0.2126 * 200 ≈ 0.7152 * 59.
+    const RED: [u8; 3] = [200, 0, 0];
+    const GREEN: [u8; 3] = [0, 59, 0];
+
+    #[test]
+    fn grayscale_misses_hue_change_at_equal_luminance() {
+        let mut filter = HistogramFilter::new(0.15, false);
+        assert!(filter.should_store(&jpeg(RED)), "first frame is always stored");
+        assert!(!filter.should_store(&jpeg(GREEN)));
+    }
+
+    #[test]
+    fn color_mode_catches_hue_change() {
+        let mut filter = HistogramFilter::new(0.15, true);
+        assert!(filter.should_store(&jpeg(RED)));
+        assert!(!filter.should_store(&jpeg(RED)));
+        assert!(filter.should_store(&jpeg(GREEN)));
+    }
+}
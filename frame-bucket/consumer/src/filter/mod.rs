@@ -1,6 +1,126 @@
-#[allow(dead_code)]
 pub mod traits;
 pub mod phash;
-#[allow(dead_code)]
 pub mod histogram;
+pub mod ssim;
+pub mod composite;
 pub mod framesize;
+
+use frame_bucket_common::config::FilterConfig;
+use tracing::warn;
+
+use self::composite::CompositeFilter;
+use self::histogram::HistogramFilter;
+use self::phash::PHashFilter;
+use self::ssim::SsimFilter;
+use self::traits::FrameFilter;
+
+/// The recorder's scene-change filter for JPEG frames: [`from_config`], or
+/// pHash when `filter.primary` is `"framesize"` (which only judges H.264).
+pub fn scene_filter(config: &FilterConfig) -> Box<dyn FrameFilter> {
+    from_config(config).unwrap_or_else(|| Box::new(PHashFilter::new(config.phash_hash_size, config.phash_threshold)))
+}
+
+/// The JPEG scene-change filter named by `filter.primary`, or `None` for
+/// `"framesize"` (H.264 only, not a [`FrameFilter`]) and unknown names.
+/// `"composite"` combines the filters listed in `filter.composite`; unknown
+/// or nested `"composite"` entries are skipped with a warning.
+pub fn from_config(config: &FilterConfig) -> Option<Box<dyn FrameFilter>> {
+    if config.primary != "composite" {
+        return single(&config.primary, config);
+    }
+    let filters = config
+        .composite
+        .iter()
+        .filter_map(|name| {
+            let filter = single(name, config);
+            if filter.is_none() {
+                warn!(name, "filter.composite: not a JPEG filter, ignoring");
+            }
+            filter
+        })
+        .collect();
+    Some(Box::new(CompositeFilter::new(filters, config.composite_mode)))
+}
+
+fn single(name: &str, config: &FilterConfig) -> Option<Box<dyn FrameFilter>> {
+    match name {
+        "phash" => Some(Box::new(PHashFilter::new(config.phash_hash_size, config.phash_threshold))),
+        "histogram" => Some(Box::new(HistogramFilter::new(config.histogram_threshold, config.histogram_color))),
+        "ssim" => Some(Box::new(SsimFilter::new(config.ssim_threshold))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: serde_json::Value) -> FilterConfig {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn primary_selects_filter() {
+        let name = |json| from_config(&config(json)).map(|f| f.name().to_string());
+        assert_eq!(name(serde_json::json!({ "primary": "ssim" })).as_deref(), Some("ssim"));
+        assert_eq!(name(serde_json::json!({ "primary": "framesize" })), None);
+        assert_eq!(scene_filter(&config(serde_json::json!({ "primary": "framesize" }))).name(), "phash");
+        assert_eq!(
+            name(serde_json::json!({
+                "primary": "composite",
+                "composite": ["phash", "histogram", "framesize"],
+                "composite_mode": "all",
+            }))
+            .as_deref(),
+            Some("composite")
+        );
+    }
+
+    #[test]
+    fn recorder_uses_composite_with_summed_distance() {
+        struct Fixed(f64);
+        impl FrameFilter for Fixed {
+            fn should_store(&mut self, _jpeg_data: &[u8]) -> bool {
+                true
+            }
+            fn last_distance(&self) -> Option<f64> {
+                Some(self.0)
+            }
+        }
+
+        let filter = scene_filter(&config(serde_json::json!({ "primary": "composite", "composite": ["phash", "ssim"] })));
+        assert_eq!(filter.name(), "composite");
+
+        let mut composite = CompositeFilter::new(vec![Box::new(Fixed(2.0)), Box::new(Fixed(0.5))], Default::default());
+        assert!(composite.should_store(b""));
+        assert_eq!(composite.last_distance(), Some(2.5));
+        // An undecodable frame has no distance.
+        let mut with_phash = CompositeFilter::new(vec![Box::new(Fixed(2.0)), Box::new(PHashFilter::new(16, 26))], Default::default());
+        with_phash.should_store(b"not a jpeg");
+        assert_eq!(with_phash.last_distance(), None);
+    }
+
+    #[test]
+    fn histogram_color_reaches_the_recorder_filter() {
+        use image::codecs::jpeg::JpegEncoder;
+        use image::{Rgb, RgbImage};
+
+        let jpeg = |color| {
+            let mut buf = Vec::new();
+            JpegEncoder::new_with_quality(&mut buf, 95)
+                .encode_image(&RgbImage::from_pixel(64, 64, Rgb(color)))
+                .unwrap();
+            buf
+        };
+        // Red and green of (nearly) equal luminance.
+        let (red, green) = (jpeg([200, 0, 0]), jpeg([0, 59, 0]));
+        let changes = |json| {
+            let mut filter = scene_filter(&config(json));
+            filter.should_store(&red);
+            filter.should_store(&green)
+        };
+        assert!(!changes(serde_json::json!({ "primary": "histogram" })));
+        assert!(changes(serde_json::json!({ "primary": "histogram", "histogram_color": true })));
+        assert!(changes(serde_json::json!({ "primary": "composite", "composite": ["histogram"], "histogram_color": true })));
+    }
+}
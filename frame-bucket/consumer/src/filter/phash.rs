@@ -42,55 +42,31 @@ pub fn hamming(a: &[bool], b: &[bool]) -> u32 {
 ///
 /// This is an average-hash (aHash) approach — extremely fast and effective
 /// for scene change detection. Performance: ~1-3ms total on ARM.
-#[allow(dead_code)]
 pub struct PHashFilter {
     hash_size: u32,
     last_hash: Option<Vec<bool>>,
     threshold: u32,
+    last_distance: Option<f64>,
 }
 
-#[allow(dead_code)]
 impl PHashFilter {
     pub fn new(hash_size: u32, threshold: u32) -> Self {
         Self {
             hash_size,
             last_hash: None,
             threshold,
+            last_distance: None,
         }
     }
-
-    fn compute_hash(&self, jpeg_data: &[u8]) -> Option<Vec<bool>> {
-        let img = ImageReader::new(Cursor::new(jpeg_data))
-            .with_guessed_format()
-            .ok()?
-            .decode()
-            .ok()?;
-
-        // Convert to grayscale and resize to hash_size x hash_size
-        let gray = img
-            .resize_exact(self.hash_size, self.hash_size, FilterType::Nearest)
-            .to_luma8();
-
-        // Compute mean pixel value
-        let pixels: Vec<u8> = gray.pixels().map(|p| p.0[0]).collect();
-        let mean: f64 = pixels.iter().map(|&p| p as f64).sum::<f64>() / pixels.len() as f64;
-
-        // Build binary hash
-        let hash: Vec<bool> = pixels.iter().map(|&p| p as f64 > mean).collect();
-        Some(hash)
-    }
-
-    fn hamming_distance(a: &[bool], b: &[bool]) -> u32 {
-        a.iter().zip(b.iter()).filter(|(a, b)| a != b).count() as u32
-    }
 }
 
 impl FrameFilter for PHashFilter {
     fn should_store(&mut self, jpeg_data: &[u8]) -> bool {
-        let hash = match self.compute_hash(jpeg_data) {
+        let hash = match compute_ahash(jpeg_data, self.hash_size) {
             Some(h) => h,
             None => {
                 warn!("failed to decode JPEG for pHash, skipping frame");
+                self.last_distance = None;
                 return false;
             }
         };
@@ -99,10 +75,12 @@ impl FrameFilter for PHashFilter {
             None => {
                 debug!("first frame, accepting unconditionally");
                 self.last_hash = Some(hash);
+                self.last_distance = Some(0.0);
                 true
             }
             Some(prev) => {
-                let distance = Self::hamming_distance(prev, &hash);
+                let distance = hamming(prev, &hash);
+                self.last_distance = Some(distance as f64);
                 let accepted = distance > self.threshold;
                 debug!(
                     distance,
@@ -118,6 +96,10 @@ impl FrameFilter for PHashFilter {
         }
     }
 
+    fn last_distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+
     fn name(&self) -> &str {
         "phash"
     }
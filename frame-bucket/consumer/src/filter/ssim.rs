@@ -0,0 +1,179 @@
+use image::imageops::FilterType;
+use image::{GrayImage, ImageReader};
+use std::io::Cursor;
+use tracing::{debug, warn};
+
+use super::traits::FrameFilter;
+
+/// Frames are compared at this size (grayscale). Larger than the aHash grid so
+/// that a change covering a small corner of the view still spans whole windows.
+const SSIM_SIZE: u32 = 128;
+/// Side of the square SSIM window, in pixels.
+const WINDOW: u32 = 8;
+/// Step between windows; windows overlap by half.
+const STRIDE: u32 = 4;
+
+/// Stabilizing constants from the SSIM paper, for 8-bit pixels.
+const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// Structural-similarity scene change filter.
+///
+/// Downsamples to 128x128 grayscale and computes the mean SSIM over 8x8
+/// windows (stride 4) against the last stored frame. Unlike pHash and the
+/// histogram, which summarize the whole frame, SSIM compares local structure,
+/// so it notices small movements such as a gripper in a corner of the view.
+/// The frame is stored when `1 - ssim > threshold`.
+pub struct SsimFilter {
+    last_frame: Option<GrayImage>,
+    threshold: f64,
+    last_distance: Option<f64>,
+}
+
+impl SsimFilter {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            last_frame: None,
+            threshold,
+            last_distance: None,
+        }
+    }
+
+    fn decode(jpeg_data: &[u8]) -> Option<GrayImage> {
+        let img = ImageReader::new(Cursor::new(jpeg_data))
+            .with_guessed_format()
+            .ok()?
+            .decode()
+            .ok()?;
+        Some(
+            img.resize_exact(SSIM_SIZE, SSIM_SIZE, FilterType::Triangle)
+                .to_luma8(),
+        )
+    }
+
+    /// Mean SSIM of two equally sized grayscale images, in `[-1, 1]`
+    /// (1 = identical).
+    pub fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+        let (width, height) = a.dimensions();
+        let n = (WINDOW * WINDOW) as f64;
+        let mut total = 0.0;
+        let mut windows = 0u32;
+
+        for y in (0..=height.saturating_sub(WINDOW)).step_by(STRIDE as usize) {
+            for x in (0..=width.saturating_sub(WINDOW)).step_by(STRIDE as usize) {
+                let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+                for dy in 0..WINDOW {
+                    for dx in 0..WINDOW {
+                        let pa = a.get_pixel(x + dx, y + dy).0[0] as f64;
+                        let pb = b.get_pixel(x + dx, y + dy).0[0] as f64;
+                        sum_a += pa;
+                        sum_b += pb;
+                        sum_aa += pa * pa;
+                        sum_bb += pb * pb;
+                        sum_ab += pa * pb;
+                    }
+                }
+                let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+                let var_a = sum_aa / n - mean_a * mean_a;
+                let var_b = sum_bb / n - mean_b * mean_b;
+                let covar = sum_ab / n - mean_a * mean_b;
+
+                total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+                windows += 1;
+            }
+        }
+        total / windows as f64
+    }
+}
+
+impl FrameFilter for SsimFilter {
+    fn should_store(&mut self, jpeg_data: &[u8]) -> bool {
+        let frame = match Self::decode(jpeg_data) {
+            Some(f) => f,
+            None => {
+                warn!("failed to decode JPEG for SSIM, skipping frame");
+                self.last_distance = None;
+                return false;
+            }
+        };
+
+        match &self.last_frame {
+            None => {
+                debug!("first frame, accepting unconditionally");
+                self.last_frame = Some(frame);
+                self.last_distance = Some(0.0);
+                true
+            }
+            Some(prev) => {
+                let ssim = Self::ssim(prev, &frame);
+                self.last_distance = Some(1.0 - ssim);
+                let accepted = 1.0 - ssim > self.threshold;
+                debug!(
+                    ssim = format!("{:.4}", ssim),
+                    threshold = format!("{:.4}", self.threshold),
+                    accepted,
+                    "SSIM comparison"
+                );
+                if accepted {
+                    self.last_frame = Some(frame);
+                }
+                accepted
+            }
+        }
+    }
+
+    fn last_distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+
+    fn name(&self) -> &str {
+        "ssim"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::Luma;
+
+    /// A 256x256 JPEG of a horizontal gradient, with an optional white
+    /// square of side `square` in the top-left corner.
+    fn jpeg(square: u32) -> Vec<u8> {
+        let img = GrayImage::from_fn(256, 256, |x, y| {
+            if x < square && y < square {
+                Luma([255])
+            } else {
+                Luma([(x / 2) as u8])
+            }
+        });
+        let mut buf = Vec::new();
+        JpegEncoder::new_with_quality(&mut buf, 90).encode_image(&img).unwrap();
+        buf
+    }
+
+    #[test]
+    fn identical_images_have_ssim_one() {
+        let a = SsimFilter::decode(&jpeg(0)).unwrap();
+        assert!((SsimFilter::ssim(&a, &a) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn skips_unchanged_and_stores_local_change() {
+        let mut filter = SsimFilter::new(0.02);
+        assert!(filter.should_store(&jpeg(0)), "first frame is always stored");
+        assert!(!filter.should_store(&jpeg(0)));
+        // A white square covering ~4% of the frame, in one corner.
+        assert!(filter.should_store(&jpeg(52)));
+        // Compared against the frame just stored, so the same frame is skipped.
+        assert!(!filter.should_store(&jpeg(52)));
+    }
+
+    #[test]
+    fn undecodable_frame_is_skipped() {
+        let mut filter = SsimFilter::new(0.02);
+        assert!(!filter.should_store(b"not a jpeg"));
+        assert_eq!(filter.last_distance(), None);
+    }
+}
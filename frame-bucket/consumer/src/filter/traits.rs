@@ -7,6 +7,13 @@ pub trait FrameFilter: Send + Sync {
     /// Returns `false` to skip (scene unchanged).
     fn should_store(&mut self, jpeg_data: &[u8]) -> bool;
 
+    /// Distance of the frame last passed to [`Self::should_store`] from the
+    /// baseline it was compared with, in the filter's own unit (0 for the
+    /// first frame). `None` if that frame couldn't be decoded.
+    fn last_distance(&self) -> Option<f64> {
+        Some(0.0)
+    }
+
     /// Human-readable name for logging.
     fn name(&self) -> &str {
         "unnamed"
@@ -1,17 +1,25 @@
 mod db;
+mod debug;
+mod dlq;
 mod eviction;
 mod filter;
+mod metrics;
 mod recorder;
 mod storage;
 
-use frame_bucket_common::config::Config;
+use dlq::{DeadLetterQueue, DlqStats};
+use frame_bucket_common::config::{Config, HwAccel};
 use frame_bucket_common::frame::TimestampedFrame;
+use frame_bucket_common::signal::shutdown_signal;
+use metrics::ConsumerMetrics;
 use rdkafka::consumer::{Consumer, StreamConsumer};
 use rdkafka::message::Message;
 use rdkafka::ClientConfig;
-use recorder::RecordingStateMachine;
+use recorder::{RecordingStateMachine, RegionRecorder, RobotRecorders};
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
 #[tokio::main]
@@ -21,13 +29,17 @@ async fn main() {
         .map(PathBuf::from)
         .unwrap_or_else(|| PathBuf::from("config.toml"));
 
-    let config = match Config::load(&config_path) {
+    let mut config = match Config::load(&config_path) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Failed to load config from {}: {e}", config_path.display());
             std::process::exit(1);
         }
     };
+    if let Err(e) = config.validate() {
+        eprintln!("{}: {e}", config_path.display());
+        std::process::exit(1);
+    }
 
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -42,13 +54,46 @@ async fn main() {
         group_id = config.kafka.group_id,
         codec = config.recording.codec,
         segment_secs = config.recording.segment_duration_secs,
-        phash_threshold = config.filter.phash_threshold,
+        filter = config.filter.primary,
         rustfs_endpoint = config.rustfs.endpoint,
         "starting frame-bucket consumer"
     );
 
+    if config.recording.keep_raw_frames {
+        warn!(
+            max_per_segment = config.recording.max_raw_frames_per_segment,
+            "recording.keep_raw_frames is enabled (debug mode): source frames will be uploaded alongside segments"
+        );
+    }
+
     // Check ffmpeg availability (encoding will fail without it).
-    recorder::encoder::check_ffmpeg_available().await;
+    if let Err(e) = recorder::encoder::validate_ffmpeg_path(&config.recording.ffmpeg_path) {
+        error!(error = e, "recording.ffmpeg_path does not exist");
+        std::process::exit(1);
+    }
+    let ffmpeg = recorder::encoder::Ffmpeg::from_config(&config.recording);
+    recorder::encoder::check_ffmpeg_available(&ffmpeg).await;
+    if config.recording.scale_height > 0 {
+        info!(
+            scale_height = config.recording.scale_height,
+            "downscaling JPEG segments; H.264 passthrough segments keep the source resolution"
+        );
+    }
+    let video_encoder = recorder::encoder::VideoEncoder::from_config(&config.recording);
+    if video_encoder != video_encoder.software() {
+        if recorder::encoder::check_encoder_available(&ffmpeg, &video_encoder).await {
+            info!(encoder = video_encoder.name(), "using hardware video encoder");
+        } else {
+            let software = video_encoder.software();
+            warn!(
+                encoder = video_encoder.name(),
+                fallback = software.name(),
+                "hardware video encoder unavailable, falling back to software encoding"
+            );
+            config.recording.hwaccel = HwAccel::None;
+            config.recording.codec = if software.name() == "libx265" { "h265" } else { "h264" }.into();
+        }
+    }
 
     // Initialize RustFS storage
     let rustfs_storage = Arc::new(storage::RustfsStorage::new(&config.rustfs).await);
@@ -58,89 +103,178 @@ async fn main() {
     }
 
     // Create Kafka consumer
-    let consumer: StreamConsumer = ClientConfig::new()
+    let mut client = ClientConfig::new();
+    client
         .set("bootstrap.servers", &config.kafka.brokers)
         .set("group.id", &config.kafka.group_id)
-        .set("auto.offset.reset", "latest")
+        .set("auto.offset.reset", config.kafka.auto_offset_reset.as_str())
         .set("enable.auto.commit", "true")
         .set("auto.commit.interval.ms", "1000")
-        .set("max.partition.fetch.bytes", "10485760")
-        .create()
-        .expect("failed to create Kafka consumer");
+        .set("max.partition.fetch.bytes", "10485760");
+    for (name, value) in config.kafka.security_properties() {
+        client.set(name, value);
+    }
+    let consumer: StreamConsumer = client.create().expect("failed to create Kafka consumer");
 
     consumer
         .subscribe(&[&config.kafka.topic])
         .expect("failed to subscribe to Kafka topic");
+    let consumer = Arc::new(consumer);
 
     info!(topic = config.kafka.topic, "subscribed to Kafka topic");
 
-    // Open per-robot SQLite database for segment metadata.
-    let robot_id = config.aws_s3.robot_id.clone();
-    let db_dir = std::path::Path::new(&config.database.path);
-    let segment_db = match db::SegmentDb::open(db_dir, &robot_id) {
-        Ok(d) => {
-            info!(path = config.database.path, robot_id, "SQLite segment DB opened");
-            Some(Arc::new(d))
-        }
-        Err(e) => {
-            error!(error = %e, "failed to open SQLite segment DB; metadata will not be persisted");
-            None
-        }
-    };
-
-    // Build the recording state machine.
-    let state_machine = RecordingStateMachine::new(
-        config.recording.clone(),
-        config.filter.phash_threshold,
-        config.filter.phash_hash_size,
-        config.filter.spike_ratio,
-        Arc::clone(&rustfs_storage),
-        segment_db,
-        config.rustfs.prefix.clone(),
-        robot_id,
+    // Build each robot's recording state machine(s) on its first frame: one
+    // for the whole frame, plus one per configured motion region, sharing the
+    // robot's SQLite database (per-robot file or shared) for segment metadata.
+    let recorder_config = config.clone();
+    let recorder_storage = Arc::clone(&rustfs_storage);
+    let mut recorders = RobotRecorders::new(
+        config.aws_s3.robot_id.clone(),
+        config.recording.wal_dir.as_ref().map(PathBuf::from),
+        config.recording.reorder_depth,
+        Duration::from_millis(config.recording.reorder_max_hold_ms),
+        move |robot_id, wal_dir| {
+            let config = &recorder_config;
+            let db_dir = std::path::Path::new(&config.database.path);
+            let segment_db = match db::SegmentDb::open(db_dir, config.database.mode, robot_id) {
+                Ok(d) => {
+                    info!(path = config.database.path, mode = ?config.database.mode, robot_id, "SQLite segment DB opened");
+                    Some(Arc::new(d))
+                }
+                Err(e) => {
+                    error!(error = %e, robot_id, "failed to open SQLite segment DB; metadata will not be persisted");
+                    None
+                }
+            };
+            let mut recording = config.recording.clone();
+            recording.wal_dir = wal_dir.map(|d| d.to_string_lossy().into_owned());
+            RegionRecorder::new(&config.recording.regions, |region| {
+                RecordingStateMachine::new(
+                    recording.clone(),
+                    &config.filter,
+                    Arc::clone(&recorder_storage),
+                    segment_db.clone(),
+                    config.rustfs.prefix.clone(),
+                    robot_id.to_string(),
+                    region,
+                )
+            })
+        },
     );
 
+    // Re-encode and upload segments interrupted by a previous crash.
+    recorders.recover_wal().await;
+
     // Spawn eviction background task
     let eviction_storage = Arc::clone(&rustfs_storage);
     let eviction_config = config.eviction.clone();
     let aws_config = config.aws_s3.clone();
+    let database_config = config.database.clone();
+    let rustfs_prefix = config.rustfs.prefix.clone();
     let stats_path = std::path::Path::new(&config.database.path).join("storage_stats.json");
+    let baseline_stats = Arc::new(eviction::BaselineStats::default());
+    let eviction_baseline = Arc::clone(&baseline_stats);
     tokio::spawn(async move {
-        eviction::run_eviction_loop(eviction_storage, &eviction_config, &aws_config, stats_path)
-            .await;
+        eviction::run_eviction_loop(
+            eviction_storage,
+            &eviction_config,
+            &aws_config,
+            &database_config,
+            &rustfs_prefix,
+            stats_path,
+            eviction_baseline,
+        )
+        .await;
     });
 
-    // Main consumption loop
+    // Dead-letter topic for undecodable messages
+    let dlq_stats = Arc::new(DlqStats::default());
+    let dlq = config.kafka.dlq_topic.clone().and_then(|topic| {
+        match DeadLetterQueue::new(&config.kafka, topic.clone(), Arc::clone(&dlq_stats)) {
+            Ok(dlq) => {
+                info!(topic, "publishing undecodable messages to dead-letter topic");
+                Some(dlq)
+            }
+            Err(e) => {
+                error!(error = %e, topic, "failed to create dead-letter producer; undecodable messages will be dropped");
+                None
+            }
+        }
+    });
+
+    // Localhost-only admin/debug server
+    if let Some(port) = config.consumer.admin_port {
+        tokio::spawn(debug::serve(
+            port,
+            Arc::clone(&rustfs_storage),
+            baseline_stats,
+            Arc::clone(&dlq_stats),
+            config.kafka.dlq_topic.clone(),
+        ));
+    }
+
+    // Prometheus scrape target
+    let consumer_metrics = Arc::new(ConsumerMetrics::default());
+    if let Some(port) = config.consumer.metrics_port {
+        tokio::spawn(metrics::serve(
+            port,
+            Arc::clone(&consumer_metrics),
+            Arc::clone(&rustfs_storage),
+            dlq_stats,
+            Arc::clone(&consumer),
+        ));
+    }
+
+    // Main consumption loop, until SIGTERM / Ctrl-C
     info!("entering main consumption loop");
-    run_consumer_loop(consumer, state_machine).await;
-}
+    run_consumer_loop(&consumer, &mut recorders, dlq, consumer_metrics).await;
 
-/// Extract the robot_id from a Kafka message key of the form `{robot_id}:{timestamp_ms}`.
-/// Falls back to "unknown" if the key is missing or malformed.
-fn robot_id_from_key(key: Option<&[u8]>) -> String {
-    key.and_then(|k| std::str::from_utf8(k).ok())
-        .and_then(|s| s.splitn(2, ':').next())
-        .unwrap_or("unknown")
-        .to_string()
+    // Finish the segments still being recorded, so neither the video nor its
+    // SQLite row is lost.
+    recorders.flush().await;
+    info!("frame-bucket consumer stopped");
 }
 
+/// How often frames held by the reordering buffers are checked for expiry.
+const REORDER_TICK: Duration = Duration::from_millis(50);
+
+/// Record frames until a shutdown signal. A frame being recorded when the
+/// signal arrives is finished first.
 async fn run_consumer_loop(
-    consumer: StreamConsumer,
-    mut state_machine: RecordingStateMachine,
+    consumer: &StreamConsumer,
+    recorders: &mut RobotRecorders,
+    dlq: Option<DeadLetterQueue>,
+    metrics: Arc<ConsumerMetrics>,
 ) {
     use futures_util::StreamExt;
     let mut stream = consumer.stream();
     let mut total: u64 = 0;
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut reorder_tick = tokio::time::interval(REORDER_TICK);
 
-    while let Some(result) = stream.next().await {
+    loop {
+        let result = tokio::select! {
+            signal = &mut shutdown => {
+                info!(signal, total, "shutting down: flushing open segments");
+                break;
+            }
+            next = stream.next() => match next {
+                Some(result) => result,
+                None => break,
+            },
+            _ = reorder_tick.tick(), if recorders.reorders() => {
+                recorders.release_expired().await;
+                continue;
+            }
+        };
         match result {
             Ok(msg) => {
-                let _robot_id = robot_id_from_key(msg.key());
-
                 let payload = match msg.payload() {
                     Some(p) => p,
                     None => {
                         debug!("empty Kafka message, skipping");
+                        metrics.frames_rejected.fetch_add(1, Ordering::Relaxed);
                         continue;
                     }
                 };
@@ -148,17 +282,25 @@ async fn run_consumer_loop(
                 let frame = match TimestampedFrame::deserialize(payload) {
                     Ok(f) => f,
                     Err(e) => {
-                        warn!(error = %e, "failed to deserialize frame, skipping");
+                        warn!(error = %e, offset = msg.offset(), "failed to deserialize frame, skipping");
+                        metrics.frames_rejected.fetch_add(1, Ordering::Relaxed);
+                        if e.is_corrupt() {
+                            metrics.frames_corrupt.fetch_add(1, Ordering::Relaxed);
+                        }
+                        if let Some(dlq) = &dlq {
+                            dlq.publish(&msg, &e.to_string()).await;
+                        }
                         continue;
                     }
                 };
 
                 total += 1;
-                if total % 100 == 0 {
+                metrics.frames_accepted.fetch_add(1, Ordering::Relaxed);
+                if total.is_multiple_of(100) {
                     debug!(total, "frames processed");
                 }
 
-                state_machine.process_frame(&frame).await;
+                recorders.process_frame(msg.key(), frame).await;
             }
             Err(e) => {
                 warn!(error = %e, "Kafka consume error");
@@ -0,0 +1,203 @@
+//! Prometheus scrape target for the consumer (`consumer.metrics_port`).
+//!
+//! `GET /metrics` reports frame throughput, what was stored in RustFS,
+//! dead-letter publishes and the Kafka lag of each assigned partition (high
+//! watermark minus the consumer's position).
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::Offset;
+use tracing::{error, info, warn};
+
+use crate::dlq::DlqStats;
+use crate::storage::RustfsStorage;
+
+/// Timeout of each high-watermark query to the broker.
+const WATERMARK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Counters kept by the consume loop.
+#[derive(Debug, Default)]
+pub struct ConsumerMetrics {
+    /// Frames decoded and handed to a recorder.
+    pub frames_accepted: AtomicU64,
+    /// Messages dropped before recording: empty or undecodable.
+    pub frames_rejected: AtomicU64,
+    /// Rejected messages whose checksum didn't match.
+    pub frames_corrupt: AtomicU64,
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    metrics: Arc<ConsumerMetrics>,
+    storage: Arc<RustfsStorage>,
+    dlq: Arc<DlqStats>,
+    consumer: Arc<StreamConsumer>,
+}
+
+/// Lag of one assigned partition.
+#[derive(Debug, PartialEq)]
+struct PartitionLag {
+    topic: String,
+    partition: i32,
+    lag: i64,
+}
+
+/// Everything reported by one scrape.
+#[derive(Debug, Default)]
+struct Snapshot {
+    frames_accepted: u64,
+    frames_rejected: u64,
+    frames_corrupt: u64,
+    segments_stored: u64,
+    idle_frames_stored: u64,
+    bytes_stored: u64,
+    dlq_published: u64,
+    dlq_failed: u64,
+    lag: Vec<PartitionLag>,
+}
+
+/// Serve `GET /metrics` on `0.0.0.0:{port}` until the process exits.
+pub async fn serve(
+    port: u16,
+    metrics: Arc<ConsumerMetrics>,
+    storage: Arc<RustfsStorage>,
+    dlq: Arc<DlqStats>,
+    consumer: Arc<StreamConsumer>,
+) {
+    let app = Router::new()
+        .route("/metrics", get(scrape))
+        .with_state(MetricsState { metrics, storage, dlq, consumer });
+
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!(error = %e, %addr, "failed to bind consumer metrics server");
+            return;
+        }
+    };
+    info!(%addr, "consumer metrics server listening");
+    if let Err(e) = axum::serve(listener, app).await {
+        error!(error = %e, "consumer metrics server stopped");
+    }
+}
+
+async fn scrape(State(state): State<MetricsState>) -> impl IntoResponse {
+    // Watermark queries block on the broker.
+    let consumer = Arc::clone(&state.consumer);
+    let lag = tokio::task::spawn_blocking(move || partition_lag(&consumer))
+        .await
+        .unwrap_or_default();
+
+    let snapshot = Snapshot {
+        frames_accepted: state.metrics.frames_accepted.load(Ordering::Relaxed),
+        frames_rejected: state.metrics.frames_rejected.load(Ordering::Relaxed),
+        frames_corrupt: state.metrics.frames_corrupt.load(Ordering::Relaxed),
+        segments_stored: state.storage.segments_stored.load(Ordering::Relaxed),
+        idle_frames_stored: state.storage.idle_frames_stored.load(Ordering::Relaxed),
+        bytes_stored: state.storage.bytes_stored.load(Ordering::Relaxed),
+        dlq_published: state.dlq.published.load(Ordering::Relaxed),
+        dlq_failed: state.dlq.failed.load(Ordering::Relaxed),
+        lag,
+    };
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&snapshot),
+    )
+}
+
+/// Lag of each partition the consumer has a position in. Partitions not
+/// consumed from yet, or whose watermarks can't be fetched, are left out.
+fn partition_lag(consumer: &StreamConsumer) -> Vec<PartitionLag> {
+    let positions = match consumer.position() {
+        Ok(p) => p,
+        Err(e) => {
+            warn!(error = %e, "failed to read consumer positions");
+            return Vec::new();
+        }
+    };
+    positions
+        .elements()
+        .iter()
+        .filter_map(|elem| {
+            let Offset::Offset(position) = elem.offset() else {
+                return None;
+            };
+            let (_, high) = consumer
+                .fetch_watermarks(elem.topic(), elem.partition(), WATERMARK_TIMEOUT)
+                .map_err(|e| warn!(error = %e, partition = elem.partition(), "failed to fetch watermarks"))
+                .ok()?;
+            Some(PartitionLag {
+                topic: elem.topic().to_string(),
+                partition: elem.partition(),
+                lag: (high - position).max(0),
+            })
+        })
+        .collect()
+}
+
+/// Prometheus text exposition of `s`.
+fn render(s: &Snapshot) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}");
+    };
+    metric("frame_bucket_frames_accepted_total", "counter", "Frames decoded and recorded.", s.frames_accepted);
+    metric("frame_bucket_frames_rejected_total", "counter", "Messages dropped as empty or undecodable.", s.frames_rejected);
+    metric("frame_bucket_frames_corrupt_total", "counter", "Rejected messages that failed the frame checksum.", s.frames_corrupt);
+    metric("frame_bucket_segments_stored_total", "counter", "Active segments uploaded to RustFS.", s.segments_stored);
+    metric("frame_bucket_idle_frames_stored_total", "counter", "Idle-period JPEGs uploaded to RustFS.", s.idle_frames_stored);
+    metric("frame_bucket_bytes_stored_total", "counter", "Bytes of segments and idle JPEGs uploaded to RustFS.", s.bytes_stored);
+    metric("frame_bucket_dlq_published_total", "counter", "Messages published to the dead-letter topic.", s.dlq_published);
+    metric("frame_bucket_dlq_failed_total", "counter", "Dead letters that could not be published.", s.dlq_failed);
+
+    out.push_str("# HELP frame_bucket_kafka_lag Messages between the consumer's position and the high watermark.\n");
+    out.push_str("# TYPE frame_bucket_kafka_lag gauge\n");
+    for p in &s.lag {
+        let _ = writeln!(
+            out,
+            "frame_bucket_kafka_lag{{topic=\"{}\",partition=\"{}\"}} {}",
+            p.topic, p.partition, p.lag
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_emits_counters_and_per_partition_lag() {
+        let text = render(&Snapshot {
+            frames_accepted: 1200,
+            frames_rejected: 3,
+            frames_corrupt: 1,
+            bytes_stored: 4096,
+            lag: vec![
+                PartitionLag { topic: "camera.frames".into(), partition: 0, lag: 12 },
+                PartitionLag { topic: "camera.frames".into(), partition: 1, lag: 0 },
+            ],
+            ..Default::default()
+        });
+        let lines: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+        assert!(lines.contains(&"frame_bucket_frames_accepted_total 1200"));
+        assert!(lines.contains(&"frame_bucket_frames_rejected_total 3"));
+        assert!(lines.contains(&"frame_bucket_frames_corrupt_total 1"));
+        assert!(lines.contains(&"frame_bucket_bytes_stored_total 4096"));
+        assert!(lines.contains(&"frame_bucket_segments_stored_total 0"));
+        assert!(lines.contains(&"frame_bucket_kafka_lag{topic=\"camera.frames\",partition=\"0\"} 12"));
+        assert!(text.contains("# TYPE frame_bucket_kafka_lag gauge"));
+        assert_eq!(lines.len(), 10);
+    }
+}
@@ -1,18 +1,292 @@
-use std::path::PathBuf;
-use tokio::io::AsyncWriteExt;
-use tokio::process::{Child, ChildStdin, Command};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use frame_bucket_common::config::{HwAccel, RecordingConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
+use super::keys::unique_suffix;
+use super::wal::SegmentWal;
+use crate::storage::{RustfsStorage, StorageError};
+
+/// How much capture time (by frame timestamps) `fps_auto` observes before fixing the input rate.
+const FPS_PROBE_WINDOW_MS: i64 = 1000;
+/// Minimum frames needed for a usable `fps_auto` estimate; otherwise the configured fps is used.
+const FPS_PROBE_MIN_FRAMES: usize = 5;
+
+/// The ffmpeg binary and how it's run (`recording.ffmpeg_path`,
+/// `recording.extra_ffmpeg_args`, `recording.encode_timeout_secs`).
+#[derive(Debug, Clone)]
+pub struct Ffmpeg {
+    pub path: String,
+    pub extra_args: Vec<String>,
+    /// Longest wait for ffmpeg to exit once a segment's input is closed.
+    pub encode_timeout: Option<Duration>,
+}
+
+impl Ffmpeg {
+    pub fn from_config(config: &RecordingConfig) -> Self {
+        Self {
+            path: config.ffmpeg_path.clone(),
+            extra_args: config.extra_ffmpeg_args.clone(),
+            encode_timeout: (config.encode_timeout_secs > 0)
+                .then(|| Duration::from_secs(config.encode_timeout_secs)),
+        }
+    }
+
+    fn command(&self) -> Command {
+        Command::new(&self.path)
+    }
+}
+
+/// File format of an encoded segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    Webm,
+}
+
+impl Container {
+    /// Extension of the temp file and the object key.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Webm => "webm",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Container::Mp4 => "video/mp4",
+            Container::Webm => "video/webm",
+        }
+    }
+}
+
+/// The ffmpeg encoder that re-encodes JPEG input, from `recording.codec`
+/// and `recording.hwaccel`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VideoEncoder {
+    /// `libx264` / `libx265`, or `libvpx-vp9` / `libaom-av1`.
+    Software(&'static str),
+    /// `h264_nvenc` / `hevc_nvenc`.
+    Nvenc(&'static str),
+    /// `h264_vaapi` / `hevc_vaapi` on the given render node.
+    Vaapi { name: &'static str, device: String },
+}
+
+impl VideoEncoder {
+    /// `recording.codec` is `"h264"` or `"h265"`, encoded with
+    /// `recording.hwaccel`, or names a hardware encoder outright
+    /// (`"h264_nvenc"`, `"hevc_vaapi"`, ...). `"vp9"` and `"av1"` are always
+    /// encoded in software.
+    pub fn from_config(config: &RecordingConfig) -> Self {
+        let (hevc, hwaccel) = match config.codec.as_str() {
+            "vp9" => return VideoEncoder::Software("libvpx-vp9"),
+            "av1" => return VideoEncoder::Software("libaom-av1"),
+            "h264_nvenc" => (false, HwAccel::Nvenc),
+            "hevc_nvenc" | "h265_nvenc" => (true, HwAccel::Nvenc),
+            "h264_vaapi" => (false, HwAccel::Vaapi),
+            "hevc_vaapi" | "h265_vaapi" => (true, HwAccel::Vaapi),
+            codec => (codec == "h265", config.hwaccel),
+        };
+        match hwaccel {
+            HwAccel::None => VideoEncoder::Software(if hevc { "libx265" } else { "libx264" }),
+            HwAccel::Nvenc => VideoEncoder::Nvenc(if hevc { "hevc_nvenc" } else { "h264_nvenc" }),
+            HwAccel::Vaapi => VideoEncoder::Vaapi {
+                name: if hevc { "hevc_vaapi" } else { "h264_vaapi" },
+                device: config.vaapi_device.clone(),
+            },
+        }
+    }
+
+    /// The software encoder for the same codec.
+    pub fn software(&self) -> Self {
+        if let VideoEncoder::Software(name) = self {
+            return VideoEncoder::Software(name);
+        }
+        let hevc = self.name().starts_with("hevc") || self.name() == "libx265";
+        VideoEncoder::Software(if hevc { "libx265" } else { "libx264" })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoEncoder::Software(name) | VideoEncoder::Nvenc(name) => name,
+            VideoEncoder::Vaapi { name, .. } => name,
+        }
+    }
+
+    /// VP9 goes in WebM; everything else, AV1 included, in MP4.
+    pub fn container(&self) -> Container {
+        match self.name() {
+            "libvpx-vp9" => Container::Webm,
+            _ => Container::Mp4,
+        }
+    }
+
+    /// Global options, before the input.
+    fn device_args(&self) -> Vec<String> {
+        match self {
+            VideoEncoder::Vaapi { device, .. } => vec!["-vaapi_device".into(), device.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The `-vf` chain: downscaling to `scale_height` (0 = source size),
+    /// then, for VA-API, the upload to the GPU. JPEG decoding stays on the
+    /// CPU, so scaling happens there too.
+    fn filter_args(&self, scale_height: u32) -> Vec<String> {
+        let mut filters = Vec::new();
+        if scale_height > 0 {
+            // Even sizes: x264/x265 reject odd 4:2:0 dimensions.
+            filters.push(format!("scale=-2:{}", scale_height & !1));
+        }
+        if matches!(self, VideoEncoder::Vaapi { .. }) {
+            filters.push("format=nv12,hwupload".into());
+        }
+        if filters.is_empty() {
+            return Vec::new();
+        }
+        vec!["-vf".into(), filters.join(",")]
+    }
+
+    /// Output options. `crf` maps to the hardware encoders' constant-quality
+    /// setting; `preset` only applies to x264/x265.
+    fn codec_args(&self, crf: u32, preset: &str, gop: u32) -> Vec<String> {
+        let (crf, gop) = (crf.to_string(), gop.to_string());
+        let args: Vec<&str> = match self {
+            // `-b:v 0` makes the CRF the only rate control (constant quality).
+            // libvpx and libaom default to their slowest speed settings, far
+            // too slow to keep up with a camera.
+            VideoEncoder::Software(name @ "libvpx-vp9") => {
+                vec!["-c:v", name, "-crf", &crf, "-b:v", "0", "-deadline", "good", "-cpu-used", "4", "-row-mt", "1"]
+            }
+            VideoEncoder::Software(name @ "libaom-av1") => {
+                vec!["-c:v", name, "-crf", &crf, "-b:v", "0", "-cpu-used", "6", "-row-mt", "1"]
+            }
+            VideoEncoder::Software(name) => vec!["-c:v", name, "-preset", preset, "-crf", &crf],
+            VideoEncoder::Nvenc(name) => vec!["-c:v", name, "-rc", "vbr", "-cq", &crf, "-b:v", "0"],
+            VideoEncoder::Vaapi { name, .. } => vec!["-c:v", name, "-qp", &crf],
+        };
+        args.into_iter()
+            .chain(["-g", &gop])
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// What ffmpeg is fed on stdin.
+enum InputKind {
+    /// JPEG frames, re-encoded with the given codec settings.
+    Mjpeg {
+        video: VideoEncoder,
+        crf: u32,
+        preset: String,
+        scale_height: u32,
+    },
+    /// Raw H.264 access units, muxed without re-encoding.
+    H264Passthrough,
+}
+
+/// Keeps frames at most `fps` times per second of capture time (`thin_to_fps`).
+/// Frames are admitted on a fixed schedule rather than relative to the last
+/// kept frame, so the kept rate doesn't drift below target with input jitter.
+struct FrameThinner {
+    interval_ms: f64,
+    next_due_ms: Option<f64>,
+}
+
+impl FrameThinner {
+    fn new(fps: f64) -> Self {
+        Self {
+            interval_ms: 1000.0 / fps,
+            next_due_ms: None,
+        }
+    }
+
+    fn admit(&mut self, captured_at_ms: i64) -> bool {
+        let ts = captured_at_ms as f64;
+        match self.next_due_ms {
+            Some(due) if ts < due => false,
+            Some(due) => {
+                // Fell more than an interval behind (gap in input): restart the schedule.
+                let next = due + self.interval_ms;
+                self.next_due_ms = Some(if next <= ts { ts + self.interval_ms } else { next });
+                true
+            }
+            None => {
+                self.next_due_ms = Some(ts + self.interval_ms);
+                true
+            }
+        }
+    }
+}
+
+/// Where an encoded segment is written.
+pub enum SegmentSink {
+    /// A temp file, read into memory by [`SegmentEncoder::finish`].
+    TempFile,
+    /// Streamed from ffmpeg's stdout to RustFS at `staging_key` while it's
+    /// encoded (`recording.stream_upload`), as fragmented MP4 or WebM.
+    Multipart {
+        storage: Arc<RustfsStorage>,
+        staging_key: String,
+    },
+}
+
+/// The encoded segment, as returned by [`SegmentEncoder::finish`].
+pub enum SegmentOutput {
+    /// The file's contents, still to be uploaded.
+    Bytes(Vec<u8>),
+    /// Already in RustFS at the sink's staging key.
+    Staged { staging_key: String, size: u64 },
+}
+
+impl SegmentOutput {
+    pub fn size(&self) -> u64 {
+        match self {
+            SegmentOutput::Bytes(bytes) => bytes.len() as u64,
+            SegmentOutput::Staged { size, .. } => *size,
+        }
+    }
+}
+
+/// Frames buffered while measuring the effective frame rate (`fps_auto`).
+struct FpsProbe {
+    frames: Vec<Vec<u8>>,
+    first_ts: i64,
+    last_ts: i64,
+}
+
 pub struct SegmentEncoder {
-    child: Child,
-    stdin: ChildStdin,
+    /// Running ffmpeg process. `None` while still probing the frame rate.
+    process: Option<(Child, ChildStdin)>,
+    ffmpeg: Ffmpeg,
+    input: InputKind,
+    configured_fps: f64,
+    /// Input rate ffmpeg was started with (known once the process is spawned).
+    effective_fps: Option<f64>,
+    probe: Option<FpsProbe>,
+    /// Drops frames above the `thin_to_fps` rate. JPEG input only.
+    thinner: Option<FrameThinner>,
     output_path: PathBuf,
+    sink: SegmentSink,
+    /// Streams ffmpeg's stdout to RustFS, with [`SegmentSink::Multipart`].
+    upload: Option<JoinHandle<Result<u64, StorageError>>>,
+    container: Container,
     frame_count: u32,
     pub start_ms: i64,
+    /// The segment key's [`unique_suffix`], shared by its raw frames.
+    pub suffix: String,
+    /// Crash-recovery log of the frames pushed so far (`recording.wal_dir`).
+    wal: Option<SegmentWal>,
 }
 
 pub struct FinishedSegment {
-    pub mp4_bytes: Vec<u8>,
+    pub output: SegmentOutput,
+    pub container: Container,
     pub frame_count: u32,
     #[allow(dead_code)]
     pub start_ms: i64,
@@ -30,94 +304,160 @@ pub enum EncoderError {
     FfmpegFailed(String),
     #[error("failed to read ffmpeg output file: {0}")]
     ReadOutput(String),
+    #[error("failed to stream segment to RustFS: {0}")]
+    Upload(String),
 }
 
 impl SegmentEncoder {
     /// Spawn an ffmpeg subprocess ready to receive MJPEG frames on stdin.
-    /// The output MP4 is written to a temp file at /tmp/segment_{start_ms}.mp4.
+    /// The output is written to a temp file at /tmp/segment_{start_ms}_{random}.{mp4,webm}.
+    ///
+    /// Encoding follows `config`: `crf`, `preset` and `fps`. With `fps_auto`,
+    /// ffmpeg is not spawned until the first second of frames has been seen,
+    /// so the input rate can be measured from their timestamps.
+    ///
+    /// With `thin_to_fps` below `fps`, frames are subsampled to that rate before
+    /// encoding and ffmpeg's `-r` is set to it, so playback speed stays correct.
+    ///
+    /// A non-zero `scale_height` downscales to that height, keeping the aspect
+    /// ratio. The output goes to `sink`.
     pub async fn start(
         start_ms: i64,
-        codec: &str,
-        crf: u32,
-        preset: &str,
-        fps: f64,
+        ffmpeg: &Ffmpeg,
+        video: &VideoEncoder,
+        config: &RecordingConfig,
+        sink: SegmentSink,
     ) -> Result<Self, EncoderError> {
-        let output_path = std::env::temp_dir().join(format!("segment_{start_ms}.mp4"));
-
-        let vcodec = match codec {
-            "h265" => "libx265",
-            _ => "libx264",
+        let input = InputKind::Mjpeg {
+            video: video.clone(),
+            crf: config.crf,
+            preset: config.preset.clone(),
+            scale_height: config.scale_height,
         };
+        let (fps, fps_auto) = (config.fps, config.fps_auto);
+        match config.thin_to_fps.filter(|t| *t > 0.0 && *t < fps) {
+            Some(thin_fps) => {
+                let mut encoder = Self::new(start_ms, ffmpeg, input, thin_fps, fps_auto, sink)?;
+                encoder.thinner = Some(FrameThinner::new(thin_fps));
+                Ok(encoder)
+            }
+            None => Self::new(start_ms, ffmpeg, input, fps, fps_auto, sink),
+        }
+    }
 
-        let fps_str = fps.to_string();
-        let crf_str = crf.to_string();
-        // Keyframe every 1 second (= fps frames) for sub-second scrubbing precision
-        let gop_str = (fps.round() as u32).max(1).to_string();
-
-        let mut cmd = Command::new("ffmpeg");
-        cmd.args([
-            "-f", "image2pipe",
-            "-vcodec", "mjpeg",
-            "-r", &fps_str,
-            "-i", "pipe:0",
-            "-c:v", vcodec,
-            "-preset", preset,
-            "-crf", &crf_str,
-            "-g", &gop_str,
-            "-movflags", "+faststart",
-            "-y",
-            output_path.to_str().unwrap(),
-        ])
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::piped());
-
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| EncoderError::Spawn(e.to_string()))?;
-
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| EncoderError::Spawn("could not get stdin handle".into()))?;
-
-        debug!(
-            codec = vcodec,
-            crf,
-            preset,
-            fps,
-            output = output_path.display().to_string(),
-            "ffmpeg encoder started"
-        );
+    /// Spawn an ffmpeg subprocess in passthrough mode for raw H.264 data.
+    /// No re-encoding — uses `-c:v copy` to mux H.264 access units into MP4.
+    pub async fn start_passthrough(
+        start_ms: i64,
+        ffmpeg: &Ffmpeg,
+        fps: f64,
+        fps_auto: bool,
+        sink: SegmentSink,
+    ) -> Result<Self, EncoderError> {
+        Self::new(start_ms, ffmpeg, InputKind::H264Passthrough, fps, fps_auto, sink)
+    }
 
-        Ok(Self {
-            child,
-            stdin,
-            output_path,
+    fn new(
+        start_ms: i64,
+        ffmpeg: &Ffmpeg,
+        input: InputKind,
+        fps: f64,
+        fps_auto: bool,
+        sink: SegmentSink,
+    ) -> Result<Self, EncoderError> {
+        let container = match &input {
+            InputKind::Mjpeg { video, .. } => video.container(),
+            InputKind::H264Passthrough => Container::Mp4,
+        };
+        let suffix = unique_suffix();
+        let mut encoder = Self {
+            process: None,
+            ffmpeg: ffmpeg.clone(),
+            input,
+            configured_fps: fps,
+            effective_fps: None,
+            probe: None,
+            thinner: None,
+            // Suffixed: several region encoders can start on the same frame.
+            output_path: std::env::temp_dir()
+                .join(format!("segment_{start_ms}_{suffix}.{}", container.extension())),
+            sink,
+            upload: None,
+            container,
             frame_count: 0,
             start_ms,
-        })
+            suffix,
+            wal: None,
+        };
+        if !fps_auto {
+            encoder.spawn(fps)?;
+        }
+        Ok(encoder)
     }
 
-    /// Spawn an ffmpeg subprocess in passthrough mode for raw H.264 data.
-    /// No re-encoding — uses `-c:v copy` to mux H.264 access units into MP4.
-    pub async fn start_passthrough(start_ms: i64, fps: f64) -> Result<Self, EncoderError> {
-        let output_path = std::env::temp_dir().join(format!("segment_{start_ms}.mp4"));
+    /// `recording.extra_ffmpeg_args`, muxer options and the output: the temp
+    /// file, or stdout when streaming (fragmented MP4, since the `+faststart`
+    /// rewrite needs a seekable file).
+    fn output_args(&self) -> Vec<String> {
+        let streaming = matches!(self.sink, SegmentSink::Multipart { .. });
+        let mut args: Vec<&str> = self.ffmpeg.extra_args.iter().map(String::as_str).collect();
+        args.extend(match (self.container, streaming) {
+            (Container::Mp4, false) => vec!["-movflags", "+faststart"],
+            (Container::Mp4, true) => vec!["-movflags", "frag_keyframe+empty_moov+default_base_moof"],
+            (Container::Webm, _) => Vec::new(),
+        });
+        let path = self.output_path.to_str().unwrap();
+        if streaming {
+            // ffmpeg's muxers are named like the extensions.
+            args.extend(["-f", self.container.extension(), "pipe:1"]);
+        } else {
+            args.extend(["-y", path]);
+        }
+        args.into_iter().map(String::from).collect()
+    }
+
+    fn spawn(&mut self, fps: f64) -> Result<(), EncoderError> {
         let fps_str = fps.to_string();
+        let output = match &self.sink {
+            SegmentSink::TempFile => self.output_path.to_str().unwrap().to_string(),
+            SegmentSink::Multipart { staging_key, .. } => staging_key.clone(),
+        };
 
-        let mut cmd = Command::new("ffmpeg");
-        cmd.args([
-            "-f", "h264",
-            "-r", &fps_str,
-            "-i", "pipe:0",
-            "-c:v", "copy",
-            "-movflags", "+faststart",
-            "-y",
-            output_path.to_str().unwrap(),
-        ])
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::piped());
+        let mut cmd = self.ffmpeg.command();
+        match &self.input {
+            InputKind::Mjpeg { video, crf, preset, scale_height } => {
+                // Keyframe every 1 second (= fps frames) for sub-second scrubbing precision
+                let gop = (fps.round() as u32).max(1);
+                cmd.args(video.device_args());
+                cmd.args([
+                    "-f", "image2pipe",
+                    "-vcodec", "mjpeg",
+                    "-r", &fps_str,
+                    "-i", "pipe:0",
+                ]);
+                cmd.args(video.filter_args(*scale_height));
+                cmd.args(video.codec_args(*crf, preset, gop));
+                cmd.args(self.output_args());
+                debug!(codec = video.name(), crf, preset, fps, output, "ffmpeg encoder started");
+            }
+            InputKind::H264Passthrough => {
+                cmd.args([
+                    "-f", "h264",
+                    "-r", &fps_str,
+                    "-i", "pipe:0",
+                    "-c:v", "copy",
+                ]);
+                cmd.args(self.output_args());
+                debug!(fps, output, "ffmpeg H.264 passthrough started");
+            }
+        }
+        let stdout = match self.sink {
+            SegmentSink::TempFile => std::process::Stdio::null(),
+            SegmentSink::Multipart { .. } => std::process::Stdio::piped(),
+        };
+        cmd.stdin(std::process::Stdio::piped())
+            .stdout(stdout)
+            .stderr(std::process::Stdio::piped());
 
         let mut child = cmd
             .spawn()
@@ -128,81 +468,217 @@ impl SegmentEncoder {
             .take()
             .ok_or_else(|| EncoderError::Spawn("could not get stdin handle".into()))?;
 
-        debug!(
-            fps,
-            output = output_path.display().to_string(),
-            "ffmpeg H.264 passthrough started"
-        );
+        if let SegmentSink::Multipart { storage, staging_key } = &self.sink {
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| EncoderError::Spawn("could not get stdout handle".into()))?;
+            let upload = stream_to_rustfs(
+                stdout,
+                Arc::clone(storage),
+                staging_key.clone(),
+                self.container.content_type(),
+            );
+            self.upload = Some(tokio::spawn(upload));
+        }
 
-        Ok(Self {
-            child,
-            stdin,
-            output_path,
-            frame_count: 0,
-            start_ms,
-        })
+        self.process = Some((child, stdin));
+        self.effective_fps = Some(fps);
+        Ok(())
     }
 
-    /// Write a single JPEG frame to ffmpeg's stdin pipe.
-    pub async fn push_frame(&mut self, jpeg_data: &[u8]) -> Result<(), EncoderError> {
-        self.stdin
-            .write_all(jpeg_data)
-            .await
-            .map_err(|e| EncoderError::Write(e.to_string()))?;
-        self.frame_count += 1;
+    /// Write a single JPEG frame to ffmpeg's stdin pipe. Returns `false` if
+    /// the frame was dropped by thinning.
+    pub async fn push_frame(&mut self, jpeg_data: &[u8], captured_at_ms: i64) -> Result<bool, EncoderError> {
+        if let Some(thinner) = &mut self.thinner {
+            if !thinner.admit(captured_at_ms) {
+                return Ok(false);
+            }
+        }
+        self.push(jpeg_data, captured_at_ms).await?;
         debug!(frame_count = self.frame_count, "pushed frame to encoder");
-        Ok(())
+        Ok(true)
     }
 
     /// Write a raw H.264 access unit (Annex B) to ffmpeg's stdin pipe.
-    pub async fn push_h264(&mut self, h264_data: &[u8]) -> Result<(), EncoderError> {
-        self.stdin
-            .write_all(h264_data)
-            .await
-            .map_err(|e| EncoderError::Write(e.to_string()))?;
-        self.frame_count += 1;
+    pub async fn push_h264(&mut self, h264_data: &[u8], captured_at_ms: i64) -> Result<(), EncoderError> {
+        self.push(h264_data, captured_at_ms).await?;
         debug!(frame_count = self.frame_count, "pushed H.264 AU to encoder");
         Ok(())
     }
 
-    /// Finalize the segment: close stdin, wait for ffmpeg to finish, read the output MP4.
+    async fn push(&mut self, data: &[u8], captured_at_ms: i64) -> Result<(), EncoderError> {
+        self.log_to_wal(data, captured_at_ms);
+        if self.process.is_none() {
+            // Probing the frame rate: buffer until the window is covered.
+            let probe = self.probe.get_or_insert_with(|| FpsProbe {
+                frames: Vec::new(),
+                first_ts: captured_at_ms,
+                last_ts: captured_at_ms,
+            });
+            probe.frames.push(data.to_vec());
+            probe.last_ts = captured_at_ms;
+            self.frame_count += 1;
+            if probe.last_ts - probe.first_ts >= FPS_PROBE_WINDOW_MS {
+                self.start_from_probe().await?;
+            }
+            return Ok(());
+        }
+
+        self.write(data).await?;
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), EncoderError> {
+        let (_, stdin) = self.process.as_mut().expect("ffmpeg process running");
+        stdin
+            .write_all(data)
+            .await
+            .map_err(|e| EncoderError::Write(e.to_string()))
+    }
+
+    /// End the fps probe: spawn ffmpeg at the measured rate (or the configured
+    /// rate if there were too few samples) and flush the buffered frames.
+    async fn start_from_probe(&mut self) -> Result<(), EncoderError> {
+        let Some(probe) = self.probe.take() else {
+            return Ok(());
+        };
+        let fps = match estimate_fps(probe.first_ts, probe.last_ts, probe.frames.len()) {
+            Some(measured) => {
+                info!(
+                    measured_fps = format!("{measured:.2}"),
+                    configured_fps = self.configured_fps,
+                    frames = probe.frames.len(),
+                    "fps_auto: using measured input rate"
+                );
+                measured
+            }
+            None => {
+                debug!(
+                    frames = probe.frames.len(),
+                    configured_fps = self.configured_fps,
+                    "fps_auto: too few samples, using configured fps"
+                );
+                self.configured_fps
+            }
+        };
+        self.spawn(fps)?;
+        for frame in &probe.frames {
+            self.write(frame).await?;
+        }
+        Ok(())
+    }
+
+    /// Finalize the segment: close stdin, wait for ffmpeg to finish, read the output file.
     /// Deletes the temp file after reading.
-    pub async fn finish(self) -> Result<FinishedSegment, EncoderError> {
+    pub async fn finish(mut self) -> Result<FinishedSegment, EncoderError> {
+        // Segment ended before the fps probe window was covered.
+        if self.process.is_none() {
+            self.start_from_probe().await?;
+        }
+        let (mut child, stdin) = self.process.take().expect("ffmpeg process running");
+
         // Close stdin so ffmpeg knows there are no more frames.
-        drop(self.stdin);
+        drop(stdin);
 
-        let output = self
-            .child
-            .wait_with_output()
-            .await
-            .map_err(|e| EncoderError::Wait(e.to_string()))?;
+        // Drain stderr alongside, so a chatty ffmpeg can't block on it.
+        let mut stderr_pipe = child.stderr.take();
+        let stderr = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            if let Some(pipe) = &mut stderr_pipe {
+                let _ = pipe.read_to_end(&mut buf).await;
+            }
+            buf
+        });
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!(stderr = %stderr, "ffmpeg exited with error");
-            // Clean up temp file on failure
-            let _ = tokio::fs::remove_file(&self.output_path).await;
-            return Err(EncoderError::FfmpegFailed(stderr.into_owned()));
+        // A hung ffmpeg (e.g. on corrupt input) would otherwise hold the
+        // recorder forever. `None`: killed after `encode_timeout`.
+        let status = match self.ffmpeg.encode_timeout {
+            Some(limit) => match tokio::time::timeout(limit, child.wait()).await {
+                Ok(status) => Some(status),
+                Err(_) => {
+                    error!(
+                        timeout_secs = limit.as_secs(),
+                        start_ms = self.start_ms,
+                        "ffmpeg did not finish within recording.encode_timeout_secs, killing it"
+                    );
+                    if let Err(e) = child.kill().await {
+                        warn!(error = %e, "failed to kill ffmpeg");
+                    }
+                    None
+                }
+            },
+            None => Some(child.wait().await),
         }
+        .transpose()
+        .map_err(|e| EncoderError::Wait(e.to_string()))?;
+        let stderr = stderr.await.unwrap_or_default();
 
-        let mp4_bytes = tokio::fs::read(&self.output_path)
-            .await
-            .map_err(|e| EncoderError::ReadOutput(e.to_string()))?;
+        // The stream upload ends with ffmpeg's stdout.
+        let uploaded = match self.upload.take() {
+            Some(upload) => Some(
+                upload
+                    .await
+                    .map_err(|e| EncoderError::Upload(e.to_string()))
+                    .and_then(|r| r.map_err(|e| EncoderError::Upload(e.to_string()))),
+            ),
+            None => None,
+        };
 
-        // Delete temp file
-        if let Err(e) = tokio::fs::remove_file(&self.output_path).await {
-            warn!(path = self.output_path.display().to_string(), error = %e, "failed to delete temp segment file");
+        let failure = match status {
+            None => Some("timeout".to_string()),
+            Some(status) if !status.success() => {
+                let stderr = String::from_utf8_lossy(&stderr).into_owned();
+                error!(stderr = %stderr, "ffmpeg exited with error");
+                Some(stderr)
+            }
+            Some(_) => None,
+        };
+        if let Some(failure) = failure {
+            // Clean up the temp file or the partial upload on failure
+            match (&self.sink, uploaded) {
+                (SegmentSink::Multipart { storage, staging_key }, Some(Ok(_))) => {
+                    storage.discard_staged(staging_key).await;
+                }
+                (SegmentSink::Multipart { .. }, _) => {}
+                (SegmentSink::TempFile, _) => {
+                    let _ = tokio::fs::remove_file(&self.output_path).await;
+                }
+            }
+            return Err(EncoderError::FfmpegFailed(failure));
         }
 
+        let output = match (&self.sink, uploaded) {
+            (SegmentSink::Multipart { staging_key, .. }, Some(size)) => SegmentOutput::Staged {
+                staging_key: staging_key.clone(),
+                size: size?,
+            },
+            _ => {
+                let video_bytes = tokio::fs::read(&self.output_path)
+                    .await
+                    .map_err(|e| EncoderError::ReadOutput(e.to_string()))?;
+
+                // Delete temp file
+                if let Err(e) = tokio::fs::remove_file(&self.output_path).await {
+                    warn!(path = self.output_path.display().to_string(), error = %e, "failed to delete temp segment file");
+                }
+                SegmentOutput::Bytes(video_bytes)
+            }
+        };
+
         info!(
             frame_count = self.frame_count,
-            bytes = mp4_bytes.len(),
+            bytes = output.size(),
             start_ms = self.start_ms,
+            fps = self.effective_fps,
+            streamed = matches!(output, SegmentOutput::Staged { .. }),
             "segment encoding complete"
         );
 
         Ok(FinishedSegment {
-            mp4_bytes,
+            output,
+            container: self.container,
             frame_count: self.frame_count,
             start_ms: self.start_ms,
         })
@@ -211,23 +687,242 @@ impl SegmentEncoder {
     pub fn frame_count(&self) -> u32 {
         self.frame_count
     }
+
+    /// Log every frame pushed from now on to `wal`.
+    pub fn attach_wal(&mut self, wal: SegmentWal) {
+        self.wal = Some(wal);
+    }
+
+    /// Detach the segment's WAL, e.g. to delete it once the segment is stored.
+    pub fn take_wal(&mut self) -> Option<SegmentWal> {
+        self.wal.take()
+    }
+
+    /// Append a frame to the WAL. On a write error the WAL is dropped (and left
+    /// on disk for recovery): the segment keeps recording without durability.
+    fn log_to_wal(&mut self, data: &[u8], captured_at_ms: i64) {
+        let Some(wal) = &mut self.wal else {
+            return;
+        };
+        if let Err(e) = wal.append(captured_at_ms, data) {
+            warn!(
+                path = wal.path().display().to_string(),
+                error = %e,
+                start_ms = self.start_ms,
+                "failed to append to segment WAL, continuing without it"
+            );
+            self.wal = None;
+        }
+    }
 }
 
-/// Check whether ffmpeg is available on PATH. Logs a warning if not found.
-pub async fn check_ffmpeg_available() {
-    match Command::new("ffmpeg").arg("-version").output().await {
+/// Effective frame rate from `frames` frames captured between `first_ts` and `last_ts`.
+/// Returns `None` when there are too few samples for a meaningful estimate.
+fn estimate_fps(first_ts: i64, last_ts: i64, frames: usize) -> Option<f64> {
+    let span_ms = last_ts - first_ts;
+    if frames < FPS_PROBE_MIN_FRAMES || span_ms <= 0 {
+        return None;
+    }
+    let fps = (frames - 1) as f64 * 1000.0 / span_ms as f64;
+    Some(fps.clamp(1.0, 120.0))
+}
+
+/// Check that an explicit `recording.ffmpeg_path` (one containing a path
+/// separator) is an existing file. Bare names are looked up on PATH when
+/// ffmpeg runs, see [`check_ffmpeg_available`].
+pub fn validate_ffmpeg_path(path: &str) -> Result<(), String> {
+    if !path.contains(std::path::MAIN_SEPARATOR) {
+        return Ok(());
+    }
+    match Path::new(path).metadata() {
+        Ok(meta) if meta.is_file() => Ok(()),
+        Ok(_) => Err(format!("{path} is not a file")),
+        Err(e) => Err(format!("{path}: {e}")),
+    }
+}
+
+/// Check whether ffmpeg can be run. Logs a warning if not found.
+pub async fn check_ffmpeg_available(ffmpeg: &Ffmpeg) {
+    match ffmpeg.command().arg("-version").output().await {
         Ok(out) if out.status.success() => {
-            debug!("ffmpeg is available");
+            debug!(path = ffmpeg.path, "ffmpeg is available");
         }
         Ok(_) => {
-            warn!("ffmpeg returned non-zero for -version; encoding may fail");
+            warn!(path = ffmpeg.path, "ffmpeg returned non-zero for -version; encoding may fail");
         }
         Err(e) => {
             warn!(
+                path = ffmpeg.path,
                 error = %e,
-                "ffmpeg not found on PATH; ACTIVE mode encoding will fail. \
-                 Install ffmpeg with libx264/libx265 support."
+                "ffmpeg not found; ACTIVE mode encoding will fail. \
+                 Install ffmpeg with libx264/libx265 support or set recording.ffmpeg_path."
             );
         }
     }
 }
+
+/// Copy ffmpeg's `stdout` into a multipart upload at `staging_key`. On an
+/// error the upload is aborted and `stdout` closed, so ffmpeg fails too.
+async fn stream_to_rustfs(
+    mut stdout: ChildStdout,
+    storage: Arc<RustfsStorage>,
+    staging_key: String,
+    content_type: &'static str,
+) -> Result<u64, StorageError> {
+    let mut upload = storage.start_multipart(&staging_key, content_type).await?;
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        let read = match stdout.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                upload.abort().await;
+                return Err(StorageError::Multipart(format!("reading ffmpeg output: {e}")));
+            }
+        };
+        if let Err(e) = upload.write(&chunk[..read]).await {
+            error!(key = staging_key, error = %e, "streaming segment to RustFS failed");
+            upload.abort().await;
+            return Err(e);
+        }
+    }
+    upload.complete().await
+}
+
+/// Whether `video` works on this machine. A hardware encoder can be built
+/// into ffmpeg without a usable GPU or driver, so this encodes one test frame
+/// rather than just listing encoders.
+pub async fn check_encoder_available(ffmpeg: &Ffmpeg, video: &VideoEncoder) -> bool {
+    let out = ffmpeg
+        .command()
+        .args(["-hide_banner", "-v", "error"])
+        .args(video.device_args())
+        .args(["-f", "lavfi", "-i", "color=c=black:s=256x256:d=0.1", "-frames:v", "1"])
+        .args(video.filter_args(0))
+        .args(video.codec_args(23, "veryfast", 1))
+        .args(["-f", "null", "-"])
+        .output()
+        .await;
+    match out {
+        Ok(out) if out.status.success() => true,
+        Ok(out) => {
+            debug!(encoder = video.name(), stderr = %String::from_utf8_lossy(&out.stderr), "test encode failed");
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_encoder_follows_codec_and_hwaccel() {
+        let encoder = |codec: &str, hwaccel| {
+            VideoEncoder::from_config(&RecordingConfig {
+                codec: codec.into(),
+                hwaccel,
+                ..Default::default()
+            })
+        };
+        assert_eq!(encoder("h264", HwAccel::None), VideoEncoder::Software("libx264"));
+        assert_eq!(encoder("h265", HwAccel::Nvenc), VideoEncoder::Nvenc("hevc_nvenc"));
+        // An explicit encoder name wins over `hwaccel`.
+        assert_eq!(encoder("h264_nvenc", HwAccel::None), VideoEncoder::Nvenc("h264_nvenc"));
+
+        let vaapi = encoder("h265", HwAccel::Vaapi);
+        assert_eq!(vaapi.name(), "hevc_vaapi");
+        assert_eq!(vaapi.device_args(), ["-vaapi_device", "/dev/dri/renderD128"]);
+        assert_eq!(vaapi.codec_args(28, "fast", 10), ["-c:v", "hevc_vaapi", "-qp", "28", "-g", "10"]);
+        assert_eq!(vaapi.filter_args(0), ["-vf", "format=nv12,hwupload"]);
+        assert_eq!(vaapi.software(), VideoEncoder::Software("libx265"));
+
+        // VP9 and AV1 ignore `hwaccel`; only VP9 changes the container.
+        let vp9 = encoder("vp9", HwAccel::Nvenc);
+        assert_eq!(vp9, VideoEncoder::Software("libvpx-vp9"));
+        assert_eq!(vp9.software(), vp9);
+        assert_eq!(vp9.container(), Container::Webm);
+        assert_eq!(encoder("av1", HwAccel::None).container(), Container::Mp4);
+        assert_eq!(encoder("h264", HwAccel::Vaapi).container(), Container::Mp4);
+    }
+
+    #[test]
+    fn scaling_precedes_the_vaapi_upload() {
+        let software = VideoEncoder::Software("libx264");
+        assert!(software.filter_args(0).is_empty());
+        assert_eq!(software.filter_args(720), ["-vf", "scale=-2:720"]);
+        assert_eq!(software.filter_args(481), ["-vf", "scale=-2:480"]);
+
+        let vaapi = VideoEncoder::Vaapi {
+            name: "h264_vaapi",
+            device: "/dev/dri/renderD128".into(),
+        };
+        assert_eq!(vaapi.filter_args(720), ["-vf", "scale=-2:720,format=nv12,hwupload"]);
+    }
+
+    #[test]
+    fn estimate_fps_from_timestamps() {
+        // 8 frames over 1s = 7 intervals → 7 fps
+        let fps = estimate_fps(0, 1000, 8).unwrap();
+        assert!((fps - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn estimate_fps_needs_enough_samples() {
+        assert!(estimate_fps(0, 1000, FPS_PROBE_MIN_FRAMES - 1).is_none());
+        // All frames share a timestamp: no span to measure.
+        assert!(estimate_fps(500, 500, 30).is_none());
+    }
+
+    #[test]
+    fn thinner_keeps_target_rate() {
+        // 30 fps for 10 s thinned to 2 fps.
+        let mut thinner = FrameThinner::new(2.0);
+        let kept = (0..300).filter(|i| thinner.admit(i * 1000 / 30)).count();
+        assert_eq!(kept, 20);
+    }
+
+    fn ffmpeg() -> Ffmpeg {
+        Ffmpeg::from_config(&RecordingConfig::default())
+    }
+
+    #[test]
+    fn explicit_ffmpeg_path_must_be_a_file() {
+        assert!(validate_ffmpeg_path("ffmpeg").is_ok(), "bare names are looked up on PATH");
+        assert!(validate_ffmpeg_path("/nonexistent/bin/ffmpeg").is_err());
+        let dir = std::env::temp_dir();
+        assert!(validate_ffmpeg_path(dir.to_str().unwrap()).is_err());
+        let exe = std::env::current_exe().unwrap();
+        assert!(validate_ffmpeg_path(exe.to_str().unwrap()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn thinning_reduces_frame_count() {
+        // fps_auto buffers the first second without spawning ffmpeg, so the
+        // count can be checked without it.
+        let config = RecordingConfig {
+            fps: 30.0,
+            fps_auto: true,
+            thin_to_fps: Some(10.0),
+            ..RecordingConfig::default()
+        };
+        let mut encoder = SegmentEncoder::start(0, &ffmpeg(), &VideoEncoder::Software("libx264"), &config, SegmentSink::TempFile)
+            .await
+            .unwrap();
+        let mut kept = 0;
+        for i in 0..29 {
+            if encoder.push_frame(b"jpeg", i * 1000 / 30).await.unwrap() {
+                kept += 1;
+            }
+        }
+        assert_eq!(kept, 10);
+        assert_eq!(encoder.frame_count(), 10);
+        assert_eq!(encoder.configured_fps, 10.0);
+    }
+
+    #[test]
+    fn estimate_fps_is_clamped() {
+        assert_eq!(estimate_fps(0, 10, 50), Some(120.0));
+    }
+}
@@ -1,4 +1,5 @@
 use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 
 fn fmt_ts(ms: i64) -> String {
     let dt: DateTime<Utc> = Utc
@@ -8,36 +9,97 @@ fn fmt_ts(ms: i64) -> String {
     dt.format("%Y%m%dT%H%M%S%3fZ").to_string()
 }
 
-fn date_str(ms: i64) -> String {
+/// Date directory for a key: the local calendar date in `tz`, so a day's
+/// footage lines up with the robot's local midnight. Filename timestamps
+/// stay in UTC for unambiguous ordering.
+fn date_str(ms: i64, tz: Tz) -> String {
     let dt: DateTime<Utc> = Utc
         .timestamp_millis_opt(ms)
         .single()
         .unwrap_or_else(Utc::now);
-    dt.format("%Y-%m-%d").to_string()
+    dt.with_timezone(&tz).format("%Y-%m-%d").to_string()
+}
+
+/// Random suffix appended to segment keys so two segments with the same
+/// start/end (restart replay, several cameras per robot) don't overwrite each
+/// other. It follows the timestamps, so keys still sort by start time. A
+/// segment draws its suffix when its encoder starts, and its raw frames reuse it.
+pub fn unique_suffix() -> String {
+    format!("{:08x}", fastrand::u32(..))
 }
 
 /// Key for the idle period's representative JPEG frame.
-/// e.g. "frames/reachy-001/camera/2026-02-18/20260218T093000000Z_20260218T094000000Z.jpg"
-pub fn idle_jpeg_key(prefix: &str, robot_id: &str, start_ms: i64, end_ms: i64) -> String {
+/// e.g. "frames/reachy-001/camera/2026-02-18/20260218T093000000Z_20260218T094000000Z_3f9a01c2.jpg"
+pub fn idle_jpeg_key(prefix: &str, robot_id: &str, start_ms: i64, end_ms: i64, tz: Tz) -> String {
     format!(
-        "{prefix}{robot_id}/camera/{date}/{start}_{end}.jpg",
-        date = date_str(start_ms),
+        "{prefix}{robot_id}/camera/{date}/{start}_{end}_{suffix}.jpg",
+        date = date_str(start_ms, tz),
         start = fmt_ts(start_ms),
         end = fmt_ts(end_ms),
+        suffix = unique_suffix(),
     )
 }
 
-/// Key for an active video segment MP4.
-/// e.g. "frames/reachy-001/camera/2026-02-18/20260218T094000000Z_20260218T095000000Z.mp4"
-pub fn active_segment_key(prefix: &str, robot_id: &str, start_ms: i64, end_ms: i64) -> String {
+/// Key for an active video segment, with the segment's [`unique_suffix`] and
+/// the container's extension (`mp4`, `webm`).
+/// e.g. "frames/reachy-001/camera/2026-02-18/20260218T094000000Z_20260218T095000000Z_b41e77d0.mp4"
+pub fn active_segment_key(
+    prefix: &str,
+    robot_id: &str,
+    start_ms: i64,
+    end_ms: i64,
+    suffix: &str,
+    ext: &str,
+    tz: Tz,
+) -> String {
     format!(
-        "{prefix}{robot_id}/camera/{date}/{start}_{end}.mp4",
-        date = date_str(start_ms),
+        "{prefix}{robot_id}/camera/{date}/{start}_{end}_{suffix}.{ext}",
+        date = date_str(start_ms, tz),
         start = fmt_ts(start_ms),
         end = fmt_ts(end_ms),
     )
 }
 
+/// Staging key of a segment streamed to RustFS while it's encoded
+/// (`recording.stream_upload`), until its end and so its final key are known.
+/// e.g. "frames/reachy-001/camera/2026-02-18/staging/20260218T094000000Z_b41e77d0.mp4"
+pub fn staging_segment_key(prefix: &str, robot_id: &str, start_ms: i64, ext: &str, tz: Tz) -> String {
+    format!(
+        "{prefix}{robot_id}/camera/{date}/staging/{start}_{suffix}.{ext}",
+        date = date_str(start_ms, tz),
+        start = fmt_ts(start_ms),
+        suffix = unique_suffix(),
+    )
+}
+
+/// A raw source frame kept for debugging, and the segment it was encoded into.
+pub struct RawFrame<'a> {
+    pub segment_start_ms: i64,
+    /// The segment key's [`unique_suffix`].
+    pub segment_suffix: &'a str,
+    pub frame_index: u32,
+    pub ext: &'a str,
+    /// The motion region's id, when recording per region.
+    pub region: Option<&'a str>,
+}
+
+/// Key for a raw source frame kept for debugging (`recording.keep_raw_frames`).
+/// Frames are grouped by the start timestamp and suffix of the segment they were
+/// encoded into, under the motion region's id when recording per region.
+/// e.g. "frames/reachy-001/camera/2026-02-18/raw/20260218T094000000Z_b41e77d0/000042.jpg"
+/// or   "frames/reachy-001/camera/2026-02-18/raw/bench/20260218T094000000Z_b41e77d0/000042.jpg"
+pub fn raw_frame_key(prefix: &str, robot_id: &str, frame: &RawFrame, tz: Tz) -> String {
+    let region_dir = frame.region.map(|r| format!("{r}/")).unwrap_or_default();
+    format!(
+        "{prefix}{robot_id}/camera/{date}/raw/{region_dir}{start}_{suffix}/{index:06}.{ext}",
+        date = date_str(frame.segment_start_ms, tz),
+        start = fmt_ts(frame.segment_start_ms),
+        suffix = frame.segment_suffix,
+        index = frame.frame_index,
+        ext = frame.ext,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,11 +110,11 @@ mod tests {
         let start = 1739871000000i64;
         let end = start + 60_000; // +60s
 
-        let k = idle_jpeg_key("frames/", "reachy-001", start, end);
+        let k = idle_jpeg_key("frames/", "reachy-001", start, end, Tz::UTC);
         assert!(k.ends_with(".jpg"), "idle jpeg key should end with .jpg");
         assert!(k.contains("reachy-001/camera/"), "should have robot/camera path");
 
-        let k2 = active_segment_key("frames/", "reachy-001", start, end);
+        let k2 = active_segment_key("frames/", "reachy-001", start, end, "b41e77d0", "mp4", Tz::UTC);
         assert!(k2.ends_with(".mp4"), "active key should end with .mp4");
 
         // Both share the same date directory
@@ -60,4 +122,100 @@ mod tests {
         let date_part2 = &k2[..k2.rfind('/').unwrap()];
         assert_eq!(date_part, date_part2, "idle and active share the same date dir");
     }
+
+    #[test]
+    fn active_key_uses_container_extension() {
+        let start = 1739871000000i64;
+        let webm = active_segment_key("frames/", "reachy-001", start, start + 60_000, "b41e77d0", "webm", Tz::UTC);
+        assert!(webm.ends_with("/20250218T093000000Z_20250218T093100000Z_b41e77d0.webm"), "{webm}");
+        assert_eq!(crate::storage::parse_start_ms_from_key(&webm), Some(start));
+
+        let mp4 = active_segment_key("frames/", "reachy-001", start, start + 60_000, "b41e77d0", "mp4", Tz::UTC);
+        assert_eq!(webm[..webm.rfind('/').unwrap()], mp4[..mp4.rfind('/').unwrap()]);
+    }
+
+    #[test]
+    fn staging_key_sits_beside_the_segment() {
+        let start = 1739871000000i64;
+        let staging = staging_segment_key("frames/", "reachy-001", start, "webm", Tz::UTC);
+        assert!(staging.contains("/camera/2025-02-18/staging/20250218T093000000Z_"), "{staging}");
+        assert!(staging.ends_with(".webm"));
+        // A staging object left by a crash is still evicted by its start time.
+        assert_eq!(crate::storage::parse_start_ms_from_key(&staging), Some(start));
+    }
+
+    #[test]
+    fn same_start_segments_get_distinct_keys() {
+        let start = 1739871000000i64;
+        let a = active_segment_key("frames/", "reachy-001", start, start + 60_000, &unique_suffix(), "mp4", Tz::UTC);
+        let b = active_segment_key("frames/", "reachy-001", start, start + 60_000, &unique_suffix(), "mp4", Tz::UTC);
+        assert_ne!(a, b);
+
+        let i1 = idle_jpeg_key("frames/", "reachy-001", start, start + 60_000, Tz::UTC);
+        let i2 = idle_jpeg_key("frames/", "reachy-001", start, start + 60_000, Tz::UTC);
+        assert_ne!(i1, i2);
+
+        // The start time is still recoverable from the key.
+        assert_eq!(crate::storage::parse_start_ms_from_key(&a), Some(start));
+        assert_eq!(crate::storage::parse_start_ms_from_key(&i1), Some(start));
+    }
+
+    #[test]
+    fn test_raw_frame_key() {
+        let start = 1739871000000i64;
+        let frame = RawFrame {
+            segment_start_ms: start,
+            segment_suffix: "b41e77d0",
+            frame_index: 42,
+            ext: "h264",
+            region: None,
+        };
+        let k = raw_frame_key("frames/", "reachy-001", &frame, Tz::UTC);
+        assert!(k.contains("reachy-001/camera/"), "should have robot/camera path");
+        assert!(k.contains("/raw/20250218T093000000Z_b41e77d0/"), "raw frames live under a raw/ sub-prefix: {k}");
+        assert!(k.ends_with("/000042.h264"));
+
+        // Raw frames share the date directory of their segment
+        let seg = active_segment_key("frames/", "reachy-001", start, start + 60_000, "b41e77d0", "mp4", Tz::UTC);
+        let seg_dir = &seg[..seg.rfind('/').unwrap()];
+        assert!(k.starts_with(seg_dir));
+
+        // Regions starting on the same frame keep their raw frames apart.
+        let region = RawFrame {
+            region: Some("bench"),
+            ..frame
+        };
+        let r = raw_frame_key("frames/", "reachy-001", &region, Tz::UTC);
+        assert!(r.contains("/raw/bench/20250218T093000000Z_b41e77d0/"), "{r}");
+
+        // So do same-start segments of one machine (e.g. a restart replay).
+        let other = RawFrame {
+            segment_suffix: "3f9a01c2",
+            ..frame
+        };
+        let other = raw_frame_key("frames/", "reachy-001", &other, Tz::UTC);
+        assert_ne!(k, other);
+    }
+
+    #[test]
+    fn date_dir_follows_timezone_across_midnight() {
+        // 2026-02-18T02:30:00Z is still 2026-02-17 (21:30) in New York.
+        let start = 1771381800000i64;
+        let utc = active_segment_key("frames/", "reachy-001", start, start + 60_000, "b41e77d0", "mp4", Tz::UTC);
+        let ny = active_segment_key(
+            "frames/",
+            "reachy-001",
+            start,
+            start + 60_000,
+            "b41e77d0",
+            "mp4",
+            chrono_tz::America::New_York,
+        );
+        assert!(utc.contains("/camera/2026-02-18/"));
+        assert!(ny.contains("/camera/2026-02-17/"));
+
+        // Filenames keep UTC timestamps regardless of the date directory.
+        assert!(ny.contains("/20260218T023000000Z_20260218T023100000Z_"));
+        assert_eq!(crate::storage::parse_start_ms_from_key(&ny), Some(start));
+    }
 }
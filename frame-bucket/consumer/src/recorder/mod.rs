@@ -1,5 +1,12 @@
 pub mod encoder;
 pub mod keys;
+pub mod preroll;
+pub mod regions;
+pub mod reorder;
+pub mod robots;
 pub mod state;
+pub mod wal;
 
+pub use regions::RegionRecorder;
+pub use robots::RobotRecorders;
 pub use state::RecordingStateMachine;
@@ -0,0 +1,99 @@
+//! Frames kept while idle, to start active segments before the motion
+//! (`recording.preroll_frames`).
+//!
+//! The scene-change check fires once motion is already visible, so the new
+//! segment is fed the last few idle frames first. H.264 passthrough can only
+//! start at a keyframe: the buffer then holds the frames since the latest
+//! keyframe, and is emptied when that GOP outgrows the buffer.
+
+use std::collections::VecDeque;
+
+pub struct Preroll {
+    capacity: usize,
+    /// `(captured_at_ms, payload)`, oldest first.
+    frames: VecDeque<(i64, Vec<u8>)>,
+}
+
+impl Preroll {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Keep a JPEG frame, dropping the oldest one when full.
+    pub fn push_jpeg(&mut self, captured_at_ms: i64, jpeg_data: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((captured_at_ms, jpeg_data.to_vec()));
+    }
+
+    /// Keep an H.264 access unit. A keyframe starts the buffer over; other
+    /// frames are only kept after one, while the GOP fits.
+    pub fn push_h264(&mut self, captured_at_ms: i64, h264_data: &[u8], keyframe: bool) {
+        if self.capacity == 0 {
+            return;
+        }
+        if keyframe {
+            self.frames.clear();
+        } else if self.frames.is_empty() {
+            return;
+        } else if self.frames.len() == self.capacity {
+            // Dropping the keyframe would leave undecodable frames.
+            self.frames.clear();
+            return;
+        }
+        self.frames.push_back((captured_at_ms, h264_data.to_vec()));
+    }
+
+    /// The kept frames, oldest first, leaving the buffer empty.
+    pub fn take(&mut self) -> Vec<(i64, Vec<u8>)> {
+        self.frames.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamps(frames: Vec<(i64, Vec<u8>)>) -> Vec<i64> {
+        frames.into_iter().map(|(ts, _)| ts).collect()
+    }
+
+    #[test]
+    fn jpeg_keeps_the_latest_frames() {
+        let mut preroll = Preroll::new(3);
+        for ts in 1..=5 {
+            preroll.push_jpeg(ts, b"jpeg");
+        }
+        assert_eq!(timestamps(preroll.take()), [3, 4, 5]);
+        assert!(preroll.take().is_empty());
+
+        let mut disabled = Preroll::new(0);
+        disabled.push_jpeg(1, b"jpeg");
+        assert!(disabled.take().is_empty());
+    }
+
+    #[test]
+    fn h264_starts_at_a_keyframe() {
+        let mut preroll = Preroll::new(3);
+        preroll.push_h264(1, b"p", false);
+        preroll.push_h264(2, b"idr", true);
+        preroll.push_h264(3, b"p", false);
+        assert_eq!(timestamps(preroll.take()), [2, 3]);
+
+        // A GOP longer than the buffer leaves nothing until the next keyframe.
+        for ts in 10..=13 {
+            preroll.push_h264(ts, b"frame", ts == 10);
+        }
+        assert!(preroll.take().is_empty());
+        preroll.push_h264(14, b"p", false);
+        preroll.push_h264(15, b"idr", true);
+        assert_eq!(timestamps(preroll.take()), [15]);
+    }
+}
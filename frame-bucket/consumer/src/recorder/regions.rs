@@ -0,0 +1,215 @@
+//! Per-region recording (`recording.regions`).
+//!
+//! Each configured rectangle gets its own [`RecordingStateMachine`] fed with
+//! JPEG crops of that rectangle, so simultaneous activity in two parts of a
+//! wide view produces independent segments tagged with their region id.
+
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use frame_bucket_common::config::RegionConfig;
+use frame_bucket_common::frame::{FramePayload, TimestampedFrame};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, GenericImageView};
+use tracing::{info, warn};
+
+use super::state::RecordingStateMachine;
+
+/// Quality for re-encoded region crops. They go straight back into an
+/// x264/x265 encode, so there's little point going higher.
+const CROP_JPEG_QUALITY: u8 = 90;
+
+struct RegionMachine {
+    region: RegionConfig,
+    machine: RecordingStateMachine,
+    /// Logged once when the region lies outside the incoming frames.
+    warned_outside: bool,
+}
+
+/// Routes frames to the whole-frame state machine or to one machine per region.
+pub struct RegionRecorder {
+    /// Records everything when no regions are configured, and H.264 frames
+    /// (which can't be cropped without decoding) when they are.
+    whole_frame: RecordingStateMachine,
+    regions: Vec<RegionMachine>,
+    warned_h264: bool,
+}
+
+impl RegionRecorder {
+    /// `build` creates a state machine for a region id (`None` = whole frame).
+    pub fn new(
+        regions: &[RegionConfig],
+        mut build: impl FnMut(Option<String>) -> RecordingStateMachine,
+    ) -> Self {
+        let regions: Vec<RegionMachine> = usable_regions(regions)
+            .into_iter()
+            .map(|region| {
+                info!(
+                    region = region.id,
+                    x = region.x,
+                    y = region.y,
+                    width = region.width,
+                    height = region.height,
+                    "recording motion region"
+                );
+                RegionMachine {
+                    machine: build(Some(region.id.clone())),
+                    region,
+                    warned_outside: false,
+                }
+            })
+            .collect();
+        Self {
+            whole_frame: build(None),
+            regions,
+            warned_h264: false,
+        }
+    }
+
+    /// Finalize segments left in the WAL by a crash, for every machine.
+    pub async fn recover_wal(&self) {
+        self.whole_frame.recover_wal().await;
+        for rm in &self.regions {
+            rm.machine.recover_wal().await;
+        }
+    }
+
+    /// Finalize the open segment or idle period of every machine.
+    pub async fn flush(&mut self) {
+        self.whole_frame.flush().await;
+        for rm in &mut self.regions {
+            rm.machine.flush().await;
+        }
+    }
+
+    pub async fn process_frame(&mut self, frame: &TimestampedFrame) {
+        if self.regions.is_empty() {
+            self.whole_frame.process_frame(frame).await;
+            return;
+        }
+
+        let jpeg_data = match &frame.payload {
+            FramePayload::Jpeg(data) => data,
+            FramePayload::H264 { .. } => {
+                if !self.warned_h264 {
+                    warn!("recording.regions only applies to JPEG input; recording H.264 whole-frame");
+                    self.warned_h264 = true;
+                }
+                self.whole_frame.process_frame(frame).await;
+                return;
+            }
+        };
+
+        let img = match image::load_from_memory(jpeg_data) {
+            Ok(img) => img,
+            Err(e) => {
+                warn!(error = %e, ts = frame.captured_at_ms, "failed to decode frame for region crops, skipping");
+                return;
+            }
+        };
+
+        for rm in &mut self.regions {
+            let Some(crop) = crop_jpeg(&img, &rm.region) else {
+                if !rm.warned_outside {
+                    warn!(
+                        region = rm.region.id,
+                        frame_width = img.width(),
+                        frame_height = img.height(),
+                        "region lies outside the frame, skipping it"
+                    );
+                    rm.warned_outside = true;
+                }
+                continue;
+            };
+            let cropped = TimestampedFrame {
+                payload: FramePayload::Jpeg(crop),
+                captured_at_ms: frame.captured_at_ms,
+                seq: frame.seq,
+                robot_id: frame.robot_id.clone(),
+            };
+            rm.machine.process_frame(&cropped).await;
+        }
+    }
+}
+
+/// Drop regions that can't be recorded: empty rectangles and repeated ids
+/// (segments of both would be indistinguishable).
+fn usable_regions(regions: &[RegionConfig]) -> Vec<RegionConfig> {
+    let mut seen = HashSet::new();
+    regions
+        .iter()
+        .filter(|r| {
+            if r.width == 0 || r.height == 0 {
+                warn!(region = r.id, "ignoring empty motion region");
+                return false;
+            }
+            if !seen.insert(r.id.as_str()) {
+                warn!(region = r.id, "ignoring duplicate motion region id");
+                return false;
+            }
+            true
+        })
+        .cloned()
+        .collect()
+}
+
+/// Crop `region` out of `img` and re-encode it as JPEG. The rectangle is
+/// clipped to the frame and rounded down to even dimensions (4:2:0 encoders
+/// reject odd sizes). `None` if nothing of it is left.
+fn crop_jpeg(img: &DynamicImage, region: &RegionConfig) -> Option<Vec<u8>> {
+    let (frame_w, frame_h) = img.dimensions();
+    let x = region.x.min(frame_w);
+    let y = region.y.min(frame_h);
+    let width = region.width.min(frame_w - x) & !1;
+    let height = region.height.min(frame_h - y) & !1;
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let crop = img.crop_imm(x, y, width, height);
+    let mut buf = Cursor::new(Vec::new());
+    let encoder = JpegEncoder::new_with_quality(&mut buf, CROP_JPEG_QUALITY);
+    crop.write_with_encoder(encoder).ok()?;
+    Some(buf.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+
+    fn region(id: &str, x: u32, y: u32, width: u32, height: u32) -> RegionConfig {
+        RegionConfig {
+            id: id.into(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn crop_is_clipped_to_frame_and_even() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(64, 48));
+
+        let jpeg = crop_jpeg(&img, &region("bench", 10, 10, 21, 100)).unwrap();
+        let decoded = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+        // 21 → 20 (even); 100 clipped to 48 - 10 = 38.
+        assert_eq!(decoded.dimensions(), (20, 38));
+
+        assert!(crop_jpeg(&img, &region("off", 64, 0, 10, 10)).is_none());
+        assert!(crop_jpeg(&img, &region("sliver", 63, 0, 10, 10)).is_none());
+    }
+
+    #[test]
+    fn empty_and_duplicate_regions_are_dropped() {
+        let regions = [
+            region("bench", 0, 0, 320, 240),
+            region("empty", 0, 0, 0, 240),
+            region("bench", 320, 0, 320, 240),
+            region("door", 320, 0, 320, 240),
+        ];
+        let ids: Vec<String> = usable_regions(&regions).into_iter().map(|r| r.id).collect();
+        assert_eq!(ids, ["bench", "door"]);
+    }
+}
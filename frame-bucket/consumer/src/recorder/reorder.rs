@@ -0,0 +1,169 @@
+//! Reordering of a robot's frames by `seq` (`recording.reorder_depth`).
+//!
+//! Partition moves and producer reconnects can deliver frames slightly out of
+//! order, which would make the state machines compare the wrong neighbours
+//! and stamp segments with the wrong ends. The buffer holds up to `depth`
+//! frames and releases them in `seq` order: a frame goes out as soon as it is
+//! the next one after the last released (the watermark), when the buffer is
+//! over `depth`, or when it (or a later frame) has waited `max_hold`. Frames
+//! at or below the watermark arrive too late and are dropped.
+//!
+//! A depth of 0 passes frames straight through.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use frame_bucket_common::frame::TimestampedFrame;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// A `seq` this far below the watermark means the producer restarted and
+/// counts from 0 again, rather than a late frame.
+const RESTART_GAP: u64 = 1000;
+
+pub struct ReorderBuffer {
+    depth: usize,
+    max_hold: Duration,
+    /// Held frames by `seq`, with their arrival time.
+    pending: BTreeMap<u64, (Instant, TimestampedFrame)>,
+    /// `seq` of the last released frame.
+    watermark: Option<u64>,
+    /// Late frames dropped so far.
+    dropped: u64,
+}
+
+impl ReorderBuffer {
+    pub fn new(depth: usize, max_hold: Duration) -> Self {
+        Self {
+            depth,
+            max_hold,
+            pending: BTreeMap::new(),
+            watermark: None,
+            dropped: 0,
+        }
+    }
+
+    /// Add a frame that arrived at `now`; returns the frames now released,
+    /// in order.
+    pub fn push(&mut self, frame: TimestampedFrame, now: Instant) -> Vec<TimestampedFrame> {
+        if self.depth == 0 {
+            return vec![frame];
+        }
+        let mut released = Vec::new();
+        if let Some(watermark) = self.watermark.filter(|&w| frame.seq <= w) {
+            if watermark - frame.seq <= RESTART_GAP {
+                self.dropped += 1;
+                debug!(
+                    seq = frame.seq,
+                    watermark,
+                    dropped = self.dropped,
+                    "dropping frame that arrived after later ones were released"
+                );
+                return released;
+            }
+            info!(seq = frame.seq, watermark, "frame sequence restarted, releasing held frames");
+            released = self.drain();
+            self.watermark = None;
+        }
+        self.pending.insert(frame.seq, (now, frame));
+        released.extend(self.release(now));
+        released
+    }
+
+    /// Frames whose hold time is up at `now`, for a robot that stopped
+    /// sending.
+    pub fn release_expired(&mut self, now: Instant) -> Vec<TimestampedFrame> {
+        self.release(now)
+    }
+
+    /// Every held frame, in order (at shutdown).
+    pub fn drain(&mut self) -> Vec<TimestampedFrame> {
+        let pending = std::mem::take(&mut self.pending);
+        if let Some(&seq) = pending.keys().next_back() {
+            self.watermark = Some(seq);
+        }
+        pending.into_values().map(|(_, frame)| frame).collect()
+    }
+
+    fn release(&mut self, now: Instant) -> Vec<TimestampedFrame> {
+        // Everything up to the last expired frame goes, gaps or not.
+        let expired_up_to = self
+            .pending
+            .iter()
+            .filter(|(_, (arrived, _))| now.duration_since(*arrived) >= self.max_hold)
+            .map(|(&seq, _)| seq)
+            .next_back();
+
+        let mut released = Vec::new();
+        while let Some(&seq) = self.pending.keys().next() {
+            let next_in_order = self.watermark.is_some_and(|w| seq == w + 1);
+            let forced = self.pending.len() > self.depth || expired_up_to.is_some_and(|e| seq <= e);
+            if !next_in_order && !forced {
+                break;
+            }
+            let (_, frame) = self.pending.remove(&seq).unwrap();
+            self.watermark = Some(seq);
+            released.push(frame);
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(seq: u64) -> TimestampedFrame {
+        TimestampedFrame::new(Vec::new(), 1_000 + seq as i64 * 100, seq)
+    }
+
+    fn seqs(frames: Vec<TimestampedFrame>) -> Vec<u64> {
+        frames.into_iter().map(|f| f.seq).collect()
+    }
+
+    #[test]
+    fn depth_zero_passes_through() {
+        let mut buffer = ReorderBuffer::new(0, Duration::from_millis(200));
+        let now = Instant::now();
+        assert_eq!(seqs(buffer.push(frame(5), now)), [5]);
+        assert_eq!(seqs(buffer.push(frame(3), now)), [3]);
+    }
+
+    #[test]
+    fn reorders_and_drops_stragglers() {
+        let mut buffer = ReorderBuffer::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(buffer.push(frame(2), now).is_empty());
+        assert!(buffer.push(frame(1), now).is_empty());
+        assert!(buffer.push(frame(4), now).is_empty());
+        // Over depth: the lowest frames go until the next gap.
+        assert_eq!(seqs(buffer.push(frame(5), now)), [1, 2]);
+        assert_eq!(seqs(buffer.push(frame(3), now)), [3, 4, 5]);
+        assert_eq!(seqs(buffer.push(frame(6), now)), [6]);
+        // Older than the watermark.
+        assert!(buffer.push(frame(4), now).is_empty());
+        assert_eq!(buffer.dropped, 1);
+    }
+
+    #[test]
+    fn held_frames_are_released_after_max_hold() {
+        let mut buffer = ReorderBuffer::new(8, Duration::from_millis(200));
+        let start = Instant::now();
+        buffer.push(frame(10), start);
+        buffer.push(frame(12), start + Duration::from_millis(150));
+        assert!(buffer.release_expired(start + Duration::from_millis(100)).is_empty());
+        assert_eq!(seqs(buffer.release_expired(start + Duration::from_millis(200))), [10]);
+        // 11 never came; 12 goes once its own hold is up.
+        assert_eq!(seqs(buffer.release_expired(start + Duration::from_millis(350))), [12]);
+    }
+
+    #[test]
+    fn producer_restart_resets_the_watermark() {
+        let mut buffer = ReorderBuffer::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        buffer.push(frame(5000), now);
+        assert_eq!(seqs(buffer.push(frame(5001), now)), [5000, 5001]);
+        assert_eq!(seqs(buffer.push(frame(0), now)), Vec::<u64>::new());
+        assert_eq!(seqs(buffer.push(frame(1), now)), [0, 1]);
+    }
+}
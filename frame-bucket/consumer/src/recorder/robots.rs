@@ -0,0 +1,228 @@
+//! One recorder per robot, for topics shared by a fleet.
+//!
+//! Frames are routed on their robot id to a [`RegionRecorder`] of their own
+//! (own state machines, database handle and WAL directory), created on the
+//! robot's first frame, so two robots on one topic never interleave into one
+//! recording. All robots share one RustFS storage, whose eviction index is
+//! keyed by object key, so same-millisecond segments of two robots both count.
+//! The robot id is the one embedded in v3 frames, or else the first
+//! `:`-separated field of the Kafka message key, which is the robot id under
+//! every `kafka.key_strategy`: `{robot_id}` (`robot`, the default) or
+//! `{robot_id}:{captured_at_ms}:{seq}` (`robot_seq`). Frames with neither
+//! (e.g. `none`) belong to the configured `aws_s3.robot_id`.
+//! Each robot's frames pass through its own [`ReorderBuffer`] first.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use frame_bucket_common::frame::TimestampedFrame;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use super::reorder::ReorderBuffer;
+use super::RegionRecorder;
+
+/// Subdirectory of `recording.wal_dir` holding the WALs of robots other than
+/// the configured one.
+const ROBOT_WAL_DIR: &str = "robots";
+
+/// Creates the recorder for a robot id, given its WAL directory.
+type BuildRecorder = Box<dyn FnMut(&str, Option<PathBuf>) -> RegionRecorder + Send>;
+
+pub struct RobotRecorders {
+    recorders: HashMap<String, RegionRecorder>,
+    buffers: HashMap<String, ReorderBuffer>,
+    /// `recording.reorder_depth` / `recording.reorder_max_hold_ms`.
+    reorder_depth: usize,
+    reorder_max_hold: Duration,
+    default_robot: String,
+    build: BuildRecorder,
+    wal_dir: Option<PathBuf>,
+}
+
+impl RobotRecorders {
+    pub fn new(
+        default_robot: String,
+        wal_dir: Option<PathBuf>,
+        reorder_depth: usize,
+        reorder_max_hold: Duration,
+        build: impl FnMut(&str, Option<PathBuf>) -> RegionRecorder + Send + 'static,
+    ) -> Self {
+        Self {
+            recorders: HashMap::new(),
+            buffers: HashMap::new(),
+            reorder_depth,
+            reorder_max_hold,
+            default_robot,
+            build: Box::new(build),
+            wal_dir,
+        }
+    }
+
+    /// Re-encode and upload segments a previous crash left in the WAL, for the
+    /// configured robot and every robot with a WAL directory.
+    pub async fn recover_wal(&mut self) {
+        let mut robots = vec![self.default_robot.clone()];
+        if let Some(dir) = &self.wal_dir {
+            robots.extend(wal_robots(dir).into_iter().filter(|r| *r != self.default_robot));
+        }
+        for robot_id in robots {
+            self.recorder(&robot_id).recover_wal().await;
+        }
+    }
+
+    /// Record `frame` for its embedded robot id, or else the robot named by
+    /// the message `key`, once the reordering buffer releases it.
+    pub async fn process_frame(&mut self, key: Option<&[u8]>, frame: TimestampedFrame) {
+        let robot_id = match frame.robot_id.as_deref().map(checked_robot_id).or_else(|| robot_id_from_key(key)) {
+            Some(Ok(id)) => id,
+            None => self.default_robot.clone(),
+            Some(Err(raw)) => {
+                warn!(key = raw, "frame has no usable robot id, skipping it");
+                return;
+            }
+        };
+        if !self.recorders.contains_key(&robot_id) {
+            // A robot seen for the first time since startup may still have
+            // WALs from before a crash.
+            self.recorder(&robot_id).recover_wal().await;
+        }
+        let (depth, max_hold) = (self.reorder_depth, self.reorder_max_hold);
+        let released = self
+            .buffers
+            .entry(robot_id.clone())
+            .or_insert_with(|| ReorderBuffer::new(depth, max_hold))
+            .push(frame, Instant::now());
+        self.record(&robot_id, released).await;
+    }
+
+    /// Whether frames may be held back, needing [`Self::release_expired`].
+    pub fn reorders(&self) -> bool {
+        self.reorder_depth > 0
+    }
+
+    /// Record frames that have waited `recording.reorder_max_hold_ms`, for
+    /// robots that stopped sending.
+    pub async fn release_expired(&mut self) {
+        let now = Instant::now();
+        let released: Vec<_> = self
+            .buffers
+            .iter_mut()
+            .map(|(robot_id, buffer)| (robot_id.clone(), buffer.release_expired(now)))
+            .collect();
+        for (robot_id, frames) in released {
+            self.record(&robot_id, frames).await;
+        }
+    }
+
+    async fn record(&mut self, robot_id: &str, frames: Vec<TimestampedFrame>) {
+        let recorder = self.recorder(robot_id);
+        for frame in &frames {
+            recorder.process_frame(frame).await;
+        }
+    }
+
+    /// Finalize every robot's open segments, for shutdown.
+    pub async fn flush(&mut self) {
+        let held: Vec<_> = self
+            .buffers
+            .iter_mut()
+            .map(|(robot_id, buffer)| (robot_id.clone(), buffer.drain()))
+            .collect();
+        for (robot_id, frames) in held {
+            self.record(&robot_id, frames).await;
+        }
+        for (robot_id, recorder) in &mut self.recorders {
+            info!(robot_id, "flushing recorder");
+            recorder.flush().await;
+        }
+    }
+
+    fn recorder(&mut self, robot_id: &str) -> &mut RegionRecorder {
+        if !self.recorders.contains_key(robot_id) {
+            info!(robot_id, robots = self.recorders.len() + 1, "recording new robot");
+            let wal_dir = self
+                .wal_dir
+                .as_deref()
+                .map(|dir| robot_wal_dir(dir, robot_id, &self.default_robot));
+            let recorder = (self.build)(robot_id, wal_dir);
+            self.recorders.insert(robot_id.to_string(), recorder);
+        }
+        self.recorders.get_mut(robot_id).unwrap()
+    }
+}
+
+/// Robot id from a Kafka message key of the form `{robot_id}:{timestamp_ms}`
+/// (producers since `seq` was added append `:{seq}`).
+/// `None` when there's no key; `Err` with the key when it doesn't start with a
+/// robot id usable in file names and object keys.
+fn robot_id_from_key(key: Option<&[u8]>) -> Option<Result<String, String>> {
+    let key = String::from_utf8_lossy(key?);
+    let robot_id = key.split(':').next().unwrap_or_default();
+    Some(checked_robot_id(robot_id).map_err(|_| key.into_owned()))
+}
+
+/// `robot_id` if it's usable in file names and object keys.
+fn checked_robot_id(robot_id: &str) -> Result<String, String> {
+    let usable = !robot_id.is_empty()
+        && !robot_id.starts_with('.')
+        && robot_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if usable { Ok(robot_id.to_string()) } else { Err(robot_id.to_string()) }
+}
+
+/// WAL directory of `robot_id`: `recording.wal_dir` itself for the configured
+/// robot (as before fleets were supported), `robots/{robot_id}/` under it for
+/// the others.
+fn robot_wal_dir(wal_dir: &Path, robot_id: &str, default_robot: &str) -> PathBuf {
+    if robot_id == default_robot {
+        wal_dir.to_path_buf()
+    } else {
+        wal_dir.join(ROBOT_WAL_DIR).join(robot_id)
+    }
+}
+
+/// Robots with a WAL directory under `wal_dir`.
+fn wal_robots(wal_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(wal_dir.join(ROBOT_WAL_DIR)) else {
+        return Vec::new();
+    };
+    let mut robots: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+    robots.sort();
+    robots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn robot_id_comes_from_message_key() {
+        assert_eq!(robot_id_from_key(Some(b"reachy-001:1739871000000")), Some(Ok("reachy-001".into())));
+        assert_eq!(robot_id_from_key(Some(b"reachy-001:1739871000000:42")), Some(Ok("reachy-001".into())));
+        assert_eq!(robot_id_from_key(Some(b"bracketbot_2")), Some(Ok("bracketbot_2".into())));
+        assert_eq!(robot_id_from_key(None), None);
+        assert!(matches!(robot_id_from_key(Some(b":123")), Some(Err(_))));
+        assert!(matches!(robot_id_from_key(Some(b"../etc:123")), Some(Err(_))));
+        assert!(matches!(robot_id_from_key(Some(b"a/b:123")), Some(Err(_))));
+        assert_eq!(robot_id_from_key(Some(b"a/b:123")), Some(Err("a/b:123".into())));
+        assert_eq!(checked_robot_id("../etc"), Err("../etc".into()));
+    }
+
+    #[test]
+    fn other_robots_get_their_own_wal_dir() {
+        let dir = std::env::temp_dir().join(format!("robot-wals-{}", fastrand::u32(..)));
+        let wal = |robot| robot_wal_dir(&dir, robot, "reachy-001");
+        assert_eq!(wal("reachy-001"), dir);
+        assert_eq!(wal("bracketbot"), dir.join("robots").join("bracketbot"));
+
+        std::fs::create_dir_all(wal("bracketbot")).unwrap();
+        std::fs::create_dir_all(wal("aloha")).unwrap();
+        assert_eq!(wal_robots(&dir), ["aloha", "bracketbot"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
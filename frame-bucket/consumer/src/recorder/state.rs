@@ -1,20 +1,35 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use frame_bucket_common::config::RecordingConfig;
+use chrono_tz::Tz;
+use frame_bucket_common::config::{FilterConfig, RecordingConfig};
 use frame_bucket_common::frame::{FramePayload, TimestampedFrame};
 use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
-use crate::db::SegmentDb;
+use crate::db::{NewActiveSegment, SegmentDb};
 use crate::filter::framesize::FrameSizeFilter;
-use crate::filter::phash::{compute_ahash, hamming};
+use crate::filter::scene_filter;
+use crate::filter::traits::FrameFilter;
 use crate::storage::RustfsStorage;
 
-use super::encoder::SegmentEncoder;
-use super::keys::{active_segment_key, idle_jpeg_key};
+use super::encoder::{Container, Ffmpeg, SegmentEncoder, SegmentOutput, SegmentSink, VideoEncoder};
+use super::keys::{active_segment_key, idle_jpeg_key, raw_frame_key, staging_segment_key, RawFrame};
+use super::preroll::Preroll;
+use super::wal::{self, SegmentWal};
 
-#[allow(dead_code)]
+/// Active segments with fewer frames than this are discarded: a single frame
+/// (segment rolled or errored right after starting) isn't a playable video.
+const MIN_SEGMENT_FRAMES: u32 = 2;
+/// MP4s smaller than this are empty or truncated ffmpeg output.
+const MIN_SEGMENT_BYTES: u64 = 1024;
+/// Lower bound on `recording.idle_snapshot_interval_secs`, so a small value
+/// can't turn a parked robot into a stream of JPEG uploads and rows.
+const MIN_IDLE_SNAPSHOT_SECS: u64 = 60;
+
+#[allow(dead_code, clippy::large_enum_variant)]
 enum RecordingState {
     /// The scene is static. We track the initial frame and the last timestamp
     /// at which the scene was still considered unchanged.
@@ -23,8 +38,6 @@ enum RecordingState {
         initial_payload: Vec<u8>,
         /// Whether the initial frame is H.264 (false = JPEG).
         is_h264: bool,
-        /// aHash of the initial frame (only for JPEG path).
-        initial_hash: Option<Vec<bool>>,
         idle_start_ms: i64,
         /// Last frame timestamp that was still considered "idle".
         last_similar_ms: i64,
@@ -37,52 +50,109 @@ enum RecordingState {
         /// Monotonic deadline for rolling the current segment.
         segment_deadline: Instant,
         segment_start_ms: i64,
-        /// aHash of the last frame (only for JPEG path).
-        last_frame_hash: Option<Vec<bool>>,
         /// Consecutive frames that look similar (potential idle transition).
         consecutive_idle_count: u32,
+        /// How much happened in the segment so far: the sum of each frame's
+        /// `filter.primary` distance from its baseline (JPEG), or of P-frame
+        /// sizes relative to their moving average (H.264).
+        motion_score: f64,
     },
 }
 
+/// What became of a segment handed to `finish_and_upload_segment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SegmentOutcome {
+    /// Stored, or discarded as unplayable or too short.
+    Done,
+    /// ffmpeg failed on the segment's frames.
+    EncodeFailed,
+    /// Encoded, but the upload failed. A later retry may succeed.
+    UploadFailed,
+}
+
 pub struct RecordingStateMachine {
     state: Option<RecordingState>, // Option so we can take() during transitions
     config: RecordingConfig,
-    phash_threshold: u32,
-    hash_size: u32,
+    /// Scene-change filter for JPEG frames (`filter.primary`). It keeps the
+    /// baseline: the last frame it saw as changed.
+    scene_filter: Box<dyn FrameFilter>,
     storage: Arc<RustfsStorage>,
     db: Option<Arc<SegmentDb>>,
     prefix: String,
     robot_id: String,
+    /// Time zone for the date directory of object keys (`recording.timezone`).
+    timezone: Tz,
     /// Frame-size heuristic filter for H.264 streams.
     frame_size_filter: FrameSizeFilter,
+    /// Motion region this machine records (`recording.regions`), `None` for
+    /// the whole frame. Frames arrive already cropped to it.
+    region: Option<String>,
+    /// Where this machine's segment WALs live (`recording.wal_dir`, with a
+    /// `regions/{id}` subdirectory per region). `None` when disabled.
+    wal_dir: Option<PathBuf>,
+    /// Capture time of the latest frame, ending a segment flushed on shutdown.
+    last_frame_ms: Option<i64>,
+    /// Segments dropped for being under `recording.min_segment_secs`.
+    suppressed_segments: AtomicU64,
+    /// Latest idle frames, fed to the next active segment first.
+    preroll: Preroll,
+    /// Encoder for JPEG segments (`recording.codec` / `recording.hwaccel`).
+    video_encoder: VideoEncoder,
+    /// `recording.ffmpeg_path` / `recording.extra_ffmpeg_args`.
+    ffmpeg: Ffmpeg,
 }
 
 impl RecordingStateMachine {
     pub fn new(
         config: RecordingConfig,
-        phash_threshold: u32,
-        hash_size: u32,
-        spike_ratio: f64,
+        filter: &FilterConfig,
         storage: Arc<RustfsStorage>,
         db: Option<Arc<SegmentDb>>,
         prefix: String,
         robot_id: String,
+        region: Option<String>,
     ) -> Self {
+        let timezone = config.timezone.parse::<Tz>().unwrap_or_else(|e| {
+            warn!(timezone = config.timezone, error = %e, "invalid recording.timezone, using UTC");
+            Tz::UTC
+        });
+        let wal_dir = config.wal_dir.as_ref().map(|dir| match &region {
+            Some(id) => PathBuf::from(dir).join("regions").join(id),
+            None => PathBuf::from(dir),
+        });
+        if (1..MIN_IDLE_SNAPSHOT_SECS).contains(&config.idle_snapshot_interval_secs) {
+            warn!(
+                interval_secs = config.idle_snapshot_interval_secs,
+                min_secs = MIN_IDLE_SNAPSHOT_SECS,
+                "recording.idle_snapshot_interval_secs is below the minimum, using the minimum"
+            );
+        }
+        if let Some(thin_fps) = config.thin_to_fps {
+            info!(thin_fps, "thinning JPEG active segments; H.264 passthrough segments keep every frame");
+        }
         Self {
             state: None,
+            preroll: Preroll::new(config.preroll_frames),
+            video_encoder: VideoEncoder::from_config(&config),
+            ffmpeg: Ffmpeg::from_config(&config),
             config,
-            phash_threshold,
-            hash_size,
+            scene_filter: scene_filter(filter),
             storage,
             db,
             prefix,
             robot_id,
-            frame_size_filter: FrameSizeFilter::new(spike_ratio),
+            timezone,
+            frame_size_filter: FrameSizeFilter::new(filter.spike_ratio),
+            region,
+            wal_dir,
+            last_frame_ms: None,
+            suppressed_segments: AtomicU64::new(0),
         }
     }
 
     /// Process one incoming frame from Kafka. This is the main entry point.
     pub async fn process_frame(&mut self, frame: &TimestampedFrame) {
+        self.last_frame_ms = Some(frame.captured_at_ms);
         match &frame.payload {
             FramePayload::Jpeg(jpeg_data) => {
                 self.process_jpeg_frame(frame, jpeg_data).await;
@@ -93,26 +163,59 @@ impl RecordingStateMachine {
         }
     }
 
+    /// Finalize whatever is open, for shutdown: the active segment is encoded,
+    /// uploaded and inserted into SQLite, an idle period gets its record. The
+    /// machine starts over (in IDLE) with its next frame.
+    pub async fn flush(&mut self) {
+        match self.state.take() {
+            Some(RecordingState::Active {
+                encoder,
+                segment_start_ms,
+                motion_score,
+                ..
+            }) => {
+                let end_ms = self.last_frame_ms.unwrap_or(segment_start_ms);
+                info!(
+                    segment_start_ms,
+                    end_ms,
+                    frames = encoder.frame_count(),
+                    region = self.region,
+                    "flushing active segment"
+                );
+                self.finish_and_upload_segment(encoder, end_ms, Some(motion_score)).await;
+            }
+            Some(RecordingState::Idle {
+                initial_payload,
+                is_h264,
+                idle_start_ms,
+                last_similar_ms,
+                ..
+            }) => {
+                self.upload_idle_record(&initial_payload, is_h264, idle_start_ms, last_similar_ms)
+                    .await;
+            }
+            None => {}
+        }
+    }
+
     // =========================================================================
-    // JPEG path (existing aHash logic)
+    // JPEG path (`filter.primary` scene-change filter)
     // =========================================================================
 
     async fn process_jpeg_frame(&mut self, frame: &TimestampedFrame, jpeg_data: &[u8]) {
-        let hash = match compute_ahash(jpeg_data, self.hash_size) {
-            Some(h) => h,
-            None => {
-                warn!(ts = frame.captured_at_ms, "failed to compute aHash for frame, skipping");
-                return;
-            }
+        let changed = self.scene_filter.should_store(jpeg_data);
+        let Some(distance) = self.scene_filter.last_distance() else {
+            warn!(ts = frame.captured_at_ms, filter = self.scene_filter.name(), "failed to decode JPEG frame, skipping");
+            return;
         };
 
         // First frame ever: enter Idle.
         if self.state.is_none() {
-            info!(ts = frame.captured_at_ms, "first frame (JPEG) — entering IDLE mode");
+            info!(ts = frame.captured_at_ms, region = self.region, "first frame (JPEG) — entering IDLE mode");
+            self.preroll.push_jpeg(frame.captured_at_ms, jpeg_data);
             self.state = Some(RecordingState::Idle {
                 initial_payload: jpeg_data.to_vec(),
                 is_h264: false,
-                initial_hash: Some(hash),
                 idle_start_ms: frame.captured_at_ms,
                 last_similar_ms: frame.captured_at_ms,
             });
@@ -121,10 +224,10 @@ impl RecordingStateMachine {
 
         match self.state.take().unwrap() {
             idle @ RecordingState::Idle { .. } => {
-                self.state = Some(self.handle_idle_jpeg(idle, frame, jpeg_data, &hash).await);
+                self.state = Some(self.handle_idle_jpeg(idle, frame, jpeg_data, changed, distance).await);
             }
             active @ RecordingState::Active { .. } => {
-                self.state = Some(self.handle_active_jpeg(active, frame, jpeg_data, &hash).await);
+                self.state = Some(self.handle_active_jpeg(active, frame, jpeg_data, changed, distance).await);
             }
         }
     }
@@ -134,11 +237,11 @@ impl RecordingStateMachine {
         state: RecordingState,
         frame: &TimestampedFrame,
         jpeg_data: &[u8],
-        hash: &[bool],
+        changed: bool,
+        distance: f64,
     ) -> RecordingState {
         let RecordingState::Idle {
             initial_payload,
-            initial_hash,
             idle_start_ms,
             last_similar_ms,
             ..
@@ -147,37 +250,51 @@ impl RecordingStateMachine {
             unreachable!()
         };
 
-        let initial_hash_ref = initial_hash.as_ref().expect("JPEG idle must have hash");
-        let dist = hamming(initial_hash_ref, hash);
-
-        if dist <= self.phash_threshold {
+        if !changed {
             debug!(
-                dist,
-                threshold = self.phash_threshold,
+                distance,
+                filter = self.scene_filter.name(),
                 ts = frame.captured_at_ms,
                 "IDLE: frame similar to baseline"
             );
+            self.preroll.push_jpeg(frame.captured_at_ms, jpeg_data);
+            if idle_snapshot_due(idle_start_ms, frame.captured_at_ms, self.config.idle_snapshot_interval_secs) {
+                // Close the period so far with its record and start the next
+                // one with this frame as its snapshot. The filter keeps its
+                // baseline, so slow drift still adds up to a scene change.
+                info!(idle_start_ms, idle_end_ms = frame.captured_at_ms, region = self.region, "IDLE: periodic snapshot");
+                self.upload_idle_record(&initial_payload, false, idle_start_ms, frame.captured_at_ms)
+                    .await;
+                return RecordingState::Idle {
+                    initial_payload: jpeg_data.to_vec(),
+                    is_h264: false,
+                    idle_start_ms: frame.captured_at_ms,
+                    last_similar_ms: frame.captured_at_ms,
+                };
+            }
             return RecordingState::Idle {
                 initial_payload,
                 is_h264: false,
-                initial_hash,
                 idle_start_ms,
                 last_similar_ms: frame.captured_at_ms,
             };
         }
 
+        let preroll = self.preroll.take();
+        let idle_end_ms = idle_end_before_preroll(idle_start_ms, last_similar_ms, &preroll);
         info!(
-            dist,
-            threshold = self.phash_threshold,
+            distance,
+            filter = self.scene_filter.name(),
             idle_start_ms,
-            idle_end_ms = last_similar_ms,
+            idle_end_ms,
+            region = self.region,
             "IDLE→ACTIVE: scene changed, finalizing idle record"
         );
-        self.upload_idle_record(&initial_payload, false, idle_start_ms, last_similar_ms)
+        self.upload_idle_record(&initial_payload, false, idle_start_ms, idle_end_ms)
             .await;
 
         match self
-            .start_active_segment_jpeg(frame, jpeg_data, hash.to_vec())
+            .start_active_segment_jpeg(frame, jpeg_data, preroll)
             .await
         {
             Some(active_state) => active_state,
@@ -186,7 +303,6 @@ impl RecordingStateMachine {
                 RecordingState::Idle {
                     initial_payload: jpeg_data.to_vec(),
                     is_h264: false,
-                    initial_hash: Some(hash.to_vec()),
                     idle_start_ms: frame.captured_at_ms,
                     last_similar_ms: frame.captured_at_ms,
                 }
@@ -199,14 +315,15 @@ impl RecordingStateMachine {
         state: RecordingState,
         frame: &TimestampedFrame,
         jpeg_data: &[u8],
-        hash: &[bool],
+        changed: bool,
+        distance: f64,
     ) -> RecordingState {
         let RecordingState::Active {
             mut encoder,
             segment_deadline,
             segment_start_ms,
-            last_frame_hash,
             mut consecutive_idle_count,
+            mut motion_score,
             ..
         } = state
         else {
@@ -221,11 +338,11 @@ impl RecordingStateMachine {
                 frames = encoder.frame_count(),
                 "ACTIVE: rolling segment (timer expired)"
             );
-            self.finish_and_upload_segment(encoder, frame.captured_at_ms)
+            self.finish_and_upload_segment(encoder, frame.captured_at_ms, Some(motion_score))
                 .await;
 
             return match self
-                .start_active_segment_jpeg(frame, jpeg_data, hash.to_vec())
+                .start_active_segment_jpeg(frame, jpeg_data, Vec::new())
                 .await
             {
                 Some(s) => s,
@@ -234,7 +351,6 @@ impl RecordingStateMachine {
                     RecordingState::Idle {
                         initial_payload: jpeg_data.to_vec(),
                         is_h264: false,
-                        initial_hash: Some(hash.to_vec()),
                         idle_start_ms: frame.captured_at_ms,
                         last_similar_ms: frame.captured_at_ms,
                     }
@@ -243,26 +359,30 @@ impl RecordingStateMachine {
         }
 
         // Push frame to encoder
-        if let Err(e) = encoder.push_frame(jpeg_data).await {
-            error!(error = %e, "ACTIVE: failed to push frame to encoder, finalizing broken segment");
-            self.finish_and_upload_segment(encoder, frame.captured_at_ms)
-                .await;
-            return RecordingState::Idle {
-                initial_payload: jpeg_data.to_vec(),
-                is_h264: false,
-                initial_hash: Some(hash.to_vec()),
-                idle_start_ms: frame.captured_at_ms,
-                last_similar_ms: frame.captured_at_ms,
-            };
+        match encoder.push_frame(jpeg_data, frame.captured_at_ms).await {
+            Ok(true) => self.keep_raw_frame(&encoder, jpeg_data, false),
+            // Thinned out; still counts toward the idle transition below.
+            Ok(false) => {}
+            Err(e) => {
+                error!(error = %e, "ACTIVE: failed to push frame to encoder, finalizing broken segment");
+                self.finish_and_upload_segment(encoder, frame.captured_at_ms, Some(motion_score))
+                    .await;
+                return RecordingState::Idle {
+                    initial_payload: jpeg_data.to_vec(),
+                    is_h264: false,
+                    idle_start_ms: frame.captured_at_ms,
+                    last_similar_ms: frame.captured_at_ms,
+                };
+            }
         }
 
-        // Check Active→Idle transition (consecutive similar frames)
-        let prev_hash = last_frame_hash.as_ref().expect("JPEG active must have hash");
-        let dist = hamming(prev_hash, hash);
-        if dist <= self.phash_threshold {
+        // Check Active→Idle transition (consecutive frames unchanged from the
+        // last changed one)
+        motion_score += distance;
+        if !changed {
             consecutive_idle_count += 1;
             debug!(
-                dist,
+                distance,
                 consecutive_idle_count,
                 threshold = self.config.active_to_idle_consecutive_frames,
                 "ACTIVE: consecutive similar frame"
@@ -271,14 +391,15 @@ impl RecordingStateMachine {
             if consecutive_idle_count >= self.config.active_to_idle_consecutive_frames {
                 info!(
                     consecutive_idle_count,
-                    segment_start_ms, "ACTIVE→IDLE: scene stabilized, finalizing active segment"
+                    segment_start_ms,
+                    region = self.region,
+                    "ACTIVE→IDLE: scene stabilized, finalizing active segment"
                 );
-                self.finish_and_upload_segment(encoder, frame.captured_at_ms)
+                self.finish_and_upload_segment(encoder, frame.captured_at_ms, Some(motion_score))
                     .await;
                 return RecordingState::Idle {
                     initial_payload: jpeg_data.to_vec(),
                     is_h264: false,
-                    initial_hash: Some(hash.to_vec()),
                     idle_start_ms: frame.captured_at_ms,
                     last_similar_ms: frame.captured_at_ms,
                 };
@@ -289,8 +410,8 @@ impl RecordingStateMachine {
                 is_h264: false,
                 segment_deadline,
                 segment_start_ms,
-                last_frame_hash: Some(hash.to_vec()),
                 consecutive_idle_count,
+                motion_score,
             }
         } else {
             RecordingState::Active {
@@ -298,24 +419,26 @@ impl RecordingStateMachine {
                 is_h264: false,
                 segment_deadline,
                 segment_start_ms,
-                last_frame_hash: Some(hash.to_vec()),
                 consecutive_idle_count: 0,
+                motion_score,
             }
         }
     }
 
+    /// Start a segment with the `preroll` frames (if any) followed by `frame`.
     async fn start_active_segment_jpeg(
         &self,
         frame: &TimestampedFrame,
         jpeg_data: &[u8],
-        hash: Vec<bool>,
+        preroll: Vec<(i64, Vec<u8>)>,
     ) -> Option<RecordingState> {
+        let start_ms = preroll.first().map_or(frame.captured_at_ms, |(ts, _)| *ts);
         let mut encoder = match SegmentEncoder::start(
-            frame.captured_at_ms,
-            &self.config.codec,
-            self.config.crf,
-            &self.config.preset,
-            self.config.fps,
+            start_ms,
+            &self.ffmpeg,
+            &self.video_encoder,
+            &self.config,
+            self.segment_sink(start_ms, self.video_encoder.container()),
         )
         .await
         {
@@ -325,18 +448,38 @@ impl RecordingStateMachine {
                 return None;
             }
         };
+        self.attach_wal(&mut encoder, false);
 
-        if let Err(e) = encoder.push_frame(jpeg_data).await {
-            error!(error = %e, "failed to push first frame to new encoder");
-            return None;
+        let preroll_frames = preroll.len();
+        for (ts, data) in &preroll {
+            match encoder.push_frame(data, *ts).await {
+                Ok(true) => self.keep_raw_frame(&encoder, data, false),
+                Ok(false) => {}
+                Err(e) => {
+                    error!(error = %e, "failed to push pre-roll frame to new encoder");
+                    discard_wal(&mut encoder);
+                    return None;
+                }
+            }
+        }
+        // The first frame of a segment is always kept by thinning.
+        match encoder.push_frame(jpeg_data, frame.captured_at_ms).await {
+            Ok(true) => self.keep_raw_frame(&encoder, jpeg_data, false),
+            Ok(false) => {}
+            Err(e) => {
+                error!(error = %e, "failed to push first frame to new encoder");
+                discard_wal(&mut encoder);
+                return None;
+            }
         }
 
         let segment_deadline =
             Instant::now() + Duration::from_secs(self.config.segment_duration_secs);
 
         info!(
-            segment_start_ms = frame.captured_at_ms,
-            codec = self.config.codec,
+            segment_start_ms = start_ms,
+            preroll_frames,
+            codec = self.video_encoder.name(),
             "ACTIVE: new JPEG segment started"
         );
 
@@ -344,9 +487,9 @@ impl RecordingStateMachine {
             encoder,
             is_h264: false,
             segment_deadline,
-            segment_start_ms: frame.captured_at_ms,
-            last_frame_hash: Some(hash),
+            segment_start_ms: start_ms,
             consecutive_idle_count: 0,
+            motion_score: 0.0,
         })
     }
 
@@ -369,10 +512,10 @@ impl RecordingStateMachine {
                 ts = frame.captured_at_ms,
                 nal_type, "first frame (H.264) — entering IDLE mode"
             );
+            self.preroll.push_h264(frame.captured_at_ms, h264_data, frame.is_keyframe());
             self.state = Some(RecordingState::Idle {
                 initial_payload: h264_data.to_vec(),
                 is_h264: true,
-                initial_hash: None,
                 idle_start_ms: frame.captured_at_ms,
                 last_similar_ms: frame.captured_at_ms,
             });
@@ -394,28 +537,30 @@ impl RecordingStateMachine {
                         ts = frame.captured_at_ms,
                         "IDLE (H.264): scene quiet"
                     );
+                    self.preroll.push_h264(frame.captured_at_ms, h264_data, frame.is_keyframe());
                     self.state = Some(RecordingState::Idle {
                         initial_payload,
                         is_h264: true,
-                        initial_hash: None,
                         idle_start_ms,
                         last_similar_ms: frame.captured_at_ms,
                     });
                 } else {
                     // Scene changed → ACTIVE
+                    let preroll = self.preroll.take();
+                    let idle_end_ms = idle_end_before_preroll(idle_start_ms, last_similar_ms, &preroll);
                     info!(
                         frame_size,
-                        nal_type, idle_start_ms, "IDLE→ACTIVE (H.264): motion detected"
+                        nal_type, idle_start_ms, idle_end_ms, "IDLE→ACTIVE (H.264): motion detected"
                     );
                     self.upload_idle_record(
                         &initial_payload,
                         true,
                         idle_start_ms,
-                        last_similar_ms,
+                        idle_end_ms,
                     )
                     .await;
 
-                    match self.start_active_segment_h264(frame, h264_data).await {
+                    match self.start_active_segment_h264(frame, h264_data, preroll).await {
                         Some(active_state) => {
                             self.state = Some(active_state);
                         }
@@ -424,7 +569,6 @@ impl RecordingStateMachine {
                             self.state = Some(RecordingState::Idle {
                                 initial_payload: h264_data.to_vec(),
                                 is_h264: true,
-                                initial_hash: None,
                                 idle_start_ms: frame.captured_at_ms,
                                 last_similar_ms: frame.captured_at_ms,
                             });
@@ -437,6 +581,7 @@ impl RecordingStateMachine {
                 segment_deadline,
                 segment_start_ms,
                 mut consecutive_idle_count,
+                mut motion_score,
                 ..
             } => {
                 // Check segment timer
@@ -447,16 +592,15 @@ impl RecordingStateMachine {
                         frames = encoder.frame_count(),
                         "ACTIVE (H.264): rolling segment (timer)"
                     );
-                    self.finish_and_upload_segment(encoder, frame.captured_at_ms)
+                    self.finish_and_upload_segment(encoder, frame.captured_at_ms, Some(motion_score))
                         .await;
 
-                    match self.start_active_segment_h264(frame, h264_data).await {
+                    match self.start_active_segment_h264(frame, h264_data, Vec::new()).await {
                         Some(s) => self.state = Some(s),
                         None => {
                             self.state = Some(RecordingState::Idle {
                                 initial_payload: h264_data.to_vec(),
                                 is_h264: true,
-                                initial_hash: None,
                                 idle_start_ms: frame.captured_at_ms,
                                 last_similar_ms: frame.captured_at_ms,
                             });
@@ -466,19 +610,20 @@ impl RecordingStateMachine {
                 }
 
                 // Push frame to encoder
-                if let Err(e) = encoder.push_h264(h264_data).await {
+                if let Err(e) = encoder.push_h264(h264_data, frame.captured_at_ms).await {
                     error!(error = %e, "ACTIVE (H.264): failed to push frame, finalizing");
-                    self.finish_and_upload_segment(encoder, frame.captured_at_ms)
+                    self.finish_and_upload_segment(encoder, frame.captured_at_ms, Some(motion_score))
                         .await;
                     self.state = Some(RecordingState::Idle {
                         initial_payload: h264_data.to_vec(),
                         is_h264: true,
-                        initial_hash: None,
                         idle_start_ms: frame.captured_at_ms,
                         last_similar_ms: frame.captured_at_ms,
                     });
                     return;
                 }
+                self.keep_raw_frame(&encoder, h264_data, true);
+                motion_score += self.frame_size_filter.motion(frame_size, nal_type);
 
                 // Check Active→Idle: count consecutive quiet P-frames
                 if self.frame_size_filter.is_quiet(frame_size) {
@@ -494,12 +639,11 @@ impl RecordingStateMachine {
                             consecutive_idle_count,
                             segment_start_ms, "ACTIVE→IDLE (H.264): scene stabilized"
                         );
-                        self.finish_and_upload_segment(encoder, frame.captured_at_ms)
+                        self.finish_and_upload_segment(encoder, frame.captured_at_ms, Some(motion_score))
                             .await;
                         self.state = Some(RecordingState::Idle {
                             initial_payload: h264_data.to_vec(),
                             is_h264: true,
-                            initial_hash: None,
                             idle_start_ms: frame.captured_at_ms,
                             last_similar_ms: frame.captured_at_ms,
                         });
@@ -514,37 +658,61 @@ impl RecordingStateMachine {
                     is_h264: true,
                     segment_deadline,
                     segment_start_ms,
-                    last_frame_hash: None,
                     consecutive_idle_count,
+                    motion_score,
                 });
             }
         }
     }
 
+    /// Start a passthrough segment with the `preroll` access units (if any,
+    /// from a keyframe) followed by `frame`.
     async fn start_active_segment_h264(
         &self,
         frame: &TimestampedFrame,
         h264_data: &[u8],
+        preroll: Vec<(i64, Vec<u8>)>,
     ) -> Option<RecordingState> {
-        let mut encoder =
-            match SegmentEncoder::start_passthrough(frame.captured_at_ms, self.config.fps).await {
-                Ok(e) => e,
-                Err(e) => {
-                    error!(error = %e, "failed to spawn ffmpeg passthrough");
-                    return None;
-                }
-            };
+        let start_ms = preroll.first().map_or(frame.captured_at_ms, |(ts, _)| *ts);
+        let mut encoder = match SegmentEncoder::start_passthrough(
+            start_ms,
+            &self.ffmpeg,
+            self.config.fps,
+            self.config.fps_auto,
+            self.segment_sink(start_ms, Container::Mp4),
+        )
+        .await
+        {
+            Ok(e) => e,
+            Err(e) => {
+                error!(error = %e, "failed to spawn ffmpeg passthrough");
+                return None;
+            }
+        };
+        self.attach_wal(&mut encoder, true);
 
-        if let Err(e) = encoder.push_h264(h264_data).await {
+        let preroll_frames = preroll.len();
+        for (ts, data) in &preroll {
+            if let Err(e) = encoder.push_h264(data, *ts).await {
+                error!(error = %e, "failed to push pre-roll H.264 AU to encoder");
+                discard_wal(&mut encoder);
+                return None;
+            }
+            self.keep_raw_frame(&encoder, data, true);
+        }
+        if let Err(e) = encoder.push_h264(h264_data, frame.captured_at_ms).await {
             error!(error = %e, "failed to push first H.264 AU to encoder");
+            discard_wal(&mut encoder);
             return None;
         }
+        self.keep_raw_frame(&encoder, h264_data, true);
 
         let segment_deadline =
             Instant::now() + Duration::from_secs(self.config.segment_duration_secs);
 
         info!(
-            segment_start_ms = frame.captured_at_ms,
+            segment_start_ms = start_ms,
+            preroll_frames,
             "ACTIVE: new H.264 passthrough segment started"
         );
 
@@ -552,9 +720,9 @@ impl RecordingStateMachine {
             encoder,
             is_h264: true,
             segment_deadline,
-            segment_start_ms: frame.captured_at_ms,
-            last_frame_hash: None,
+            segment_start_ms: start_ms,
             consecutive_idle_count: 0,
+            motion_score: 0.0,
         })
     }
 
@@ -562,45 +730,280 @@ impl RecordingStateMachine {
     // Shared helpers
     // =========================================================================
 
-    /// Finalize the encoder and upload the resulting MP4 to RustFS.
-    async fn finish_and_upload_segment(&self, encoder: SegmentEncoder, end_ms: i64) {
+    /// DEBUG: upload the source frame just pushed to `encoder` under the segment's
+    /// `raw/` sub-prefix, up to `max_raw_frames_per_segment`. No-op unless
+    /// `recording.keep_raw_frames` is set. Uploads run in the background so the
+    /// consume loop is not held up.
+    fn keep_raw_frame(&self, encoder: &SegmentEncoder, payload: &[u8], is_h264: bool) {
+        if !self.config.keep_raw_frames {
+            return;
+        }
+        let frame_index = encoder.frame_count().saturating_sub(1);
+        if frame_index >= self.config.max_raw_frames_per_segment {
+            if frame_index == self.config.max_raw_frames_per_segment {
+                warn!(
+                    segment_start_ms = encoder.start_ms,
+                    max = self.config.max_raw_frames_per_segment,
+                    "raw frame limit reached for segment, dropping further raw frames"
+                );
+            }
+            return;
+        }
+
+        let (ext, content_type) = if is_h264 {
+            ("h264", "video/h264")
+        } else {
+            ("jpg", "image/jpeg")
+        };
+        let frame = RawFrame {
+            segment_start_ms: encoder.start_ms,
+            segment_suffix: &encoder.suffix,
+            frame_index,
+            ext,
+            region: self.region.as_deref(),
+        };
+        let key = raw_frame_key(&self.prefix, &self.robot_id, &frame, self.timezone);
+        let storage = Arc::clone(&self.storage);
+        let data = payload.to_vec();
+        tokio::spawn(async move {
+            if let Err(e) = storage.put_raw_frame(&key, data, content_type).await {
+                warn!(error = %e, key, "failed to upload raw debug frame");
+            }
+        });
+    }
+
+    /// Where a segment starting at `start_ms` is encoded to: streamed to a
+    /// staging key with `recording.stream_upload`, else a temp file.
+    fn segment_sink(&self, start_ms: i64, container: Container) -> SegmentSink {
+        if !self.config.stream_upload {
+            return SegmentSink::TempFile;
+        }
+        SegmentSink::Multipart {
+            storage: Arc::clone(&self.storage),
+            staging_key: staging_segment_key(
+                &self.prefix,
+                &self.robot_id,
+                start_ms,
+                container.extension(),
+                self.timezone,
+            ),
+        }
+    }
+
+    /// A segment that won't be stored: drop its staging object, if streamed.
+    async fn discard_output(&self, output: SegmentOutput) {
+        if let SegmentOutput::Staged { staging_key, .. } = output {
+            self.storage.discard_staged(&staging_key).await;
+        }
+    }
+
+    /// Finalize the encoder and upload the resulting video to RustFS. The
+    /// segment's WAL is deleted once it's stored (or discarded as unplayable)
+    /// and kept otherwise, so a restart can retry. `motion_score` is recorded
+    /// with the segment (`None` when unknown, as for segments recovered from
+    /// a WAL).
+    async fn finish_and_upload_segment(
+        &self,
+        mut encoder: SegmentEncoder,
+        end_ms: i64,
+        motion_score: Option<f64>,
+    ) -> SegmentOutcome {
         let start_ms = encoder.start_ms;
-        match encoder.finish().await {
+        let suffix = encoder.suffix.clone();
+        let wal = encoder.take_wal();
+        let outcome = match encoder.finish().await {
+            Ok(seg) if !is_playable_segment(seg.frame_count, seg.output.size()) => {
+                warn!(
+                    start_ms,
+                    end_ms,
+                    frames = seg.frame_count,
+                    bytes = seg.output.size(),
+                    "discarding unplayable active segment (too few frames or bytes)"
+                );
+                self.discard_output(seg.output).await;
+                SegmentOutcome::Done
+            }
+            Ok(seg) if is_flicker_segment(start_ms, end_ms, self.config.min_segment_secs) => {
+                let suppressed = self.suppressed_segments.fetch_add(1, Ordering::Relaxed) + 1;
+                info!(
+                    start_ms,
+                    end_ms,
+                    frames = seg.frame_count,
+                    min_segment_secs = self.config.min_segment_secs,
+                    suppressed,
+                    region = self.region,
+                    "suppressing active segment shorter than recording.min_segment_secs"
+                );
+                self.discard_output(seg.output).await;
+                SegmentOutcome::Done
+            }
             Ok(seg) => {
-                let key = active_segment_key(&self.prefix, &self.robot_id, start_ms, end_ms);
-                let size_bytes = seg.mp4_bytes.len() as u64;
-                match self
-                    .storage
-                    .put_segment(&key, seg.mp4_bytes, start_ms)
-                    .await
-                {
+                let key = active_segment_key(
+                    &self.prefix,
+                    &self.robot_id,
+                    start_ms,
+                    end_ms,
+                    &suffix,
+                    seg.container.extension(),
+                    self.timezone,
+                );
+                let size_bytes = seg.output.size();
+                let stored = match seg.output {
+                    SegmentOutput::Bytes(bytes) => {
+                        self.storage
+                            .put_segment(&key, bytes, seg.container.content_type(), start_ms)
+                            .await
+                    }
+                    SegmentOutput::Staged { staging_key, size } => {
+                        self.storage.promote_segment(&staging_key, &key, size, start_ms).await
+                    }
+                };
+                match stored {
                     Ok(()) => {
                         info!(
                             key,
                             frames = seg.frame_count,
                             start_ms,
                             end_ms,
+                            region = self.region,
                             "uploaded active segment to RustFS"
                         );
                         if let Some(db) = &self.db {
-                            if let Err(e) = db.insert_active(
+                            let segment = NewActiveSegment {
                                 start_ms,
                                 end_ms,
-                                &key,
+                                s3_key: &key,
                                 size_bytes,
-                                seg.frame_count,
-                            ) {
+                                frame_count: seg.frame_count,
+                                motion_score,
+                                region: self.region.as_deref(),
+                            };
+                            if let Err(e) = db.insert_active(&segment) {
                                 error!(error = %e, key, "failed to insert active segment into SQLite");
                             }
                         }
+                        SegmentOutcome::Done
                     }
                     Err(e) => {
                         error!(error = %e, key, "failed to upload segment to RustFS");
+                        SegmentOutcome::UploadFailed
                     }
                 }
             }
             Err(e) => {
                 error!(error = %e, start_ms, end_ms, "encoder finish failed, segment lost");
+                SegmentOutcome::EncodeFailed
+            }
+        };
+
+        if let Some(wal) = wal {
+            if outcome == SegmentOutcome::Done {
+                wal.remove();
+            } else {
+                warn!(
+                    path = wal.path().display().to_string(),
+                    start_ms, "keeping segment WAL for recovery on restart"
+                );
+            }
+        }
+        outcome
+    }
+
+    /// Start logging `encoder`'s frames to a new WAL, if `recording.wal_dir` is set.
+    fn attach_wal(&self, encoder: &mut SegmentEncoder, is_h264: bool) {
+        let Some(dir) = &self.wal_dir else {
+            return;
+        };
+        match SegmentWal::create(dir, encoder.start_ms, is_h264, self.config.wal_flush_frames) {
+            Ok(wal) => encoder.attach_wal(wal),
+            Err(e) => {
+                warn!(
+                    dir = dir.display().to_string(),
+                    error = %e,
+                    "failed to create segment WAL, recording without it"
+                );
+            }
+        }
+    }
+
+    /// Finalize segments interrupted by a crash: re-encode the frames in each
+    /// WAL left in this machine's directory and upload them like any other
+    /// segment. Logs that fail to upload are kept for the next start; logs
+    /// ffmpeg fails on are moved to `failed/`, as retrying won't help.
+    pub async fn recover_wal(&self) {
+        let Some(dir) = &self.wal_dir else {
+            return;
+        };
+        for path in wal::list_wals(dir) {
+            let path_str = path.display().to_string();
+            let seg = match wal::read_wal(&path) {
+                Ok(seg) => seg,
+                Err(e) => {
+                    warn!(path = path_str, error = %e, "unreadable segment WAL, deleting");
+                    wal::remove_wal(&path);
+                    continue;
+                }
+            };
+            let Some(&(end_ms, _)) = seg.frames.last() else {
+                wal::remove_wal(&path);
+                continue;
+            };
+            info!(
+                path = path_str,
+                start_ms = seg.start_ms,
+                end_ms,
+                frames = seg.frames.len(),
+                region = self.region,
+                "recovering segment interrupted by a crash"
+            );
+
+            let encoder = if seg.is_h264 {
+                SegmentEncoder::start_passthrough(
+                    seg.start_ms,
+                    &self.ffmpeg,
+                    self.config.fps,
+                    self.config.fps_auto,
+                    self.segment_sink(seg.start_ms, Container::Mp4),
+                )
+                .await
+            } else {
+                SegmentEncoder::start(
+                    seg.start_ms,
+                    &self.ffmpeg,
+                    &self.video_encoder,
+                    &self.config,
+                    self.segment_sink(seg.start_ms, self.video_encoder.container()),
+                )
+                .await
+            };
+            let mut encoder = match encoder {
+                Ok(e) => e,
+                Err(e) => {
+                    error!(path = path_str, error = %e, "failed to spawn ffmpeg for WAL recovery");
+                    continue;
+                }
+            };
+            let mut pushed = Ok(());
+            for (ts, data) in &seg.frames {
+                pushed = if seg.is_h264 {
+                    encoder.push_h264(data, *ts).await
+                } else {
+                    encoder.push_frame(data, *ts).await.map(|_| ())
+                };
+                if pushed.is_err() {
+                    break;
+                }
+            }
+            if let Err(e) = pushed {
+                error!(path = path_str, error = %e, "failed to re-encode WAL frames");
+                give_up_on_wal(&path);
+                continue;
+            }
+
+            match self.finish_and_upload_segment(encoder, end_ms, None).await {
+                SegmentOutcome::Done => wal::remove_wal(&path),
+                SegmentOutcome::EncodeFailed => give_up_on_wal(&path),
+                SegmentOutcome::UploadFailed => {}
             }
         }
     }
@@ -619,14 +1022,14 @@ impl RecordingStateMachine {
             info!(idle_start_ms, idle_end_ms, "H.264 idle period (no snapshot)");
             if let Some(db) = &self.db {
                 let key = format!("idle:{}/{}", idle_start_ms, idle_end_ms);
-                if let Err(e) = db.insert_idle(idle_start_ms, idle_end_ms, &key, 0) {
+                if let Err(e) = db.insert_idle(idle_start_ms, idle_end_ms, &key, 0, self.region.as_deref()) {
                     error!(error = %e, "failed to insert H.264 idle record into SQLite");
                 }
             }
             return;
         }
 
-        let jpeg_key = idle_jpeg_key(&self.prefix, &self.robot_id, idle_start_ms, idle_end_ms);
+        let jpeg_key = idle_jpeg_key(&self.prefix, &self.robot_id, idle_start_ms, idle_end_ms, self.timezone);
         let jpeg_size = initial_payload.len() as u64;
         match self
             .storage
@@ -636,11 +1039,11 @@ impl RecordingStateMachine {
             Ok(()) => {
                 info!(
                     key = jpeg_key,
-                    idle_start_ms, idle_end_ms, "uploaded idle frame to RustFS"
+                    idle_start_ms, idle_end_ms, region = self.region, "uploaded idle frame to RustFS"
                 );
                 if let Some(db) = &self.db {
                     if let Err(e) =
-                        db.insert_idle(idle_start_ms, idle_end_ms, &jpeg_key, jpeg_size)
+                        db.insert_idle(idle_start_ms, idle_end_ms, &jpeg_key, jpeg_size, self.region.as_deref())
                     {
                         error!(error = %e, key = jpeg_key, "failed to insert idle record into SQLite");
                     }
@@ -652,3 +1055,86 @@ impl RecordingStateMachine {
         }
     }
 }
+
+/// Delete the WAL of a segment that failed to start: it is never finished,
+/// so nothing else would remove it.
+fn discard_wal(encoder: &mut SegmentEncoder) {
+    if let Some(wal) = encoder.take_wal() {
+        wal.remove();
+    }
+}
+
+/// Stop recovering a WAL whose frames ffmpeg fails on.
+fn give_up_on_wal(path: &Path) {
+    if let Some(moved) = wal::move_to_failed(path) {
+        warn!(path = moved.display().to_string(), "moved unencodable segment WAL aside, it won't be retried");
+    }
+}
+
+/// Whether a finished segment is worth uploading and recording.
+fn is_playable_segment(frame_count: u32, video_bytes: u64) -> bool {
+    frame_count >= MIN_SEGMENT_FRAMES && video_bytes >= MIN_SEGMENT_BYTES
+}
+
+/// Whether an idle period that started at `idle_start_ms` is due for a
+/// snapshot at `now_ms` (`recording.idle_snapshot_interval_secs`, 0 = never).
+fn idle_snapshot_due(idle_start_ms: i64, now_ms: i64, interval_secs: u64) -> bool {
+    if interval_secs == 0 {
+        return false;
+    }
+    let interval_ms = (interval_secs.max(MIN_IDLE_SNAPSHOT_SECS) * 1000) as i64;
+    now_ms - idle_start_ms >= interval_ms
+}
+
+/// Where the idle record closed on IDLE→ACTIVE ends: the pre-roll frames
+/// open the active segment, so the idle period stops at the oldest of them
+/// (never before it started). Without pre-roll, at the last similar frame.
+fn idle_end_before_preroll(idle_start_ms: i64, last_similar_ms: i64, preroll: &[(i64, Vec<u8>)]) -> i64 {
+    preroll.first().map_or(last_similar_ms, |(ts, _)| (*ts).max(idle_start_ms))
+}
+
+/// Whether a segment is too short to keep under `recording.min_segment_secs`.
+fn is_flicker_segment(start_ms: i64, end_ms: i64, min_segment_secs: u64) -> bool {
+    end_ms - start_ms < (min_segment_secs * 1000) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_snapshots_respect_interval_and_minimum() {
+        assert!(!idle_snapshot_due(0, 10_000_000, 0));
+        assert!(!idle_snapshot_due(0, 599_999, 600));
+        assert!(idle_snapshot_due(0, 600_000, 600));
+        // 5 s is raised to the 60 s minimum.
+        assert!(!idle_snapshot_due(0, 30_000, 5));
+        assert!(idle_snapshot_due(0, 60_000, 5));
+    }
+
+    #[test]
+    fn idle_record_ends_where_preroll_starts() {
+        let preroll = vec![(4_000, vec![]), (5_000, vec![])];
+        assert_eq!(idle_end_before_preroll(1_000, 5_000, &preroll), 4_000);
+        // Pre-roll kept from before a periodic snapshot restarted the period.
+        assert_eq!(idle_end_before_preroll(4_500, 5_000, &preroll), 4_500);
+        assert_eq!(idle_end_before_preroll(1_000, 5_000, &[]), 5_000);
+    }
+
+    #[test]
+    fn segments_under_min_duration_are_flicker() {
+        assert!(!is_flicker_segment(0, 1_500, 0));
+        assert!(is_flicker_segment(0, 1_500, 3));
+        assert!(!is_flicker_segment(0, 3_000, 3));
+    }
+
+    #[test]
+    fn zero_and_one_frame_segments_are_discarded() {
+        assert!(!is_playable_segment(0, 0));
+        assert!(!is_playable_segment(1, 40_000));
+        // ffmpeg produced (almost) nothing despite frames being pushed.
+        assert!(!is_playable_segment(30, 0));
+        assert!(!is_playable_segment(30, MIN_SEGMENT_BYTES - 1));
+        assert!(is_playable_segment(MIN_SEGMENT_FRAMES, 40_000));
+    }
+}
@@ -0,0 +1,232 @@
+//! Write-ahead log of the frames fed to an active segment (`recording.wal_dir`).
+//!
+//! Every frame pushed to a segment's encoder is appended to a local file,
+//! synced to disk every `wal_flush_frames` frames. The file is removed once
+//! the segment is uploaded (or deliberately discarded). Files still present
+//! at startup belong to segments interrupted by a crash and are re-encoded.
+//! A file whose frames ffmpeg can't encode is moved to `failed/` instead, so
+//! recovery doesn't retry it on every start.
+//!
+//! Format: `FBWAL1`, kind byte (0 = JPEG, 1 = H.264), segment start (i64 BE),
+//! then records of `captured_at_ms` (i64 BE), length (u32 BE), payload. A
+//! truncated trailing record (crash mid-write) is ignored on read.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+const MAGIC: &[u8; 6] = b"FBWAL1";
+const WAL_EXT: &str = "wal";
+/// Subdirectory, next to a log, that logs recovery gave up on are moved to.
+const FAILED_DIR: &str = "failed";
+
+pub struct SegmentWal {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    flush_every: u32,
+    unsynced: u32,
+}
+
+impl SegmentWal {
+    /// Create the log for a segment starting at `start_ms` in `dir` (created if missing).
+    pub fn create(dir: &Path, start_ms: i64, is_h264: bool, flush_every: u32) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{start_ms}_{:08x}.{WAL_EXT}", fastrand::u32(..)));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[is_h264 as u8])?;
+        writer.write_all(&start_ms.to_be_bytes())?;
+        Ok(Self {
+            path,
+            writer,
+            flush_every: flush_every.max(1),
+            unsynced: 0,
+        })
+    }
+
+    /// Append one frame, syncing to disk every `flush_every` frames.
+    pub fn append(&mut self, captured_at_ms: i64, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(&captured_at_ms.to_be_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_be_bytes())?;
+        self.writer.write_all(data)?;
+        self.unsynced += 1;
+        if self.unsynced >= self.flush_every {
+            self.writer.flush()?;
+            self.writer.get_ref().sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Delete the log: its segment no longer needs recovering.
+    pub fn remove(self) {
+        let Self { path, writer, .. } = self;
+        drop(writer);
+        remove_wal(&path);
+    }
+}
+
+/// Delete a log file, warning if that fails.
+pub fn remove_wal(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!(path = path.display().to_string(), error = %e, "failed to delete segment WAL");
+    }
+}
+
+/// Move a log whose frames failed to encode to `failed/` in its directory,
+/// out of [`list_wals`]'s reach but kept for inspection. Deletes it if the
+/// move fails, so recovery still stops retrying it.
+pub fn move_to_failed(path: &Path) -> Option<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        remove_wal(path);
+        return None;
+    };
+    let failed_dir = dir.join(FAILED_DIR);
+    let target = failed_dir.join(name);
+    match std::fs::create_dir_all(&failed_dir).and_then(|()| std::fs::rename(path, &target)) {
+        Ok(()) => Some(target),
+        Err(e) => {
+            warn!(path = path.display().to_string(), error = %e, "failed to move segment WAL to failed/, deleting it");
+            remove_wal(path);
+            None
+        }
+    }
+}
+
+/// Frames read back from a segment's log.
+#[derive(Debug)]
+pub struct RecoveredSegment {
+    pub start_ms: i64,
+    pub is_h264: bool,
+    /// `(captured_at_ms, payload)` in push order.
+    pub frames: Vec<(i64, Vec<u8>)>,
+}
+
+/// Read a log left behind by an interrupted segment.
+pub fn read_wal(path: &Path) -> io::Result<RecoveredSegment> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    let header_len = MAGIC.len() + 1 + 8;
+    if bytes.len() < header_len || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a segment WAL"));
+    }
+    let is_h264 = bytes[MAGIC.len()] != 0;
+    let start_ms = i64::from_be_bytes(bytes[MAGIC.len() + 1..header_len].try_into().unwrap());
+
+    let mut frames = Vec::new();
+    let mut rest = &bytes[header_len..];
+    while rest.len() >= 12 {
+        let ts = i64::from_be_bytes(rest[..8].try_into().unwrap());
+        let len = u32::from_be_bytes(rest[8..12].try_into().unwrap()) as usize;
+        let Some(data) = rest.get(12..12 + len) else {
+            break; // torn write
+        };
+        frames.push((ts, data.to_vec()));
+        rest = &rest[12 + len..];
+    }
+
+    Ok(RecoveredSegment {
+        start_ms,
+        is_h264,
+        frames,
+    })
+}
+
+/// Log files in `dir` (not its subdirectories), oldest segment first.
+/// Empty if the directory doesn't exist.
+pub fn list_wals(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == WAL_EXT))
+        .collect();
+    // File names start with the segment's start_ms.
+    paths.sort_by_key(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.split('_').next())
+            .and_then(|ms| ms.parse::<i64>().ok())
+    });
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("frame-bucket-wal-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn wal_round_trips_and_tolerates_torn_tail() {
+        let dir = temp_dir("roundtrip");
+        let mut wal = SegmentWal::create(&dir, 1_000, false, 2).unwrap();
+        wal.append(1_000, b"frame-a").unwrap();
+        wal.append(1_033, b"frame-b").unwrap();
+        wal.append(1_066, b"frame-c").unwrap();
+        let path = wal.path().to_path_buf();
+        // Simulate a crash: the last frame is still in the buffer, and a
+        // partial record follows the synced ones.
+        std::mem::forget(wal);
+        let mut f = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        f.write_all(&2_000i64.to_be_bytes()).unwrap();
+        f.write_all(&100u32.to_be_bytes()).unwrap();
+        f.write_all(b"trunc").unwrap();
+
+        assert_eq!(list_wals(&dir), vec![path.clone()]);
+        let seg = read_wal(&path).unwrap();
+        assert_eq!(seg.start_ms, 1_000);
+        assert!(!seg.is_h264);
+        let frames: Vec<(i64, &[u8])> = seg.frames.iter().map(|(ts, d)| (*ts, d.as_slice())).collect();
+        assert_eq!(frames, [(1_000, &b"frame-a"[..]), (1_033, &b"frame-b"[..])]);
+
+        remove_wal(&path);
+        assert!(list_wals(&dir).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn removed_wal_is_gone_and_garbage_is_rejected() {
+        let dir = temp_dir("remove");
+        let mut wal = SegmentWal::create(&dir, 5, true, 1).unwrap();
+        wal.append(5, b"au").unwrap();
+        let path = wal.path().to_path_buf();
+        assert!(read_wal(&path).unwrap().is_h264);
+        wal.remove();
+        assert!(!path.exists());
+
+        let junk = dir.join("junk.wal");
+        std::fs::write(&junk, b"hello").unwrap();
+        assert!(read_wal(&junk).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_wal_is_kept_out_of_recovery() {
+        let dir = temp_dir("failed");
+        let mut wal = SegmentWal::create(&dir, 7, false, 1).unwrap();
+        wal.append(7, b"bad").unwrap();
+        let path = wal.path().to_path_buf();
+        drop(wal);
+
+        let moved = move_to_failed(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(moved, dir.join(FAILED_DIR).join(path.file_name().unwrap()));
+        assert_eq!(read_wal(&moved).unwrap().frames.len(), 1);
+        assert!(list_wals(&dir).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,18 +1,28 @@
 use aws_credential_types::Credentials;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
 use aws_types::region::Region;
+use bytes::Bytes;
 use chrono::NaiveDateTime;
-use frame_bucket_common::config::RustfsConfig;
-use std::collections::BTreeMap;
+use frame_bucket_common::config::{RustfsConfig, is_thumbnail_key};
+use std::collections::{BTreeMap, HashSet};
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tracing::{debug, info, warn};
 
+/// Longest wait between two upload attempts.
+const MAX_UPLOAD_BACKOFF: Duration = Duration::from_secs(30);
+/// Size of each part of a streamed segment but the last, and so the most of
+/// it held in memory. S3 requires at least 5 MiB.
+pub const MULTIPART_PART_SIZE: usize = 8 * 1024 * 1024;
+
 /// Tracks stored objects for ring-buffer eviction ordering.
 #[derive(Debug)]
 pub struct ObjectEntry {
-    #[allow(dead_code)]
-    pub key: String,
+    pub start_ms: i64,
     pub size_bytes: u64,
 }
 
@@ -20,10 +30,20 @@ pub struct ObjectEntry {
 pub struct RustfsStorage {
     client: aws_sdk_s3::Client,
     bucket: String,
-    #[allow(dead_code)]
     prefix: String,
-    /// Ordered map: captured_at_ms -> stored object metadata.
-    pub index: Arc<Mutex<BTreeMap<i64, ObjectEntry>>>,
+    /// Object key -> stored object metadata. Keyed by key rather than start
+    /// time: two objects can share a start (whole-frame and region segments,
+    /// or two robots writing through one storage).
+    pub index: Arc<Mutex<BTreeMap<String, ObjectEntry>>>,
+    /// Uploads since startup, for `/metrics`.
+    pub segments_stored: AtomicU64,
+    pub idle_frames_stored: AtomicU64,
+    /// Bytes of the segments and idle frames above.
+    pub bytes_stored: AtomicU64,
+    /// `rustfs.upload_attempts`, at least 1.
+    upload_attempts: u32,
+    /// `rustfs.upload_backoff_ms`.
+    upload_backoff: Duration,
 }
 
 impl RustfsStorage {
@@ -54,6 +74,11 @@ impl RustfsStorage {
             bucket: config.bucket.clone(),
             prefix: config.prefix.clone(),
             index: Arc::new(Mutex::new(BTreeMap::new())),
+            segments_stored: AtomicU64::new(0),
+            idle_frames_stored: AtomicU64::new(0),
+            bytes_stored: AtomicU64::new(0),
+            upload_attempts: config.upload_attempts.max(1),
+            upload_backoff: Duration::from_millis(config.upload_backoff_ms),
         }
     }
 
@@ -100,33 +125,41 @@ impl RustfsStorage {
 
         debug!(key = object_key, size, "stored frame in RustFS");
 
+        self.index_object(object_key, captured_at_ms, size).await;
+
+        Ok(())
+    }
+
+    /// Add a stored object to the in-memory index.
+    async fn index_object(&self, object_key: &str, start_ms: i64, size_bytes: u64) {
         self.index.lock().await.insert(
-            captured_at_ms,
+            object_key.to_string(),
             ObjectEntry {
-                key: object_key.to_string(),
-                size_bytes: size,
+                start_ms,
+                size_bytes,
             },
         );
-
-        Ok(())
     }
 
     /// Get the oldest N entries from the in-memory index.
     #[allow(dead_code)]
-    pub async fn oldest_n(&self, n: usize) -> Vec<(i64, ObjectEntry)> {
+    pub async fn oldest_n(&self, n: usize) -> Vec<(String, ObjectEntry)> {
         let idx = self.index.lock().await;
-        idx.iter()
-            .take(n)
-            .map(|(&ts, entry)| {
+        let mut entries: Vec<_> = idx
+            .iter()
+            .map(|(key, entry)| {
                 (
-                    ts,
+                    key.clone(),
                     ObjectEntry {
-                        key: entry.key.clone(),
+                        start_ms: entry.start_ms,
                         size_bytes: entry.size_bytes,
                     },
                 )
             })
-            .collect()
+            .collect();
+        entries.sort_by_key(|(_, entry)| entry.start_ms);
+        entries.truncate(n);
+        entries
     }
 
     /// Download an object's bytes from RustFS.
@@ -150,7 +183,7 @@ impl RustfsStorage {
     }
 
     /// Delete an object from RustFS and remove from the index.
-    pub async fn delete_object(&self, key: &str, captured_at_ms: i64) -> Result<(), StorageError> {
+    pub async fn delete_object(&self, key: &str) -> Result<(), StorageError> {
         self.client
             .delete_object()
             .bucket(&self.bucket)
@@ -159,7 +192,7 @@ impl RustfsStorage {
             .await
             .map_err(|e| StorageError::DeleteObject(e.to_string()))?;
 
-        self.index.lock().await.remove(&captured_at_ms);
+        self.index.lock().await.remove(key);
         debug!(key, "deleted from RustFS");
         Ok(())
     }
@@ -173,58 +206,131 @@ impl RustfsStorage {
     ) -> Result<(), StorageError> {
         let size = jpeg_data.len() as u64;
 
+        self.put_with_retry(object_key, jpeg_data.into(), "image/jpeg")
+            .await?;
+
+        debug!(key = object_key, size, "stored idle frame in RustFS");
+        self.idle_frames_stored.fetch_add(1, Ordering::Relaxed);
+        self.bytes_stored.fetch_add(size, Ordering::Relaxed);
+
+        self.index_object(object_key, start_ms, size).await;
+
+        Ok(())
+    }
+
+    /// Store a completed video segment (MP4 or WebM). Indexed for eviction.
+    pub async fn put_segment(
+        &self,
+        object_key: &str,
+        video_data: Vec<u8>,
+        content_type: &str,
+        start_ms: i64,
+    ) -> Result<(), StorageError> {
+        let size = video_data.len() as u64;
+
+        self.put_with_retry(object_key, video_data.into(), content_type)
+            .await?;
+
+        debug!(key = object_key, size, "stored segment in RustFS");
+        self.segments_stored.fetch_add(1, Ordering::Relaxed);
+        self.bytes_stored.fetch_add(size, Ordering::Relaxed);
+
+        self.index_object(object_key, start_ms, size).await;
+
+        Ok(())
+    }
+
+    /// Start a multipart upload at `object_key`, for a segment streamed out
+    /// of ffmpeg (`recording.stream_upload`).
+    pub async fn start_multipart(
+        &self,
+        object_key: &str,
+        content_type: &str,
+    ) -> Result<MultipartUpload, StorageError> {
+        let resp = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(object_key)
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(|e| StorageError::Multipart(e.to_string()))?;
+        let upload_id = resp
+            .upload_id()
+            .ok_or_else(|| StorageError::Multipart("no upload id in response".into()))?
+            .to_string();
+        debug!(key = object_key, upload_id, "started multipart upload");
+        Ok(MultipartUpload {
+            client: self.client.clone(),
+            bucket: self.bucket.clone(),
+            key: object_key.to_string(),
+            upload_id,
+            parts: Vec::new(),
+            buffer: Vec::with_capacity(MULTIPART_PART_SIZE),
+            size: 0,
+            upload_attempts: self.upload_attempts,
+            upload_backoff: self.upload_backoff,
+        })
+    }
+
+    /// Move a streamed segment from its staging key to `object_key` (a
+    /// server-side copy) and index it for eviction, like [`Self::put_segment`].
+    pub async fn promote_segment(
+        &self,
+        staging_key: &str,
+        object_key: &str,
+        size: u64,
+        start_ms: i64,
+    ) -> Result<(), StorageError> {
         self.client
-            .put_object()
+            .copy_object()
             .bucket(&self.bucket)
+            .copy_source(format!("{}/{staging_key}", self.bucket))
             .key(object_key)
-            .content_type("image/jpeg")
-            .body(ByteStream::from(jpeg_data))
             .send()
             .await
-            .map_err(|e| StorageError::PutObject(e.to_string()))?;
+            .map_err(|e| StorageError::CopyObject(e.to_string()))?;
+        self.discard_staged(staging_key).await;
 
-        debug!(key = object_key, size, "stored idle frame in RustFS");
+        debug!(key = object_key, size, "stored streamed segment in RustFS");
+        self.segments_stored.fetch_add(1, Ordering::Relaxed);
+        self.bytes_stored.fetch_add(size, Ordering::Relaxed);
 
-        self.index.lock().await.insert(
-            start_ms,
-            ObjectEntry {
-                key: object_key.to_string(),
-                size_bytes: size,
-            },
-        );
+        self.index_object(object_key, start_ms, size).await;
 
         Ok(())
     }
 
-    /// Store a completed MP4 video segment. Indexed for eviction.
-    pub async fn put_segment(
+    /// Best-effort delete of a streamed segment's staging object. One left
+    /// behind is still picked up by eviction through the bucket listing.
+    pub async fn discard_staged(&self, staging_key: &str) {
+        if let Err(e) = self.client.delete_object().bucket(&self.bucket).key(staging_key).send().await {
+            warn!(key = staging_key, error = %e, "failed to delete staged segment");
+        }
+    }
+
+    /// Store a raw source frame kept for debugging. Not indexed: raw frames are
+    /// only picked up by eviction through the bucket listing.
+    pub async fn put_raw_frame(
         &self,
         object_key: &str,
-        mp4_data: Vec<u8>,
-        start_ms: i64,
+        data: Vec<u8>,
+        content_type: &str,
     ) -> Result<(), StorageError> {
-        let size = mp4_data.len() as u64;
+        let size = data.len() as u64;
 
         self.client
             .put_object()
             .bucket(&self.bucket)
             .key(object_key)
-            .content_type("video/mp4")
-            .body(ByteStream::from(mp4_data))
+            .content_type(content_type)
+            .body(ByteStream::from(data))
             .send()
             .await
             .map_err(|e| StorageError::PutObject(e.to_string()))?;
 
-        debug!(key = object_key, size, "stored segment in RustFS");
-
-        self.index.lock().await.insert(
-            start_ms,
-            ObjectEntry {
-                key: object_key.to_string(),
-                size_bytes: size,
-            },
-        );
-
+        debug!(key = object_key, size, "stored raw frame in RustFS");
         Ok(())
     }
 
@@ -236,85 +342,187 @@ impl RustfsStorage {
         (count, bytes)
     }
 
-    /// Scan the entire bucket and return (object_count, total_bytes).
-    /// Used once at startup to bootstrap stats when the persistent stats file is stale or missing.
-    pub async fn bucket_stats(&self) -> (usize, u64) {
+    /// Scan the entire bucket and return (object_count, total_bytes), or None
+    /// if listing fails. Used at startup and for the eviction loop's rescans.
+    pub async fn bucket_stats(&self) -> Option<(usize, u64)> {
         let mut count: usize = 0;
         let mut total_bytes: u64 = 0;
-        let mut continuation_token: Option<String> = None;
-
-        loop {
-            let mut req = self.client.list_objects_v2().bucket(&self.bucket);
-            if let Some(token) = &continuation_token {
-                req = req.continuation_token(token);
-            }
-
-            let resp = match req.send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    warn!(error = %e, "failed to scan bucket for stats");
-                    return (count, total_bytes);
-                }
-            };
-
-            for obj in resp.contents() {
+        let listed = self
+            .for_each_object(None, |_, size| {
                 count += 1;
-                total_bytes += obj.size().unwrap_or(0) as u64;
-            }
+                total_bytes += size;
+                ControlFlow::Continue(())
+            })
+            .await;
+        if let Err(e) = listed {
+            warn!(error = e, "failed to scan bucket for stats");
+            return None;
+        }
+        Some((count, total_bytes))
+    }
 
-            if resp.is_truncated() == Some(true) {
-                continuation_token = resp.next_continuation_token().map(|s| s.to_string());
-            } else {
-                break;
+    /// Scan the bucket like [`Self::bucket_stats`] and drop the index entries
+    /// whose object is no longer in it (deleted through the API), so they stop
+    /// counting as session usage. Entries indexed after the scan started are
+    /// kept, since the listing may have missed them.
+    pub async fn rescan(&self) -> Option<(usize, u64)> {
+        let mut missing: HashSet<String> = self.index.lock().await.keys().cloned().collect();
+        let mut count: usize = 0;
+        let mut total_bytes: u64 = 0;
+        let listed = self
+            .for_each_object(None, |key, size| {
+                count += 1;
+                total_bytes += size;
+                missing.remove(key);
+                ControlFlow::Continue(())
+            })
+            .await;
+        if let Err(e) = listed {
+            warn!(error = e, "failed to scan bucket for stats");
+            return None;
+        }
+        if !missing.is_empty() {
+            let mut idx = self.index.lock().await;
+            for key in &missing {
+                idx.remove(key);
             }
+            debug!(dropped = missing.len(), "dropped index entries no longer in the bucket");
         }
-
-        (count, total_bytes)
+        Some((count, total_bytes))
     }
 
     /// List the N oldest objects directly from the bucket (by lexicographic key order).
     /// Used for eviction when the in-memory index may not have pre-existing objects.
     pub async fn list_oldest_from_bucket(&self, n: usize) -> Vec<(String, u64, i64)> {
         let mut result = Vec::new();
-        let mut continuation_token: Option<String> = None;
+        if n == 0 {
+            return result;
+        }
+        let listed = self
+            .for_each_object(Some(n), |key, size| {
+                if !is_thumbnail_key(&self.prefix, key) {
+                    result.push((key.to_string(), size, parse_start_ms_from_key(key).unwrap_or(0)));
+                }
+                if result.len() < n {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            })
+            .await;
+        if let Err(e) = listed {
+            warn!(error = e, "failed to list objects from bucket for eviction");
+        }
+        result
+    }
 
+    /// Up to `n` objects to evict with `eviction.policy = "idle_first"`: the
+    /// oldest non-video objects (idle JPEGs), then the oldest video segments
+    /// if there are fewer than `n` of those. Lists the whole bucket when it
+    /// holds fewer than `n` idle objects.
+    pub async fn list_idle_first_from_bucket(&self, n: usize) -> Vec<(String, u64, i64)> {
+        let mut idle = Vec::new();
+        let mut videos = Vec::new();
+        let listed = self
+            .for_each_object(None, |key, size| {
+                if is_thumbnail_key(&self.prefix, key) {
+                    return ControlFlow::Continue(());
+                }
+                let entry = (key.to_string(), size, parse_start_ms_from_key(key).unwrap_or(0));
+                if is_video_key(key) {
+                    if videos.len() < n {
+                        videos.push(entry);
+                    }
+                } else {
+                    idle.push(entry);
+                }
+                if idle.len() < n {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            })
+            .await;
+        if let Err(e) = listed {
+            warn!(error = e, "failed to list objects from bucket for eviction");
+        }
+        idle_first(idle, videos, n)
+    }
+
+    /// Page through the bucket in key order (`page_size` keys per request, or
+    /// the server's default), calling `visit` with each object's key and size
+    /// until it breaks or the listing ends. A failed request ends the listing
+    /// with its error, after the pages before it were visited.
+    async fn for_each_object(
+        &self,
+        page_size: Option<usize>,
+        mut visit: impl FnMut(&str, u64) -> ControlFlow<()>,
+    ) -> Result<(), String> {
+        let mut continuation_token: Option<String> = None;
         loop {
-            let mut req = self
-                .client
-                .list_objects_v2()
-                .bucket(&self.bucket)
-                .max_keys(n as i32);
+            let mut req = self.client.list_objects_v2().bucket(&self.bucket);
+            if let Some(n) = page_size {
+                req = req.max_keys(n.min(i32::MAX as usize) as i32);
+            }
             if let Some(token) = &continuation_token {
                 req = req.continuation_token(token);
             }
-
-            let resp = match req.send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    warn!(error = %e, "failed to list objects from bucket for eviction");
-                    return result;
-                }
-            };
+            let resp = req.send().await.map_err(|e| e.to_string())?;
 
             for obj in resp.contents() {
-                if result.len() >= n {
-                    return result;
-                }
-                if let Some(key) = obj.key() {
-                    let size = obj.size().unwrap_or(0) as u64;
-                    let ts = parse_start_ms_from_key(key).unwrap_or(0);
-                    result.push((key.to_string(), size, ts));
+                let Some(key) = obj.key() else { continue };
+                if visit(key, obj.size().unwrap_or(0) as u64).is_break() {
+                    return Ok(());
                 }
             }
 
-            if resp.is_truncated() == Some(true) && result.len() < n {
-                continuation_token = resp.next_continuation_token().map(|s| s.to_string());
-            } else {
-                break;
+            match resp.next_continuation_token() {
+                Some(token) if resp.is_truncated() == Some(true) => continuation_token = Some(token.to_string()),
+                _ => return Ok(()),
             }
         }
+    }
 
-        result
+    /// PUT `data` at `object_key`, retrying failures with exponential backoff
+    /// for up to `rustfs.upload_attempts` tries in all, so a RustFS blip
+    /// doesn't lose the segment.
+    async fn put_with_retry(
+        &self,
+        object_key: &str,
+        data: Bytes,
+        content_type: &str,
+    ) -> Result<(), StorageError> {
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(object_key)
+                .content_type(content_type)
+                .body(ByteStream::from(data.clone()))
+                .send()
+                .await;
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.upload_attempts => {
+                    let delay = upload_backoff(self.upload_backoff, attempt);
+                    warn!(
+                        key = object_key,
+                        attempt,
+                        max_attempts = self.upload_attempts,
+                        retry_in_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "RustFS upload failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(StorageError::PutObject(format!("{e} (gave up after {attempt} attempts)")));
+                }
+            }
+        }
     }
 
     #[allow(dead_code)]
@@ -329,8 +537,9 @@ impl RustfsStorage {
 }
 
 /// Parse the start timestamp (in ms) from an object key.
-/// Keys look like: `robot/camera/2026-02-18/20260218T093000000Z_20260218T094000000Z.jpg`
-fn parse_start_ms_from_key(key: &str) -> Option<i64> {
+/// Keys look like: `robot/camera/2026-02-18/20260218T093000000Z_20260218T094000000Z_3f9a01c2.jpg`
+/// (older keys have no random suffix).
+pub(crate) fn parse_start_ms_from_key(key: &str) -> Option<i64> {
     let filename = key.rsplit('/').next()?;
     let start_part = filename.split('_').next()?;
     let clean = start_part.trim_end_matches('Z');
@@ -341,6 +550,138 @@ fn parse_start_ms_from_key(key: &str) -> Option<i64> {
     Some(dt.and_utc().timestamp_millis())
 }
 
+/// Whether `key` is a video segment (as opposed to an idle JPEG or a raw
+/// debug frame).
+fn is_video_key(key: &str) -> bool {
+    key.ends_with(".mp4") || key.ends_with(".webm")
+}
+
+/// The first `n` of `idle` followed by `videos`, each already oldest first.
+fn idle_first<T>(idle: Vec<T>, videos: Vec<T>, n: usize) -> Vec<T> {
+    idle.into_iter().chain(videos).take(n).collect()
+}
+
+/// Wait after failed attempt number `attempt` (from 1): `base`, doubling each
+/// time, capped at [`MAX_UPLOAD_BACKOFF`].
+fn upload_backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(1 << (attempt - 1).min(16)).min(MAX_UPLOAD_BACKOFF)
+}
+
+/// An object uploaded in [`MULTIPART_PART_SIZE`] parts as its bytes arrive.
+pub struct MultipartUpload {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    parts: Vec<CompletedPart>,
+    /// Bytes not yet sent, less than a part except within `write`.
+    buffer: Vec<u8>,
+    /// Bytes written so far.
+    size: u64,
+    upload_attempts: u32,
+    upload_backoff: Duration,
+}
+
+impl MultipartUpload {
+    /// Append `data`, sending each part as soon as it's full.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), StorageError> {
+        self.buffer.extend_from_slice(data);
+        self.size += data.len() as u64;
+        while self.buffer.len() >= MULTIPART_PART_SIZE {
+            let rest = self.buffer.split_off(MULTIPART_PART_SIZE);
+            let part = std::mem::replace(&mut self.buffer, rest);
+            self.upload_part(part.into()).await?;
+        }
+        Ok(())
+    }
+
+    /// Send the last part and complete the object. Returns its size.
+    pub async fn complete(mut self) -> Result<u64, StorageError> {
+        // The last part may be short, and an upload needs at least one.
+        if !self.buffer.is_empty() || self.parts.is_empty() {
+            let part = std::mem::take(&mut self.buffer);
+            self.upload_part(part.into()).await?;
+        }
+        self.client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .multipart_upload(
+                CompletedMultipartUpload::builder()
+                    .set_parts(Some(std::mem::take(&mut self.parts)))
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(|e| StorageError::Multipart(e.to_string()))?;
+        debug!(key = self.key, size = self.size, "completed multipart upload");
+        Ok(self.size)
+    }
+
+    /// Abort the upload, so RustFS drops the parts sent so far.
+    pub async fn abort(self) {
+        let result = self
+            .client
+            .abort_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(&self.upload_id)
+            .send()
+            .await;
+        if let Err(e) = result {
+            warn!(key = self.key, error = %e, "failed to abort multipart upload");
+        }
+    }
+
+    /// Send one part, retrying like [`RustfsStorage::put_with_retry`].
+    async fn upload_part(&mut self, body: Bytes) -> Result<(), StorageError> {
+        let part_number = self.parts.len() as i32 + 1;
+        let mut attempt = 1;
+        let e_tag = loop {
+            let result = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .upload_id(&self.upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(body.clone()))
+                .send()
+                .await;
+            match result {
+                Ok(resp) => break resp.e_tag().map(str::to_string),
+                Err(e) if attempt < self.upload_attempts => {
+                    let delay = upload_backoff(self.upload_backoff, attempt);
+                    warn!(
+                        key = self.key,
+                        part_number,
+                        attempt,
+                        max_attempts = self.upload_attempts,
+                        retry_in_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "RustFS part upload failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(StorageError::Multipart(format!(
+                        "part {part_number}: {e} (gave up after {attempt} attempts)"
+                    )));
+                }
+            }
+        };
+        self.parts.push(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .set_e_tag(e_tag)
+                .build(),
+        );
+        Ok(())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum StorageError {
     #[error("failed to create bucket: {0}")]
@@ -351,4 +692,79 @@ pub enum StorageError {
     GetObject(String),
     #[error("failed to delete object: {0}")]
     DeleteObject(String),
+    #[error("multipart upload failed: {0}")]
+    Multipart(String),
+    #[error("failed to copy object: {0}")]
+    CopyObject(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_first_fills_up_with_videos() {
+        assert!(is_video_key("frames/r/camera/d/a_b_c.webm"));
+        assert!(!is_video_key("frames/r/camera/d/a_b_c.jpg"));
+
+        let idle = vec!["i1", "i2"];
+        let videos = vec!["v1", "v2", "v3"];
+        assert_eq!(idle_first(idle.clone(), videos.clone(), 3), ["i1", "i2", "v1"]);
+        assert_eq!(idle_first(idle, videos, 1), ["i1"]);
+    }
+
+    fn offline_storage() -> RustfsStorage {
+        let s3_config = aws_sdk_s3::Config::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .build();
+        RustfsStorage {
+            client: aws_sdk_s3::Client::from_conf(s3_config),
+            bucket: "frames".into(),
+            prefix: String::new(),
+            index: Arc::new(Mutex::new(BTreeMap::new())),
+            segments_stored: AtomicU64::new(0),
+            idle_frames_stored: AtomicU64::new(0),
+            bytes_stored: AtomicU64::new(0),
+            upload_attempts: 1,
+            upload_backoff: Duration::ZERO,
+        }
+    }
+
+    #[tokio::test]
+    async fn index_keeps_objects_with_the_same_start() {
+        let storage = offline_storage();
+        // A whole-frame and a region segment starting on the same frame.
+        let whole = "frames/r/camera/2026-02-18/20260218T094000000Z_20260218T095000000Z_b41e77d0.mp4";
+        let region = "frames/r/camera/2026-02-18/20260218T094000000Z_20260218T094500000Z_3f9a01c2.mp4";
+        storage.index_object(whole, 1_000, 10).await;
+        storage.index_object(region, 1_000, 20).await;
+        assert_eq!(storage.stats().await, (2, 30));
+
+        // Re-indexing the same key replaces its entry rather than adding one.
+        storage.index_object(whole, 1_000, 15).await;
+        assert_eq!(storage.stats().await, (2, 35));
+    }
+
+    #[tokio::test]
+    async fn index_keeps_two_robots_segments_with_the_same_start() {
+        let storage = offline_storage();
+        let a = "frames/reachy-001/camera/2026-02-18/20260218T094000000Z_20260218T095000000Z_b41e77d0.mp4";
+        let b = "frames/reachy-002/camera/2026-02-18/20260218T094000000Z_20260218T095000000Z_b41e77d0.mp4";
+        storage.index_object(a, 1_000, 10).await;
+        storage.index_object(b, 1_000, 20).await;
+        assert_eq!(storage.stats().await, (2, 30));
+
+        storage.index.lock().await.remove(a);
+        assert_eq!(storage.stats().await, (1, 20));
+    }
+
+    #[test]
+    fn upload_backoff_doubles_up_to_the_cap() {
+        let base = Duration::from_millis(500);
+        assert_eq!(upload_backoff(base, 1), Duration::from_millis(500));
+        assert_eq!(upload_backoff(base, 2), Duration::from_secs(1));
+        assert_eq!(upload_backoff(base, 4), Duration::from_secs(4));
+        assert_eq!(upload_backoff(base, 7), MAX_UPLOAD_BACKOFF);
+        assert_eq!(upload_backoff(base, 1000), MAX_UPLOAD_BACKOFF);
+    }
 }
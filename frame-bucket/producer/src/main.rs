@@ -1,10 +1,15 @@
 mod h264;
+mod metrics;
 mod mjpeg;
+mod sender;
 
 use frame_bucket_common::config::Config;
+use frame_bucket_common::signal::shutdown_signal;
+use rdkafka::producer::Producer;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ProducerError {
@@ -20,6 +25,8 @@ pub enum ProducerError {
     TcpConnect(String),
     #[error("TCP stream error: {0}")]
     TcpStream(String),
+    #[error("ffmpeg error: {0}")]
+    Ffmpeg(String),
     #[error("config error: {0}")]
     Config(#[from] frame_bucket_common::config::ConfigError),
 }
@@ -38,6 +45,10 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    if let Err(e) = config.validate() {
+        eprintln!("{}: {e}", config_path.display());
+        std::process::exit(1);
+    }
 
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -58,47 +69,102 @@ async fn main() {
         "starting frame-bucket producer"
     );
 
-    let producer = match mjpeg::create_producer(&config.kafka.brokers, &config.kafka.compression) {
+    let producer = match mjpeg::create_producer(&config.kafka) {
         Ok(p) => p,
         Err(e) => {
             error!(error = %e, "failed to create Kafka producer");
             std::process::exit(1);
         }
     };
+    let producer_metrics = Arc::new(metrics::ProducerMetrics::default());
+    if let Some(port) = config.stream.metrics_port {
+        tokio::spawn(metrics::serve(
+            port,
+            Arc::clone(&producer_metrics),
+            Duration::from_secs(config.stream.health_stale_secs),
+        ));
+    }
+    let mut sender = sender::FrameSender::new(&producer, &config.kafka, producer_metrics);
 
-    match config.stream.mode.as_str() {
-        "mjpeg" => {
-            let url = format!(
-                "{}?quality={}&fps={}",
-                stream_url, config.stream.quality, config.stream.fps
-            );
-            mjpeg::run_mjpeg_producer(&url, &config.kafka.topic, &producer, robot_id).await.ok();
-        }
-        // hitting the /api/camera/frame endpoint instead of keeping an HTTP connection, only good as a fallback
-        "polling" => {
-            let url = format!(
-                "{}?quality={}",
-                stream_url.replace("/stream", "/frame"),
-                config.stream.quality
-            );
-            let interval = Duration::from_secs_f64(1.0 / config.stream.fps);
-            mjpeg::run_polling_producer(&url, &config.kafka.topic, &producer, interval, robot_id)
-                .await
-                .ok();
+    let run = async {
+        match config.stream.mode.as_str() {
+            "mjpeg" => {
+                let url = format!(
+                    "{}?quality={}&fps={}",
+                    stream_url, config.stream.quality, config.stream.fps
+                );
+                mjpeg::run_mjpeg_producer(&url, &config.stream, &mut sender, robot_id)
+                    .await
+                    .ok();
+            }
+            // hitting the /api/camera/frame endpoint instead of keeping an HTTP connection, only good as a fallback
+            "polling" => {
+                let url = format!(
+                    "{}?quality={}",
+                    stream_url.replace("/stream", "/frame"),
+                    config.stream.quality
+                );
+                let interval = Duration::from_secs_f64(1.0 / config.stream.fps);
+                mjpeg::run_polling_producer(
+                    &url,
+                    &config.stream,
+                    &mut sender,
+                    interval,
+                    robot_id,
+                )
+                    .await
+                    .ok();
+            }
+            "h264" => {
+                let addr = config.stream.h264_url
+                    .as_deref()
+                    .unwrap_or_else(|| {
+                        error!("h264_url is required when mode = \"h264\"");
+                        std::process::exit(1);
+                    });
+                info!(addr, "using H.264 TCP mode");
+                if config.stream.username.is_some() || !config.stream.headers.is_empty() {
+                    warn!("stream.username/password/headers are ignored in H.264 TCP mode");
+                }
+                h264::run_h264_producer(&h264::H264Source::Tcp(addr), &mut sender, robot_id).await.ok();
+            }
+            "rtsp" => {
+                let url = config.stream.rtsp_url
+                    .as_deref()
+                    .unwrap_or_else(|| {
+                        error!("rtsp_url is required when mode = \"rtsp\"");
+                        std::process::exit(1);
+                    });
+                info!(ffmpeg = config.recording.ffmpeg_path, "using RTSP mode");
+                if config.stream.username.is_some() || !config.stream.headers.is_empty() {
+                    warn!("stream.username/password/headers are ignored in RTSP mode; put credentials in rtsp_url");
+                }
+                let source = h264::H264Source::Rtsp { url, ffmpeg: &config.recording.ffmpeg_path };
+                h264::run_h264_producer(&source, &mut sender, robot_id).await.ok();
+            }
+            other => {
+                error!(mode = other, "unknown stream mode, expected 'mjpeg', 'polling', 'h264' or 'rtsp'");
+                std::process::exit(1);
+            }
         }
-        "h264" => {
-            let addr = config.stream.h264_url
-                .as_deref()
-                .unwrap_or_else(|| {
-                    error!("h264_url is required when mode = \"h264\"");
-                    std::process::exit(1);
-                });
-            info!(addr, "using H.264 TCP mode");
-            h264::run_h264_producer(addr, &config.kafka.topic, &producer, robot_id).await.ok();
-        }
-        other => {
-            error!(mode = other, "unknown stream mode, expected 'mjpeg', 'polling', or 'h264'");
-            std::process::exit(1);
+    };
+
+    // Stop reading the camera on a signal; the stream future is dropped here.
+    tokio::select! {
+        _ = run => warn!("stream producer exited"),
+        signal = shutdown_signal() => info!(signal, "shutting down producer"),
+    }
+
+    // Frames already handed to librdkafka (including one whose delivery the
+    // stream loop was awaiting) are still queued; wait for them to go out.
+    let released = sender.release_pending();
+    let queued = producer.in_flight_count();
+    let timeout = Duration::from_secs(config.kafka.flush_timeout_secs);
+    match producer.flush(timeout) {
+        Ok(()) => info!(flushed = queued, released, "flushed Kafka queue"),
+        Err(e) => {
+            let lost = producer.in_flight_count();
+            warn!(error = %e, flushed = (queued - lost).max(0), lost, timeout_secs = timeout.as_secs(), "Kafka flush timed out, frames lost");
         }
     }
 }
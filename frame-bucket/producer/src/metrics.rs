@@ -0,0 +1,194 @@
+//! Liveness probe and Prometheus scrape target for the producer
+//! (`stream.metrics_port`).
+//!
+//! `GET /healthz` answers 200 while frames are reaching Kafka and 503 once
+//! none has for `stream.health_stale_secs`, so an orchestrator can restart a
+//! producer stuck reconnecting. `GET /metrics` reports frames and bytes
+//! produced, frames dropped, reconnects and the current reconnect backoff.
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use tracing::{error, info};
+
+/// Counters kept by the sender and the reconnect loops.
+#[derive(Debug, Default)]
+pub struct ProducerMetrics {
+    /// Frames delivered to Kafka.
+    frames_produced: AtomicU64,
+    /// Payload bytes of those frames.
+    bytes_produced: AtomicU64,
+    /// Frames dropped under `kafka.queue_full_policy` or failed delivery.
+    frames_dropped: AtomicU64,
+    /// Stream reconnects (each wait in a reconnect loop).
+    reconnects: AtomicU64,
+    /// Wait before the next reconnect attempt; 0 once frames flow again.
+    backoff_ms: AtomicU64,
+    /// Unix ms of the last delivered frame; 0 before the first.
+    last_frame_ms: AtomicI64,
+}
+
+impl ProducerMetrics {
+    pub fn record_frame(&self, bytes: usize) {
+        self.frames_produced.fetch_add(1, Ordering::Relaxed);
+        self.bytes_produced.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.backoff_ms.store(0, Ordering::Relaxed);
+        self.last_frame_ms.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn record_drops(&self, n: u64) {
+        self.frames_dropped.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn record_reconnect(&self, backoff: Duration) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+        self.backoff_ms.store(backoff.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            frames_produced: self.frames_produced.load(Ordering::Relaxed),
+            bytes_produced: self.bytes_produced.load(Ordering::Relaxed),
+            frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            backoff_ms: self.backoff_ms.load(Ordering::Relaxed),
+            last_frame_ms: self.last_frame_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    metrics: Arc<ProducerMetrics>,
+    stale_after: Duration,
+}
+
+/// Everything reported by one scrape.
+#[derive(Debug, Default)]
+struct Snapshot {
+    frames_produced: u64,
+    bytes_produced: u64,
+    frames_dropped: u64,
+    reconnects: u64,
+    backoff_ms: u64,
+    last_frame_ms: i64,
+}
+
+/// Serve `GET /healthz` and `GET /metrics` on `0.0.0.0:{port}` until the
+/// process exits.
+pub async fn serve(port: u16, metrics: Arc<ProducerMetrics>, stale_after: Duration) {
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(scrape))
+        .with_state(MetricsState { metrics, stale_after });
+
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!(error = %e, %addr, "failed to bind producer metrics server");
+            return;
+        }
+    };
+    info!(%addr, "producer metrics server listening");
+    if let Err(e) = axum::serve(listener, app).await {
+        error!(error = %e, "producer metrics server stopped");
+    }
+}
+
+async fn healthz(State(state): State<MetricsState>) -> impl IntoResponse {
+    let last_frame_ms = state.metrics.last_frame_ms.load(Ordering::Relaxed);
+    if is_fresh(last_frame_ms, Utc::now().timestamp_millis(), state.stale_after) {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "no frame produced recently")
+    }
+}
+
+/// Whether a frame was produced within `stale_after` of `now_ms`.
+fn is_fresh(last_frame_ms: i64, now_ms: i64, stale_after: Duration) -> bool {
+    last_frame_ms > 0 && now_ms - last_frame_ms <= stale_after.as_millis() as i64
+}
+
+async fn scrape(State(state): State<MetricsState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&state.metrics.snapshot()),
+    )
+}
+
+/// Prometheus text exposition of `s`.
+fn render(s: &Snapshot) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}");
+    };
+    metric("frame_bucket_producer_frames_total", "counter", "Frames delivered to Kafka.", s.frames_produced.to_string());
+    metric("frame_bucket_producer_bytes_total", "counter", "Payload bytes delivered to Kafka.", s.bytes_produced.to_string());
+    metric(
+        "frame_bucket_producer_frames_dropped_total",
+        "counter",
+        "Frames dropped because the Kafka queue was full or delivery failed.",
+        s.frames_dropped.to_string(),
+    );
+    metric("frame_bucket_producer_reconnects_total", "counter", "Camera stream reconnects.", s.reconnects.to_string());
+    metric(
+        "frame_bucket_producer_backoff_seconds",
+        "gauge",
+        "Wait before the next reconnect attempt; 0 while frames flow.",
+        format!("{}", s.backoff_ms as f64 / 1000.0),
+    );
+    metric(
+        "frame_bucket_producer_last_frame_timestamp_seconds",
+        "gauge",
+        "Unix time of the last delivered frame; 0 before the first.",
+        format!("{}", s.last_frame_ms as f64 / 1000.0),
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_emits_counters_and_gauges() {
+        let text = render(&Snapshot {
+            frames_produced: 1200,
+            bytes_produced: 4096,
+            frames_dropped: 7,
+            reconnects: 3,
+            backoff_ms: 8000,
+            last_frame_ms: 1_700_000_000_500,
+        });
+        let lines: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(
+            lines,
+            [
+                "frame_bucket_producer_frames_total 1200",
+                "frame_bucket_producer_bytes_total 4096",
+                "frame_bucket_producer_frames_dropped_total 7",
+                "frame_bucket_producer_reconnects_total 3",
+                "frame_bucket_producer_backoff_seconds 8",
+                "frame_bucket_producer_last_frame_timestamp_seconds 1700000000.5",
+            ]
+        );
+    }
+
+    #[test]
+    fn health_needs_a_recent_frame() {
+        let stale_after = Duration::from_secs(30);
+        assert!(!is_fresh(0, 1_000_000, stale_after));
+        assert!(is_fresh(1_000_000, 1_030_000, stale_after));
+        assert!(!is_fresh(1_000_000, 1_030_001, stale_after));
+    }
+}
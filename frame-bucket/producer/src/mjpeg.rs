@@ -1,23 +1,29 @@
 use bytes::BytesMut;
 use chrono::Utc;
+use frame_bucket_common::config::StreamConfig;
 use frame_bucket_common::frame::TimestampedFrame;
+use frame_bucket_common::config::KafkaConfig;
 use futures_util::StreamExt;
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::producer::FutureProducer;
 use rdkafka::ClientConfig;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
+use crate::sender::FrameSender;
 use crate::ProducerError;
 
 static SEQ_COUNTER: AtomicU64 = AtomicU64::new(0);
 
-const BOUNDARY: &[u8] = b"--frame\r\n";
+/// Boundary used when the response `Content-Type` doesn't name one.
+const DEFAULT_BOUNDARY: &str = "frame";
 const HEADER_END: &[u8] = b"\r\n\r\n";
+/// How often the frame-rate cap logs its sent/dropped counts.
+const CAP_REPORT_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Parse state for the MJPEG multipart stream.
 enum ParseState {
-    /// Looking for the boundary marker `--frame\r\n`.
+    /// Looking for the boundary marker (e.g. `--frame\r\n`).
     SeekingBoundary,
     /// Found boundary, now looking for end of headers `\r\n\r\n`.
     SeekingHeaderEnd,
@@ -25,18 +31,24 @@ enum ParseState {
     CollectingJpeg,
 }
 
-pub fn create_producer(
-    brokers: &str,
-    compression: &str,
-) -> Result<FutureProducer, ProducerError> {
-    let producer: FutureProducer = ClientConfig::new()
-        .set("bootstrap.servers", brokers)
+pub fn create_producer(kafka: &KafkaConfig) -> Result<FutureProducer, ProducerError> {
+    let mut client = ClientConfig::new();
+    client
+        .set("bootstrap.servers", &kafka.brokers)
         .set("message.max.bytes", "1048576")
-        .set("compression.type", compression)
+        .set("compression.type", &kafka.compression)
         .set("linger.ms", "5")
         .set("batch.num.messages", "10")
         .set("queue.buffering.max.messages", "1000")
-        .set("request.timeout.ms", "5000")
+        .set("request.timeout.ms", "5000");
+    if kafka.idempotent {
+        // Broker-side dedup of retried batches; needs acks from all in-sync replicas.
+        client.set("enable.idempotence", "true").set("acks", "all");
+    }
+    for (name, value) in kafka.security_properties() {
+        client.set(name, value);
+    }
+    let producer: FutureProducer = client
         .create()
         .map_err(|e| ProducerError::KafkaCreate(e.to_string()))?;
     Ok(producer)
@@ -46,16 +58,17 @@ pub fn create_producer(
 /// Reconnects with exponential backoff on failure.
 pub async fn run_mjpeg_producer(
     stream_url: &str,
-    topic: &str,
-    producer: &FutureProducer,
+    stream: &StreamConfig,
+    sender: &mut FrameSender<'_>,
     robot_id: &str,
 ) -> Result<(), ProducerError> {
     let mut backoff = Duration::from_secs(2);
     let max_backoff = Duration::from_secs(30);
+    let mut cap = FrameRateCap::new(stream.max_fps, Instant::now());
 
     loop {
         info!(url = stream_url, robot_id, "connecting to MJPEG stream");
-        match consume_stream(stream_url, topic, producer, robot_id).await {
+        match consume_stream(stream_url, stream, sender, &mut cap, robot_id).await {
             Ok(()) => {
                 info!(robot_id, "stream ended cleanly, reconnecting");
                 backoff = Duration::from_secs(2);
@@ -64,6 +77,7 @@ pub async fn run_mjpeg_producer(
                 error!(error = %e, robot_id, "stream error, reconnecting in {:?}", backoff);
             }
         }
+        sender.metrics().record_reconnect(backoff);
         tokio::time::sleep(backoff).await;
         backoff = (backoff * 2).min(max_backoff);
     }
@@ -71,16 +85,16 @@ pub async fn run_mjpeg_producer(
 
 async fn consume_stream(
     url: &str,
-    topic: &str,
-    producer: &FutureProducer,
+    stream: &StreamConfig,
+    sender: &mut FrameSender<'_>,
+    cap: &mut FrameRateCap,
     robot_id: &str,
 ) -> Result<(), ProducerError> {
     let client = reqwest::Client::builder()
         .connect_timeout(Duration::from_secs(10))
         .build()
         .map_err(ProducerError::HttpConnect)?;
-    let response = client
-        .get(url)
+    let response = camera_request(&client, url, stream)
         .send()
         .await
         .map_err(ProducerError::HttpConnect)?;
@@ -89,46 +103,193 @@ async fn consume_stream(
         return Err(ProducerError::HttpStatus(response.status().as_u16()));
     }
 
-    info!(status = %response.status(), "connected to MJPEG stream");
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    let boundary = boundary_from_content_type(content_type);
+    info!(
+        status = %response.status(),
+        boundary = %String::from_utf8_lossy(&boundary).trim_end(),
+        "connected to MJPEG stream"
+    );
 
     let mut byte_stream = response.bytes_stream();
-    let mut buffer = BytesMut::with_capacity(256 * 1024);
-    let mut state = ParseState::SeekingBoundary;
-    let mut jpeg_start: usize = 0;
+    let mut parser = MjpegParser::new(boundary);
 
     while let Some(chunk) = byte_stream.next().await {
         let chunk = chunk.map_err(ProducerError::HttpStream)?;
-        buffer.extend_from_slice(&chunk);
+
+        for jpeg_data in parser.push(&chunk) {
+            let now = Instant::now();
+            if let Some((sent, dropped)) = cap.report(now) {
+                info!(sent, dropped, max_fps = stream.max_fps, "frame-rate cap");
+            }
+            if !cap.admit(now) {
+                continue;
+            }
+            let seq = SEQ_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let now_ms = Utc::now().timestamp_millis();
+            let frame = TimestampedFrame::new(jpeg_data, now_ms, seq).with_robot_id(robot_id);
+            let payload = sender.encode(&frame);
+            let key = sender.message_key(robot_id, now_ms, seq);
+
+            debug!(seq, bytes = payload.len(), "producing frame to Kafka");
+
+            // With the `block` policy this await stalls reading the stream,
+            // pushing backpressure onto the camera connection.
+            sender.send(key, payload, seq).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Drops MJPEG frames arriving faster than `stream.max_fps`.
+///
+/// Send times follow a schedule of `1 / max_fps` steps rather than restarting
+/// from each sent frame, and a frame up to a tenth of a step early still
+/// counts, so arrival jitter doesn't pull the rate below the cap (30 fps capped
+/// at 10 sends every third frame). After a stall the schedule restarts instead
+/// of letting a burst through.
+struct FrameRateCap {
+    /// `None` when uncapped (`max_fps = 0`).
+    interval: Option<Duration>,
+    next_due: Option<Instant>,
+    sent: u64,
+    dropped: u64,
+    last_report: Instant,
+}
+
+impl FrameRateCap {
+    fn new(max_fps: f64, now: Instant) -> Self {
+        Self {
+            interval: (max_fps > 0.0).then(|| Duration::from_secs_f64(1.0 / max_fps)),
+            next_due: None,
+            sent: 0,
+            dropped: 0,
+            last_report: now,
+        }
+    }
+
+    /// Whether to send a frame arriving at `now`.
+    fn admit(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.interval else {
+            self.sent += 1;
+            return true;
+        };
+        if let Some(due) = self.next_due {
+            if now + interval / 10 < due {
+                self.dropped += 1;
+                return false;
+            }
+        }
+        let due = match self.next_due {
+            Some(due) if now.saturating_duration_since(due) < interval => due,
+            _ => now,
+        };
+        self.next_due = Some(due + interval);
+        self.sent += 1;
+        true
+    }
+
+    /// Sent and dropped totals, once per `CAP_REPORT_INTERVAL` while capping.
+    fn report(&mut self, now: Instant) -> Option<(u64, u64)> {
+        if self.interval.is_none() || now.duration_since(self.last_report) < CAP_REPORT_INTERVAL {
+            return None;
+        }
+        self.last_report = now;
+        Some((self.sent, self.dropped))
+    }
+}
+
+/// GET `url` with the camera's configured Basic auth and extra headers.
+fn camera_request(client: &reqwest::Client, url: &str, stream: &StreamConfig) -> reqwest::RequestBuilder {
+    let mut request = client.get(url);
+    if let Some(username) = &stream.username {
+        request = request.basic_auth(username, stream.password.as_deref());
+    }
+    for (name, value) in &stream.headers {
+        request = request.header(name, value);
+    }
+    request
+}
+
+/// Delimiter line for a multipart stream, from its `Content-Type` header
+/// (`multipart/x-mixed-replace; boundary=...`). Falls back to `--frame` when
+/// the header is missing or names no boundary. Tolerates cameras that include
+/// the leading `--` in the parameter.
+fn boundary_from_content_type(content_type: Option<&str>) -> Vec<u8> {
+    let boundary = content_type
+        .and_then(|ct| {
+            ct.split(';').find_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("boundary")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+        })
+        .filter(|b| !b.is_empty())
+        .unwrap_or(DEFAULT_BOUNDARY);
+    let boundary = boundary.strip_prefix("--").unwrap_or(boundary);
+    format!("--{boundary}\r\n").into_bytes()
+}
+
+/// Incremental parser for a `multipart/x-mixed-replace` MJPEG body.
+struct MjpegParser {
+    /// Delimiter line including the leading `--` and trailing CRLF.
+    boundary: Vec<u8>,
+    buffer: BytesMut,
+    state: ParseState,
+    jpeg_start: usize,
+}
+
+impl MjpegParser {
+    fn new(boundary: Vec<u8>) -> Self {
+        Self {
+            boundary,
+            buffer: BytesMut::with_capacity(256 * 1024),
+            state: ParseState::SeekingBoundary,
+            jpeg_start: 0,
+        }
+    }
+
+    /// Feed the next chunk of the body; returns the JPEGs it completed.
+    fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+        let boundary = self.boundary.as_slice();
+        let buffer = &mut self.buffer;
+        buffer.extend_from_slice(chunk);
+        let mut frames = Vec::new();
 
         loop {
-            match state {
+            match self.state {
                 ParseState::SeekingBoundary => {
-                    if let Some(pos) = find_subsequence(&buffer, BOUNDARY) {
+                    if let Some(pos) = find_subsequence(buffer, boundary) {
                         // Discard everything up to and including the boundary
-                        let _ = buffer.split_to(pos + BOUNDARY.len());
-                        state = ParseState::SeekingHeaderEnd;
+                        let _ = buffer.split_to(pos + boundary.len());
+                        self.state = ParseState::SeekingHeaderEnd;
                     } else {
                         // Keep last few bytes in case boundary spans chunks
-                        if buffer.len() > BOUNDARY.len() {
-                            let _ = buffer.split_to(buffer.len() - BOUNDARY.len());
+                        if buffer.len() > boundary.len() {
+                            let _ = buffer.split_to(buffer.len() - boundary.len());
                         }
                         break;
                     }
                 }
                 ParseState::SeekingHeaderEnd => {
-                    if let Some(pos) = find_subsequence(&buffer, HEADER_END) {
+                    if let Some(pos) = find_subsequence(buffer, HEADER_END) {
                         // Discard headers
                         let _ = buffer.split_to(pos + HEADER_END.len());
-                        jpeg_start = 0;
-                        state = ParseState::CollectingJpeg;
+                        self.jpeg_start = 0;
+                        self.state = ParseState::CollectingJpeg;
                     } else {
                         break;
                     }
                 }
                 ParseState::CollectingJpeg => {
                     // Look for the next boundary to know where JPEG ends
-                    if let Some(pos) = find_subsequence(&buffer[jpeg_start..], BOUNDARY) {
-                        let jpeg_end = jpeg_start + pos;
+                    if let Some(pos) = find_subsequence(&buffer[self.jpeg_start..], boundary) {
+                        let jpeg_end = self.jpeg_start + pos;
                         // Strip trailing \r\n before boundary
                         let end = if jpeg_end >= 2
                             && buffer[jpeg_end - 2] == b'\r'
@@ -142,35 +303,19 @@ async fn consume_stream(
                         let jpeg_data = buffer[..end].to_vec();
 
                         // Advance past the boundary
-                        let _ = buffer.split_to(jpeg_end + BOUNDARY.len());
+                        let _ = buffer.split_to(jpeg_end + boundary.len());
 
                         if !jpeg_data.is_empty() {
-                            let seq = SEQ_COUNTER.fetch_add(1, Ordering::Relaxed);
-                            let now_ms = Utc::now().timestamp_millis();
-                            let frame = TimestampedFrame::new(jpeg_data, now_ms, seq);
-                            let payload = frame.serialize();
-                            let key = format!("{}:{}", robot_id, now_ms);
-
-                            debug!(seq, bytes = payload.len(), "producing frame to Kafka");
-
-                            let record = FutureRecord::to(topic)
-                                .key(&key)
-                                .payload(&payload);
-
-                            if let Err((e, _)) =
-                                producer.send(record, Duration::from_secs(5)).await
-                            {
-                                warn!(error = %e, seq, "failed to produce frame to Kafka");
-                            }
+                            frames.push(jpeg_data);
                         }
 
                         // Already past boundary, go to header parsing
-                        state = ParseState::SeekingHeaderEnd;
+                        self.state = ParseState::SeekingHeaderEnd;
                     } else {
                         // No boundary found yet, keep accumulating
                         // Update jpeg_start to avoid re-scanning old data
-                        jpeg_start = if buffer.len() > BOUNDARY.len() {
-                            buffer.len() - BOUNDARY.len()
+                        self.jpeg_start = if buffer.len() > boundary.len() {
+                            buffer.len() - boundary.len()
                         } else {
                             0
                         };
@@ -179,16 +324,16 @@ async fn consume_stream(
                 }
             }
         }
-    }
 
-    Ok(())
+        frames
+    }
 }
 
 /// Polling-based fallback: periodically fetch single frames.
 pub async fn run_polling_producer(
     frame_url: &str,
-    topic: &str,
-    producer: &FutureProducer,
+    stream: &StreamConfig,
+    sender: &mut FrameSender<'_>,
     interval: Duration,
     robot_id: &str,
 ) -> Result<(), ProducerError> {
@@ -198,19 +343,16 @@ pub async fn run_polling_producer(
     loop {
         ticker.tick().await;
 
-        match client.get(frame_url).send().await {
+        match camera_request(&client, frame_url, stream).send().await {
             Ok(resp) if resp.status().is_success() => {
                 let jpeg_data = resp.bytes().await.map_err(ProducerError::HttpStream)?.to_vec();
                 let seq = SEQ_COUNTER.fetch_add(1, Ordering::Relaxed);
                 let now_ms = Utc::now().timestamp_millis();
-                let frame = TimestampedFrame::new(jpeg_data, now_ms, seq);
-                let payload = frame.serialize();
-                let key = format!("{}:{}", robot_id, now_ms);
+                let frame = TimestampedFrame::new(jpeg_data, now_ms, seq).with_robot_id(robot_id);
+                let payload = sender.encode(&frame);
+                let key = sender.message_key(robot_id, now_ms, seq);
 
-                let record = FutureRecord::to(topic).key(&key).payload(&payload);
-                if let Err((e, _)) = producer.send(record, Duration::from_secs(5)).await {
-                    warn!(error = %e, seq, "failed to produce frame to Kafka");
-                }
+                sender.send(key, payload, seq).await;
             }
             Ok(resp) => {
                 warn!(status = %resp.status(), "non-success response from camera");
@@ -228,3 +370,107 @@ fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
         .windows(needle.len())
         .position(|window| window == needle)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_config() -> StreamConfig {
+        StreamConfig {
+            url: "http://camera/stream".into(),
+            quality: 80,
+            fps: 10.0,
+            mode: "mjpeg".into(),
+            h264_url: None,
+            rtsp_url: None,
+            max_fps: 0.0,
+            metrics_port: None,
+            health_stale_secs: 30,
+            username: None,
+            password: None,
+            headers: Default::default(),
+        }
+    }
+
+    /// Sent count for `secs` of frames arriving at `source_fps`.
+    fn capped(max_fps: f64, source_fps: u64, secs: u64) -> u64 {
+        let start = Instant::now();
+        let mut cap = FrameRateCap::new(max_fps, start);
+        (0..source_fps * secs)
+            .filter(|i| cap.admit(start + Duration::from_nanos(i * 1_000_000_000 / source_fps)))
+            .count() as u64
+    }
+
+    #[test]
+    fn frame_rate_cap_keeps_the_configured_rate() {
+        assert_eq!(capped(10.0, 30, 10), 100);
+        assert_eq!(capped(20.0, 30, 10), 200);
+        assert_eq!(capped(0.0, 30, 10), 300);
+        // Cap above the source rate: everything goes through.
+        assert_eq!(capped(60.0, 30, 10), 300);
+    }
+
+    #[test]
+    fn frame_rate_cap_restarts_after_a_stall() {
+        let start = Instant::now();
+        let mut cap = FrameRateCap::new(10.0, start);
+        assert!(cap.admit(start));
+        // Nothing for 5 s, then frames every 33 ms: no catch-up burst.
+        let resumed = start + Duration::from_secs(5);
+        assert!(cap.admit(resumed));
+        assert!(!cap.admit(resumed + Duration::from_millis(33)));
+        assert!(!cap.admit(resumed + Duration::from_millis(66)));
+        assert!(cap.admit(resumed + Duration::from_millis(100)));
+        assert_eq!((cap.sent, cap.dropped), (3, 2));
+    }
+
+    #[test]
+    fn camera_request_attaches_auth_and_headers() {
+        let mut stream = stream_config();
+        stream.username = Some("user".into());
+        stream.password = Some("pass".into());
+        stream.headers.insert("X-Api-Token".into(), "secret".into());
+
+        let client = reqwest::Client::new();
+        let request = camera_request(&client, &stream.url, &stream).build().unwrap();
+        let headers = request.headers();
+        // base64("user:pass")
+        assert_eq!(headers["authorization"], "Basic dXNlcjpwYXNz");
+        assert_eq!(headers["x-api-token"], "secret");
+    }
+
+    #[test]
+    fn camera_request_without_auth_sends_no_authorization() {
+        let stream = stream_config();
+        let client = reqwest::Client::new();
+        let request = camera_request(&client, &stream.url, &stream).build().unwrap();
+        assert!(request.headers().get("authorization").is_none());
+    }
+
+    #[test]
+    fn boundary_is_read_from_content_type() {
+        let parse = |ct| String::from_utf8(boundary_from_content_type(ct)).unwrap();
+        assert_eq!(parse(Some("multipart/x-mixed-replace; boundary=myboundary")), "--myboundary\r\n");
+        assert_eq!(parse(Some("multipart/x-mixed-replace;boundary=\"BoundaryString\"")), "--BoundaryString\r\n");
+        assert_eq!(parse(Some("multipart/x-mixed-replace; boundary=--myboundary")), "--myboundary\r\n");
+        assert_eq!(parse(Some("multipart/x-mixed-replace")), "--frame\r\n");
+        assert_eq!(parse(None), "--frame\r\n");
+    }
+
+    #[test]
+    fn parser_splits_frames_on_non_default_boundary() {
+        let body = b"--myboundary\r\nContent-Type: image/jpeg\r\nContent-Length: 4\r\n\r\n\xff\xd8AA\r\n\
+                     --myboundary\r\nContent-Type: image/jpeg\r\n\r\n\xff\xd8BBB\r\n\
+                     --myboundary\r\n";
+        let boundary = boundary_from_content_type(Some("multipart/x-mixed-replace; boundary=myboundary"));
+
+        // Feed in small chunks so boundaries and headers straddle chunk edges.
+        let mut parser = MjpegParser::new(boundary);
+        let frames: Vec<Vec<u8>> = body.chunks(3).flat_map(|c| parser.push(c)).collect();
+        assert_eq!(frames, vec![b"\xff\xd8AA".to_vec(), b"\xff\xd8BBB".to_vec()]);
+
+        // The old hardcoded `--frame` boundary finds nothing in this stream.
+        let mut default_parser = MjpegParser::new(boundary_from_content_type(None));
+        assert!(default_parser.push(body).is_empty());
+    }
+}
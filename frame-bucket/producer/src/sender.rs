@@ -0,0 +1,260 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use frame_bucket_common::config::{KafkaConfig, KeyStrategy, QueueFullPolicy};
+use frame_bucket_common::frame::TimestampedFrame;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord};
+use tracing::{debug, warn};
+
+use crate::metrics::ProducerMetrics;
+
+/// Enqueue timeout of the `drop` policy (the producers' historical behavior).
+const DROP_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Log a summary every this many dropped frames so sustained loss is visible.
+const DROP_LOG_EVERY: u64 = 100;
+
+/// Kafka message key of a frame under `strategy`. The consumer takes the
+/// robot id from the first `:`-separated field, and records unkeyed frames
+/// as `aws_s3.robot_id`.
+fn message_key(strategy: KeyStrategy, robot_id: &str, timestamp_ms: i64, seq: u64) -> Option<String> {
+    match strategy {
+        KeyStrategy::Robot => Some(robot_id.to_string()),
+        KeyStrategy::RobotSeq => Some(format!("{robot_id}:{timestamp_ms}:{seq}")),
+        KeyStrategy::None => None,
+    }
+}
+
+/// A record for `topic`, keyed only when `key` is set (unkeyed records are
+/// spread over partitions by librdkafka).
+fn record<'r>(topic: &'r str, key: Option<&'r str>, payload: &'r [u8]) -> FutureRecord<'r, str, [u8]> {
+    let record = FutureRecord::to(topic).payload(payload);
+    match key {
+        Some(key) => record.key(key),
+        None => record,
+    }
+}
+
+/// Count the frame once the broker acknowledges it, or as dropped if delivery
+/// fails, without holding up the caller.
+fn track_delivery(delivery: DeliveryFuture, metrics: Arc<ProducerMetrics>, bytes: usize, seq: u64) {
+    tokio::spawn(async move {
+        match delivery.await {
+            Ok(Ok(_)) => metrics.record_frame(bytes),
+            Ok(Err((e, _))) => {
+                warn!(error = %e, seq, "Kafka failed to deliver frame");
+                metrics.record_drops(1);
+            }
+            // The producer went away with the frame still queued.
+            Err(_) => metrics.record_drops(1),
+        }
+    });
+}
+
+/// Sends serialized frames to Kafka, applying the configured queue-full policy
+/// and counting frames that never made it.
+pub struct FrameSender<'a> {
+    producer: &'a FutureProducer,
+    topic: String,
+    key_strategy: KeyStrategy,
+    compress_payload: bool,
+    policy: QueueFullPolicy,
+    block_timeout: Duration,
+    pending: PendingFrames,
+    /// Frames dropped so far (queue full, timed out or failed delivery).
+    dropped: u64,
+    metrics: Arc<ProducerMetrics>,
+}
+
+impl<'a> FrameSender<'a> {
+    pub fn new(producer: &'a FutureProducer, kafka: &KafkaConfig, metrics: Arc<ProducerMetrics>) -> Self {
+        Self {
+            producer,
+            topic: kafka.topic.clone(),
+            key_strategy: kafka.key_strategy,
+            compress_payload: kafka.compress_payload,
+            policy: kafka.queue_full_policy,
+            block_timeout: Duration::from_secs(kafka.queue_block_secs),
+            pending: PendingFrames::new(kafka.queue_pending_frames),
+            dropped: 0,
+            metrics,
+        }
+    }
+
+    /// Message key for a frame, per `kafka.key_strategy`.
+    pub fn message_key(&self, robot_id: &str, timestamp_ms: i64, seq: u64) -> Option<String> {
+        message_key(self.key_strategy, robot_id, timestamp_ms, seq)
+    }
+
+    /// Kafka payload of `frame`, compressed under `kafka.compress_payload`.
+    pub fn encode(&self, frame: &TimestampedFrame) -> Vec<u8> {
+        if self.compress_payload {
+            frame.serialize_compressed()
+        } else {
+            frame.serialize()
+        }
+    }
+
+    /// Counters shared with the metrics server; the stream loops record
+    /// reconnects here.
+    pub fn metrics(&self) -> &ProducerMetrics {
+        &self.metrics
+    }
+
+    /// Produce one frame. Never fails: frames that can't be delivered are
+    /// counted and logged according to the policy.
+    pub async fn send(&mut self, key: Option<String>, payload: Vec<u8>, seq: u64) {
+        match self.policy {
+            QueueFullPolicy::Block => self.send_one(key.as_deref(), &payload, seq, self.block_timeout).await,
+            QueueFullPolicy::Drop => self.send_one(key.as_deref(), &payload, seq, DROP_QUEUE_TIMEOUT).await,
+            QueueFullPolicy::DropNewest => {
+                // Only enqueue: waiting for the delivery report would stall
+                // the camera loop on every broker round trip.
+                let record = record(&self.topic, key.as_deref(), &payload);
+                match self.producer.send_result(record) {
+                    Ok(delivery) => track_delivery(delivery, Arc::clone(&self.metrics), payload.len(), seq),
+                    Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                        self.record_drops(1, "Kafka queue full, dropping new frames");
+                    }
+                    Err((e, _)) => {
+                        warn!(error = %e, seq, "failed to produce frame to Kafka");
+                        self.record_drops(1, "dropping frames: Kafka unavailable");
+                    }
+                }
+            }
+            QueueFullPolicy::DropOldest => {
+                let evicted = self.pending.push(key, payload, seq);
+                self.record_drops(evicted, "Kafka queue full, discarded oldest pending frames");
+                self.flush_pending();
+            }
+        }
+    }
+
+    async fn send_one(&mut self, key: Option<&str>, payload: &[u8], seq: u64, queue_timeout: Duration) {
+        let record = record(&self.topic, key, payload);
+        match self.producer.send(record, queue_timeout).await {
+            Ok(_) => self.metrics.record_frame(payload.len()),
+            Err((e, _)) => {
+                warn!(error = %e, seq, "failed to produce frame to Kafka");
+                self.record_drops(1, "dropping frames: Kafka unavailable or queue full");
+            }
+        }
+    }
+
+    /// Enqueue pending frames oldest-first, waiting neither for queue space
+    /// nor for delivery; stop at the first full-queue rejection and keep the
+    /// rest for later.
+    fn flush_pending(&mut self) {
+        while let Some((key, payload, seq)) = self.pending.frames.front() {
+            let seq = *seq;
+            let record = record(&self.topic, key.as_deref(), payload);
+            match self.producer.send_result(record) {
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                    debug!(pending = self.pending.len(), "Kafka queue full, holding frames locally");
+                    return;
+                }
+                Err((e, _)) => {
+                    warn!(error = %e, seq, "failed to produce frame to Kafka");
+                    self.pending.pop_front();
+                    self.record_drops(1, "dropping frames: Kafka unavailable");
+                }
+                Ok(delivery) => {
+                    track_delivery(delivery, Arc::clone(&self.metrics), payload.len(), seq);
+                    self.pending.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Shutdown: hand frames still held locally (`drop_oldest`) to
+    /// librdkafka without waiting for delivery, so a flush covers them.
+    /// Frames it rejects are counted as dropped. Returns how many were queued.
+    pub fn release_pending(&mut self) -> usize {
+        let mut queued = 0;
+        while let Some((key, payload, seq)) = self.pending.frames.pop_front() {
+            let record = record(&self.topic, key.as_deref(), &payload);
+            match self.producer.send_result(record) {
+                Ok(delivery) => {
+                    track_delivery(delivery, Arc::clone(&self.metrics), payload.len(), seq);
+                    queued += 1;
+                }
+                Err((e, _)) => {
+                    warn!(error = %e, seq, "failed to queue held frame at shutdown");
+                    self.record_drops(1, "dropping frames: Kafka unavailable or queue full");
+                }
+            }
+        }
+        queued
+    }
+
+    fn record_drops(&mut self, n: u64, msg: &'static str) {
+        if n == 0 {
+            return;
+        }
+        self.metrics.record_drops(n);
+        let before = self.dropped;
+        self.dropped += n;
+        // Log on the first drop and then once per DROP_LOG_EVERY.
+        if before == 0 || before / DROP_LOG_EVERY != self.dropped / DROP_LOG_EVERY {
+            warn!(dropped_total = self.dropped, policy = ?self.policy, "{msg}");
+        }
+    }
+}
+
+/// Bounded FIFO of frames waiting for Kafka queue space (`drop_oldest`).
+struct PendingFrames {
+    frames: VecDeque<(Option<String>, Vec<u8>, u64)>,
+    capacity: usize,
+}
+
+impl PendingFrames {
+    fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Queue a frame, discarding the oldest ones past capacity. Returns how many were discarded.
+    fn push(&mut self, key: Option<String>, payload: Vec<u8>, seq: u64) -> u64 {
+        self.frames.push_back((key, payload, seq));
+        let mut evicted = 0;
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn pop_front(&mut self) {
+        self.frames.pop_front();
+    }
+
+    fn len(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_key_follows_strategy() {
+        let key = |strategy| message_key(strategy, "reachy-001", 1739871000000, 42);
+        assert_eq!(key(KeyStrategy::Robot).as_deref(), Some("reachy-001"));
+        assert_eq!(key(KeyStrategy::RobotSeq).as_deref(), Some("reachy-001:1739871000000:42"));
+        assert_eq!(key(KeyStrategy::None), None);
+    }
+
+    #[test]
+    fn pending_frames_discard_oldest_past_capacity() {
+        let mut pending = PendingFrames::new(2);
+        assert_eq!(pending.push(Some("a".into()), vec![1], 1), 0);
+        assert_eq!(pending.push(Some("b".into()), vec![2], 2), 0);
+        assert_eq!(pending.push(Some("c".into()), vec![3], 3), 1);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending.frames.front().unwrap().2, 2);
+    }
+}